@@ -21,6 +21,7 @@ use const_table::const_table;
 use std::error::Error;
 use std::fmt;
 
+use crate::config::ChannelAccessLevel;
 use crate::utils::*;
 
 #[derive(Clone, Copy, Debug)]
@@ -60,10 +61,14 @@ impl<'a> Message<'a> {
             } else {
                 0
             };
-            let (rest, last_param) = if let Some((rest, lp)) = trimmed[start_pos..].split_once(':')
-            {
+            // last parameter starts from ':' at beginning of word.
+            let last_param_pos = trimmed.as_bytes()[start_pos..]
+                .windows(2)
+                .position(|w| w[0].is_ascii_whitespace() && w[1] == b':');
+            let (rest, last_param) = if let Some(pos) = last_param_pos {
                 // get rest. add first character length to rest length.
-                (&trimmed[0..rest.len() + start_pos], Some(lp))
+                let rest_len = pos + 1 + start_pos;
+                (&trimmed[0..rest_len], Some(&trimmed[rest_len + 1..]))
             } else {
                 (trimmed, None)
             };
@@ -135,7 +140,7 @@ pub(crate) enum CommandId {
         pub(crate) name: &'static str,
     },
     CAPId = CommandName { name: "CAP" },
-    _AUTHENTICATEId = CommandName {
+    AUTHENTICATEId = CommandName {
         name: "AUTHENTICATE",
     },
     PASSId = CommandName { name: "PASS" },
@@ -177,6 +182,18 @@ pub(crate) enum CommandId {
     WALLOPSId = CommandName { name: "WALLOPS" },
     ISONId = CommandName { name: "ISON" },
     _DIEId = CommandName { name: "DIE" },
+    RESUMEId = CommandName { name: "RESUME" },
+    OPERWALLId = CommandName { name: "OPERWALL" },
+    MONITORId = CommandName { name: "MONITOR" },
+    WATCHId = CommandName { name: "WATCH" },
+    CPRIVMSGId = CommandName { name: "CPRIVMSG" },
+    CNOTICEId = CommandName { name: "CNOTICE" },
+    RENAMEId = CommandName { name: "RENAME" },
+    CLEARCHANId = CommandName { name: "CLEARCHAN" },
+    ACCEPTId = CommandName { name: "ACCEPT" },
+    ACCESSId = CommandName { name: "ACCESS" },
+    RELAYMSGId = CommandName { name: "RELAYMSG" },
+    SESSIONSId = CommandName { name: "SESSIONS" },
 }
 
 use CommandId::*;
@@ -186,7 +203,6 @@ pub(crate) enum CommandError {
     UnknownCommand(String),
     UnknownSubcommand(CommandId, String),
     NeedMoreParams(CommandId),
-    ParameterDoesntMatch(CommandId, usize),
     WrongParameter(CommandId, usize),
     UnknownMode(usize, char, String),
     UnknownUModeFlag(usize),
@@ -208,9 +224,6 @@ impl fmt::Display for CommandError {
                 write!(f, "Unknown subcommand '{}' in command '{}'", scmd, cmd.name)
             }
             NeedMoreParams(s) => write!(f, "Command '{}' needs more parameters", s.name),
-            ParameterDoesntMatch(s, i) => {
-                write!(f, "Parameter {} doesn't match for command '{}'", i, s.name)
-            }
             WrongParameter(s, i) => write!(f, "Wrong parameter {} in command '{}'", i, s.name),
             UnknownMode(i, c, ch) => write!(f, "Unknown mode {} in parameter {} for {}", c, i, ch),
             UnknownUModeFlag(i) => write!(f, "Unknown umode flag in parameter {}", i),
@@ -239,6 +252,35 @@ pub(crate) enum CapCommand {
     END,
 }
 
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum MonitorCommand {
+    Add,
+    Remove,
+    Clear,
+    List,
+    Status,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum AccessCommand<'a> {
+    Add {
+        mask: &'a str,
+        level: ChannelAccessLevel,
+    },
+    Del {
+        mask: &'a str,
+    },
+    List,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum SessionsCommand<'a> {
+    List,
+    Kill {
+        nick: &'a str,
+    },
+}
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Debug)]
 pub(crate) enum Command<'a> {
@@ -247,7 +289,9 @@ pub(crate) enum Command<'a> {
         caps: Option<Vec<&'a str>>,
         version: Option<u32>,
     },
-    AUTHENTICATE {},
+    AUTHENTICATE {
+        data: &'a str,
+    },
     PASS {
         password: &'a str,
     },
@@ -262,6 +306,7 @@ pub(crate) enum Command<'a> {
     },
     PING {
         token: &'a str,
+        server: Option<&'a str>,
     },
     PONG {
         token: &'a str,
@@ -270,7 +315,9 @@ pub(crate) enum Command<'a> {
         name: &'a str,
         password: &'a str,
     },
-    QUIT {},
+    QUIT {
+        reason: Option<&'a str>,
+    },
     JOIN {
         channels: Vec<&'a str>,
         keys: Option<Vec<&'a str>>,
@@ -281,6 +328,7 @@ pub(crate) enum Command<'a> {
     },
     TOPIC {
         channel: &'a str,
+        // None - query topic, empty string - clear topic.
         topic: Option<&'a str>,
     },
     NAMES {
@@ -357,7 +405,9 @@ pub(crate) enum Command<'a> {
         nickname: &'a str,
         comment: &'a str,
     },
-    REHASH {},
+    REHASH {
+        subcommand: Option<&'a str>,
+    },
     RESTART {},
     SQUIT {
         server: &'a str,
@@ -378,13 +428,71 @@ pub(crate) enum Command<'a> {
     DIE {
         message: Option<&'a str>,
     },
+    RESUME {
+        token: &'a str,
+    },
+    OPERWALL {
+        text: &'a str,
+    },
+    MONITOR {
+        subcommand: MonitorCommand,
+        targets: Vec<&'a str>,
+    },
+    WATCH {
+        entries: Vec<&'a str>,
+    },
+    CPRIVMSG {
+        nickname: &'a str,
+        channel: &'a str,
+        text: &'a str,
+    },
+    CNOTICE {
+        nickname: &'a str,
+        channel: &'a str,
+        text: &'a str,
+    },
+    RENAME {
+        channel: &'a str,
+        new_channel: &'a str,
+        reason: Option<&'a str>,
+    },
+    // all - kick also channel operators and IRC operators,
+    // lock - set +i and +m modes after clearing.
+    CLEARCHAN {
+        channel: &'a str,
+        all: bool,
+        lock: bool,
+    },
+    // nicknames: '+nick' or 'nick' - add, '-nick' - remove, '*' - list.
+    ACCEPT {
+        nicknames: Vec<&'a str>,
+    },
+    ACCESS {
+        channel: &'a str,
+        subcommand: AccessCommand<'a>,
+    },
+    // nick - spoofed nick of relayed user (with relay separator).
+    RELAYMSG {
+        channel: &'a str,
+        nick: &'a str,
+        text: &'a str,
+    },
+    // sessions of account of user: list or terminate other session.
+    SESSIONS {
+        subcommand: SessionsCommand<'a>,
+    },
 }
 
 use Command::*;
 
-pub(crate) const NUM_COMMANDS: usize = 41;
+pub(crate) const NUM_COMMANDS: usize = 53;
 
 impl<'a> Command<'a> {
+    // name of command from command table.
+    pub(crate) fn name(&self) -> &'static str {
+        CommandId::iter().nth(self.index()).unwrap().name
+    }
+
     pub(crate) fn index(&self) -> usize {
         match self {
             CAP { .. } => 0,
@@ -428,6 +536,18 @@ impl<'a> Command<'a> {
             WALLOPS { .. } => 38,
             ISON { .. } => 39,
             DIE { .. } => 40,
+            RESUME { .. } => 41,
+            OPERWALL { .. } => 42,
+            MONITOR { .. } => 43,
+            WATCH { .. } => 44,
+            CPRIVMSG { .. } => 45,
+            CNOTICE { .. } => 46,
+            RENAME { .. } => 47,
+            CLEARCHAN { .. } => 48,
+            ACCEPT { .. } => 49,
+            ACCESS { .. } => 50,
+            RELAYMSG { .. } => 51,
+            SESSIONS { .. } => 52,
         }
     }
 
@@ -479,7 +599,15 @@ impl<'a> Command<'a> {
                     Err(NeedMoreParams(CAPId))
                 }
             }
-            "AUTHENTICATE" => Ok(AUTHENTICATE {}),
+            "AUTHENTICATE" => {
+                if !message.params.is_empty() {
+                    Ok(AUTHENTICATE {
+                        data: message.params[0],
+                    })
+                } else {
+                    Err(NeedMoreParams(AUTHENTICATEId))
+                }
+            }
             "PASS" => {
                 if !message.params.is_empty() {
                     Ok(PASS {
@@ -514,6 +642,7 @@ impl<'a> Command<'a> {
                 if !message.params.is_empty() {
                     Ok(PING {
                         token: message.params[0],
+                        server: message.params.get(1).copied(),
                     })
                 } else {
                     Err(NeedMoreParams(PINGId))
@@ -538,19 +667,17 @@ impl<'a> Command<'a> {
                     Err(NeedMoreParams(OPERId))
                 }
             }
-            "QUIT" => Ok(QUIT {}),
+            "QUIT" => Ok(QUIT {
+                reason: message.params.get(0).copied(),
+            }),
             "JOIN" => {
                 if !message.params.is_empty() {
                     let mut param_it = message.params.iter();
                     // channels are separated by ','
                     let channels = param_it.next().unwrap().split(',').collect::<Vec<_>>();
-                    // keys are separated by ','
+                    // keys are separated by ',' and matched to channels by position.
+                    // extra keys are ignored and missing keys are treated as empty.
                     let keys_opt = param_it.next().map(|x| x.split(',').collect::<Vec<_>>());
-                    if let Some(ref keys) = keys_opt {
-                        if keys.len() != channels.len() {
-                            return Err(ParameterDoesntMatch(JOINId, 1));
-                        }
-                    }
                     Ok(JOIN {
                         channels,
                         keys: keys_opt,
@@ -817,7 +944,9 @@ impl<'a> Command<'a> {
                     Err(NeedMoreParams(KILLId))
                 }
             }
-            "REHASH" => Ok(REHASH {}),
+            "REHASH" => Ok(REHASH {
+                subcommand: message.params.first().copied(),
+            }),
             "RESTART" => Ok(RESTART {}),
             "SQUIT" => {
                 if message.params.len() >= 2 {
@@ -868,6 +997,208 @@ impl<'a> Command<'a> {
                     Ok(DIE { message: None })
                 }
             }
+            "RESUME" => {
+                if !message.params.is_empty() {
+                    Ok(RESUME {
+                        token: message.params[0],
+                    })
+                } else {
+                    Err(NeedMoreParams(RESUMEId))
+                }
+            }
+            "OPERWALL" => {
+                if !message.params.is_empty() {
+                    Ok(OPERWALL {
+                        text: message.params[0],
+                    })
+                } else {
+                    Err(NeedMoreParams(OPERWALLId))
+                }
+            }
+            "MONITOR" => {
+                if !message.params.is_empty() {
+                    let subcommand = match message.params[0].to_ascii_uppercase().as_str() {
+                        "+" => MonitorCommand::Add,
+                        "-" => MonitorCommand::Remove,
+                        "C" => MonitorCommand::Clear,
+                        "L" => MonitorCommand::List,
+                        "S" => MonitorCommand::Status,
+                        _ => {
+                            return Err(UnknownSubcommand(
+                                MONITORId,
+                                message.params[0].to_string(),
+                            ))
+                        }
+                    };
+                    // targets are separated by ','
+                    let targets = if message.params.len() >= 2 {
+                        message.params[1]
+                            .split(',')
+                            .filter(|t| !t.is_empty())
+                            .collect::<Vec<_>>()
+                    } else {
+                        vec![]
+                    };
+                    if targets.is_empty()
+                        && (subcommand == MonitorCommand::Add
+                            || subcommand == MonitorCommand::Remove)
+                    {
+                        Err(NeedMoreParams(MONITORId))
+                    } else {
+                        Ok(MONITOR {
+                            subcommand,
+                            targets,
+                        })
+                    }
+                } else {
+                    Err(NeedMoreParams(MONITORId))
+                }
+            }
+            "WATCH" => {
+                // entries can be separated by space or ','
+                let entries = message
+                    .params
+                    .iter()
+                    .flat_map(|p| p.split(','))
+                    .filter(|e| !e.is_empty())
+                    .collect::<Vec<_>>();
+                Ok(WATCH { entries })
+            }
+            "CPRIVMSG" => {
+                if message.params.len() >= 3 {
+                    Ok(CPRIVMSG {
+                        nickname: message.params[0],
+                        channel: message.params[1],
+                        text: message.params[2],
+                    })
+                } else {
+                    Err(NeedMoreParams(CPRIVMSGId))
+                }
+            }
+            "CNOTICE" => {
+                if message.params.len() >= 3 {
+                    Ok(CNOTICE {
+                        nickname: message.params[0],
+                        channel: message.params[1],
+                        text: message.params[2],
+                    })
+                } else {
+                    Err(NeedMoreParams(CNOTICEId))
+                }
+            }
+            "RENAME" => {
+                if message.params.len() >= 2 {
+                    Ok(RENAME {
+                        channel: message.params[0],
+                        new_channel: message.params[1],
+                        reason: message.params.get(2).copied(),
+                    })
+                } else {
+                    Err(NeedMoreParams(RENAMEId))
+                }
+            }
+            "CLEARCHAN" => {
+                if !message.params.is_empty() {
+                    let mut all = false;
+                    let mut lock = false;
+                    for (i, p) in message.params.iter().enumerate().skip(1) {
+                        match p.to_ascii_uppercase().as_str() {
+                            "ALL" => all = true,
+                            "LOCK" => lock = true,
+                            _ => return Err(WrongParameter(CLEARCHANId, i)),
+                        }
+                    }
+                    Ok(CLEARCHAN {
+                        channel: message.params[0],
+                        all,
+                        lock,
+                    })
+                } else {
+                    Err(NeedMoreParams(CLEARCHANId))
+                }
+            }
+            "ACCEPT" => {
+                if !message.params.is_empty() {
+                    Ok(ACCEPT {
+                        nicknames: message.params[0].split(',').collect::<Vec<_>>(),
+                    })
+                } else {
+                    Err(NeedMoreParams(ACCEPTId))
+                }
+            }
+            "ACCESS" => {
+                if message.params.len() >= 2 {
+                    let subcommand = match message.params[1].to_ascii_uppercase().as_str() {
+                        "ADD" => {
+                            if message.params.len() >= 4 {
+                                AccessCommand::Add {
+                                    mask: message.params[2],
+                                    level: message.params[3]
+                                        .parse()
+                                        .map_err(|_| WrongParameter(ACCESSId, 3))?,
+                                }
+                            } else {
+                                return Err(NeedMoreParams(ACCESSId));
+                            }
+                        }
+                        "DEL" => {
+                            if message.params.len() >= 3 {
+                                AccessCommand::Del {
+                                    mask: message.params[2],
+                                }
+                            } else {
+                                return Err(NeedMoreParams(ACCESSId));
+                            }
+                        }
+                        "LIST" => AccessCommand::List,
+                        _ => {
+                            return Err(UnknownSubcommand(
+                                ACCESSId,
+                                message.params[1].to_string(),
+                            ))
+                        }
+                    };
+                    Ok(ACCESS {
+                        channel: message.params[0],
+                        subcommand,
+                    })
+                } else {
+                    Err(NeedMoreParams(ACCESSId))
+                }
+            }
+            "RELAYMSG" => {
+                if message.params.len() >= 3 {
+                    Ok(RELAYMSG {
+                        channel: message.params[0],
+                        nick: message.params[1],
+                        text: message.params[2],
+                    })
+                } else {
+                    Err(NeedMoreParams(RELAYMSGId))
+                }
+            }
+            "SESSIONS" => {
+                let subcommand_name = message.params.first().map(|sc| sc.to_ascii_uppercase());
+                let subcommand = match subcommand_name.as_deref() {
+                    None | Some("LIST") => SessionsCommand::List,
+                    Some("KILL") => {
+                        if message.params.len() >= 2 {
+                            SessionsCommand::Kill {
+                                nick: message.params[1],
+                            }
+                        } else {
+                            return Err(NeedMoreParams(SESSIONSId));
+                        }
+                    }
+                    Some(_) => {
+                        return Err(UnknownSubcommand(
+                            SESSIONSId,
+                            message.params[0].to_string(),
+                        ))
+                    }
+                };
+                Ok(SESSIONS { subcommand })
+            }
             s => Err(UnknownCommand(s.to_string())),
         }
     }
@@ -904,10 +1235,6 @@ impl<'a> Command<'a> {
                 validate_username(username).map_err(|_| WrongParameter(USERId, 0))
             }
             OPER { name, .. } => validate_username(name).map_err(|_| WrongParameter(OPERId, 0)),
-            JOIN { channels, .. } => channels
-                .iter()
-                .try_for_each(|ch| validate_channel(ch))
-                .map_err(|_| WrongParameter(JOINId, 0)),
             PART { channels, .. } => channels
                 .iter()
                 .try_for_each(|ch| validate_channel(ch))
@@ -1012,18 +1339,28 @@ impl<'a> Command<'a> {
             }
             PRIVMSG { targets, .. } => {
                 targets.iter().try_for_each(|n| {
-                    validate_username(n)
-                        // in PRIVMSG we can use prefixed channels
-                        .map_err(|_| WrongParameter(PRIVMSGId, 0))
-                        .or_else(|_| validate_prefixed_channel(n, WrongParameter(PRIVMSGId, 0)))
+                    if let Some(mask) = get_masked_target(n).map(|(_, m)| m) {
+                        // operator can send to host mask ($#) or server mask ($$)
+                        validate_server_mask(mask, WrongParameter(PRIVMSGId, 0))
+                    } else {
+                        validate_username(n)
+                            // in PRIVMSG we can use prefixed channels
+                            .map_err(|_| WrongParameter(PRIVMSGId, 0))
+                            .or_else(|_| validate_prefixed_channel(n, WrongParameter(PRIVMSGId, 0)))
+                    }
                 })
             }
             NOTICE { targets, .. } => {
                 targets.iter().try_for_each(|n| {
-                    validate_username(n)
-                        // in NOTICE we can use prefixed channels
-                        .map_err(|_| WrongParameter(NOTICEId, 0))
-                        .or_else(|_| validate_prefixed_channel(n, WrongParameter(NOTICEId, 0)))
+                    if let Some(mask) = get_masked_target(n).map(|(_, m)| m) {
+                        // operator can send to host mask ($#) or server mask ($$)
+                        validate_server_mask(mask, WrongParameter(NOTICEId, 0))
+                    } else {
+                        validate_username(n)
+                            // in NOTICE we can use prefixed channels
+                            .map_err(|_| WrongParameter(NOTICEId, 0))
+                            .or_else(|_| validate_prefixed_channel(n, WrongParameter(NOTICEId, 0)))
+                    }
                 })
             }
             //WHO{ mask } => { Ok(()) }
@@ -1058,6 +1395,61 @@ impl<'a> Command<'a> {
             USERHOST { nicknames } => nicknames.iter().enumerate().try_for_each(|(i, n)| {
                 validate_username(n).map_err(|_| WrongParameter(USERHOSTId, i))
             }),
+            MONITOR { targets, .. } => targets
+                .iter()
+                .try_for_each(|n| validate_username(n))
+                .map_err(|_| WrongParameter(MONITORId, 1)),
+            WATCH { entries } => entries.iter().try_for_each(|e| {
+                if let Some(nick) = e.strip_prefix('+').or_else(|| e.strip_prefix('-')) {
+                    validate_username(nick).map_err(|_| WrongParameter(WATCHId, 0))
+                } else {
+                    match *e {
+                        "C" | "c" | "L" | "l" | "S" | "s" => Ok(()),
+                        _ => Err(WrongParameter(WATCHId, 0)),
+                    }
+                }
+            }),
+            CPRIVMSG { nickname, channel, .. } => {
+                validate_username(nickname).map_err(|_| WrongParameter(CPRIVMSGId, 0))?;
+                validate_channel(channel).map_err(|_| WrongParameter(CPRIVMSGId, 1))
+            }
+            CNOTICE { nickname, channel, .. } => {
+                validate_username(nickname).map_err(|_| WrongParameter(CNOTICEId, 0))?;
+                validate_channel(channel).map_err(|_| WrongParameter(CNOTICEId, 1))
+            }
+            RENAME {
+                channel,
+                new_channel,
+                ..
+            } => {
+                validate_channel(channel).map_err(|_| WrongParameter(RENAMEId, 0))?;
+                validate_channel(new_channel).map_err(|_| WrongParameter(RENAMEId, 1))
+            }
+            CLEARCHAN { channel, .. } => {
+                validate_channel(channel).map_err(|_| WrongParameter(CLEARCHANId, 0))
+            }
+            ACCEPT { nicknames } => nicknames.iter().try_for_each(|n| {
+                if *n == "*" {
+                    Ok(())
+                } else {
+                    let nick = n.strip_prefix(|c| c == '+' || c == '-').unwrap_or(n);
+                    validate_username(nick).map_err(|_| WrongParameter(ACCEPTId, 0))
+                }
+            }),
+            ACCESS { channel, .. } => {
+                validate_channel(channel).map_err(|_| WrongParameter(ACCESSId, 0))
+            }
+            RELAYMSG { channel, nick, .. } => {
+                validate_channel(channel).map_err(|_| WrongParameter(RELAYMSGId, 0))?;
+                // spoofed nick is put in source - it must not contain source separators.
+                if nick.contains(|c: char| c == '!' || c == '@' || c == '*' || c.is_whitespace()) {
+                    return Err(WrongParameter(RELAYMSGId, 1));
+                }
+                validate_username(nick).map_err(|_| WrongParameter(RELAYMSGId, 1))
+            }
+            SESSIONS {
+                subcommand: SessionsCommand::Kill { nick },
+            } => validate_username(nick).map_err(|_| WrongParameter(SESSIONSId, 1)),
             _ => Ok(()),
         }
     }
@@ -1109,6 +1501,14 @@ mod test {
             }),
             Message::from_shared_str("USER guest 0 * Benny").map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "ADD", "$a:tommy", "op"]
+            }),
+            Message::from_shared_str("ACCESS #cats ADD $a:tommy op").map_err(|e| e.to_string())
+        );
         assert_eq!(
             Ok(Message {
                 source: None,
@@ -1117,6 +1517,22 @@ mod test {
             }),
             Message::from_shared_str("PRIVMSG bobby ::-). Hello guy!").map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(Message {
+                source: None,
+                command: "TOPIC",
+                params: vec!["#gizmo"]
+            }),
+            Message::from_shared_str("TOPIC #gizmo").map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(Message {
+                source: None,
+                command: "TOPIC",
+                params: vec!["#gizmo", ""]
+            }),
+            Message::from_shared_str("TOPIC #gizmo :").map_err(|e| e.to_string())
+        );
         assert_eq!(
             Ok(Message {
                 source: Some("mati!mat@gg.com"),
@@ -1129,6 +1545,34 @@ mod test {
             Err("Wrong source syntax".to_string()),
             Message::from_shared_str(":mati@mat!gg.com QUIT").map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Err("Message is empty".to_string()),
+            Message::from_shared_str("").map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Message is empty".to_string()),
+            Message::from_shared_str("     ").map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Message is empty".to_string()),
+            Message::from_shared_str(" \t  ").map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(Message {
+                source: None,
+                command: "PING",
+                params: vec![]
+            }),
+            Message::from_shared_str("PING    ").map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(Message {
+                source: None,
+                command: "PING",
+                params: vec!["welcome"]
+            }),
+            Message::from_shared_str("  PING welcome   ").map_err(|e| e.to_string())
+        );
     }
 
     #[test]
@@ -1262,7 +1706,16 @@ mod test {
         );
 
         assert_eq!(
-            Ok(AUTHENTICATE {}),
+            Ok(AUTHENTICATE { data: "PLAIN" }),
+            Command::from_message(&Message {
+                source: None,
+                command: "AUTHENTICATE",
+                params: vec!["PLAIN"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'AUTHENTICATE' needs more parameters".to_string()),
             Command::from_message(&Message {
                 source: None,
                 command: "AUTHENTICATE",
@@ -1370,7 +1823,10 @@ mod test {
         );
 
         assert_eq!(
-            Ok(PING { token: "xxxaaa" }),
+            Ok(PING {
+                token: "xxxaaa",
+                server: None
+            }),
             Command::from_message(&Message {
                 source: None,
                 command: "PING",
@@ -1378,6 +1834,18 @@ mod test {
             })
             .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(PING {
+                token: "xxxaaa",
+                server: Some("irc.irc")
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "PING",
+                params: vec!["xxxaaa", "irc.irc"]
+            })
+            .map_err(|e| e.to_string())
+        );
         assert_eq!(
             Err("Command 'PING' needs more parameters".to_string()),
             Command::from_message(&Message {
@@ -1420,7 +1888,7 @@ mod test {
         );
 
         assert_eq!(
-            Ok(QUIT {}),
+            Ok(QUIT { reason: None }),
             Command::from_message(&Message {
                 source: None,
                 command: "QUIT",
@@ -1428,6 +1896,17 @@ mod test {
             })
             .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(QUIT {
+                reason: Some("Gone to sleep")
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "QUIT",
+                params: vec!["Gone to sleep"]
+            })
+            .map_err(|e| e.to_string())
+        );
 
         assert_eq!(
             Ok(JOIN {
@@ -1453,17 +1932,22 @@ mod test {
             })
             .map_err(|e| e.to_string())
         );
+        // invalid channel names are reported by JOIN handler
         assert_eq!(
-            Err("Wrong parameter 0 in command 'JOIN'".to_string()),
-            Command::from_message(&Message {
-                source: None,
-                command: "JOIN",
-                params: vec!["#cats,&fru:its,#software"]
-            })
+            Ok(JOIN {
+                channels: vec!["#cats", "&fru:its", "#software"],
+                keys: None
+            }),
+            Command::from_message(
+                &Message::from_shared_str("JOIN #cats,&fru:its,#software").unwrap()
+            )
             .map_err(|e| e.to_string())
         );
         assert_eq!(
-            Err("Wrong parameter 0 in command 'JOIN'".to_string()),
+            Ok(JOIN {
+                channels: vec!["#cats", "fruits", "#software"],
+                keys: None
+            }),
             Command::from_message(&Message {
                 source: None,
                 command: "JOIN",
@@ -1484,7 +1968,10 @@ mod test {
             .map_err(|e| e.to_string())
         );
         assert_eq!(
-            Err("Parameter 1 doesn't match for command 'JOIN'".to_string()),
+            Ok(JOIN {
+                channels: vec!["#cats", "&fruits", "#software", "#countries"],
+                keys: Some(vec!["mycat", "apple", "wesnoth"])
+            }),
             Command::from_message(&Message {
                 source: None,
                 command: "JOIN",
@@ -1493,7 +1980,10 @@ mod test {
             .map_err(|e| e.to_string())
         );
         assert_eq!(
-            Err("Parameter 1 doesn't match for command 'JOIN'".to_string()),
+            Ok(JOIN {
+                channels: vec!["#cats", "&fruits", "#software"],
+                keys: Some(vec!["mycat", "apple", "wesnoth", "zizi"])
+            }),
             Command::from_message(&Message {
                 source: None,
                 command: "JOIN",
@@ -1578,6 +2068,18 @@ mod test {
             })
             .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(TOPIC {
+                channel: "#gizmo",
+                topic: Some("")
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "TOPIC",
+                params: vec!["#gizmo", ""]
+            })
+            .map_err(|e| e.to_string())
+        );
         assert_eq!(
             Err("Wrong parameter 0 in command 'TOPIC'".to_string()),
             Command::from_message(&Message {
@@ -2781,9 +3283,30 @@ mod test {
             .map_err(|e| e.to_string())
         );
         assert_eq!(
-            Err("Command 'PRIVMSG' needs more parameters".to_string()),
-            Command::from_message(&Message {
-                source: None,
+            Ok(PRIVMSG {
+                targets: vec!["$#*.org", "$$irc.*"],
+                text: "Hello, cruel world!"
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "PRIVMSG",
+                params: vec!["$#*.org,$$irc.*", "Hello, cruel world!"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'PRIVMSG'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "PRIVMSG",
+                params: vec!["$#somebody", "Hello, cruel world!"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'PRIVMSG' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
                 command: "PRIVMSG",
                 params: vec!["#graphics,&musics,jimmy"]
             })
@@ -3033,7 +3556,7 @@ mod test {
         );
 
         assert_eq!(
-            Ok(REHASH {}),
+            Ok(REHASH { subcommand: None }),
             Command::from_message(&Message {
                 source: None,
                 command: "REHASH",
@@ -3041,6 +3564,17 @@ mod test {
             })
             .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(REHASH {
+                subcommand: Some("BANS")
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "REHASH",
+                params: vec!["BANS"]
+            })
+            .map_err(|e| e.to_string())
+        );
 
         assert_eq!(
             Ok(RESTART {}),
@@ -3205,6 +3739,547 @@ mod test {
             .map_err(|e| e.to_string())
         );
 
+        assert_eq!(
+            Ok(RESUME { token: "abcdef" }),
+            Command::from_message(&Message {
+                source: None,
+                command: "RESUME",
+                params: vec!["abcdef"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(RESUME { token: "abcdef" }),
+            Command::from_message(&Message {
+                source: None,
+                command: "RESUME",
+                params: vec!["abcdef", "2022-04-01T10:00:00.000Z"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'RESUME' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "RESUME",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(OPERWALL {
+                text: "This is some message"
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "OPERWALL",
+                params: vec!["This is some message"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'OPERWALL' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "OPERWALL",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(MONITOR {
+                subcommand: MonitorCommand::Add,
+                targets: vec!["bob", "jerry"]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["+", "bob,jerry"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(MONITOR {
+                subcommand: MonitorCommand::Remove,
+                targets: vec!["bob"]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["-", "bob"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(MONITOR {
+                subcommand: MonitorCommand::Clear,
+                targets: vec![]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["C"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(MONITOR {
+                subcommand: MonitorCommand::List,
+                targets: vec![]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["l"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(MONITOR {
+                subcommand: MonitorCommand::Status,
+                targets: vec![]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["S"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'MONITOR' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["+"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Unknown subcommand 'X' in command 'MONITOR'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "MONITOR",
+                params: vec!["X", "bob"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(WATCH {
+                entries: vec!["+bob", "-jerry", "+tom", "L"]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "WATCH",
+                params: vec!["+bob,-jerry", "+tom", "L"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(WATCH { entries: vec![] }),
+            Command::from_message(&Message {
+                source: None,
+                command: "WATCH",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'WATCH'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "WATCH",
+                params: vec!["+bob", "X"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(CPRIVMSG {
+                nickname: "bob",
+                channel: "#chat",
+                text: "Hello"
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "CPRIVMSG",
+                params: vec!["bob", "#chat", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(CNOTICE {
+                nickname: "bob",
+                channel: "#chat",
+                text: "Hello"
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "CNOTICE",
+                params: vec!["bob", "#chat", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'CPRIVMSG' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "CPRIVMSG",
+                params: vec!["bob", "#chat"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 1 in command 'CNOTICE'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "CNOTICE",
+                params: vec!["bob", "chat", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(RENAME {
+                channel: "#old",
+                new_channel: "#new",
+                reason: Some("Better name")
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "RENAME",
+                params: vec!["#old", "#new", "Better name"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(RENAME {
+                channel: "#old",
+                new_channel: "#new",
+                reason: None
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "RENAME",
+                params: vec!["#old", "#new"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 1 in command 'RENAME'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "RENAME",
+                params: vec!["#old", "new"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(CLEARCHAN {
+                channel: "#spam",
+                all: false,
+                lock: false
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "CLEARCHAN",
+                params: vec!["#spam"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(CLEARCHAN {
+                channel: "#spam",
+                all: true,
+                lock: true
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "CLEARCHAN",
+                params: vec!["#spam", "lock", "ALL"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 1 in command 'CLEARCHAN'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "CLEARCHAN",
+                params: vec!["#spam", "EVERYONE"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'CLEARCHAN'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "CLEARCHAN",
+                params: vec!["spam"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'CLEARCHAN' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "CLEARCHAN",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(ACCEPT {
+                nicknames: vec!["+bobby", "-andy", "*", "jimmy"]
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCEPT",
+                params: vec!["+bobby,-andy,*,jimmy"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'ACCEPT'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCEPT",
+                params: vec!["+bob.by"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'ACCEPT' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCEPT",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(ACCESS {
+                channel: "#cats",
+                subcommand: AccessCommand::Add {
+                    mask: "*!*@cats.net",
+                    level: ChannelAccessLevel::HalfOp
+                }
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "add", "*!*@cats.net", "HALFOP"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(ACCESS {
+                channel: "#cats",
+                subcommand: AccessCommand::Del { mask: "$a:tommy" }
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "DEL", "$a:tommy"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(ACCESS {
+                channel: "#cats",
+                subcommand: AccessCommand::Add {
+                    mask: "$a:tommy",
+                    level: ChannelAccessLevel::Founder
+                }
+            }),
+            Command::from_message(
+                &Message::from_shared_str("ACCESS #cats ADD $a:tommy founder").unwrap()
+            )
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(ACCESS {
+                channel: "#cats",
+                subcommand: AccessCommand::List
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "LIST"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 3 in command 'ACCESS'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "ADD", "*!*@cats.net", "king"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'ACCESS'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["cats", "LIST"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Unknown subcommand 'CLEAR' in command 'ACCESS'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "CLEAR"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'ACCESS' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "ACCESS",
+                params: vec!["#cats", "ADD", "*!*@cats.net"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok(RELAYMSG {
+                channel: "#chat",
+                nick: "discord/alice",
+                text: "Hello"
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "RELAYMSG",
+                params: vec!["#chat", "discord/alice", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'RELAYMSG' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "RELAYMSG",
+                params: vec!["#chat", "discord/alice"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 0 in command 'RELAYMSG'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "RELAYMSG",
+                params: vec!["chat", "discord/alice", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 1 in command 'RELAYMSG'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "RELAYMSG",
+                params: vec!["#chat", "discord/al:ice", "Hello"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        for nick in [
+            "discord/al!ice",
+            "discord/al@ice",
+            "discord/*",
+            "discord/al ice",
+        ] {
+            assert_eq!(
+                Err("Wrong parameter 1 in command 'RELAYMSG'".to_string()),
+                Command::from_message(&Message {
+                    source: None,
+                    command: "RELAYMSG",
+                    params: vec!["#chat", nick, "Hello"]
+                })
+                .map_err(|e| e.to_string())
+            );
+        }
+
+        assert_eq!(
+            Ok(SESSIONS {
+                subcommand: SessionsCommand::List
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec![]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(SESSIONS {
+                subcommand: SessionsCommand::List
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec!["list"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(SESSIONS {
+                subcommand: SessionsCommand::Kill { nick: "bobby" }
+            }),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec!["KILL", "bobby"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Command 'SESSIONS' needs more parameters".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec!["KILL"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Wrong parameter 1 in command 'SESSIONS'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec!["KILL", "bob:by"]
+            })
+            .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Unknown subcommand 'DROP' in command 'SESSIONS'".to_string()),
+            Command::from_message(&Message {
+                source: None,
+                command: "SESSIONS",
+                params: vec!["DROP"]
+            })
+            .map_err(|e| e.to_string())
+        );
+
+        assert_eq!(
+            Ok("REHASH"),
+            Command::from_message(&Message {
+                source: None,
+                command: "rehash",
+                params: vec![]
+            })
+            .map(|cmd| cmd.name())
+            .map_err(|e| e.to_string())
+        );
+
         // case-insensitivness
         assert_eq!(
             Ok(RESTART {}),
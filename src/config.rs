@@ -17,20 +17,23 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use serde::de::DeserializeOwned;
 use serde::Deserializer;
 use serde_derive::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::net::IpAddr;
 use std::str::FromStr;
 use validator::Validate;
 
-use crate::utils::match_wildcard;
+use crate::utils::CHANMODE_FREE_INVITE;
+use crate::utils::match_user_mask;
 use crate::utils::validate_channel;
 use crate::utils::validate_password_hash;
+use crate::utils::validate_server_name;
 use crate::utils::validate_username;
 
 #[derive(clap::Parser, Clone)]
@@ -66,13 +69,75 @@ pub(crate) struct TLSConfig {
     pub(crate) cert_key_file: String,
 }
 
-#[derive(PartialEq, Eq, Deserialize, Debug, Validate)]
+// who can create new channels by joining to them.
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ChannelCreation {
+    Anyone,
+    Registered,
+    Opers,
+}
+
+// how host of client is displayed in source, WHO and WHOIS.
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HostDisplay {
+    // hostname from DNS lookup or IP address if lookup is disabled.
+    Hostname,
+    // IP address - DNS lookup is not done.
+    Ip,
+    // cloaked IP address.
+    Cloak,
+}
+
+// TCP socket options of listener. Accepted connections inherit them from
+// listening socket.
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug, Default)]
+pub(crate) struct SocketConfig {
+    // maximal number of pending connections. Default is 1024.
+    pub(crate) backlog: Option<u32>,
+    // disable Nagle's algorithm (TCP_NODELAY).
+    #[serde(default)]
+    pub(crate) nodelay: bool,
+    // enable TCP keepalive (SO_KEEPALIVE).
+    #[serde(default)]
+    pub(crate) keepalive: bool,
+    // allow to bind address that is in TIME_WAIT state (SO_REUSEADDR).
+    // Default is true on unix systems.
+    pub(crate) reuseaddr: Option<bool>,
+    // allow many listeners (processes) to bind the same port (SO_REUSEPORT, unix only).
+    #[serde(default)]
+    pub(crate) reuseport: bool,
+}
+
+// additional listener - it can report own server name to clients. This name is used
+// only in registration burst (001-005 and 221 replies) and in PONG. Other replies and
+// messages use name of server.
+#[derive(PartialEq, Eq, Deserialize, Debug, Clone, Validate)]
+pub(crate) struct ListenerConfig {
+    pub(crate) listen: IpAddr,
+    pub(crate) port: u16,
+    #[validate(custom = "validate_server_name")]
+    pub(crate) name: Option<String>,
+    // capabilities that are not available for clients connected to this listener.
+    pub(crate) disabled_caps: Option<Vec<String>>,
+    // display of host of clients connected to this listener. If not set then
+    // global host_display is used.
+    pub(crate) host_display: Option<HostDisplay>,
+    // socket options of this listener.
+    pub(crate) socket: Option<SocketConfig>,
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize, Debug, Validate)]
 pub(crate) struct OperatorConfig {
     #[validate(custom = "validate_username")]
     pub(crate) name: String,
     #[validate(custom = "validate_password_hash")]
     pub(crate) password: String,
     pub(crate) mask: Option<String>,
+    // if true then OPER is possible only from TLS connection.
+    #[serde(default)]
+    pub(crate) require_tls: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug, Default)]
@@ -82,6 +147,15 @@ pub(crate) struct UserModes {
     pub(crate) local_oper: bool,
     pub(crate) registered: bool,
     pub(crate) wallops: bool,
+    // bot mode (+B) - marks user as bot.
+    #[serde(default)]
+    pub(crate) bot: bool,
+    // hidden operator (+H) - operator status is visible only for other operators.
+    #[serde(default)]
+    pub(crate) hide_oper: bool,
+    // caller-ID (+g) - only users from accept list can send private messages.
+    #[serde(default)]
+    pub(crate) caller_id: bool,
 }
 
 impl fmt::Display for UserModes {
@@ -102,6 +176,15 @@ impl fmt::Display for UserModes {
         if self.wallops {
             s.push('w');
         }
+        if self.bot {
+            s.push('B');
+        }
+        if self.hide_oper {
+            s.push('H');
+        }
+        if self.caller_id {
+            s.push('g');
+        }
         f.write_str(&s)
     }
 }
@@ -112,6 +195,120 @@ impl UserModes {
     }
 }
 
+// maximal number of targets in commands (TARGMAX). If not set then no limit.
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug, Default)]
+pub(crate) struct TargMaxConfig {
+    pub(crate) ison: Option<usize>,
+    pub(crate) join: Option<usize>,
+    pub(crate) kick: Option<usize>,
+    pub(crate) notice: Option<usize>,
+    pub(crate) part: Option<usize>,
+    pub(crate) privmsg: Option<usize>,
+    pub(crate) userhost: Option<usize>,
+    pub(crate) whois: Option<usize>,
+}
+
+impl TargMaxConfig {
+    // get command names with their limits.
+    pub(crate) fn limits(&self) -> [(&'static str, Option<usize>); 8] {
+        [
+            ("ISON", self.ison),
+            ("JOIN", self.join),
+            ("KICK", self.kick),
+            ("NOTICE", self.notice),
+            ("PART", self.part),
+            ("PRIVMSG", self.privmsg),
+            ("USERHOST", self.userhost),
+            ("WHOIS", self.whois),
+        ]
+    }
+}
+
+// files with parts of configuration that can be reloaded separately by REHASH command.
+// If file is given then its content replaces appropriate part of main configuration.
+#[derive(Clone, PartialEq, Eq, Deserialize, Debug, Default)]
+pub(crate) struct IncludeConfig {
+    // text file with MOTD.
+    pub(crate) motd: Option<String>,
+    // TOML file with operators (operators tables like in main configuration).
+    pub(crate) operators: Option<String>,
+    // TOML file with server bans (bans - list of source masks).
+    pub(crate) bans: Option<String>,
+    // TOML file with reserved nicks (reserved_nicks - list of nick masks).
+    pub(crate) reserved_nicks: Option<String>,
+    // TOML file with reserved channels (reserved_channels - list of channel masks).
+    pub(crate) reserved_channels: Option<String>,
+}
+
+#[derive(Deserialize, Validate)]
+struct OperatorsFile {
+    #[validate]
+    operators: Vec<OperatorConfig>,
+}
+
+#[derive(Deserialize)]
+struct BansFile {
+    bans: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ReservedNicksFile {
+    reserved_nicks: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ReservedChannelsFile {
+    reserved_channels: Vec<String>,
+}
+
+// add file path to error to easily find bad file.
+fn include_error<E: fmt::Display>(path: &str, e: E) -> Box<dyn Error> {
+    format!("{}: {}", path, e).into()
+}
+
+fn read_include_file(path: &str) -> Result<String, Box<dyn Error>> {
+    fs::read_to_string(path).map_err(|e| include_error(path, e))
+}
+
+fn parse_include_file<T: DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    let content = read_include_file(path)?;
+    toml::from_str(&content).map_err(|e| include_error(path, e))
+}
+
+impl IncludeConfig {
+    pub(crate) fn load_motd(path: &str) -> Result<String, Box<dyn Error>> {
+        Ok(read_include_file(path)?.trim_end().to_string())
+    }
+
+    pub(crate) fn load_operators(path: &str) -> Result<Vec<OperatorConfig>, Box<dyn Error>> {
+        let file: OperatorsFile = parse_include_file(path)?;
+        file.validate().map_err(|e| include_error(path, e))?;
+        Ok(file.operators)
+    }
+
+    pub(crate) fn load_bans(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let file: BansFile = parse_include_file(path)?;
+        Ok(file.bans)
+    }
+
+    pub(crate) fn load_reserved_nicks(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let file: ReservedNicksFile = parse_include_file(path)?;
+        Ok(file.reserved_nicks)
+    }
+
+    pub(crate) fn load_reserved_channels(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let file: ReservedChannelsFile = parse_include_file(path)?;
+        Ok(file.reserved_channels)
+    }
+}
+
+// rate limit: maximal number of requests in period (in seconds).
+#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Debug)]
+pub(crate) struct RateLimitConfig {
+    pub(crate) count: usize,
+    pub(crate) period: u64,
+}
+
 #[derive(Clone, PartialEq, Eq, Deserialize, Debug, Validate, Default)]
 pub(crate) struct ChannelModes {
     // If channel modes we use Option to avoid unnecessary field definition if list
@@ -131,6 +328,23 @@ pub(crate) struct ChannelModes {
     pub(crate) secret: bool,
     pub(crate) protected_topic: bool,
     pub(crate) no_external_messages: bool,
+    #[serde(default)]
+    pub(crate) free_invite: bool,
+    // permanent channel is not removed if it has no users.
+    #[serde(default)]
+    pub(crate) permanent: bool,
+    // joining users are hidden from other users until they send message to channel.
+    #[serde(default)]
+    pub(crate) delayed_join: bool,
+    // only users logged in to account can join to channel.
+    #[serde(default)]
+    pub(crate) registered_only: bool,
+    // messages with color or formatting codes can be sent only by channel operators.
+    #[serde(default)]
+    pub(crate) no_colors: bool,
+    // channel to which are forwarded users that can't join to this channel.
+    #[serde(default)]
+    pub(crate) forward: Option<String>,
 }
 
 impl ChannelModes {
@@ -144,14 +358,15 @@ impl ChannelModes {
         }
     }
 
-    pub(crate) fn banned(&self, source: &str) -> bool {
+    // check whether user is banned. account is name of account that user logged in.
+    pub(crate) fn banned(&self, source: &str, account: Option<&str>) -> bool {
         self.ban
             .as_ref()
-            .map_or(false, |b| b.iter().any(|b| match_wildcard(b, source)))
+            .map_or(false, |b| b.iter().any(|b| match_user_mask(b, source, account)))
             && (!self
                 .exception
                 .as_ref()
-                .map_or(false, |e| e.iter().any(|e| match_wildcard(e, source))))
+                .map_or(false, |e| e.iter().any(|e| match_user_mask(e, source, account))))
     }
 
     // rename user - just rename nick in lists.
@@ -202,12 +417,30 @@ impl fmt::Display for ChannelModes {
         if self.no_external_messages {
             s.push('n');
         }
+        if self.free_invite {
+            s.push(CHANMODE_FREE_INVITE);
+        }
+        if self.permanent {
+            s.push('P');
+        }
+        if self.delayed_join {
+            s.push('D');
+        }
+        if self.registered_only {
+            s.push('R');
+        }
+        if self.no_colors {
+            s.push('c');
+        }
         if self.key.is_some() {
             s.push('k');
         }
         if self.client_limit.is_some() {
             s.push('l');
         }
+        if self.forward.is_some() {
+            s.push('f');
+        }
         if let Some(ref k) = self.key {
             s.push(' ');
             s += k;
@@ -216,6 +449,10 @@ impl fmt::Display for ChannelModes {
             s.push(' ');
             s += &l.to_string();
         }
+        if let Some(ref fw) = self.forward {
+            s.push(' ');
+            s += fw;
+        }
         if let Some(ref ban) = self.ban {
             ban.iter().for_each(|b| {
                 s += " +b ";
@@ -269,6 +506,53 @@ impl fmt::Display for ChannelModes {
     }
 }
 
+// channel status granted automatically by channel access list.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ChannelAccessLevel {
+    Voice,
+    HalfOp,
+    Op,
+    Founder,
+}
+
+impl ChannelAccessLevel {
+    // channel mode letter of status.
+    pub(crate) fn mode_char(self) -> char {
+        match self {
+            ChannelAccessLevel::Voice => 'v',
+            ChannelAccessLevel::HalfOp => 'h',
+            ChannelAccessLevel::Op => 'o',
+            ChannelAccessLevel::Founder => 'q',
+        }
+    }
+}
+
+impl fmt::Display for ChannelAccessLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelAccessLevel::Voice => f.write_str("voice"),
+            ChannelAccessLevel::HalfOp => f.write_str("halfop"),
+            ChannelAccessLevel::Op => f.write_str("op"),
+            ChannelAccessLevel::Founder => f.write_str("founder"),
+        }
+    }
+}
+
+impl FromStr for ChannelAccessLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "voice" => Ok(ChannelAccessLevel::Voice),
+            "halfop" => Ok(ChannelAccessLevel::HalfOp),
+            "op" => Ok(ChannelAccessLevel::Op),
+            "founder" => Ok(ChannelAccessLevel::Founder),
+            _ => Err(format!("Unknown channel access level '{}'", s)),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Deserialize, Debug, Validate)]
 pub(crate) struct ChannelConfig {
     #[validate(custom = "validate_channel")]
@@ -276,6 +560,9 @@ pub(crate) struct ChannelConfig {
     pub(crate) topic: Option<String>,
     #[validate]
     pub(crate) modes: ChannelModes,
+    // access list - source masks (or account masks prefixed by '$a:') with
+    // channel statuses granted automatically on join.
+    pub(crate) access: Option<HashMap<String, ChannelAccessLevel>>,
 }
 
 #[derive(PartialEq, Eq, Deserialize, Debug, Validate)]
@@ -293,28 +580,108 @@ pub(crate) struct UserConfig {
 /// Main configuration structure.
 #[derive(PartialEq, Eq, Deserialize, Debug, Validate)]
 pub(crate) struct MainConfig {
-    #[validate(contains = ".")]
+    #[validate(custom = "validate_server_name")]
     pub(crate) name: String,
     pub(crate) admin_info: String,
     pub(crate) admin_info2: Option<String>,
     pub(crate) admin_email: Option<String>,
     pub(crate) info: String,
     pub(crate) motd: String,
+    // NOTICE lines sent to client just after connection (before registration).
+    pub(crate) banner: Option<String>,
     pub(crate) listen: IpAddr,
     pub(crate) port: u16,
     pub(crate) network: String,
     #[validate(custom = "validate_password_hash")]
     pub(crate) password: Option<String>,
     pub(crate) max_connections: Option<usize>,
+    // IP address masks of hosts that are not limited by max_connections.
+    pub(crate) max_connections_exempt: Option<Vec<String>>,
     pub(crate) max_joins: Option<usize>,
+    // maximal number of targets in single user's MONITOR or WATCH list.
+    pub(crate) max_monitor: Option<usize>,
+    // maximal number of MONITOR and WATCH entries in whole server.
+    pub(crate) max_monitor_global: Option<usize>,
+    // maximal number of WHO replies sent to non-operator. Operators are not limited.
+    pub(crate) max_who_results: Option<usize>,
+    // number of channel members above which JOIN and PART of users are sent only to
+    // channel operators (and to joining or parting user). If not set then sent to all.
+    pub(crate) quiet_join_threshold: Option<usize>,
+    // maximal length of line received from client (advertised as LINELEN).
+    #[validate(range(min = 512))]
+    pub(crate) max_line_length: Option<usize>,
+    pub(crate) targmax: Option<TargMaxConfig>,
+    // restriction for creating new channels. If not set then anyone can create channel.
+    pub(crate) channel_creation: Option<ChannelCreation>,
+    // nick masks reserved for operators and users defined with that nick.
+    pub(crate) reserved_nicks: Option<Vec<String>>,
+    // channel masks that can be created only by operators.
+    pub(crate) reserved_channels: Option<Vec<String>>,
+    // limit of WHOIS queries per user. Operators are not limited.
+    pub(crate) whois_rate_limit: Option<RateLimitConfig>,
+    // source masks of users (bots, bridges) that are not limited by rate limits.
+    pub(crate) rate_limit_exempt: Option<Vec<String>>,
+    // limit of CTCP replies sent by user to single target - breaks CTCP reply loops.
+    pub(crate) ctcp_reply_rate_limit: Option<RateLimitConfig>,
+    // time in seconds after registration when user can not send CTCP requests.
+    pub(crate) ctcp_grace_period: Option<u64>,
+    // limit of NOTICE messages sent by user. Operators and bots are not limited.
+    pub(crate) notice_rate_limit: Option<RateLimitConfig>,
+    // limit of new channels created by user. Operators are not limited.
+    pub(crate) channel_creation_rate_limit: Option<RateLimitConfig>,
+    // limit of AWAY changes notified to monitors. Excess changes are not notified.
+    pub(crate) away_rate_limit: Option<RateLimitConfig>,
+    // accounts of bridge bots that can relay messages by RELAYMSG command.
+    pub(crate) relay_accounts: Option<Vec<String>>,
+    // maximal number of concurrent sessions logged in to the same account by SASL.
+    pub(crate) max_account_sessions: Option<usize>,
+    // SHA-256 fingerprints of TLS client certificates mapped to accounts (SASL EXTERNAL).
+    pub(crate) cert_fingerprints: Option<HashMap<String, String>>,
+    // additional commands that can be used only by operators.
+    pub(crate) oper_commands: Option<Vec<String>>,
+    // server bans - source masks of users that can not connect to server.
+    pub(crate) bans: Option<Vec<String>>,
+    // included files that can be reloaded by REHASH.
+    pub(crate) include: Option<IncludeConfig>,
+    // time in seconds after which unused invitation expires. If not set then never expires.
+    pub(crate) invite_timeout: Option<u64>,
+    // time in seconds after which WHOWAS entries are removed. If not set then kept forever.
+    pub(crate) whowas_retention: Option<u64>,
+    pub(crate) max_realname_len: Option<usize>,
+    #[serde(default)]
+    pub(crate) realname_no_control_chars: bool,
+    // if true then operator that killed user is shown only to operators.
+    #[serde(default)]
+    pub(crate) kill_hide_oper: bool,
+    // if true then operator can view (but not change) modes of other users.
+    #[serde(default)]
+    pub(crate) oper_view_user_modes: bool,
     pub(crate) ping_timeout: u64,
     pub(crate) pong_timeout: u64,
+    // time in seconds after which registered client that sends nothing except PONG
+    // is disconnected. If not set then idle clients are not disconnected.
+    pub(crate) idle_timeout: Option<u64>,
+    // grace period in seconds for resuming session. If not set then resume is disabled.
+    pub(crate) resume_timeout: Option<u64>,
     pub(crate) dns_lookup: bool,
+    // path to MaxMind GeoIP2 or GeoLite2 country database (needs geoip feature).
+    pub(crate) geoip_db: Option<String>,
     pub(crate) default_user_modes: UserModes,
     pub(crate) log_file: Option<String>,
     #[serde(deserialize_with = "tracing_log_level_deserialize")]
     pub(crate) log_level: tracing::Level,
     pub(crate) tls: Option<TLSConfig>,
+    // additional plain listeners. The TLS applies only for main listener.
+    #[validate]
+    pub(crate) listeners: Option<Vec<ListenerConfig>>,
+    // socket options of main listener.
+    pub(crate) socket: Option<SocketConfig>,
+    // capabilities that are not available for clients connected to main listener.
+    pub(crate) disabled_caps: Option<Vec<String>>,
+    // display of host of clients. If not set then hostname is displayed.
+    pub(crate) host_display: Option<HostDisplay>,
+    // secret key used to make cloaked hosts.
+    pub(crate) cloak_key: Option<String>,
     // If MainConfig modes we use Option to avoid unnecessary field definition if list
     // in this field should be. The administrator can omit fields for empty lists.
     #[validate]
@@ -371,6 +738,7 @@ impl MainConfig {
                 config.log_file = Some(log_file)
             }
             config.dns_lookup = config.dns_lookup || cli.dns_lookup;
+            config.load_includes()?;
 
             // get indicator to check later
             let (have_cert, have_cert_key) =
@@ -399,12 +767,39 @@ impl MainConfig {
                     clap::ErrorKind::ValueValidation,
                     "Wrong nikname lengths",
                 )))
+            } else if !config.validate_cloak_key() {
+                Err(Box::new(clap::error::Error::raw(
+                    clap::ErrorKind::ValueValidation,
+                    "Cloaked hosts require non-empty cloak_key",
+                )))
             } else {
                 Ok(config)
             }
         }
     }
 
+    // load included files - they replace appropriate parts of configuration.
+    fn load_includes(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(include) = self.include.clone() {
+            if let Some(ref path) = include.motd {
+                self.motd = IncludeConfig::load_motd(path)?;
+            }
+            if let Some(ref path) = include.operators {
+                self.operators = Some(IncludeConfig::load_operators(path)?);
+            }
+            if let Some(ref path) = include.bans {
+                self.bans = Some(IncludeConfig::load_bans(path)?);
+            }
+            if let Some(ref path) = include.reserved_nicks {
+                self.reserved_nicks = Some(IncludeConfig::load_reserved_nicks(path)?);
+            }
+            if let Some(ref path) = include.reserved_channels {
+                self.reserved_channels = Some(IncludeConfig::load_reserved_channels(path)?);
+            }
+        }
+        Ok(())
+    }
+
     fn validate_nicknames(&self) -> bool {
         if let Some(ref users) = self.users {
             !users.iter().any(|u| u.nick.len() > 200)
@@ -412,6 +807,17 @@ impl MainConfig {
             true
         }
     }
+
+    // cloaks made with empty key can be reversed by checking all IP addresses.
+    fn validate_cloak_key(&self) -> bool {
+        let cloak = Some(HostDisplay::Cloak);
+        let cloak_used = self.host_display == cloak
+            || self
+                .listeners
+                .as_ref()
+                .map_or(false, |ls| ls.iter().any(|l| l.host_display == cloak));
+        !cloak_used || self.cloak_key.as_ref().map_or(false, |k| !k.is_empty())
+    }
 }
 
 impl Default for MainConfig {
@@ -427,16 +833,54 @@ impl Default for MainConfig {
             network: "IRCnetwork".to_string(),
             password: None,
             motd: "Hello, world!".to_string(),
+            banner: None,
             max_connections: None,
             max_joins: None,
+            max_monitor: None,
+            max_monitor_global: None,
+            max_who_results: None,
+            quiet_join_threshold: None,
+            max_line_length: None,
+            targmax: None,
+            channel_creation: None,
+            reserved_nicks: None,
+            reserved_channels: None,
+            whois_rate_limit: None,
+            bans: None,
+            include: None,
+            invite_timeout: None,
+            whowas_retention: None,
+            max_connections_exempt: None,
+            disabled_caps: None,
+            host_display: None,
+            cloak_key: None,
+            rate_limit_exempt: None,
+            idle_timeout: None,
+            ctcp_reply_rate_limit: None,
+            ctcp_grace_period: None,
+            notice_rate_limit: None,
+            channel_creation_rate_limit: None,
+            away_rate_limit: None,
+            relay_accounts: None,
+            max_account_sessions: None,
+            cert_fingerprints: None,
+            oper_commands: None,
+            geoip_db: None,
+            max_realname_len: None,
+            realname_no_control_chars: false,
+            kill_hide_oper: false,
+            oper_view_user_modes: false,
             ping_timeout: 120,
             pong_timeout: 20,
+            resume_timeout: None,
             dns_lookup: false,
             channels: None,
             operators: None,
             users: None,
             default_user_modes: UserModes::default(),
             tls: None,
+            listeners: None,
+            socket: None,
             log_file: None,
             log_level: tracing::Level::INFO,
         }
@@ -498,6 +942,7 @@ password = "VgWezXctjWvsY6V7gzSQPnluUuAwq06m5IxwcIg3OfBIMM+zWCJntk8HEZDgh4ctFei3
 network = "IRCInetwork"
 max_connections = 4000
 max_joins = 10
+channel_creation = "opers"
 ping_timeout = 100
 pong_timeout = 30
 motd = "Hello, guys!"
@@ -551,6 +996,9 @@ client_limit = 200
 secret = false
 protected_topic = true
 no_external_messages = false
+[channels.access]
+"*!*@buru.com" = "voice"
+"$a:lucas" = "op"
 "##,
         )
         .unwrap();
@@ -570,11 +1018,47 @@ no_external_messages = false
                         .to_string()
                 ),
                 motd: "Hello, guys!".to_string(),
+                banner: None,
                 network: "IRCInetwork".to_string(),
                 max_connections: Some(4000),
                 max_joins: Some(10),
+                max_monitor: None,
+                max_monitor_global: None,
+                max_who_results: None,
+                quiet_join_threshold: None,
+                max_line_length: None,
+                targmax: None,
+                channel_creation: Some(ChannelCreation::Opers),
+                reserved_nicks: None,
+                reserved_channels: None,
+                whois_rate_limit: None,
+                bans: None,
+                include: None,
+                invite_timeout: None,
+                whowas_retention: None,
+                max_connections_exempt: None,
+                disabled_caps: None,
+                host_display: None,
+                cloak_key: None,
+                rate_limit_exempt: None,
+                idle_timeout: None,
+                ctcp_reply_rate_limit: None,
+                ctcp_grace_period: None,
+                notice_rate_limit: None,
+                channel_creation_rate_limit: None,
+                away_rate_limit: None,
+                relay_accounts: None,
+                max_account_sessions: None,
+                cert_fingerprints: None,
+                oper_commands: None,
+                geoip_db: None,
+                max_realname_len: None,
+                realname_no_control_chars: false,
+                kill_hide_oper: false,
+                oper_view_user_modes: false,
                 ping_timeout: 100,
                 pong_timeout: 30,
+                resume_timeout: None,
                 dns_lookup: false,
                 log_file: None,
                 log_level: tracing::Level::INFO,
@@ -582,19 +1066,25 @@ no_external_messages = false
                     cert_file: "cert.crt".to_string(),
                     cert_key_file: "cert_key.crt".to_string()
                 }),
+                listeners: None,
+                socket: None,
                 default_user_modes: UserModes {
                     invisible: false,
                     oper: false,
                     local_oper: false,
                     registered: true,
                     wallops: false,
+                    bot: false,
+                    hide_oper: false,
+                    caller_id: false,
                 },
                 operators: Some(vec![OperatorConfig {
                     name: "matiszpaki".to_string(),
                     password: "u1hG814j88zYGsEZoKba2op9ems63On/QsqWWTFvEkUWaZFkzcr\
                         4Bri/sUIG5+u01qbfQ+GWF+PMXNFIPCJdag"
                         .to_string(),
-                    mask: None
+                    mask: None,
+                    require_tls: false
                 }]),
                 users: Some(vec![UserConfig {
                     name: "lucas".to_string(),
@@ -625,8 +1115,15 @@ no_external_messages = false
                             moderated: false,
                             secret: false,
                             protected_topic: false,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: None,
                     },
                     ChannelConfig {
                         name: "#channel2".to_string(),
@@ -649,8 +1146,21 @@ no_external_messages = false
                             moderated: true,
                             secret: false,
                             protected_topic: true,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: Some(
+                            [
+                                ("*!*@buru.com".to_string(), ChannelAccessLevel::Voice),
+                                ("$a:lucas".to_string(), ChannelAccessLevel::Op)
+                            ]
+                            .into()
+                        ),
                     },
                 ]),
             }),
@@ -687,11 +1197,47 @@ no_external_messages = false
                         .to_string()
                 ),
                 motd: "Hello, guys!".to_string(),
+                banner: None,
                 network: "SomeNetwork".to_string(),
                 max_connections: Some(4000),
                 max_joins: Some(10),
+                max_monitor: None,
+                max_monitor_global: None,
+                max_who_results: None,
+                quiet_join_threshold: None,
+                max_line_length: None,
+                targmax: None,
+                channel_creation: Some(ChannelCreation::Opers),
+                reserved_nicks: None,
+                reserved_channels: None,
+                whois_rate_limit: None,
+                bans: None,
+                include: None,
+                invite_timeout: None,
+                whowas_retention: None,
+                max_connections_exempt: None,
+                disabled_caps: None,
+                host_display: None,
+                cloak_key: None,
+                rate_limit_exempt: None,
+                idle_timeout: None,
+                ctcp_reply_rate_limit: None,
+                ctcp_grace_period: None,
+                notice_rate_limit: None,
+                channel_creation_rate_limit: None,
+                away_rate_limit: None,
+                relay_accounts: None,
+                max_account_sessions: None,
+                cert_fingerprints: None,
+                oper_commands: None,
+                geoip_db: None,
+                max_realname_len: None,
+                realname_no_control_chars: false,
+                kill_hide_oper: false,
+                oper_view_user_modes: false,
                 ping_timeout: 100,
                 pong_timeout: 30,
+                resume_timeout: None,
                 dns_lookup: true,
                 log_file: Some("irc.log".to_string()),
                 log_level: tracing::Level::INFO,
@@ -699,19 +1245,25 @@ no_external_messages = false
                     cert_file: "some_cert.crt".to_string(),
                     cert_key_file: "some_key.crt".to_string()
                 }),
+                listeners: None,
+                socket: None,
                 default_user_modes: UserModes {
                     invisible: false,
                     oper: false,
                     local_oper: false,
                     registered: true,
                     wallops: false,
+                    bot: false,
+                    hide_oper: false,
+                    caller_id: false,
                 },
                 operators: Some(vec![OperatorConfig {
                     name: "matiszpaki".to_string(),
                     password: "u1hG814j88zYGsEZoKba2op9ems63On/QsqWWTFvEkUWaZFkzcr\
                         4Bri/sUIG5+u01qbfQ+GWF+PMXNFIPCJdag"
                         .to_string(),
-                    mask: None
+                    mask: None,
+                    require_tls: false
                 }]),
                 users: Some(vec![UserConfig {
                     name: "lucas".to_string(),
@@ -742,8 +1294,15 @@ no_external_messages = false
                             moderated: false,
                             secret: false,
                             protected_topic: false,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: None,
                     },
                     ChannelConfig {
                         name: "#channel2".to_string(),
@@ -766,8 +1325,21 @@ no_external_messages = false
                             moderated: true,
                             secret: false,
                             protected_topic: true,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: Some(
+                            [
+                                ("*!*@buru.com".to_string(), ChannelAccessLevel::Voice),
+                                ("$a:lucas".to_string(), ChannelAccessLevel::Op)
+                            ]
+                            .into()
+                        ),
                     },
                 ]),
             }),
@@ -858,21 +1430,62 @@ no_external_messages = false
                 port: 6667,
                 password: None,
                 motd: "Hello, guys!".to_string(),
+                banner: None,
                 network: "IRCInetwork".to_string(),
                 max_connections: None,
                 max_joins: None,
+                max_monitor: None,
+                max_monitor_global: None,
+                max_who_results: None,
+                quiet_join_threshold: None,
+                max_line_length: None,
+                targmax: None,
+                channel_creation: None,
+                reserved_nicks: None,
+                reserved_channels: None,
+                whois_rate_limit: None,
+                bans: None,
+                include: None,
+                invite_timeout: None,
+                whowas_retention: None,
+                max_connections_exempt: None,
+                disabled_caps: None,
+                host_display: None,
+                cloak_key: None,
+                rate_limit_exempt: None,
+                idle_timeout: None,
+                ctcp_reply_rate_limit: None,
+                ctcp_grace_period: None,
+                notice_rate_limit: None,
+                channel_creation_rate_limit: None,
+                away_rate_limit: None,
+                relay_accounts: None,
+                max_account_sessions: None,
+                cert_fingerprints: None,
+                oper_commands: None,
+                geoip_db: None,
+                max_realname_len: None,
+                realname_no_control_chars: false,
+                kill_hide_oper: false,
+                oper_view_user_modes: false,
                 ping_timeout: 100,
                 pong_timeout: 30,
+                resume_timeout: None,
                 dns_lookup: false,
                 log_file: Some("log.log".to_string()),
                 log_level: tracing::Level::INFO,
                 tls: None,
+                listeners: None,
+                socket: None,
                 default_user_modes: UserModes {
                     invisible: false,
                     oper: false,
                     local_oper: false,
                     registered: true,
                     wallops: false,
+                    bot: false,
+                    hide_oper: false,
+                    caller_id: false,
                 },
                 operators: None,
                 users: None,
@@ -895,8 +1508,15 @@ no_external_messages = false
                             moderated: false,
                             secret: false,
                             protected_topic: false,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: None,
                     },
                     ChannelConfig {
                         name: "#channel2".to_string(),
@@ -916,8 +1536,15 @@ no_external_messages = false
                             moderated: true,
                             secret: false,
                             protected_topic: true,
-                            no_external_messages: false
+                            no_external_messages: false,
+                            free_invite: false,
+                            permanent: false,
+                            delayed_join: false,
+                            registered_only: false,
+                            no_colors: false,
+                            forward: None
                         },
+                        access: None,
                     },
                 ]),
             }),
@@ -993,18 +1620,11 @@ no_external_messages = false
         )
         .unwrap();
         let result = MainConfig::new(cli.clone()).map_err(|e| e.to_string());
-        // because sorting of ValidationErrors changes order we check to cases
-        assert!(
-            Err(
-                "name: Validation error: contains [{\"value\": String(\"ircilocalhost\"), \
-\"needle\": String(\".\")}]"
-                    .to_string()
-            ) == result
-                || Err(
-                    "name: Validation error: contains [{\"needle\": String(\".\"), \
-\"value\": String(\"ircilocalhost\")}]"
-                        .to_string()
-                ) == result
+        assert_eq!(
+            Err("name: Validation error: Server name must contain '.' and must have only \
+letters, digits and '-' in its parts. [{\"value\": String(\"ircilocalhost\")}]"
+                .to_string()),
+            result
         );
 
         fs::write(
@@ -1622,6 +2242,176 @@ no_external_messages = false
         );
     }
 
+    #[test]
+    fn test_mainconfig_validate_server_name() {
+        assert!(MainConfig::default().validate().is_ok());
+        assert!(MainConfig {
+            name: "ircilocalhost".to_string(),
+            ..MainConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(MainConfig {
+            name: "irci local.host".to_string(),
+            ..MainConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(MainConfig {
+            listeners: Some(vec![ListenerConfig {
+                listen: "127.0.0.1".parse().unwrap(),
+                port: 6668,
+                name: Some("chat example.net".to_string()),
+                disabled_caps: None,
+                host_display: None,
+                socket: None,
+            }]),
+            ..MainConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_mainconfig_validate_cloak_key() {
+        assert!(MainConfig::default().validate_cloak_key());
+        assert!(!MainConfig {
+            host_display: Some(HostDisplay::Cloak),
+            ..MainConfig::default()
+        }
+        .validate_cloak_key());
+        assert!(!MainConfig {
+            host_display: Some(HostDisplay::Cloak),
+            cloak_key: Some("".to_string()),
+            ..MainConfig::default()
+        }
+        .validate_cloak_key());
+        assert!(MainConfig {
+            host_display: Some(HostDisplay::Cloak),
+            cloak_key: Some("secret".to_string()),
+            ..MainConfig::default()
+        }
+        .validate_cloak_key());
+        assert!(!MainConfig {
+            listeners: Some(vec![ListenerConfig {
+                listen: "127.0.0.1".parse().unwrap(),
+                port: 6668,
+                name: None,
+                disabled_caps: None,
+                host_display: Some(HostDisplay::Cloak),
+                socket: None,
+            }]),
+            ..MainConfig::default()
+        }
+        .validate_cloak_key());
+    }
+
+    #[test]
+    fn test_mainconfig_new_include() {
+        let file_handle = TempFileHandle::new("temp_config_include.toml");
+        let motd_handle = TempFileHandle::new("temp_config_include_motd.txt");
+        let opers_handle = TempFileHandle::new("temp_config_include_opers.toml");
+        let bans_handle = TempFileHandle::new("temp_config_include_bans.toml");
+        let nicks_handle = TempFileHandle::new("temp_config_include_nicks.toml");
+        let channels_handle = TempFileHandle::new("temp_config_include_channels.toml");
+        let cli = Cli {
+            config: Some(file_handle.path.clone()),
+            gen_password_hash: false,
+            password: None,
+            listen: None,
+            port: None,
+            name: None,
+            network: None,
+            dns_lookup: false,
+            tls_cert_file: None,
+            tls_cert_key_file: None,
+            log_file: None,
+        };
+
+        fs::write(
+            file_handle.path.as_str(),
+            format!(
+                r##"
+name = "irci.localhost"
+admin_info = "IRCI is local IRC server"
+info = "This is IRCI server"
+listen = "127.0.0.1"
+port = 6667
+motd = "Hello, guys!"
+network = "IRCInetwork"
+ping_timeout = 100
+pong_timeout = 30
+dns_lookup = false
+log_level = "INFO"
+bans = [ "*!*@10.0.0.1" ]
+
+[default_user_modes]
+invisible = false
+oper = false
+local_oper = false
+registered = false
+wallops = false
+
+[include]
+motd = "{}"
+operators = "{}"
+bans = "{}"
+reserved_nicks = "{}"
+reserved_channels = "{}"
+"##,
+                motd_handle.path,
+                opers_handle.path,
+                bans_handle.path,
+                nicks_handle.path,
+                channels_handle.path
+            ),
+        )
+        .unwrap();
+        fs::write(motd_handle.path.as_str(), "Hello from file!\nSecond line\n").unwrap();
+        fs::write(
+            opers_handle.path.as_str(),
+            r##"
+[[operators]]
+name = "matiszpaki"
+password = "u1hG814j88zYGsEZoKba2op9ems63On/QsqWWTFvEkUWaZFkzcr4Bri/sUIG5+u01qbfQ+GWF+PMXNFIPCJdag"
+"##,
+        )
+        .unwrap();
+        fs::write(bans_handle.path.as_str(), "bans = [ \"guru!*@*\" ]\n").unwrap();
+        fs::write(
+            nicks_handle.path.as_str(),
+            "reserved_nicks = [ \"oper*\" ]\n",
+        )
+        .unwrap();
+        fs::write(
+            channels_handle.path.as_str(),
+            "reserved_channels = [ \"#staff*\" ]\n",
+        )
+        .unwrap();
+
+        let config = MainConfig::new(cli.clone()).unwrap();
+        assert_eq!("Hello from file!\nSecond line", config.motd);
+        assert_eq!(
+            Some(vec![OperatorConfig {
+                name: "matiszpaki".to_string(),
+                password: "u1hG814j88zYGsEZoKba2op9ems63On/QsqWWTFvEkUWaZFkzcr4Bri/\
+                    sUIG5+u01qbfQ+GWF+PMXNFIPCJdag"
+                    .to_string(),
+                mask: None,
+                require_tls: false,
+            }]),
+            config.operators
+        );
+        assert_eq!(Some(vec!["guru!*@*".to_string()]), config.bans);
+        assert_eq!(Some(vec!["oper*".to_string()]), config.reserved_nicks);
+        assert_eq!(Some(vec!["#staff*".to_string()]), config.reserved_channels);
+
+        // bad included file
+        fs::write(bans_handle.path.as_str(), "bans = [ \"guru!*@*\" \n").unwrap();
+        let result = MainConfig::new(cli.clone()).map_err(|e| e.to_string());
+        assert!(result.unwrap_err().starts_with(&format!("{}: ", bans_handle.path)));
+    }
+
     #[test]
     fn test_usermodes_to_string() {
         assert_eq!(
@@ -1631,7 +2421,10 @@ no_external_messages = false
                 oper: true,
                 local_oper: true,
                 registered: true,
-                wallops: false
+                wallops: false,
+                bot: false,
+                hide_oper: false,
+                caller_id: false
             }
             .to_string()
         );
@@ -1642,7 +2435,24 @@ no_external_messages = false
                 oper: false,
                 local_oper: false,
                 registered: true,
-                wallops: true
+                wallops: true,
+                bot: false,
+                hide_oper: false,
+                caller_id: false
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "+oBH".to_string(),
+            UserModes {
+                invisible: false,
+                oper: true,
+                local_oper: false,
+                registered: false,
+                wallops: false,
+                bot: true,
+                hide_oper: true,
+                caller_id: false
             }
             .to_string()
         );
@@ -1667,7 +2477,13 @@ no_external_messages = false
                 moderated: false,
                 secret: false,
                 protected_topic: true,
-                no_external_messages: true
+                no_external_messages: true,
+                free_invite: false,
+                permanent: false,
+                delayed_join: false,
+                registered_only: false,
+                no_colors: false,
+                forward: None
             }
             .to_string()
         );
@@ -1687,6 +2503,12 @@ no_external_messages = false
             secret: true,
             protected_topic: true,
             no_external_messages: false,
+            free_invite: false,
+            permanent: false,
+            delayed_join: false,
+            registered_only: false,
+            no_colors: false,
+            forward: None,
         }
         .to_string();
         assert!(
@@ -1709,6 +2531,12 @@ no_external_messages = false
             secret: false,
             protected_topic: false,
             no_external_messages: true,
+            free_invite: false,
+            permanent: false,
+            delayed_join: false,
+            registered_only: false,
+            no_colors: false,
+            forward: None,
         }
         .to_string();
         assert!(
@@ -1731,6 +2559,12 @@ no_external_messages = false
             secret: false,
             protected_topic: false,
             no_external_messages: true,
+            free_invite: false,
+            permanent: false,
+            delayed_join: false,
+            registered_only: false,
+            no_colors: false,
+            forward: None,
         }
         .to_string();
         assert!(
@@ -1753,12 +2587,52 @@ no_external_messages = false
             secret: false,
             protected_topic: false,
             no_external_messages: true,
+            free_invite: false,
+            permanent: false,
+            delayed_join: false,
+            registered_only: false,
+            no_colors: false,
+            forward: None,
         }
         .to_string();
         assert!(
             "+imn +I somebody +a guy1 +a guy2".to_string() == chm_str
                 || "+imn +I somebody +a guy2 +a guy1".to_string() == chm_str
         );
+        assert_eq!(
+            "+ing".to_string(),
+            ChannelModes {
+                invite_only: true,
+                no_external_messages: true,
+                free_invite: true,
+                ..ChannelModes::default()
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "+tnP".to_string(),
+            ChannelModes {
+                protected_topic: true,
+                no_external_messages: true,
+                permanent: true,
+                ..ChannelModes::default()
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "+tnlf 10 #overflow".to_string(),
+            ChannelModes {
+                protected_topic: true,
+                no_external_messages: true,
+                client_limit: Some(10),
+                delayed_join: false,
+                registered_only: false,
+                no_colors: false,
+                forward: Some("#overflow".to_string()),
+                ..ChannelModes::default()
+            }
+            .to_string()
+        );
     }
 
     #[test]
@@ -1773,20 +2647,27 @@ no_external_messages = false
     fn test_channelmodes_banned() {
         let mut chm = ChannelModes::default();
         chm.ban = Some(["bom!*@*".to_string()].into());
-        assert!(chm.banned("bom!bom@gugu.com"));
-        assert!(chm.banned("bom!bam@ggregi.com"));
-        assert!(!chm.banned("bam!bom@gugu.com"));
+        assert!(chm.banned("bom!bom@gugu.com", None));
+        assert!(chm.banned("bom!bam@ggregi.com", None));
+        assert!(!chm.banned("bam!bom@gugu.com", None));
         chm.exception = Some(["bom!*@ggregi*".to_string()].into());
-        assert!(chm.banned("bom!bom@gugu.com"));
-        assert!(!chm.banned("bom!bam@ggregi.com"));
+        assert!(chm.banned("bom!bom@gugu.com", None));
+        assert!(!chm.banned("bom!bam@ggregi.com", None));
         chm.exception = Some(["*!*@ggregi*".to_string()].into());
-        assert!(chm.banned("bom!bom@gugu.com"));
-        assert!(!chm.banned("bom!bam@ggregi.com"));
+        assert!(chm.banned("bom!bom@gugu.com", None));
+        assert!(!chm.banned("bom!bam@ggregi.com", None));
         chm.ban = Some(["bom!*@*".to_string(), "zigi!*@*".to_string()].into());
-        assert!(chm.banned("bom!bom@gugu.com"));
-        assert!(chm.banned("zigi!zigol@gugu.com"));
-        assert!(!chm.banned("bom!bam@ggregi.com"));
-        assert!(!chm.banned("zigi!zigol@ggregi.net"));
+        assert!(chm.banned("bom!bom@gugu.com", None));
+        assert!(chm.banned("zigi!zigol@gugu.com", None));
+        assert!(!chm.banned("bom!bam@ggregi.com", None));
+        assert!(!chm.banned("zigi!zigol@ggregi.net", None));
+        // account extbans
+        chm.ban = Some(["$a:zigi*".to_string()].into());
+        chm.exception = Some(["$a:zigiok".to_string()].into());
+        assert!(chm.banned("bom!bom@gugu.com", Some("zigiacc")));
+        assert!(!chm.banned("bom!bom@gugu.com", Some("zigiok")));
+        assert!(!chm.banned("bom!bom@gugu.com", Some("bomacc")));
+        assert!(!chm.banned("zigi!zigol@gugu.com", None));
     }
 
     #[test]
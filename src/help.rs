@@ -22,11 +22,16 @@ pub(crate) static HELP_TOPICS: [(&str, &str); 2] = [
     (
         "COMMANDS",
         r##"List of commands:
+ACCEPT
+ACCESS
 ADMIN
-AUTHENTICATE - unsupported
+AUTHENTICATE
 AWAY
 CAP
+CLEARCHAN
+CNOTICE
 CONNECT - unsupported
+CPRIVMSG
 DIE
 HELP
 INFO
@@ -39,11 +44,13 @@ LINKS
 LIST
 LUSERS
 MODE
+MONITOR
 MOTD
 NAMES
 NICK
 NOTICE
 OPER
+OPERWALL
 PART
 PASS
 PING
@@ -51,7 +58,11 @@ PONG
 PRIVMSG
 QUIT
 REHASH
+RELAYMSG
+RENAME
 RESTART
+RESUME
+SESSIONS
 SQUIT
 STATS
 TIME
@@ -60,6 +71,7 @@ USER
 USERHOST
 VERSION
 WALLOPS
+WATCH
 WHO
 WHOIS
 WHOWAS"##,
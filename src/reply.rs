@@ -63,6 +63,18 @@ pub(crate) enum Reply<'a> {
         client: &'a str,
         tokens: &'a str,
     },
+    RplYourId042 {
+        client: &'a str,
+        id: &'a str,
+    },
+    RplStatsLinkInfo211 {
+        client: &'a str,
+        linkname: &'a str,
+        sendq: usize,
+        recvq: usize,
+        sent_bytes: u64,
+        recv_bytes: u64,
+    },
     RplStatsCommands212 {
         client: &'a str,
         command: &'a str,
@@ -130,6 +142,13 @@ pub(crate) enum Reply<'a> {
         max_clients_num: usize,
     },
     //RplWhoIsCertFP276{ client: &'a str, nick: &'a str, fingerprint: &'a str },
+    RplAcceptList281 {
+        client: &'a str,
+        nicks: &'a str,
+    },
+    RplEndOfAccept282 {
+        client: &'a str,
+    },
     RplAway301 {
         client: &'a str,
         nick: &'a str,
@@ -207,6 +226,11 @@ pub(crate) enum Reply<'a> {
         nick: &'a str,
         channels: &'a [WhoIsChannelStruct<'a>],
     },
+    RplWhoIsSpecial320 {
+        client: &'a str,
+        nick: &'a str,
+        special_info: &'a str,
+    },
     RplListStart321 {
         client: &'a str,
     },
@@ -229,6 +253,11 @@ pub(crate) enum Reply<'a> {
         channel: &'a str,
         creation_time: u64,
     },
+    RplWhoIsAccount330 {
+        client: &'a str,
+        nick: &'a str,
+        account: &'a str,
+    },
     RplNoTopic331 {
         client: &'a str,
         channel: &'a str,
@@ -340,7 +369,10 @@ pub(crate) enum Reply<'a> {
     RplYoureOper381 {
         client: &'a str,
     },
-    //RplRehashing382{ client: &'a str, config_file: &'a str },
+    RplRehashing382 {
+        client: &'a str,
+        config_file: &'a str,
+    },
     RplTime391 {
         client: &'a str,
         server: &'a str,
@@ -358,7 +390,10 @@ pub(crate) enum Reply<'a> {
         client: &'a str,
         nick: &'a str,
     },
-    //ErrNoSuchServer402{ client: &'a str, server: &'a str },
+    ErrNoSuchServer402 {
+        client: &'a str,
+        server: &'a str,
+    },
     ErrNoSuchChannel403 {
         client: &'a str,
         channel: &'a str,
@@ -375,6 +410,15 @@ pub(crate) enum Reply<'a> {
         client: &'a str,
         nick: &'a str,
     },
+    ErrTooManyTargets407 {
+        client: &'a str,
+        target: &'a str,
+    },
+    ErrTooManyMatches416 {
+        client: &'a str,
+        command: &'a str,
+        mask: &'a str,
+    },
     ErrInputTooLong417 {
         client: &'a str,
     },
@@ -382,6 +426,10 @@ pub(crate) enum Reply<'a> {
         client: &'a str,
         command: &'a str,
     },
+    ErrErroneusNickname432 {
+        client: &'a str,
+        nick: &'a str,
+    },
     ErrNicknameInUse433 {
         client: &'a str,
         nick: &'a str,
@@ -403,6 +451,14 @@ pub(crate) enum Reply<'a> {
     ErrNotRegistered451 {
         client: &'a str,
     },
+    ErrAcceptExist457 {
+        client: &'a str,
+        nick: &'a str,
+    },
+    ErrAcceptNot458 {
+        client: &'a str,
+        nick: &'a str,
+    },
     ErrNeedMoreParams461 {
         client: &'a str,
         command: &'a str,
@@ -413,7 +469,9 @@ pub(crate) enum Reply<'a> {
     ErrPasswdMismatch464 {
         client: &'a str,
     },
-    //ErrYoureBannedCreep465{ client: &'a str },
+    ErrYoureBannedCreep465 {
+        client: &'a str,
+    },
     ErrChannelIsFull471 {
         client: &'a str,
         channel: &'a str,
@@ -435,6 +493,14 @@ pub(crate) enum Reply<'a> {
         client: &'a str,
         channel: &'a str,
     },
+    ErrBadChanMask476 {
+        client: &'a str,
+        channel: &'a str,
+    },
+    ErrNeedReggedNick477 {
+        client: &'a str,
+        channel: &'a str,
+    },
     ErrNoPrivileges481 {
         client: &'a str,
     },
@@ -457,6 +523,11 @@ pub(crate) enum Reply<'a> {
     ErrUsersDontMatch502 {
         client: &'a str,
     },
+    ErrTooManyWatch512 {
+        client: &'a str,
+        nick: &'a str,
+        limit: usize,
+    },
     ErrHelpNotFound524 {
         client: &'a str,
         subject: &'a str,
@@ -467,6 +538,50 @@ pub(crate) enum Reply<'a> {
         nick: &'a str,
     },
     //ErrStartTls691{ client: &'a str },
+    RplLogOn600 {
+        client: &'a str,
+        nick: &'a str,
+        user: &'a str,
+        host: &'a str,
+        ts: u64,
+    },
+    RplLogOff601 {
+        client: &'a str,
+        nick: &'a str,
+        user: &'a str,
+        host: &'a str,
+        ts: u64,
+    },
+    RplWatchOff602 {
+        client: &'a str,
+        nick: &'a str,
+        user: &'a str,
+        host: &'a str,
+        ts: u64,
+    },
+    RplWatchStat603 {
+        client: &'a str,
+        watches: usize,
+        watched_by: usize,
+    },
+    RplNowOn604 {
+        client: &'a str,
+        nick: &'a str,
+        user: &'a str,
+        host: &'a str,
+        ts: u64,
+    },
+    RplNowOff605 {
+        client: &'a str,
+        nick: &'a str,
+    },
+    RplWatchList606 {
+        client: &'a str,
+        nicks: &'a str,
+    },
+    RplEndOfWatchList607 {
+        client: &'a str,
+    },
     ErrInvalidModeParam696 {
         client: &'a str,
         target: &'a str,
@@ -489,16 +604,68 @@ pub(crate) enum Reply<'a> {
         subject: &'a str,
         line: &'a str,
     },
-    //RplLoggedIn900{ client: &'a str, nick: &'a str, user: &'a str, host: &'a str,
-    //        account: &'a str, username: &'a str },
+    ErrTargUmodeG716 {
+        client: &'a str,
+        nick: &'a str,
+    },
+    RplTargNotify717 {
+        client: &'a str,
+        nick: &'a str,
+    },
+    RplUmodeGMsg718 {
+        client: &'a str,
+        nick: &'a str,
+        user_host: &'a str,
+    },
+    RplMonOnline730 {
+        client: &'a str,
+        targets: &'a str,
+    },
+    RplMonOffline731 {
+        client: &'a str,
+        targets: &'a str,
+    },
+    RplMonList732 {
+        client: &'a str,
+        targets: &'a str,
+    },
+    RplEndOfMonList733 {
+        client: &'a str,
+    },
+    ErrMonListIsFull734 {
+        client: &'a str,
+        limit: usize,
+        targets: &'a str,
+    },
+    RplLoggedIn900 {
+        client: &'a str,
+        nick: &'a str,
+        user: &'a str,
+        host: &'a str,
+        account: &'a str,
+        username: &'a str,
+    },
     //RplLoggedOut901{ client: &'a str, nick: &'a str, user: &'a str, host: &'a str },
     //ErrNickLocked902{ client: &'a str },
-    //RplSaslSuccess903{ client: &'a str },
-    //ErrSaslFail904{ client: &'a str },
-    //ErrSaslTooLong905{ client: &'a str },
-    //ErrSaslAborted906{ client: &'a str },
-    //ErrSaslAlready907{ client: &'a str },
-    //RplSaslMechs908{ client: &'a str, mechanisms: &'a str },
+    RplSaslSuccess903 {
+        client: &'a str,
+    },
+    ErrSaslFail904 {
+        client: &'a str,
+    },
+    ErrSaslTooLong905 {
+        client: &'a str,
+    },
+    ErrSaslAborted906 {
+        client: &'a str,
+    },
+    ErrSaslAlready907 {
+        client: &'a str,
+    },
+    RplSaslMechs908 {
+        client: &'a str,
+        mechanisms: &'a str,
+    },
     ErrCannotDoCommand972 {
         client: &'a str,
     },
@@ -561,6 +728,23 @@ impl<'a> fmt::Display for Reply<'a> {
             RplISupport005 { client, tokens } => {
                 write!(f, "005 {} {} :are supported by this server", client, tokens)
             }
+            RplYourId042 { client, id } => {
+                write!(f, "042 {} {} :your unique ID", client, id)
+            }
+            RplStatsLinkInfo211 {
+                client,
+                linkname,
+                sendq,
+                recvq,
+                sent_bytes,
+                recv_bytes,
+            } => {
+                write!(
+                    f,
+                    "211 {} {} {} {} {} {}",
+                    client, linkname, sendq, recvq, sent_bytes, recv_bytes
+                )
+            }
             RplStatsCommands212 {
                 client,
                 command,
@@ -661,6 +845,12 @@ impl<'a> fmt::Display for Reply<'a> {
             //RplWhoIsCertFP276{ client, nick, fingerprint } => {
             //    write!(f, "276 {} {} :has client certificate fingerprint {}", client, nick,
             //        fingerprint) }
+            RplAcceptList281 { client, nicks } => {
+                write!(f, "281 {} {}", client, nicks)
+            }
+            RplEndOfAccept282 { client } => {
+                write!(f, "282 {} :End of /ACCEPT list", client)
+            }
             RplAway301 {
                 client,
                 nick,
@@ -796,6 +986,13 @@ impl<'a> fmt::Display for Reply<'a> {
                         .join(" ")
                 )
             }
+            RplWhoIsSpecial320 {
+                client,
+                nick,
+                special_info,
+            } => {
+                write!(f, "320 {} {} :{}", client, nick, special_info)
+            }
             RplListStart321 { client } => {
                 write!(f, "321 {} Channel :Users  Name", client)
             }
@@ -824,6 +1021,13 @@ impl<'a> fmt::Display for Reply<'a> {
             } => {
                 write!(f, "329 {} {} {}", client, channel, creation_time)
             }
+            RplWhoIsAccount330 {
+                client,
+                nick,
+                account,
+            } => {
+                write!(f, "330 {} {} {} :is logged in as", client, nick, account)
+            }
             RplNoTopic331 { client, channel } => {
                 write!(f, "331 {} {} :No topic is set", client, channel)
             }
@@ -970,8 +1174,12 @@ impl<'a> fmt::Display for Reply<'a> {
             RplYoureOper381 { client } => {
                 write!(f, "381 {} :You are now an IRC operator", client)
             }
-            //RplRehashing382{ client, config_file } => {
-            //    write!(f, "382 {} {} :Rehashing", client, config_file) }
+            RplRehashing382 {
+                client,
+                config_file,
+            } => {
+                write!(f, "382 {} {} :Rehashing", client, config_file)
+            }
             RplTime391 {
                 client,
                 server,
@@ -1000,8 +1208,9 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrNoSuchNick401 { client, nick } => {
                 write!(f, "401 {} {} :No such nick/channel", client, nick)
             }
-            //ErrNoSuchServer402{ client, server } => {
-            //    write!(f, "402 {} {} :No such server", client, server) }
+            ErrNoSuchServer402 { client, server } => {
+                write!(f, "402 {} {} :No such server", client, server)
+            }
             ErrNoSuchChannel403 { client, channel } => {
                 write!(f, "403 {} {} :No such channel", client, channel)
             }
@@ -1018,12 +1227,29 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrWasNoSuchNick406 { client, nick } => {
                 write!(f, "406 {} {} :There was no such nickname", client, nick)
             }
+            ErrTooManyTargets407 { client, target } => {
+                write!(f, "407 {} {} :Too many targets", client, target)
+            }
+            ErrTooManyMatches416 {
+                client,
+                command,
+                mask,
+            } => {
+                write!(
+                    f,
+                    "416 {} {} {} :Output too long (try locally)",
+                    client, command, mask
+                )
+            }
             ErrInputTooLong417 { client } => {
                 write!(f, "417 {} :Input line was too long", client)
             }
             ErrUnknownCommand421 { client, command } => {
                 write!(f, "421 {} {} :Unknown command", client, command)
             }
+            ErrErroneusNickname432 { client, nick } => {
+                write!(f, "432 {} {} :Erroneus nickname", client, nick)
+            }
             ErrNicknameInUse433 { client, nick } => {
                 write!(f, "433 {} {} :Nickname is already in use", client, nick)
             }
@@ -1055,6 +1281,12 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrNotRegistered451 { client } => {
                 write!(f, "451 {} :You have not registered", client)
             }
+            ErrAcceptExist457 { client, nick } => {
+                write!(f, "457 {} {} :is already on your accept list", client, nick)
+            }
+            ErrAcceptNot458 { client, nick } => {
+                write!(f, "458 {} {} :is not on your accept list", client, nick)
+            }
             ErrNeedMoreParams461 { client, command } => {
                 write!(f, "461 {} {} :Not enough parameters", client, command)
             }
@@ -1064,8 +1296,9 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrPasswdMismatch464 { client } => {
                 write!(f, "464 {} :Password incorrect", client)
             }
-            //ErrYoureBannedCreep465{ client } => {
-            //    write!(f, "465 {} :You are banned from this server.", client) }
+            ErrYoureBannedCreep465 { client } => {
+                write!(f, "465 {} :You are banned from this server.", client)
+            }
             ErrChannelIsFull471 { client, channel } => {
                 write!(f, "471 {} {} :Cannot join channel (+l)", client, channel)
             }
@@ -1089,6 +1322,17 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrBadChannelKey475 { client, channel } => {
                 write!(f, "475 {} {} :Cannot join channel (+k)", client, channel)
             }
+            ErrBadChanMask476 { client, channel } => {
+                write!(f, "476 {} {} :Bad Channel Mask", client, channel)
+            }
+            ErrNeedReggedNick477 { client, channel } => {
+                write!(
+                    f,
+                    "477 {} {} :Cannot join channel (+R) - you need to be logged into \
+                    your account",
+                    client, channel
+                )
+            }
             ErrNoPrivileges481 { client } => {
                 write!(
                     f,
@@ -1114,6 +1358,17 @@ impl<'a> fmt::Display for Reply<'a> {
             ErrUsersDontMatch502 { client } => {
                 write!(f, "502 {} :Cant change mode for other users", client)
             }
+            ErrTooManyWatch512 {
+                client,
+                nick,
+                limit,
+            } => {
+                write!(
+                    f,
+                    "512 {} {} :Maximum size for WATCH-list is {} entries",
+                    client, nick, limit
+                )
+            }
             ErrHelpNotFound524 { client, subject } => {
                 write!(
                     f,
@@ -1128,6 +1383,62 @@ impl<'a> fmt::Display for Reply<'a> {
             }
             //ErrStartTls691{ client } => {
             //    write!(f, "691 {} :STARTTLS failed (Wrong moon phase)", client) }
+            RplLogOn600 {
+                client,
+                nick,
+                user,
+                host,
+                ts,
+            } => {
+                write!(f, "600 {} {} {} {} {} :logged online", client, nick, user, host, ts)
+            }
+            RplLogOff601 {
+                client,
+                nick,
+                user,
+                host,
+                ts,
+            } => {
+                write!(f, "601 {} {} {} {} {} :logged offline", client, nick, user, host, ts)
+            }
+            RplWatchOff602 {
+                client,
+                nick,
+                user,
+                host,
+                ts,
+            } => {
+                write!(f, "602 {} {} {} {} {} :stopped watching", client, nick, user, host, ts)
+            }
+            RplWatchStat603 {
+                client,
+                watches,
+                watched_by,
+            } => {
+                write!(
+                    f,
+                    "603 {} :You have {} and are on {} WATCH entries",
+                    client, watches, watched_by
+                )
+            }
+            RplNowOn604 {
+                client,
+                nick,
+                user,
+                host,
+                ts,
+            } => {
+                write!(f, "604 {} {} {} {} {} :is online", client, nick, user, host, ts)
+            }
+            RplNowOff605 { client, nick } => {
+                write!(f, "605 {} {} * * 0 :is offline", client, nick)
+            }
+            RplWatchList606 { client, nicks } => {
+                write!(f, "606 {} :{}", client, nicks)
+            }
+            RplEndOfWatchList607 { client } => {
+                write!(f, "607 {} :End of WATCH list", client)
+            }
             ErrInvalidModeParam696 {
                 client,
                 target,
@@ -1162,26 +1473,84 @@ impl<'a> fmt::Display for Reply<'a> {
             } => {
                 write!(f, "706 {} {} :{}", client, subject, line)
             }
-            //RplLoggedIn900{ client, nick, user, host, account, username } => {
-            //    write!(f, "900 {} {}!~{}@{} {} :You are now logged in as {}", client, nick,
-            //        user, host, account, username) }
+            ErrTargUmodeG716 { client, nick } => {
+                write!(f, "716 {} {} :is in +g mode (server-side ignore)", client, nick)
+            }
+            RplTargNotify717 { client, nick } => {
+                write!(
+                    f,
+                    "717 {} {} :has been informed that you messaged them.",
+                    client, nick
+                )
+            }
+            RplUmodeGMsg718 {
+                client,
+                nick,
+                user_host,
+            } => {
+                write!(
+                    f,
+                    "718 {} {} {} :is messaging you, and you have user mode +g set. \
+                    Use /ACCEPT +{} to allow.",
+                    client, nick, user_host, nick
+                )
+            }
+            RplMonOnline730 { client, targets } => {
+                write!(f, "730 {} :{}", client, targets)
+            }
+            RplMonOffline731 { client, targets } => {
+                write!(f, "731 {} :{}", client, targets)
+            }
+            RplMonList732 { client, targets } => {
+                write!(f, "732 {} :{}", client, targets)
+            }
+            RplEndOfMonList733 { client } => {
+                write!(f, "733 {} :End of MONITOR list", client)
+            }
+            ErrMonListIsFull734 {
+                client,
+                limit,
+                targets,
+            } => {
+                write!(f, "734 {} {} {} :Monitor list is full.", client, limit, targets)
+            }
+            RplLoggedIn900 {
+                client,
+                nick,
+                user,
+                host,
+                account,
+                username,
+            } => {
+                write!(
+                    f,
+                    "900 {} {}!~{}@{} {} :You are now logged in as {}",
+                    client, nick, user, host, account, username
+                )
+            }
             //RplLoggedOut901{ client, nick, user, host } => {
             //    write!(f, "901 {} {}!~{}@{} :You are now logged out", client, nick,
             //        user, host) }
             //ErrNickLocked902{ client } => {
             //    write!(f, "902 {} :You must use a nick assigned to you", client) }
-            //RplSaslSuccess903{ client } => {
-            //    write!(f, "903 {} :SASL authentication successful", client) }
-            //ErrSaslFail904{ client } => {
-            //    write!(f, "904 {} :SASL authentication failed", client) }
-            //ErrSaslTooLong905{ client } => {
-            //    write!(f, "905 {} :SASL message too long", client) }
-            //ErrSaslAborted906{ client } => {
-            //    write!(f, "906 {} :SASL authentication aborted", client) }
-            //ErrSaslAlready907{ client } => {
-            //    write!(f, "907 {} :You have already authenticated using SASL", client) }
-            //RplSaslMechs908{ client, mechanisms } => {
-            //    write!(f, "908 {} {} :are available SASL mechanisms", client, mechanisms) }
+            RplSaslSuccess903 { client } => {
+                write!(f, "903 {} :SASL authentication successful", client)
+            }
+            ErrSaslFail904 { client } => {
+                write!(f, "904 {} :SASL authentication failed", client)
+            }
+            ErrSaslTooLong905 { client } => {
+                write!(f, "905 {} :SASL message too long", client)
+            }
+            ErrSaslAborted906 { client } => {
+                write!(f, "906 {} :SASL authentication aborted", client)
+            }
+            ErrSaslAlready907 { client } => {
+                write!(f, "907 {} :You have already authenticated using SASL", client)
+            }
+            RplSaslMechs908 { client, mechanisms } => {
+                write!(f, "908 {} {} :are available SASL mechanisms", client, mechanisms)
+            }
             ErrCannotDoCommand972 { client } => {
                 write!(f, "972 {} :Can not do command", client)
             }
@@ -1269,6 +1638,30 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "042 <client> <id> :your unique ID",
+            format!(
+                "{}",
+                RplYourId042 {
+                    client: "<client>",
+                    id: "<id>"
+                }
+            )
+        );
+        assert_eq!(
+            "211 <client> <linkname> 12 5 4456 781",
+            format!(
+                "{}",
+                RplStatsLinkInfo211 {
+                    client: "<client>",
+                    linkname: "<linkname>",
+                    sendq: 12,
+                    recvq: 5,
+                    sent_bytes: 4456,
+                    recv_bytes: 781
+                }
+            )
+        );
         assert_eq!(
             "212 <client> <command> 67",
             format!(
@@ -1428,6 +1821,20 @@ mod test {
         //assert_eq!("276 <client> <nick> :has client certificate fingerprint <fingerprint>",
         //    format!("{}", RplWhoIsCertFP276{ client: "<client>", nick: "<nick>",
         //        fingerprint: "<fingerprint>" }));
+        assert_eq!(
+            "281 <client> <nick1> <nick2>",
+            format!(
+                "{}",
+                RplAcceptList281 {
+                    client: "<client>",
+                    nicks: "<nick1> <nick2>"
+                }
+            )
+        );
+        assert_eq!(
+            "282 <client> :End of /ACCEPT list",
+            format!("{}", RplEndOfAccept282 { client: "<client>" })
+        );
         assert_eq!(
             "301 <client> <nick> :<message>",
             format!(
@@ -1613,6 +2020,17 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "320 <client> <nick> :<special info>",
+            format!(
+                "{}",
+                RplWhoIsSpecial320 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    special_info: "<special info>"
+                }
+            )
+        );
         assert_eq!(
             "321 <client> Channel :Users  Name",
             format!("{}", RplListStart321 { client: "<client>" })
@@ -1655,6 +2073,17 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "330 <client> <nick> <account> :is logged in as",
+            format!(
+                "{}",
+                RplWhoIsAccount330 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    account: "<account>"
+                }
+            )
+        );
         assert_eq!(
             "331 <client> <channel> :No topic is set",
             format!(
@@ -1904,9 +2333,16 @@ mod test {
             "381 <client> :You are now an IRC operator",
             format!("{}", RplYoureOper381 { client: "<client>" })
         );
-        //assert_eq!("382 <client> <config file> :Rehashing",
-        //    format!("{}", RplRehashing382{ client: "<client>",
-        //        config_file: "<config file>" }));
+        assert_eq!(
+            "382 <client> <config file> :Rehashing",
+            format!(
+                "{}",
+                RplRehashing382 {
+                    client: "<client>",
+                    config_file: "<config file>"
+                }
+            )
+        );
         assert_eq!(
             "391 <client> <server> 485829211 <TS offset> :<human-readable time>",
             format!(
@@ -1954,9 +2390,16 @@ mod test {
                 }
             )
         );
-        //assert_eq!("402 <client> <server name> :No such server",
-        //    format!("{}", ErrNoSuchServer402{ client: "<client>",
-        //        server: "<server name>" }));
+        assert_eq!(
+            "402 <client> <server name> :No such server",
+            format!(
+                "{}",
+                ErrNoSuchServer402 {
+                    client: "<client>",
+                    server: "<server name>"
+                }
+            )
+        );
         assert_eq!(
             "403 <client> <channel> :No such channel",
             format!(
@@ -1997,6 +2440,27 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "407 <client> <target> :Too many targets",
+            format!(
+                "{}",
+                ErrTooManyTargets407 {
+                    client: "<client>",
+                    target: "<target>"
+                }
+            )
+        );
+        assert_eq!(
+            "416 <client> <command> <mask> :Output too long (try locally)",
+            format!(
+                "{}",
+                ErrTooManyMatches416 {
+                    client: "<client>",
+                    command: "<command>",
+                    mask: "<mask>"
+                }
+            )
+        );
         assert_eq!(
             "417 <client> :Input line was too long",
             format!("{}", ErrInputTooLong417 { client: "<client>" })
@@ -2011,6 +2475,16 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "432 <client> <nick> :Erroneus nickname",
+            format!(
+                "{}",
+                ErrErroneusNickname432 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
         assert_eq!(
             "433 <client> <nick> :Nickname is already in use",
             format!(
@@ -2057,6 +2531,26 @@ mod test {
             "451 <client> :You have not registered",
             format!("{}", ErrNotRegistered451 { client: "<client>" })
         );
+        assert_eq!(
+            "457 <client> <nick> :is already on your accept list",
+            format!(
+                "{}",
+                ErrAcceptExist457 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
+        assert_eq!(
+            "458 <client> <nick> :is not on your accept list",
+            format!(
+                "{}",
+                ErrAcceptNot458 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
         assert_eq!(
             "461 <client> <command> :Not enough parameters",
             format!(
@@ -2075,8 +2569,10 @@ mod test {
             "464 <client> :Password incorrect",
             format!("{}", ErrPasswdMismatch464 { client: "<client>" })
         );
-        //assert_eq!("465 <client> :You are banned from this server.",
-        //    format!("{}", ErrYoureBannedCreep465{ client: "<client>" }));
+        assert_eq!(
+            "465 <client> :You are banned from this server.",
+            format!("{}", ErrYoureBannedCreep465 { client: "<client>" })
+        );
         assert_eq!(
             "471 <client> <channel> :Cannot join channel (+l)",
             format!(
@@ -2128,6 +2624,27 @@ mod test {
                 }
             )
         );
+        assert_eq!(
+            "476 <client> <channel> :Bad Channel Mask",
+            format!(
+                "{}",
+                ErrBadChanMask476 {
+                    client: "<client>",
+                    channel: "<channel>"
+                }
+            )
+        );
+        assert_eq!(
+            "477 <client> <channel> :Cannot join channel (+R) - you need to be logged into \
+                your account",
+            format!(
+                "{}",
+                ErrNeedReggedNick477 {
+                    client: "<client>",
+                    channel: "<channel>"
+                }
+            )
+        );
         assert_eq!(
             "481 <client> :Permission Denied- You're not an IRC operator",
             format!("{}", ErrNoPrivileges481 { client: "<client>" })
@@ -2162,6 +2679,17 @@ mod test {
             "502 <client> :Cant change mode for other users",
             format!("{}", ErrUsersDontMatch502 { client: "<client>" })
         );
+        assert_eq!(
+            "512 <client> <nick> :Maximum size for WATCH-list is 128 entries",
+            format!(
+                "{}",
+                ErrTooManyWatch512 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    limit: 128
+                }
+            )
+        );
         assert_eq!(
             "524 <client> <subject> :No help available on this topic",
             format!(
@@ -2178,6 +2706,93 @@ mod test {
         //    format!("{}", RplWhoIsSecure671{ client: "<client>", nick: "<nick>" }));
         //assert_eq!("691 <client> :STARTTLS failed (Wrong moon phase)",
         //    format!("{}", ErrStartTls691{ client: "<client>" }));
+        assert_eq!(
+            "600 <client> <nick> <user> <host> 1652345678 :logged online",
+            format!(
+                "{}",
+                RplLogOn600 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user: "<user>",
+                    host: "<host>",
+                    ts: 1652345678
+                }
+            )
+        );
+        assert_eq!(
+            "601 <client> <nick> <user> <host> 1652345678 :logged offline",
+            format!(
+                "{}",
+                RplLogOff601 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user: "<user>",
+                    host: "<host>",
+                    ts: 1652345678
+                }
+            )
+        );
+        assert_eq!(
+            "602 <client> <nick> <user> <host> 1652345678 :stopped watching",
+            format!(
+                "{}",
+                RplWatchOff602 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user: "<user>",
+                    host: "<host>",
+                    ts: 1652345678
+                }
+            )
+        );
+        assert_eq!(
+            "603 <client> :You have 5 and are on 3 WATCH entries",
+            format!(
+                "{}",
+                RplWatchStat603 {
+                    client: "<client>",
+                    watches: 5,
+                    watched_by: 3
+                }
+            )
+        );
+        assert_eq!(
+            "604 <client> <nick> <user> <host> 1652345678 :is online",
+            format!(
+                "{}",
+                RplNowOn604 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user: "<user>",
+                    host: "<host>",
+                    ts: 1652345678
+                }
+            )
+        );
+        assert_eq!(
+            "605 <client> <nick> * * 0 :is offline",
+            format!(
+                "{}",
+                RplNowOff605 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
+        assert_eq!(
+            "606 <client> :<nick1> <nick2>",
+            format!(
+                "{}",
+                RplWatchList606 {
+                    client: "<client>",
+                    nicks: "<nick1> <nick2>"
+                }
+            )
+        );
+        assert_eq!(
+            "607 <client> :End of WATCH list",
+            format!("{}", RplEndOfWatchList607 { client: "<client>" })
+        );
         assert_eq!(
             "696 <client> <target chan/user> x <parameter> :<description>",
             format!(
@@ -2224,29 +2839,133 @@ mod test {
                 }
             )
         );
-        //assert_eq!("900 <client> <nick>!~<user>@<host> <account> \
-        //    :You are now logged in as <username>",
-        //    format!("{}", RplLoggedIn900{ client: "<client>", nick: "<nick>",
-        //        user: "<user>", host: "<host>", account: "<account>",
-        //        username: "<username>" }));
+        assert_eq!(
+            "716 <client> <nick> :is in +g mode (server-side ignore)",
+            format!(
+                "{}",
+                ErrTargUmodeG716 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
+        assert_eq!(
+            "717 <client> <nick> :has been informed that you messaged them.",
+            format!(
+                "{}",
+                RplTargNotify717 {
+                    client: "<client>",
+                    nick: "<nick>"
+                }
+            )
+        );
+        assert_eq!(
+            "718 <client> <nick> <user>@<host> :is messaging you, and you have user mode +g \
+            set. Use /ACCEPT +<nick> to allow.",
+            format!(
+                "{}",
+                RplUmodeGMsg718 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user_host: "<user>@<host>"
+                }
+            )
+        );
+        assert_eq!(
+            "730 <client> :<target1>,<target2>",
+            format!(
+                "{}",
+                RplMonOnline730 {
+                    client: "<client>",
+                    targets: "<target1>,<target2>"
+                }
+            )
+        );
+        assert_eq!(
+            "731 <client> :<target1>,<target2>",
+            format!(
+                "{}",
+                RplMonOffline731 {
+                    client: "<client>",
+                    targets: "<target1>,<target2>"
+                }
+            )
+        );
+        assert_eq!(
+            "732 <client> :<target1>,<target2>",
+            format!(
+                "{}",
+                RplMonList732 {
+                    client: "<client>",
+                    targets: "<target1>,<target2>"
+                }
+            )
+        );
+        assert_eq!(
+            "733 <client> :End of MONITOR list",
+            format!("{}", RplEndOfMonList733 { client: "<client>" })
+        );
+        assert_eq!(
+            "734 <client> 100 <targets> :Monitor list is full.",
+            format!(
+                "{}",
+                ErrMonListIsFull734 {
+                    client: "<client>",
+                    limit: 100,
+                    targets: "<targets>"
+                }
+            )
+        );
+        assert_eq!(
+            "900 <client> <nick>!~<user>@<host> <account> \
+                :You are now logged in as <username>",
+            format!(
+                "{}",
+                RplLoggedIn900 {
+                    client: "<client>",
+                    nick: "<nick>",
+                    user: "<user>",
+                    host: "<host>",
+                    account: "<account>",
+                    username: "<username>"
+                }
+            )
+        );
         //assert_eq!("901 <client> <nick>!~<user>@<host> :You are now logged out",
         //    format!("{}", RplLoggedOut901{ client: "<client>", nick: "<nick>",
         //        user: "<user>", host: "<host>" }));
         //assert_eq!("902 <client> :You must use a nick assigned to you",
         //    format!("{}", ErrNickLocked902{ client: "<client>" }));
-        //assert_eq!("903 <client> :SASL authentication successful",
-        //    format!("{}", RplSaslSuccess903{ client: "<client>" }));
-        //assert_eq!("904 <client> :SASL authentication failed",
-        //    format!("{}", ErrSaslFail904{ client: "<client>" }));
-        //assert_eq!("905 <client> :SASL message too long",
-        //    format!("{}", ErrSaslTooLong905{ client: "<client>" }));
-        //assert_eq!("906 <client> :SASL authentication aborted",
-        //    format!("{}", ErrSaslAborted906{ client: "<client>" }));
-        //assert_eq!("907 <client> :You have already authenticated using SASL",
-        //    format!("{}", ErrSaslAlready907{ client: "<client>" }));
-        //assert_eq!("908 <client> <mechanisms> :are available SASL mechanisms",
-        //    format!("{}", RplSaslMechs908{ client: "<client>",
-        //        mechanisms: "<mechanisms>" }));
+        assert_eq!(
+            "903 <client> :SASL authentication successful",
+            format!("{}", RplSaslSuccess903 { client: "<client>" })
+        );
+        assert_eq!(
+            "904 <client> :SASL authentication failed",
+            format!("{}", ErrSaslFail904 { client: "<client>" })
+        );
+        assert_eq!(
+            "905 <client> :SASL message too long",
+            format!("{}", ErrSaslTooLong905 { client: "<client>" })
+        );
+        assert_eq!(
+            "906 <client> :SASL authentication aborted",
+            format!("{}", ErrSaslAborted906 { client: "<client>" })
+        );
+        assert_eq!(
+            "907 <client> :You have already authenticated using SASL",
+            format!("{}", ErrSaslAlready907 { client: "<client>" })
+        );
+        assert_eq!(
+            "908 <client> <mechanisms> :are available SASL mechanisms",
+            format!(
+                "{}",
+                RplSaslMechs908 {
+                    client: "<client>",
+                    mechanisms: "<mechanisms>"
+                }
+            )
+        );
         assert_eq!(
             "972 <client> :Can not do command",
             format!("{}", ErrCannotDoCommand972 { client: "<client>" })
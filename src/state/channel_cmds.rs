@@ -21,48 +21,104 @@ use super::*;
 use std::collections::HashMap;
 use std::error::Error;
 use std::ops::DerefMut;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// maximal length of NAMES reply line (512 bytes without CRLF).
+const NAMES_LINE_LEN: usize = 510;
 
 impl super::MainState {
+    // check whether JOIN and PART of users should be sent only to channel operators.
+    fn quiet_join_part(&self, chanobj: &Channel) -> bool {
+        self.config
+            .quiet_join_threshold
+            .map_or(false, |threshold| chanobj.users.len() > threshold)
+    }
+
     pub(super) async fn process_join<'a>(
         &self,
         conn_state: &mut ConnState,
         channels: Vec<&'a str>,
         keys_opt: Option<Vec<&'a str>>,
     ) -> Result<(), Box<dyn Error>> {
+        let forwards = self.join_channels(conn_state, channels, keys_opt).await?;
+        if !forwards.is_empty() {
+            let client = conn_state.user_state.client_name();
+            for (chname, target) in &forwards {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!(
+                        "NOTICE {} :Cannot join to {}, forwarding to {}",
+                        client, chname, target
+                    ),
+                )
+                .await?;
+            }
+            // forward only once - forwards of target channels are not followed.
+            let targets = forwards.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>();
+            self.join_channels(conn_state, targets, None).await?;
+        }
+        Ok(())
+    }
+
+    // join user to channels. Returns channels (with their targets) to which user
+    // can't join and that forward users to other channels.
+    async fn join_channels<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        channels: Vec<&'a str>,
+        keys_opt: Option<Vec<&'a str>>,
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
         let mut statem = self.state.write().await;
         let state = statem.deref_mut();
         let user_nick = conn_state.user_state.nick.as_ref().unwrap().clone();
         let user_joined = state.users.get(&user_nick).unwrap().channels.len();
         let mut join_count = user_joined;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
 
         let mut joined_created = vec![];
+        // statuses granted by channel access list
+        let mut granted = vec![];
+        // channels that forward user to other channels
+        let mut forwards: Vec<(String, String)> = vec![];
 
         {
             let client = conn_state.user_state.client_name();
             let user = state.users.get_mut(user_nick.as_str()).unwrap();
             for (i, chname_str) in channels.iter().enumerate() {
+                // check channel name - invalid channel is skipped.
+                if validate_channel(chname_str).is_err() {
+                    let channel = chname_str;
+                    if has_channel_prefix(channel) {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            ErrBadChanMask476 { client, channel },
+                        )
+                        .await?;
+                    } else {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            ErrNoSuchChannel403 { client, channel },
+                        )
+                        .await?;
+                    }
+                    joined_created.push((false, false));
+                    continue;
+                }
                 let chname = chname_str.to_string();
                 let (join, create) = if let Some(channel) = state.channels.get(&chname) {
                     // if already created
                     let do_join = if let Some(key) = &channel.modes.key {
-                        if let Some(ref keys) = keys_opt {
-                            // check key
-                            if key != keys[i] {
-                                self.feed_msg(
-                                    &mut conn_state.stream,
-                                    ErrBadChannelKey475 {
-                                        client,
-                                        channel: chname_str,
-                                    },
-                                )
-                                .await?;
-                                false
-                            } else {
-                                true
-                            }
-                        } else {
-                            // no key then bad key
+                        // keys are matched to channels by position, missing key is empty.
+                        let given_key = keys_opt
+                            .as_ref()
+                            .and_then(|keys| keys.get(i))
+                            .copied()
+                            .unwrap_or("");
+                        // check key - missing or invalid key then bad key
+                        if validate_key(given_key).is_err() || key != given_key {
                             self.feed_msg(
                                 &mut conn_state.stream,
                                 ErrBadChannelKey475 {
@@ -72,15 +128,31 @@ impl super::MainState {
                             )
                             .await?;
                             false
+                        } else {
+                            true
                         }
                     } else {
                         true
                     };
 
+                    // forward target is used only if target channel exists.
+                    let forward_target = match channel.modes.forward {
+                        Some(ref target) if state.channels.contains_key(target) => Some(target),
+                        _ => None,
+                    };
+
                     // check whether user is banned
                     let do_join = do_join && {
-                        if !channel.modes.banned(&conn_state.user_state.source) {
+                        if !channel
+                            .modes
+                            .banned(&conn_state.user_state.source, user.account.as_deref())
+                        {
                             true
+                        } else if let Some(target) = forward_target {
+                            if forwards.iter().all(|(_, t)| t != target) {
+                                forwards.push((chname.clone(), target.clone()));
+                            }
+                            false
                         } else {
                             self.feed_msg(
                                 &mut conn_state.stream,
@@ -94,16 +166,47 @@ impl super::MainState {
                         }
                     };
 
+                    // check whether user is logged in to account if channel is +R
+                    let do_join = do_join && {
+                        if !channel.modes.registered_only || user.account.is_some() {
+                            true
+                        } else {
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                ErrNeedReggedNick477 {
+                                    client,
+                                    channel: chname_str,
+                                },
+                            )
+                            .await?;
+                            false
+                        }
+                    };
+
                     // check whether must have invitation
                     let do_join = do_join && {
                         if !channel.modes.invite_only
-                            || user.invited_to.contains(&chname)
+                            || user.invited_to.get(&chname).map_or(false, |t| {
+                                self.config
+                                    .invite_timeout
+                                    .map_or(true, |timeout| now <= t + timeout)
+                            })
                             || channel.modes.invite_exception.as_ref().map_or(false, |e| {
-                                e.iter()
-                                    .any(|e| match_wildcard(e, &conn_state.user_state.source))
+                                e.iter().any(|e| {
+                                    match_user_mask(
+                                        e,
+                                        &conn_state.user_state.source,
+                                        user.account.as_deref(),
+                                    )
+                                })
                             })
                         {
                             true
+                        } else if let Some(target) = forward_target {
+                            if forwards.iter().all(|(_, t)| t != target) {
+                                forwards.push((chname.clone(), target.clone()));
+                            }
+                            false
                         } else {
                             self.feed_msg(
                                 &mut conn_state.stream,
@@ -126,6 +229,11 @@ impl super::MainState {
                         };
                         if not_full {
                             true
+                        } else if let Some(target) = forward_target {
+                            if forwards.iter().all(|(_, t)| t != target) {
+                                forwards.push((chname.clone(), target.clone()));
+                            }
+                            false
                         } else {
                             self.feed_msg(
                                 &mut conn_state.stream,
@@ -147,8 +255,49 @@ impl super::MainState {
                         (false, false)
                     }
                 } else {
-                    // if new channel
-                    (true, true)
+                    // if new channel - check whether user can create it
+                    let can_create = self.can_create_channel(user, &chname).await;
+                    if !can_create {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            ErrNoSuchChannel403 {
+                                client,
+                                channel: chname_str,
+                            },
+                        )
+                        .await?;
+                    }
+                    // check limit of created channels - operators and exempt users
+                    // are not limited.
+                    let can_create = can_create && {
+                        if let Some(rate_limit) = self.config.channel_creation_rate_limit {
+                            if user.modes.is_local_oper()
+                                || self.rate_limit_exempt(&conn_state.user_state.source)
+                                || check_rate_limit(
+                                    &mut conn_state.channel_creation_times,
+                                    rate_limit.count,
+                                    Duration::from_secs(rate_limit.period),
+                                    Instant::now(),
+                                )
+                            {
+                                true
+                            } else {
+                                self.feed_msg(
+                                    &mut conn_state.stream,
+                                    format!(
+                                        "FAIL JOIN RATE_LIMITED {} :Too many channels \
+                                        created, try again later",
+                                        chname_str
+                                    ),
+                                )
+                                .await?;
+                                false
+                            }
+                        } else {
+                            true
+                        }
+                    };
+                    (can_create, can_create)
                 };
 
                 // check whether user is not in max channels
@@ -177,6 +326,7 @@ impl super::MainState {
             // insert create channel or add user to channel
             for ((join, create), chname_str) in joined_created.iter().zip(channels.iter()) {
                 let chname = chname_str.to_string();
+                let mut level = None;
 
                 if *join {
                     user.channels.insert(chname.clone());
@@ -190,13 +340,25 @@ impl super::MainState {
                             .channels
                             .insert(chname, Channel::new_on_user_join(user_nick.clone()));
                     } else {
-                        state
-                            .channels
-                            .get_mut(&chname)
-                            .unwrap()
-                            .add_user(&user_nick);
+                        let chanobj = state.channels.get_mut(&chname).unwrap();
+                        chanobj.add_user(&user_nick);
+                        level = chanobj.apply_access(
+                            &user_nick,
+                            &conn_state.user_state.source,
+                            user.account.as_deref(),
+                        );
+                        // user that got status from access list is not hidden.
+                        // in large channel JOIN is sent only to channel operators.
+                        if level.is_none() {
+                            if chanobj.modes.delayed_join {
+                                chanobj.delayed_users.insert(user_nick.clone(), false);
+                            } else if self.quiet_join_part(chanobj) {
+                                chanobj.delayed_users.insert(user_nick.clone(), true);
+                            }
+                        }
                     }
                 }
+                granted.push(level);
             }
             // if something done - then change last activity
             if join_count != user_joined {
@@ -209,7 +371,9 @@ impl super::MainState {
 
         // sending messages
         {
-            for ((join, _), chname_str) in joined_created.iter().zip(channels.iter()) {
+            for (((join, _), level), chname_str) in
+                joined_created.iter().zip(granted.iter()).zip(channels.iter())
+            {
                 if *join {
                     let chanobj = state.channels.get(&chname_str.to_string()).unwrap();
                     let join_msg = "JOIN ".to_string() + chname_str;
@@ -231,6 +395,16 @@ impl super::MainState {
                                 },
                             )
                             .await?;
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                RplTopicWhoTime333 {
+                                    client,
+                                    channel: chname_str,
+                                    nick: &topic.nick,
+                                    setat: topic.set_time,
+                                },
+                            )
+                            .await?;
                         }
                     }
                     self.send_names_from_channel(
@@ -242,20 +416,34 @@ impl super::MainState {
                     )
                     .await?;
 
-                    // send message to other users in channel
-                    for nick in chanobj.users.keys() {
-                        if nick != user_nick.as_str() {
+                    // send message to other users in channel. hidden user is shown
+                    // to others after its first message in channel.
+                    for (nick, chum) in &chanobj.users {
+                        if nick != user_nick.as_str() && chanobj.is_shown_to(&user_nick, chum) {
                             state.users.get(&nick.clone()).unwrap().send_msg_display(
                                 &conn_state.user_state.source,
                                 join_msg.as_str(),
                             )?;
                         }
                     }
+
+                    // send status granted by access list to all users in channel
+                    if let Some(level) = level {
+                        let mode_msg =
+                            format!("MODE {} +{} {}", chname_str, level.mode_char(), user_nick);
+                        for nick in chanobj.users.keys() {
+                            state
+                                .users
+                                .get(nick)
+                                .unwrap()
+                                .send_msg_display(&self.config.name, mode_msg.as_str())?;
+                        }
+                    }
                 }
             }
         }
 
-        Ok(())
+        Ok(forwards)
     }
 
     pub(super) async fn process_part<'a>(
@@ -295,12 +483,14 @@ impl super::MainState {
                     } else {
                         format!("PART {}", channel)
                     };
-                    for nick in chanobj.users.keys() {
-                        state
-                            .users
-                            .get(&nick.clone())
-                            .unwrap()
-                            .send_msg_display(&conn_state.user_state.source, part_msg.as_str())?;
+                    // part of hidden user is sent only to users that got its JOIN.
+                    for (nick, chum) in &chanobj.users {
+                        if nick == &user_nick || chanobj.is_shown_to(&user_nick, chum) {
+                            state.users.get(&nick.clone()).unwrap().send_msg_display(
+                                &conn_state.user_state.source,
+                                part_msg.as_str(),
+                            )?;
+                        }
                     }
                 }
 
@@ -466,36 +656,54 @@ impl super::MainState {
         let client = conn_state.user_state.client_name();
         let conn_user_nick = conn_state.user_state.nick.as_ref().unwrap();
 
-        let in_channel = channel.users.contains_key(conn_user_nick);
+        let conn_chum = channel.users.get(conn_user_nick);
+        let in_channel = conn_chum.is_some();
         // if channel is not secret or user on channel.
         if !channel.modes.secret || in_channel {
-            const NAMES_COUNT: usize = 20;
             let symbol = if channel.modes.secret { "@" } else { "=" };
+            // space for names in reply line after server name and reply header.
+            let header_len =
+                format!(":{} 353 {} {} {} :", self.config.name, client, symbol, channel_name)
+                    .len();
+            let names_space = NAMES_LINE_LEN.saturating_sub(header_len);
 
             let mut name_chunk = vec![];
-            name_chunk.reserve(NAMES_COUNT);
+            let mut chunk_len = 0;
 
             for (unick, chum) in &channel.users {
                 let user = users.get(unick.as_str()).unwrap();
-                // do not send names of invisible users or user on channel
-                if !user.modes.invisible || in_channel {
-                    name_chunk.push(NameReplyStruct {
+                // do not send names of invisible users or user on channel.
+                // hidden users are shown only to themselves and to users that got JOIN.
+                if (!user.modes.invisible || in_channel)
+                    && (!channel.delayed_users.contains_key(unick)
+                        || unick == conn_user_nick
+                        || conn_chum.map_or(false, |c| channel.is_shown_to(unick, c)))
+                {
+                    let name = NameReplyStruct {
                         prefix: chum.to_string(&conn_state.caps),
                         nick: unick,
-                    });
-                }
-                if name_chunk.len() == NAMES_COUNT {
-                    self.feed_msg(
-                        &mut conn_state.stream,
-                        RplNameReply353 {
-                            client,
-                            symbol,
-                            channel: channel_name,
-                            replies: &name_chunk,
-                        },
-                    )
-                    .await?;
-                    name_chunk.clear();
+                    };
+                    let name_len = name.prefix.len() + name.nick.len();
+                    // send chunk if next name (with separator) does not fit
+                    if !name_chunk.is_empty() && chunk_len + 1 + name_len > names_space {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            RplNameReply353 {
+                                client,
+                                symbol,
+                                channel: channel_name,
+                                replies: &name_chunk,
+                            },
+                        )
+                        .await?;
+                        name_chunk.clear();
+                        chunk_len = 0;
+                    }
+                    if !name_chunk.is_empty() {
+                        chunk_len += 1;
+                    }
+                    chunk_len += name_len;
+                    name_chunk.push(name);
                 }
             }
             if !name_chunk.is_empty() {
@@ -650,48 +858,43 @@ impl super::MainState {
         let user_nick = conn_state.user_state.nick.as_ref().unwrap();
         let client = conn_state.user_state.client_name();
 
+        // IRC operators can invite to channels without being on channel.
+        let oper = state.users.get(user_nick).unwrap().modes.is_local_oper();
         let do_invite = if let Some(chanobj) = state.channels.get(channel) {
-            if chanobj.users.contains_key(user_nick) {
-                let do_invite2 = if chanobj.modes.invite_only {
-                    // only operator can invite into channel if channel is invite_only.
-                    if !chanobj.users.get(user_nick).unwrap().operator {
-                        self.feed_msg(
-                            &mut conn_state.stream,
-                            ErrChanOpPrivsNeeded482 { client, channel },
-                        )
-                        .await?;
-                        false
-                    } else {
-                        true
-                    }
-                } else {
-                    true
-                };
-                if do_invite2 {
-                    if chanobj.users.contains_key(nickname) {
-                        self.feed_msg(
-                            &mut conn_state.stream,
-                            ErrUserOnChannel443 {
-                                client,
-                                nick: nickname,
-                                channel,
-                            },
-                        )
-                        .await?;
-                        false
-                    } else {
-                        true
-                    }
-                } else {
-                    false
-                }
-            } else {
+            let chum = chanobj.users.get(user_nick);
+            if chum.is_none() && !oper {
                 self.feed_msg(
                     &mut conn_state.stream,
                     ErrNotOnChannel442 { client, channel },
                 )
                 .await?;
                 false
+            } else if chanobj.modes.invite_only
+                && !chanobj.modes.free_invite
+                && !oper
+                && !chum.map_or(false, |chum| chum.is_operator())
+            {
+                // only operator can invite into channel if channel is invite_only
+                // and free invite is not set.
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrChanOpPrivsNeeded482 { client, channel },
+                )
+                .await?;
+                false
+            } else if chanobj.users.contains_key(nickname) {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrUserOnChannel443 {
+                        client,
+                        nick: nickname,
+                        channel,
+                    },
+                )
+                .await?;
+                false
+            } else {
+                true
             }
         } else {
             self.feed_msg(
@@ -705,7 +908,11 @@ impl super::MainState {
         if do_invite {
             // check user
             if let Some(invited) = state.users.get_mut(nickname) {
-                invited.invited_to.insert(channel.to_string());
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                invited.invited_to.insert(channel.to_string(), now);
                 self.feed_msg(
                     &mut conn_state.stream,
                     RplInviting341 {
@@ -824,50 +1031,374 @@ impl super::MainState {
         }
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::super::test::*;
-    use super::*;
-    use tokio::net::TcpStream;
+    // RENAME - rename channel (draft/channel-rename).
+    pub(super) async fn process_rename<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        channel: &'a str,
+        new_channel: &'a str,
+        reason: Option<&'a str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut statem = self.state.write().await;
+        let state = statem.deref_mut();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+        let client = conn_state.user_state.client_name();
 
-    fn equal_channel_names<'a>(
-        exp_msg: &'a str,
-        exp_names: &'a [&'a str],
-        names_replies: &'a [&'a str],
-    ) -> bool {
-        let mut exp_names_sorted = Vec::from(exp_names);
-        exp_names_sorted.sort();
-        let mut touched = vec![false; exp_names.len()];
-        names_replies.iter().all(|reply| {
-            if reply.starts_with(exp_msg) {
-                reply[exp_msg.len()..].split_terminator(" ").all(|c| {
-                    if let Ok(p) = exp_names_sorted.binary_search(&c) {
-                        touched[p] = true;
-                        true
-                    } else {
-                        false
-                    }
-                })
-            } else {
-                false
+        if let Some(chanobj) = state.channels.get(channel) {
+            let oper = state.users.get(user_nick).unwrap().modes.is_local_oper();
+            // only channel operator or IRC operator can rename channel.
+            if !oper && !chanobj.users.get(user_nick).map_or(false, |chum| chum.is_operator()) {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrChanOpPrivsNeeded482 { client, channel },
+                )
+                .await?;
+                return Ok(());
             }
-        }) && touched.iter().all(|x| *x)
-    }
-
-    #[tokio::test]
-    async fn test_command_join() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+        } else {
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrNoSuchChannel403 { client, channel },
+            )
+            .await?;
+            return Ok(());
+        }
 
+        // channel type can not be changed.
+        if channel.as_bytes()[0] != new_channel.as_bytes()[0] {
+            self.feed_msg(
+                &mut conn_state.stream,
+                format!(
+                    "FAIL RENAME CANNOT_RENAME {} {} :Channel type can not be changed",
+                    channel, new_channel
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        // new name must be allowed like name of new channel.
+        if !self
+            .can_create_channel(state.users.get(user_nick).unwrap(), new_channel)
+            .await
         {
-            let mut line_stream =
-                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
+            self.feed_msg(
+                &mut conn_state.stream,
+                format!(
+                    "FAIL RENAME CANNOT_RENAME {} {} :Channel name is not allowed",
+                    channel, new_channel
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        if state.channels.contains_key(new_channel) {
+            self.feed_msg(
+                &mut conn_state.stream,
+                format!(
+                    "FAIL RENAME CHANNEL_NAME_IN_USE {} {} :Channel name is already taken",
+                    channel, new_channel
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
 
-            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
-            assert_eq!(
-                ":charlie!~charlie2@127.0.0.1 JOIN #fruits".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+        state.rename_channel(channel, new_channel);
+        info!(
+            "User {} renamed channel {} to {}",
+            conn_state.user_state.source, channel, new_channel
+        );
+
+        let reason = reason.unwrap_or("");
+        let rename_msg = format!("RENAME {} {} :{}", channel, new_channel, reason);
+        let part_msg = format!("PART {} :Channel renamed: {}", channel, reason);
+        let join_msg = format!("JOIN {}", new_channel);
+        let chanobj = state.channels.get(new_channel).unwrap();
+        for nick in chanobj.users.keys() {
+            let user = state.users.get(nick).unwrap();
+            if user.caps.channel_rename {
+                user.send_msg_display(&conn_state.user_state.source, rename_msg.as_str())?;
+            } else {
+                // clients without capability get PART and JOIN like for new channel.
+                user.send_msg_display(&user.source, part_msg.as_str())?;
+                user.send_msg_display(&user.source, join_msg.as_str())?;
+                if let Some(ref topic) = chanobj.topic {
+                    user.send_msg_display(
+                        &self.config.name,
+                        RplTopic332 {
+                            client: nick,
+                            channel: new_channel,
+                            topic: &topic.topic,
+                        },
+                    )?;
+                    user.send_msg_display(
+                        &self.config.name,
+                        RplTopicWhoTime333 {
+                            client: nick,
+                            channel: new_channel,
+                            nick: &topic.nick,
+                            setat: topic.set_time,
+                        },
+                    )?;
+                }
+                let symbol = if chanobj.modes.secret { "@" } else { "=" };
+                let names = chanobj
+                    .users
+                    .iter()
+                    .map(|(unick, chum)| NameReplyStruct {
+                        prefix: chum.to_string(&user.caps),
+                        nick: unick,
+                    })
+                    .collect::<Vec<_>>();
+                for name_chunk in names.chunks(20) {
+                    user.send_msg_display(
+                        &self.config.name,
+                        RplNameReply353 {
+                            client: nick,
+                            symbol,
+                            channel: new_channel,
+                            replies: name_chunk,
+                        },
+                    )?;
+                }
+                user.send_msg_display(
+                    &self.config.name,
+                    RplEndOfNames366 {
+                        client: nick,
+                        channel: new_channel,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // CLEARCHAN - remove users from channel (for example during spam raid).
+    // Only IRC operator can clear channel.
+    pub(super) async fn process_clearchan<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        channel: &'a str,
+        all: bool,
+        lock: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut statem = self.state.write().await;
+        let state = statem.deref_mut();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+        let client = conn_state.user_state.client_name();
+
+        let kicked = if let Some(chanobj) = state.channels.get(channel) {
+            // issuing operator is never kicked. channel operators and IRC operators
+            // are kicked only if all is given.
+            chanobj
+                .users
+                .iter()
+                .filter(|(nick, chum)| {
+                    *nick != user_nick
+                        && (all
+                            || (!chum.is_operator()
+                                && !state.users.get(*nick).unwrap().modes.is_local_oper()))
+                })
+                .map(|(nick, _)| nick.clone())
+                .collect::<Vec<_>>()
+        } else {
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrNoSuchChannel403 { client, channel },
+            )
+            .await?;
+            return Ok(());
+        };
+
+        for ku in &kicked {
+            let kick_msg = format!("KICK {} {} :Channel has been cleared", channel, ku);
+            for nick in state.channels.get(channel).unwrap().users.keys() {
+                state
+                    .users
+                    .get(nick)
+                    .unwrap()
+                    .send_msg_display(&conn_state.user_state.source, kick_msg.clone())?;
+            }
+            state.remove_user_from_channel(channel, ku);
+        }
+
+        let mut mode_str = String::new();
+        if lock {
+            if let Some(chanobj) = state.channels.get_mut(channel) {
+                if !chanobj.modes.invite_only {
+                    chanobj.modes.invite_only = true;
+                    mode_str.push('i');
+                }
+                if !chanobj.modes.moderated {
+                    chanobj.modes.moderated = true;
+                    mode_str.push('m');
+                }
+                if !mode_str.is_empty() {
+                    let mode_msg = format!("MODE {} +{}", channel, mode_str);
+                    for nick in chanobj.users.keys() {
+                        state
+                            .users
+                            .get(nick)
+                            .unwrap()
+                            .send_msg_display(&conn_state.user_state.source, mode_msg.clone())?;
+                    }
+                }
+            }
+        }
+
+        // audit notice for all IRC operators
+        info!(
+            "Channel {} cleared by {}: {} users kicked",
+            channel,
+            conn_state.user_state.source,
+            kicked.len()
+        );
+        let notice = format!(
+            "CLEARCHAN {} by {}: {} users kicked{}",
+            channel,
+            user_nick,
+            kicked.len(),
+            if !mode_str.is_empty() {
+                format!(", modes +{} set", mode_str)
+            } else {
+                String::new()
+            }
+        );
+        for (nick, user) in &state.users {
+            if user.modes.is_local_oper() {
+                user.send_msg_display(
+                    &self.config.name,
+                    format!("NOTICE {} :{}", nick, notice),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // ACCESS - manage channel access list. Only channel operators can use it.
+    pub(super) async fn process_access<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        channel: &'a str,
+        subcommand: AccessCommand<'a>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut statem = self.state.write().await;
+        let state = statem.deref_mut();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+        let client = conn_state.user_state.client_name();
+
+        let chanobj = if let Some(chanobj) = state.channels.get_mut(channel) {
+            chanobj
+        } else {
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrNoSuchChannel403 { client, channel },
+            )
+            .await?;
+            return Ok(());
+        };
+        // only founder can manage founder entries (successors of channel founder).
+        let founder_entry = match subcommand {
+            AccessCommand::Add { level, .. } => level == ChannelAccessLevel::Founder,
+            AccessCommand::Del { mask } => {
+                chanobj.access.get(mask) == Some(&ChannelAccessLevel::Founder)
+            }
+            AccessCommand::List => false,
+        };
+        let chum = chanobj.users.get(user_nick).copied().unwrap_or_default();
+        if !chum.is_operator() || (founder_entry && !chum.founder) {
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrChanOpPrivsNeeded482 { client, channel },
+            )
+            .await?;
+            return Ok(());
+        }
+
+        match subcommand {
+            AccessCommand::Add { mask, level } => {
+                info!(
+                    "Access {} {} added to channel {} by {}",
+                    mask, level, channel, conn_state.user_state.source
+                );
+                chanobj.access.insert(mask.to_string(), level);
+            }
+            AccessCommand::Del { mask } => {
+                if chanobj.access.remove(mask).is_none() {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        format!(
+                            "FAIL ACCESS NO_SUCH_ENTRY {} {} :No such entry in access list",
+                            channel, mask
+                        ),
+                    )
+                    .await?;
+                }
+            }
+            AccessCommand::List => {
+                let mut entries = chanobj.access.iter().collect::<Vec<_>>();
+                entries.sort_unstable();
+                for (mask, level) in entries {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        format!("NOTICE {} :{} {} {}", client, channel, mask, level),
+                    )
+                    .await?;
+                }
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!("NOTICE {} :End of {} access list", client, channel),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+    use tokio::net::TcpStream;
+
+    fn equal_channel_names<'a>(
+        exp_msg: &'a str,
+        exp_names: &'a [&'a str],
+        names_replies: &'a [&'a str],
+    ) -> bool {
+        let mut exp_names_sorted = Vec::from(exp_names);
+        exp_names_sorted.sort();
+        let mut touched = vec![false; exp_names.len()];
+        names_replies.iter().all(|reply| {
+            if reply.starts_with(exp_msg) {
+                reply[exp_msg.len()..].split_terminator(" ").all(|c| {
+                    if let Ok(p) = exp_names_sorted.binary_search(&c) {
+                        touched[p] = true;
+                        true
+                    } else {
+                        false
+                    }
+                })
+            } else {
+                false
+            }
+        }) && touched.iter().all(|x| *x)
+    }
+
+    #[tokio::test]
+    async fn test_command_join() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
+
+            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+            assert_eq!(
+                ":charlie!~charlie2@127.0.0.1 JOIN #fruits".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
                 ":irc.irc 353 charlie = #fruits :~charlie".to_string(),
@@ -921,6 +1452,8 @@ mod test {
                 topic: None,
                 creation_time: 0,
                 preconfigured: false,
+                access: HashMap::new(),
+                delayed_users: HashMap::new(),
                 modes: ChannelModes::new_for_channel("charlie".to_string()),
                 default_modes: ChannelDefaultModes::default(),
                 ban_info: HashMap::new(),
@@ -1006,49 +1539,52 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_join_already_joined() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_join_channel_creation_opers() {
+        let mut config = MainConfig::default();
+        config.channel_creation = Some(ChannelCreation::Opers);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        config.channels = Some(vec![ChannelConfig {
+            name: "#carrots".to_string(),
+            topic: None,
+            modes: ChannelModes::default(),
+            access: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
-            let mut line_stream =
-                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
-
-            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
-            for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
-            }
-
-            time::sleep(Duration::from_millis(100)).await;
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            line_stream.send("JOIN #apples".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 403 brian #apples :No such channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("JOIN #carrots".to_string()).await.unwrap();
+            assert_eq!(
+                ":brian!~brianx@127.0.0.1 JOIN #carrots".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
 
-            {
-                // set some channel user mode
-                main_state
-                    .state
-                    .write()
-                    .await
-                    .channels
-                    .get_mut("#fruits")
-                    .unwrap()
-                    .add_voice("charlie");
-            }
+            let mut oper_stream = login_to_test_and_skip(port, "fanny", "fanny", "Fanny").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+            oper_stream.send("JOIN #apples".to_string()).await.unwrap();
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 JOIN #apples".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
 
-            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
-            time::sleep(Duration::from_millis(100)).await;
+            time::sleep(Duration::from_millis(50)).await;
             {
-                // check user mode (voice)
-                assert!(
-                    main_state
-                        .state
-                        .read()
-                        .await
-                        .channels
-                        .get("#fruits")
-                        .unwrap()
-                        .users
-                        .get("charlie")
-                        .unwrap()
-                        .voice
-                );
+                let state = main_state.state.read().await;
+                assert!(state.channels.contains_key("#apples"));
             }
         }
 
@@ -1056,50 +1592,268 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_join_with_topic() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_join_channel_creation_rate_limit() {
+        let mut config = MainConfig::default();
+        config.channel_creation_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
-
-            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+                login_to_test_and_skip(port, "flooder", "flooder", "Channel Flooder").await;
+            let mut other_stream =
+                login_to_test_and_skip(port, "maker", "maker", "Channel Maker").await;
+            other_stream
+                .send("JOIN #existing".to_string())
+                .await
+                .unwrap();
             for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
+                other_stream.next().await.unwrap().unwrap();
             }
 
-            time::sleep(Duration::from_millis(50)).await;
-            {
-                let mut state = main_state.state.write().await;
-                state.channels.get_mut("#fruits").unwrap().topic = Some(
-                    ChannelTopic::new_with_nick("This topic".to_string(), "charlie".to_string()),
+            // within limit
+            line_stream
+                .send("JOIN #flood1,#flood2".to_string())
+                .await
+                .unwrap();
+            for chname in ["#flood1", "#flood2"] {
+                assert_eq!(
+                    format!(":flooder!~flooder@127.0.0.1 JOIN {}", chname),
+                    line_stream.next().await.unwrap().unwrap()
                 );
+                for _ in 0..2 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
             }
-
-            let mut line_stream2 =
-                login_to_test_and_skip(port, "eddix", "eddie", "Eddie Flower").await;
-            line_stream2.send("JOIN #fruits".to_string()).await.unwrap();
-            assert_eq!(
-                ":eddix!~eddie@127.0.0.1 JOIN #fruits".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
-            );
+            // over limit
+            line_stream.send("JOIN #flood3".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 332 eddix #fruits :This topic".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
+                ":irc.irc FAIL JOIN RATE_LIMITED #flood3 :Too many channels created, \
+                    try again later"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
-            assert!(equal_channel_names(
-                ":irc.irc 353 eddix = #fruits :",
-                &["eddix", "~charlie"],
-                &[&line_stream2.next().await.unwrap().unwrap()]
-            ));
+            // joining existing channel is not limited
+            line_stream
+                .send("JOIN #existing".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 366 eddix #fruits :End of /NAMES list".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
+                ":flooder!~flooder@127.0.0.1 JOIN #existing".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
-        }
 
-        quit_test_server(main_state, handle).await;
-    }
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.channels.contains_key("#flood3"));
+                assert!(state.channels["#existing"].users.contains_key("flooder"));
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_reserved_channel() {
+        let mut config = MainConfig::default();
+        config.reserved_channels = Some(vec!["#staff*".to_string()]);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            line_stream
+                .send("JOIN #staffroom".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 403 brian #staffroom :No such channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("JOIN #stuff".to_string()).await.unwrap();
+            assert_eq!(
+                ":brian!~brianx@127.0.0.1 JOIN #stuff".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            let mut oper_stream = login_to_test_and_skip(port, "fanny", "fanny", "Fanny").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+            oper_stream
+                .send("JOIN #staffroom".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 JOIN #staffroom".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_invalid_channels() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            line_stream
+                .send("JOIN #good,badname,#bad:name".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 403 brian badname :No such channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 476 brian #bad:name :Bad Channel Mask".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":brian!~brianx@127.0.0.1 JOIN #good".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert_eq!(
+                    HashSet::from(["#good".to_string()]),
+                    HashSet::from_iter(state.channels.keys().cloned())
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_already_joined() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
+
+            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            time::sleep(Duration::from_millis(100)).await;
+
+            {
+                // set some channel user mode
+                main_state
+                    .state
+                    .write()
+                    .await
+                    .channels
+                    .get_mut("#fruits")
+                    .unwrap()
+                    .add_voice("charlie");
+            }
+
+            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+            time::sleep(Duration::from_millis(100)).await;
+            {
+                // check user mode (voice)
+                assert!(
+                    main_state
+                        .state
+                        .read()
+                        .await
+                        .channels
+                        .get("#fruits")
+                        .unwrap()
+                        .users
+                        .get("charlie")
+                        .unwrap()
+                        .voice
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_with_topic() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "charlie", "charlie2", "Charlie Brown").await;
+
+            // no topic replies if channel has no topic
+            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+            for expected in [
+                ":charlie!~charlie2@127.0.0.1 JOIN #fruits",
+                ":irc.irc 353 charlie = #fruits :~charlie",
+                ":irc.irc 366 charlie #fruits :End of /NAMES list",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.channels.get_mut("#fruits").unwrap().topic = Some(
+                    ChannelTopic::new_with_nick("This topic".to_string(), "charlie".to_string()),
+                );
+            }
+
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "eddix", "eddie", "Eddie Flower").await;
+            line_stream2.send("JOIN #fruits".to_string()).await.unwrap();
+            assert_eq!(
+                ":eddix!~eddie@127.0.0.1 JOIN #fruits".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 332 eddix #fruits :This topic".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            let set_time = main_state
+                .state
+                .read()
+                .await
+                .channels
+                .get("#fruits")
+                .unwrap()
+                .topic
+                .as_ref()
+                .unwrap()
+                .set_time;
+            assert_eq!(
+                format!(":irc.irc 333 eddix #fruits charlie {}", set_time),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert!(equal_channel_names(
+                ":irc.irc 353 eddix = #fruits :",
+                &["eddix", "~charlie"],
+                &[&line_stream2.next().await.unwrap().unwrap()]
+            ));
+            assert_eq!(
+                ":irc.irc 366 eddix #fruits :End of /NAMES list".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
 
     #[tokio::test]
     async fn test_command_join_limit_check() {
@@ -1232,84 +1986,361 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_join_invite() {
+    async fn test_command_join_delayed() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
-        // invite
+
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "damian", "damian", "Damian Kozlowski").await;
+                login_to_test_and_skip(port, "alice", "alice", "Alice Smith").await;
+            line_stream.send("JOIN #delay".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
             line_stream
-                .send("JOIN #exclusive".to_string())
+                .send("MODE #delay +D".to_string())
                 .await
                 .unwrap();
+            assert_eq!(
+                ":alice!~alice@127.0.0.1 MODE #delay +D".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
 
-            time::sleep(Duration::from_millis(70)).await;
-            {
-                let mut state = main_state.state.write().await;
-                let mut chmodes = &mut state.channels.get_mut("#exclusive").unwrap().modes;
-                chmodes.invite_only = true;
-                chmodes.invite_exception = Some(["ex*!*@*".to_string()].into());
-            }
+            let mut bob_stream = login_to_test_and_skip(port, "bob", "bob", "Bob Brown").await;
+            bob_stream.send("JOIN #delay".to_string()).await.unwrap();
+            assert_eq!(
+                ":bob!~bob@127.0.0.1 JOIN #delay".to_string(),
+                bob_stream.next().await.unwrap().unwrap()
+            );
+            assert!(equal_channel_names(
+                ":irc.irc 353 bob = #delay :",
+                &["~alice", "bob"],
+                &[&bob_stream.next().await.unwrap().unwrap()]
+            ));
+            bob_stream.next().await.unwrap().unwrap();
 
-            let mut henry_stream =
-                login_to_test_and_skip(port, "henry", "henryk", "Henri Stones").await;
-            let mut excel_stream =
-                login_to_test_and_skip(port, "excel", "excel", "Excel Total").await;
+            // bob is hidden until he sends message
+            line_stream.send("NAMES #delay".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 353 alice = #delay :~alice".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.next().await.unwrap().unwrap();
 
-            henry_stream
-                .send("JOIN #exclusive".to_string())
+            bob_stream
+                .send("PRIVMSG #delay :Hello".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 473 henry #exclusive :Cannot join channel (+i)".to_string(),
-                henry_stream.next().await.unwrap().unwrap()
+                ":bob!~bob@127.0.0.1 JOIN #delay".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
-            excel_stream
-                .send("JOIN #exclusive".to_string())
-                .await
-                .unwrap();
             assert_eq!(
-                ":excel!~excel@127.0.0.1 JOIN #exclusive".to_string(),
-                excel_stream.next().await.unwrap().unwrap()
+                ":bob!~bob@127.0.0.1 PRIVMSG #delay :Hello".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
+            assert!(main_state
+                .state
+                .read()
+                .await
+                .channels
+                .get("#delay")
+                .unwrap()
+                .delayed_users
+                .is_empty());
 
-            {
-                let mut state = main_state.state.write().await;
-                state.users.get_mut("henry").unwrap().invited_to =
-                    ["#exclusive".to_string()].into();
+            // part of hidden user is not shown
+            let mut carol_stream =
+                login_to_test_and_skip(port, "carol", "carol", "Carol White").await;
+            carol_stream.send("JOIN #delay".to_string()).await.unwrap();
+            for _ in 0..3 {
+                carol_stream.next().await.unwrap().unwrap();
             }
-            henry_stream
-                .send("JOIN #exclusive".to_string())
-                .await
-                .unwrap();
+            carol_stream.send("PART #delay".to_string()).await.unwrap();
             assert_eq!(
-                ":henry!~henryk@127.0.0.1 JOIN #exclusive".to_string(),
-                henry_stream.next().await.unwrap().unwrap()
+                ":carol!~carol@127.0.0.1 PART #delay".to_string(),
+                carol_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
         }
+
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_join_key_check() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
-        // key check
+    async fn test_command_join_registered_only() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![UserConfig {
+            name: "lucky".to_string(),
+            nick: "luckboy".to_string(),
+            password: None,
+            mask: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "garry", "garry", "Garry NextSomebody").await;
-            line_stream
-                .send("JOIN #protected".to_string())
-                .await
-                .unwrap();
-
-            time::sleep(Duration::from_millis(70)).await;
-            {
-                let mut state = main_state.state.write().await;
-                state.channels.get_mut("#protected").unwrap().modes.key =
-                    Some("longpassword!!".to_string());
+                login_to_test_and_skip(port, "luckboy", "lucky", "Lucky Boy").await;
+            line_stream.send("JOIN #club".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
             }
-
-            let mut jobe_stream = login_to_test_and_skip(port, "jobe", "jobe", "Jobe Smith").await;
+            line_stream.send("MODE #club +R".to_string()).await.unwrap();
+            assert_eq!(
+                ":luckboy!~lucky@127.0.0.1 MODE #club +R".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("MODE #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 324 luckboy #club +R +q luckboy +o luckboy".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.next().await.unwrap().unwrap(); // 329
+
+            let mut guest_stream =
+                login_to_test_and_skip(port, "guest", "guest", "Guest User").await;
+            guest_stream.send("JOIN #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 477 guest #club :Cannot join channel (+R) - you need to be \
+                    logged into your account"
+                    .to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("MODE #club -R".to_string()).await.unwrap();
+            assert_eq!(
+                ":luckboy!~lucky@127.0.0.1 MODE #club -R".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            guest_stream.send("JOIN #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":guest!~guest@127.0.0.1 JOIN #club".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_ban_forward() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "expert", "expertx", "SuperExpert").await;
+            line_stream
+                .send("JOIN #secrets,#lobby".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(70)).await;
+            {
+                let mut state = main_state.state.write().await;
+                let chmodes = &mut state.channels.get_mut("#secrets").unwrap().modes;
+                chmodes.ban = Some(["roland!*@*".to_string()].into());
+                chmodes.forward = Some("#lobby".to_string());
+            }
+
+            let mut roland_stream =
+                login_to_test_and_skip(port, "roland", "Roland", "Roland XX").await;
+            roland_stream
+                .send("JOIN #secrets".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc NOTICE roland :Cannot join to #secrets, forwarding to #lobby"
+                    .to_string(),
+                roland_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":roland!~Roland@127.0.0.1 JOIN #lobby".to_string(),
+                roland_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state
+                    .channels
+                    .get("#secrets")
+                    .unwrap()
+                    .users
+                    .contains_key("roland"));
+                assert!(state
+                    .channels
+                    .get("#lobby")
+                    .unwrap()
+                    .users
+                    .contains_key("roland"));
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_invite() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+        // invite
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "damian", "damian", "Damian Kozlowski").await;
+            line_stream
+                .send("JOIN #exclusive".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(70)).await;
+            {
+                let mut state = main_state.state.write().await;
+                let mut chmodes = &mut state.channels.get_mut("#exclusive").unwrap().modes;
+                chmodes.invite_only = true;
+                chmodes.invite_exception = Some(["ex*!*@*".to_string()].into());
+            }
+
+            let mut henry_stream =
+                login_to_test_and_skip(port, "henry", "henryk", "Henri Stones").await;
+            let mut excel_stream =
+                login_to_test_and_skip(port, "excel", "excel", "Excel Total").await;
+
+            henry_stream
+                .send("JOIN #exclusive".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 473 henry #exclusive :Cannot join channel (+i)".to_string(),
+                henry_stream.next().await.unwrap().unwrap()
+            );
+            excel_stream
+                .send("JOIN #exclusive".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":excel!~excel@127.0.0.1 JOIN #exclusive".to_string(),
+                excel_stream.next().await.unwrap().unwrap()
+            );
+
+            {
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("henry").unwrap().invited_to =
+                    [("#exclusive".to_string(), 0)].into();
+            }
+            henry_stream
+                .send("JOIN #exclusive".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":henry!~henryk@127.0.0.1 JOIN #exclusive".to_string(),
+                henry_stream.next().await.unwrap().unwrap()
+            );
+        }
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_invite_once_and_expire() {
+        let mut config = MainConfig::default();
+        config.invite_timeout = Some(2);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut owner_stream =
+                login_to_test_and_skip(port, "owner", "owner", "Owner Club").await;
+            owner_stream.send("JOIN #club".to_string()).await.unwrap();
+            for _ in 0..3 {
+                owner_stream.next().await.unwrap().unwrap();
+            }
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.channels.get_mut("#club").unwrap().modes.invite_only = true;
+            }
+
+            let mut guest_stream =
+                login_to_test_and_skip(port, "guest", "guest", "Guest Visitor").await;
+            owner_stream
+                .send("INVITE guest #club".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 341 owner guest #club".to_string(),
+                owner_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":owner!~owner@127.0.0.1 INVITE guest #club".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+            guest_stream.send("JOIN #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":guest!~guest@127.0.0.1 JOIN #club".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 0..2 {
+                guest_stream.next().await.unwrap().unwrap();
+            }
+            guest_stream.send("PART #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":guest!~guest@127.0.0.1 PART #club".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+            // invitation has been consumed by previous join
+            guest_stream.send("JOIN #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 473 guest #club :Cannot join channel (+i)".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+
+            // skip guest's JOIN and PART
+            for _ in 0..2 {
+                owner_stream.next().await.unwrap().unwrap();
+            }
+            owner_stream
+                .send("INVITE guest #club".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 341 owner guest #club".to_string(),
+                owner_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":owner!~owner@127.0.0.1 INVITE guest #club".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+            // unused invitation expires after timeout (times are in whole seconds)
+            time::sleep(Duration::from_millis(3100)).await;
+            guest_stream.send("JOIN #club".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 473 guest #club :Cannot join channel (+i)".to_string(),
+                guest_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_join_key_check() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+        // key check
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "garry", "garry", "Garry NextSomebody").await;
+            line_stream
+                .send("JOIN #protected".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(70)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.channels.get_mut("#protected").unwrap().modes.key =
+                    Some("longpassword!!".to_string());
+            }
+
+            let mut jobe_stream = login_to_test_and_skip(port, "jobe", "jobe", "Jobe Smith").await;
             jobe_stream
                 .send("JOIN #protected".to_string())
                 .await
@@ -1732,6 +2763,98 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_join_multiple_key_alignment() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "derek", "derek-z", "Derek Zinni").await;
+            line_stream
+                .send("JOIN #alpha,#beta,#gamma".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.channels.get_mut("#beta").unwrap().modes.key = Some("middle".to_string());
+            }
+
+            // only second key is given - first key is empty, third is missing.
+            let mut greg_stream =
+                login_to_test_and_skip(port, "greg", "gregory", "Gregory Powerful").await;
+            greg_stream
+                .send("JOIN #alpha,#beta,#gamma ,middle".to_string())
+                .await
+                .unwrap();
+            for chname in ["#alpha", "#beta", "#gamma"] {
+                assert_eq!(
+                    format!(":greg!~gregory@127.0.0.1 JOIN {}", chname),
+                    greg_stream.next().await.unwrap().unwrap()
+                );
+                for _ in 0..2 {
+                    greg_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            // no keys - middle channel rejected.
+            let mut mia_stream = login_to_test_and_skip(port, "mia", "mia", "Mia Smith").await;
+            mia_stream
+                .send("JOIN #alpha,#beta,#gamma".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 475 mia #beta :Cannot join channel (+k)".to_string(),
+                mia_stream.next().await.unwrap().unwrap()
+            );
+            for chname in ["#alpha", "#gamma"] {
+                assert_eq!(
+                    format!(":mia!~mia@127.0.0.1 JOIN {}", chname),
+                    mia_stream.next().await.unwrap().unwrap()
+                );
+                for _ in 0..2 {
+                    mia_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            // wrong key at middle position, extra key is ignored.
+            let mut tom_stream = login_to_test_and_skip(port, "tom", "tom", "Tom Smith").await;
+            tom_stream
+                .send("JOIN #alpha,#beta,#gamma middle,wrong,middle,middle".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 475 tom #beta :Cannot join channel (+k)".to_string(),
+                tom_stream.next().await.unwrap().unwrap()
+            );
+            for chname in ["#alpha", "#gamma"] {
+                assert_eq!(
+                    format!(":tom!~tom@127.0.0.1 JOIN {}", chname),
+                    tom_stream.next().await.unwrap().unwrap()
+                );
+                for _ in 0..2 {
+                    tom_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                let beta = state.channels.get("#beta").unwrap();
+                assert!(beta.users.contains_key("greg"));
+                assert!(!beta.users.contains_key("mia"));
+                assert!(!beta.users.contains_key("tom"));
+                let gamma = state.channels.get("#gamma").unwrap();
+                assert!(gamma.users.contains_key("greg"));
+                assert!(gamma.users.contains_key("mia"));
+                assert!(gamma.users.contains_key("tom"));
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_join_multiple_no_max_joins() {
         const MAX_JOINS: usize = 10;
@@ -2001,7 +3124,7 @@ mod test {
                 .unwrap();
             line_stream.send("CAP END".to_string()).await.unwrap();
 
-            for _ in 0..20 {
+            for _ in 0..21 {
                 line_stream.next().await.unwrap().unwrap();
             }
 
@@ -2155,25 +3278,159 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_part_preconfigured() {
+    async fn test_command_join_part_quiet_large_channel() {
         let mut config = MainConfig::default();
-        config.channels = Some(vec![ChannelConfig {
-            name: "#carrots".to_string(),
-            topic: None,
-            modes: ChannelModes::default(),
-        }]);
+        config.quiet_join_threshold = Some(3);
         let (main_state, handle, port) = run_test_server(config).await;
 
         {
-            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
-            line_stream
-                .send("JOIN #carrots,#apples".to_string())
-                .await
-                .unwrap();
+            let mut op_stream = login_to_test_and_skip(port, "oliver", "oliver", "Oliver").await;
+            op_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                op_stream.next().await.unwrap().unwrap();
+            }
+            let mut bob_stream = login_to_test_and_skip(port, "bob", "bob", "Bob").await;
+            bob_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                bob_stream.next().await.unwrap().unwrap();
+            }
+            op_stream.next().await.unwrap().unwrap();
 
-            line_stream
-                .send("PART #carrots,#apples".to_string())
-                .await
+            // below threshold - everyone gets JOIN
+            let mut carl_stream = login_to_test_and_skip(port, "carl", "carl", "Carl").await;
+            carl_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                carl_stream.next().await.unwrap().unwrap();
+            }
+            for line_stream in [&mut op_stream, &mut bob_stream] {
+                assert_eq!(
+                    ":carl!~carl@127.0.0.1 JOIN #big".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            // above threshold - only operators get JOIN and PART
+            let mut dave_stream = login_to_test_and_skip(port, "dave", "dave", "Dave").await;
+            dave_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                dave_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":dave!~dave@127.0.0.1 JOIN #big".to_string(),
+                op_stream.next().await.unwrap().unwrap()
+            );
+            dave_stream.send("PART #big".to_string()).await.unwrap();
+            assert_eq!(
+                ":dave!~dave@127.0.0.1 PART #big".to_string(),
+                dave_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":dave!~dave@127.0.0.1 PART #big".to_string(),
+                op_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            for line_stream in [&mut bob_stream, &mut carl_stream] {
+                line_stream.send("PING :x".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc PONG irc.irc :x".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            // hidden user is shown to others after first message
+            let mut eve_stream = login_to_test_and_skip(port, "eve", "eve", "Eve").await;
+            eve_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                eve_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":eve!~eve@127.0.0.1 JOIN #big".to_string(),
+                op_stream.next().await.unwrap().unwrap()
+            );
+            eve_stream
+                .send("PRIVMSG #big :Hi all".to_string())
+                .await
+                .unwrap();
+            for line_stream in [&mut bob_stream, &mut carl_stream] {
+                assert_eq!(
+                    ":eve!~eve@127.0.0.1 JOIN #big".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            for line_stream in [&mut op_stream, &mut bob_stream, &mut carl_stream] {
+                assert_eq!(
+                    ":eve!~eve@127.0.0.1 PRIVMSG #big :Hi all".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            eve_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            for line_stream in [&mut op_stream, &mut bob_stream, &mut carl_stream] {
+                assert_eq!(
+                    ":eve!~eve@127.0.0.1 QUIT :Quit: Bye".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            // quit of hidden user is sent only to operators
+            let mut fred_stream = login_to_test_and_skip(port, "fred", "fred", "Fred").await;
+            fred_stream.send("JOIN #big".to_string()).await.unwrap();
+            for _ in 0..3 {
+                fred_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":fred!~fred@127.0.0.1 JOIN #big".to_string(),
+                op_stream.next().await.unwrap().unwrap()
+            );
+            fred_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            assert_eq!(
+                ":fred!~fred@127.0.0.1 QUIT :Quit: Bye".to_string(),
+                op_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            for line_stream in [&mut bob_stream, &mut carl_stream] {
+                line_stream.send("PING :y".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc PONG irc.irc :y".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            // below threshold again
+            carl_stream.send("PART #big".to_string()).await.unwrap();
+            for line_stream in [&mut carl_stream, &mut op_stream, &mut bob_stream] {
+                assert_eq!(
+                    ":carl!~carl@127.0.0.1 PART #big".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_part_preconfigured() {
+        let mut config = MainConfig::default();
+        config.channels = Some(vec![ChannelConfig {
+            name: "#carrots".to_string(),
+            topic: None,
+            modes: ChannelModes::default(),
+            access: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            line_stream
+                .send("JOIN #carrots,#apples".to_string())
+                .await
+                .unwrap();
+
+            line_stream
+                .send("PART #carrots,#apples".to_string())
+                .await
                 .unwrap();
             time::sleep(Duration::from_millis(50)).await;
             {
@@ -2186,6 +3443,77 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_part_permanent() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            line_stream.send("JOIN #apples".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            // only operator can set permanent channel
+            line_stream
+                .send("MODE #apples +P".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 481 brian :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            let mut oper_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+            oper_stream.send("JOIN #carrots".to_string()).await.unwrap();
+            for _ in 0..3 {
+                oper_stream.next().await.unwrap().unwrap();
+            }
+            oper_stream
+                .send("MODE #carrots +P".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 MODE #carrots +P".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+            oper_stream
+                .send("TOPIC #carrots :About carrots".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+
+            line_stream.send("PART #apples".to_string()).await.unwrap();
+            oper_stream.send("PART #carrots".to_string()).await.unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.channels.contains_key("#apples"));
+                let chanobj = state.channels.get("#carrots").unwrap();
+                assert!(chanobj.users.is_empty());
+                assert!(chanobj.modes.permanent);
+                assert_eq!(
+                    "About carrots",
+                    chanobj.topic.as_ref().unwrap().topic.as_str()
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_part_multiple() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -2497,7 +3825,7 @@ mod test {
                 .send("JOIN #hifi,#techno,#trance".to_string())
                 .await
                 .unwrap();
-            for _ in 0..(4 * 3) {
+            for _ in 0..(5 * 3) {
                 line_stream2.next().await.unwrap().unwrap();
             }
             // skip JOIN messages for robbie
@@ -2579,6 +3907,72 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_topic_clear() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "maniac", "maniac", "SuperGeek").await;
+            line_stream.send("JOIN #cpus".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let mut newbie_stream =
+                login_to_test_and_skip(port, "newbie", "newbie0", "Computer's Newbie").await;
+            newbie_stream.send("JOIN #cpus".to_string()).await.unwrap();
+            for _ in 0..3 {
+                newbie_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap(); // newbie's JOIN
+
+            line_stream
+                .send("TOPIC #cpus :About processors".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            newbie_stream.next().await.unwrap().unwrap();
+
+            // query - topic will not be changed
+            newbie_stream.send("TOPIC #cpus".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 332 newbie #cpus :About processors".to_string(),
+                newbie_stream.next().await.unwrap().unwrap()
+            );
+            newbie_stream.next().await.unwrap().unwrap(); // 333
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                let topic = state.channels.get("#cpus").unwrap().topic.clone().unwrap();
+                assert_eq!("About processors", topic.topic);
+            }
+
+            // clear topic
+            line_stream.send("TOPIC #cpus :".to_string()).await.unwrap();
+            assert_eq!(
+                ":maniac!~maniac@127.0.0.1 TOPIC #cpus :".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":maniac!~maniac@127.0.0.1 TOPIC #cpus :".to_string(),
+                newbie_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert_eq!(None, state.channels.get("#cpus").unwrap().topic);
+            }
+
+            newbie_stream.send("TOPIC #cpus".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 331 newbie #cpus :No topic is set".to_string(),
+                newbie_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_topic_read() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -2633,7 +4027,7 @@ mod test {
                 newbie_stream.next().await.unwrap().unwrap()
             );
             newbie_stream.send("JOIN #cpus".to_string()).await.unwrap();
-            for _ in 0..4 {
+            for _ in 0..5 {
                 newbie_stream.next().await.unwrap().unwrap();
             }
             // after join
@@ -2934,7 +4328,7 @@ mod test {
             time::sleep(Duration::from_millis(100)).await;
 
             line_stream.send("NAMES".to_string()).await.unwrap();
-            assert_names_lists_all(&exp_names, &mut line_stream, 11, "maniac").await;
+            assert_names_lists_all(&exp_names, &mut line_stream, 6, "maniac").await;
 
             let mut exp_names_2 = HashMap::new();
             exp_names_2.insert("#cpus", exp_names.get("#cpus").unwrap().clone());
@@ -2944,19 +4338,19 @@ mod test {
                 .send("NAMES #cpus,#psus".to_string())
                 .await
                 .unwrap();
-            assert_names_lists_chanlist(&exp_names_2, &mut line_stream, 6, "maniac").await;
+            assert_names_lists_chanlist(&exp_names_2, &mut line_stream, 4, "maniac").await;
 
             line_stream
                 .send("NAMES #cpus,#xxxx,#psus".to_string())
                 .await
                 .unwrap();
-            assert_names_lists_chanlist(&exp_names_2, &mut line_stream, 7, "maniac").await;
+            assert_names_lists_chanlist(&exp_names_2, &mut line_stream, 5, "maniac").await;
 
             line_streams[0].send("NAMES".to_string()).await.unwrap();
             for _ in 0..48 {
                 line_streams[0].next().await.unwrap().unwrap();
             }
-            assert_names_lists_all(&exp_names, &mut line_streams[0], 11, "geek0").await;
+            assert_names_lists_all(&exp_names, &mut line_streams[0], 6, "geek0").await;
         }
 
         quit_test_server(main_state, handle).await;
@@ -2980,7 +4374,7 @@ mod test {
                 .unwrap();
             line_stream.send("CAP END".to_string()).await.unwrap();
 
-            for _ in 0..20 {
+            for _ in 0..21 {
                 line_stream.next().await.unwrap().unwrap();
             }
 
@@ -3253,6 +4647,47 @@ mod test {
         }) && touched.iter().all(|x| *x)
     }
 
+    #[tokio::test]
+    async fn test_command_names_long_nicks() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let nicks = (0..12)
+                .map(|i| format!("longnick{:02}{}", i, "x".repeat(110)))
+                .collect::<Vec<_>>();
+            let mut streams = vec![];
+            for nick in &nicks {
+                let mut line_stream = login_to_test_and_skip(port, nick, "long", "Long").await;
+                line_stream.send("JOIN #longs".to_string()).await.unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+                streams.push(line_stream);
+            }
+
+            let mut line_stream = login_to_test_and_skip(port, "shorty", "shorty", "Shorty").await;
+            line_stream.send("NAMES #longs".to_string()).await.unwrap();
+            let mut names = vec![];
+            let mut lines_count = 0;
+            loop {
+                let line = line_stream.next().await.unwrap().unwrap();
+                if line.starts_with(":irc.irc 366 ") {
+                    break;
+                }
+                // 512 bytes with CRLF
+                assert!(line.len() <= 510);
+                let rest = line.strip_prefix(":irc.irc 353 shorty = #longs :").unwrap();
+                names.extend(rest.split(' ').map(|n| n.trim_start_matches('~').to_string()));
+                lines_count += 1;
+            }
+            assert_eq!(4, lines_count);
+            names.sort();
+            assert_eq!(nicks, names);
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_list() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -3275,7 +4710,7 @@ mod test {
                 .send("JOIN #politics,#economics".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 * 2 + 1 {
+            for _ in 0..3 * 2 + 2 {
                 line_stream2.next().await.unwrap().unwrap();
             }
 
@@ -3432,7 +4867,7 @@ mod test {
                     .get("stan")
                     .unwrap()
                     .invited_to
-                    .contains("#funky"));
+                    .contains_key("#funky"));
             }
             assert_eq!(
                 ":seba!~sebastian@127.0.0.1 INVITE stan #funky".to_string(),
@@ -3450,7 +4885,7 @@ mod test {
                     .get("stan")
                     .unwrap()
                     .invited_to
-                    .contains("#funky"));
+                    .contains_key("#funky"));
                 assert!(state
                     .channels
                     .get("#funky")
@@ -3478,7 +4913,7 @@ mod test {
                     .get("stan")
                     .unwrap()
                     .invited_to
-                    .contains("#punky"));
+                    .contains_key("#punky"));
             }
             line_stream2.send("JOIN #punky".to_string()).await.unwrap();
             assert_eq!(
@@ -3493,7 +4928,7 @@ mod test {
                     .get("stan")
                     .unwrap()
                     .invited_to
-                    .contains("#punky"));
+                    .contains_key("#punky"));
                 assert!(state
                     .channels
                     .get("#punky")
@@ -3579,63 +5014,223 @@ mod test {
                 ":irc.irc 401 seba sunday :No such nick/channel".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            // user already on channel is not invited
+            line_stream
+                .send("INVITE stan #punky".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 443 seba stan #punky :is already on channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert!(!main_state
+                .state
+                .read()
+                .await
+                .users
+                .get("stan")
+                .unwrap()
+                .invited_to
+                .contains_key("#punky"));
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_kick() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_invite_oper() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "adam", "adam", "Adam Sandwich").await;
-            line_stream
-                .send("JOIN #impressions".to_string())
-                .await
-                .unwrap();
+                login_to_test_and_skip(port, "seba", "sebastian", "Sebastian Gross").await;
+            line_stream.send("JOIN #punky".to_string()).await.unwrap();
             for _ in 0..3 {
                 line_stream.next().await.unwrap().unwrap();
             }
-
-            let mut ben_stream =
-                login_to_test_and_skip(port, "ben", "benedict", "Benedict Tomato").await;
-            ben_stream
-                .send("JOIN #impressions".to_string())
+            line_stream
+                .send("MODE #punky +i".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                ben_stream.next().await.unwrap().unwrap();
-            }
+            line_stream.next().await.unwrap().unwrap();
 
-            let mut chris_stream =
-                login_to_test_and_skip(port, "chris", "christopher", "Christopher Lambda").await;
-            chris_stream
-                .send("JOIN #impressions".to_string())
+            let mut oper_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBum").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                chris_stream.next().await.unwrap().unwrap();
-            }
+            oper_stream.next().await.unwrap().unwrap();
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "sonny", "sonny9", "Sonny Sunshine").await;
 
-            let mut charlie_stream =
-                login_to_test_and_skip(port, "charlie", "charlie", "Charlie Pingy").await;
-            charlie_stream
-                .send("JOIN #impressions".to_string())
+            // operator can invite without being on channel
+            oper_stream
+                .send("INVITE sonny #punky".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                charlie_stream.next().await.unwrap().unwrap();
-            }
+            assert_eq!(
+                ":irc.irc 341 fanny sonny #punky".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 INVITE sonny #punky".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream2.send("JOIN #punky".to_string()).await.unwrap();
+            assert_eq!(
+                ":sonny!~sonny9@127.0.0.1 JOIN #punky".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
 
-            let mut david_stream =
-                login_to_test_and_skip(port, "david", "david", "David Storm").await;
-            david_stream
-                .send("JOIN #impressions".to_string())
-                .await
-                .unwrap();
-            for _ in 0..3 {
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_invite_free_invite() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "seba", "sebastian", "Sebastian Gross").await;
+            line_stream.send("JOIN #punky".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "stan", "stan", "Stan Straightforward").await;
+            line_stream2.send("JOIN #punky".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+
+            line_stream.next().await.unwrap().unwrap(); // skip JOIN stan
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                // set invite only and free invite for punky
+                let mut state = main_state.state.write().await;
+                let modes = &mut state.channels.get_mut("#punky").unwrap().modes;
+                modes.invite_only = true;
+                modes.free_invite = true;
+            }
+
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "sonny", "sonny9", "Sonny Sunshine").await;
+            time::sleep(Duration::from_millis(50)).await;
+            line_stream2
+                .send("INVITE sonny #punky".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 341 stan sonny #punky".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":stan!~stan@127.0.0.1 INVITE sonny #punky".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                assert!(main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("sonny")
+                    .unwrap()
+                    .invited_to
+                    .contains_key("#punky"));
+            }
+
+            {
+                // unset free invite - only operator can invite
+                main_state
+                    .state
+                    .write()
+                    .await
+                    .channels
+                    .get_mut("#punky")
+                    .unwrap()
+                    .modes
+                    .free_invite = false;
+            }
+            login_to_test_and_skip(port, "susan", "susan", "Susan Sunny").await;
+            time::sleep(Duration::from_millis(50)).await;
+            line_stream2
+                .send("INVITE susan #punky".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 482 stan #punky :You're not channel operator".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_kick() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "adam", "adam", "Adam Sandwich").await;
+            line_stream
+                .send("JOIN #impressions".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut ben_stream =
+                login_to_test_and_skip(port, "ben", "benedict", "Benedict Tomato").await;
+            ben_stream
+                .send("JOIN #impressions".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                ben_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut chris_stream =
+                login_to_test_and_skip(port, "chris", "christopher", "Christopher Lambda").await;
+            chris_stream
+                .send("JOIN #impressions".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                chris_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut charlie_stream =
+                login_to_test_and_skip(port, "charlie", "charlie", "Charlie Pingy").await;
+            charlie_stream
+                .send("JOIN #impressions".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                charlie_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut david_stream =
+                login_to_test_and_skip(port, "david", "david", "David Storm").await;
+            david_stream
+                .send("JOIN #impressions".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
                 david_stream.next().await.unwrap().unwrap();
             }
 
@@ -4092,4 +5687,571 @@ mod test {
 
         quit_test_server(main_state, handle).await;
     }
+
+    #[tokio::test]
+    async fn test_command_channel_targmax() {
+        let mut config = MainConfig::default();
+        config.targmax = Some(TargMaxConfig {
+            join: Some(2),
+            kick: Some(1),
+            part: Some(2),
+            ..TargMaxConfig::default()
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "adam", "adam", "Adam Sandwich").await;
+            line_stream
+                .send("JOIN #fruits,#vegetables,#meat".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 407 adam #meat :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("JOIN #fruits,#vegetables".to_string())
+                .await
+                .unwrap();
+            for _ in 0..6 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            line_stream
+                .send("KICK #fruits ben,chris".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 407 adam chris :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("PART #fruits,#vegetables,#meat".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 407 adam #meat :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("PART #fruits,#vegetables".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":adam!~adam@127.0.0.1 PART #fruits".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":adam!~adam@127.0.0.1 PART #vegetables".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_rename() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream
+                .send("CAP REQ :draft/channel-rename".to_string())
+                .await
+                .unwrap();
+            line_stream.send("NICK adam".to_string()).await.unwrap();
+            line_stream
+                .send("USER adam 8 * :Adam Sandwich".to_string())
+                .await
+                .unwrap();
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..21 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.send("JOIN #old".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut ben_stream =
+                login_to_test_and_skip(port, "ben", "benedict", "Benedict Tomato").await;
+            ben_stream.send("JOIN #old".to_string()).await.unwrap();
+            for _ in 0..3 {
+                ben_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                let channel = state.channels.get_mut("#old").unwrap();
+                channel.modes.protected_topic = true;
+                channel.add_voice("ben");
+            }
+
+            ben_stream
+                .send("RENAME #old #new :Better name".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 482 ben #old :You're not channel operator".to_string(),
+                ben_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("RENAME #old #new :Better name".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":adam!~adam@127.0.0.1 RENAME #old #new :Better name".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // fallback for client without capability
+            assert_eq!(
+                ":ben!~benedict@127.0.0.1 PART #old :Channel renamed: Better name".to_string(),
+                ben_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":ben!~benedict@127.0.0.1 JOIN #new".to_string(),
+                ben_stream.next().await.unwrap().unwrap()
+            );
+            let names = ben_stream.next().await.unwrap().unwrap();
+            assert!(names.starts_with(":irc.irc 353 ben = #new :"), "{}", names);
+            assert_eq!(
+                ":irc.irc 366 ben #new :End of /NAMES list".to_string(),
+                ben_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.channels.contains_key("#old"));
+                let channel = state.channels.get("#new").unwrap();
+                assert_eq!(
+                    HashSet::from(["adam".to_string(), "ben".to_string()]),
+                    HashSet::from_iter(channel.users.keys().cloned())
+                );
+                assert!(channel.modes.protected_topic);
+                assert!(channel.users.get("ben").unwrap().voice);
+                assert!(channel.users.get("adam").unwrap().operator);
+                for nick in ["adam", "ben"] {
+                    assert_eq!(
+                        HashSet::from(["#new".to_string()]),
+                        state.users.get(nick).unwrap().channels
+                    );
+                }
+            }
+
+            ben_stream.send("JOIN #other".to_string()).await.unwrap();
+            line_stream
+                .send("RENAME #new #other".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RENAME CHANNEL_NAME_IN_USE #new #other :Channel name is \
+                    already taken"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_rename_not_allowed() {
+        let mut config = MainConfig::default();
+        config.reserved_channels = Some(vec!["#staff*".to_string()]);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "adam", "adam", "Adam").await;
+            line_stream.send("JOIN #lobby".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream
+                .send("RENAME #lobby #staffroom".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RENAME CANNOT_RENAME #lobby #staffroom :Channel name is \
+                    not allowed"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            {
+                let state = main_state.state.read().await;
+                assert!(state.channels.contains_key("#lobby"));
+                assert!(!state.channels.contains_key("#staffroom"));
+            }
+
+            // IRC operator can rename to reserved name
+            let mut oper_stream = login_to_test_and_skip(port, "fanny", "fanny", "Fanny").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+            oper_stream
+                .send("RENAME #lobby #staffroom :Staff only".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":adam!~adam@127.0.0.1 PART #lobby :Channel renamed: Staff only".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(main_state
+                .state
+                .read()
+                .await
+                .channels
+                .contains_key("#staffroom"));
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_clearchan() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut alice_stream =
+                login_to_test_and_skip(port, "alice", "alice", "Alice Wonder").await;
+            alice_stream.send("JOIN #raid".to_string()).await.unwrap();
+            for _ in 0..3 {
+                alice_stream.next().await.unwrap().unwrap();
+            }
+            let mut bob_stream = login_to_test_and_skip(port, "bob", "bobby", "Bob Spammer").await;
+            bob_stream.send("JOIN #raid".to_string()).await.unwrap();
+            for _ in 0..3 {
+                bob_stream.next().await.unwrap().unwrap();
+            }
+            let mut carl_stream =
+                login_to_test_and_skip(port, "carl", "carl", "Carl Spammer").await;
+            carl_stream.send("JOIN #raid".to_string()).await.unwrap();
+            for _ in 0..3 {
+                carl_stream.next().await.unwrap().unwrap();
+            }
+            // skip JOINs of bob and carl
+            for _ in 0..2 {
+                alice_stream.next().await.unwrap().unwrap();
+            }
+            bob_stream.next().await.unwrap().unwrap();
+
+            bob_stream.send("CLEARCHAN #raid".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 bob :Permission Denied- You're not an IRC operator".to_string(),
+                bob_stream.next().await.unwrap().unwrap()
+            );
+
+            let mut oper_stream = login_to_test_and_skip(port, "fanny", "fanny", "Fanny").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+
+            oper_stream
+                .send("CLEARCHAN #raid LOCK".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc NOTICE fanny :CLEARCHAN #raid by fanny: 2 users kicked, \
+                    modes +im set"
+                    .to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+            let mut alice_lines = HashSet::new();
+            for _ in 0..2 {
+                alice_lines.insert(alice_stream.next().await.unwrap().unwrap());
+            }
+            assert_eq!(
+                HashSet::from([
+                    ":fanny!~fanny@127.0.0.1 KICK #raid bob :Channel has been cleared"
+                        .to_string(),
+                    ":fanny!~fanny@127.0.0.1 KICK #raid carl :Channel has been cleared"
+                        .to_string(),
+                ]),
+                alice_lines
+            );
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 MODE #raid +im".to_string(),
+                alice_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                let channel = state.channels.get("#raid").unwrap();
+                assert_eq!(
+                    HashSet::from(["alice".to_string()]),
+                    channel.users.keys().cloned().collect::<HashSet<_>>()
+                );
+                assert!(channel.modes.invite_only);
+                assert!(channel.modes.moderated);
+                assert!(!state.users.get("bob").unwrap().channels.contains("#raid"));
+            }
+
+            // kick also channel operators
+            oper_stream
+                .send("CLEARCHAN #raid ALL".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc NOTICE fanny :CLEARCHAN #raid by fanny: 1 users kicked".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 KICK #raid alice :Channel has been cleared".to_string(),
+                alice_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.channels.contains_key("#raid"));
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_access() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "charlie", "charlie", "Charlie Brown").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "eddix", "eddie", "Eddie Flower").await;
+            line_stream.send("JOIN #fruits".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            // only channel operator can manage access list
+            line_stream2
+                .send("ACCESS #fruits ADD *!~eddie@* op".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 482 eddix #fruits :You're not channel operator".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            for cmd in [
+                "ACCESS #fruits ADD *!~eddie@* op",
+                "ACCESS #fruits ADD $a:nobody voice",
+                "ACCESS #fruits DEL *!*@nowhere",
+                "ACCESS #fruits LIST",
+            ] {
+                line_stream.send(cmd.to_string()).await.unwrap();
+            }
+            for expected in [
+                ":irc.irc FAIL ACCESS NO_SUCH_ENTRY #fruits *!*@nowhere :No such entry in \
+                access list",
+                ":irc.irc NOTICE charlie :#fruits $a:nobody voice",
+                ":irc.irc NOTICE charlie :#fruits *!~eddie@* op",
+                ":irc.irc NOTICE charlie :End of #fruits access list",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+
+            // matching user gets operator status on join
+            line_stream2.send("JOIN #fruits".to_string()).await.unwrap();
+            assert_eq!(
+                ":eddix!~eddie@127.0.0.1 JOIN #fruits".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert!(equal_channel_names(
+                ":irc.irc 353 eddix = #fruits :",
+                &["~charlie", "@eddix"],
+                &[&line_stream2.next().await.unwrap().unwrap()]
+            ));
+            assert_eq!(
+                ":irc.irc 366 eddix #fruits :End of /NAMES list".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc MODE #fruits +o eddix".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            for expected in [
+                ":eddix!~eddie@127.0.0.1 JOIN #fruits",
+                ":irc.irc MODE #fruits +o eddix",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+            {
+                let state = main_state.state.read().await;
+                let chanobj = state.channels.get("#fruits").unwrap();
+                assert!(chanobj.users.get("eddix").unwrap().operator);
+                assert!(chanobj.modes.operators.as_ref().unwrap().contains("eddix"));
+            }
+
+            // removed entry is not applied anymore
+            line_stream
+                .send("ACCESS #fruits DEL *!~eddie@*".to_string())
+                .await
+                .unwrap();
+            line_stream.send("PING :y".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :y".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream2.send("PART #fruits".to_string()).await.unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+            line_stream2.send("JOIN #fruits".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            {
+                let state = main_state.state.read().await;
+                let chanobj = state.channels.get("#fruits").unwrap();
+                assert!(!chanobj.users.get("eddix").unwrap().operator);
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_access_founder_transfer() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![
+            UserConfig {
+                name: "roland".to_string(),
+                nick: "roland".to_string(),
+                password: None,
+                mask: None,
+            },
+            UserConfig {
+                name: "harry".to_string(),
+                nick: "harry".to_string(),
+                password: None,
+                mask: None,
+            },
+        ]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "roland", "roland", "Roland Founder").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Successor").await;
+            line_stream.send("JOIN #club".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            // founder adds successor by account
+            line_stream
+                .send("ACCESS #club ADD $a:harry founder".to_string())
+                .await
+                .unwrap();
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream2.send("JOIN #club".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc MODE #club +q harry".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            for expected in [
+                ":harry!~harry@127.0.0.1 JOIN #club",
+                ":irc.irc MODE #club +q harry",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+
+            // old founder gives up founder status
+            line_stream.send("MODE #club -q roland".to_string()).await.unwrap();
+            assert_eq!(
+                ":roland!~roland@127.0.0.1 MODE #club -q roland".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":roland!~roland@127.0.0.1 MODE #club -q roland".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("ACCESS #club ADD $a:roland founder".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 482 roland #club :You're not channel operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // new founder privileges: founder entries and account bans
+            line_stream2
+                .send("MODE #club +b $a:roland".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":harry!~harry@127.0.0.1 MODE #club +b $a:roland".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":harry!~harry@127.0.0.1 MODE #club +b $a:roland".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("PRIVMSG #club :Hello!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 404 roland #club :Cannot send to channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream2
+                .send("ACCESS #club DEL $a:harry".to_string())
+                .await
+                .unwrap();
+            line_stream2.send("ACCESS #club LIST".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc NOTICE harry :End of #club access list".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            {
+                let state = main_state.state.read().await;
+                let chanobj = state.channels.get("#club").unwrap();
+                assert!(chanobj.users.get("harry").unwrap().founder);
+                assert!(!chanobj.users.get("roland").unwrap().founder);
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
 }
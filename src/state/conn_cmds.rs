@@ -20,14 +20,20 @@
 use super::*;
 use std::error::Error;
 use std::ops::DerefMut;
-use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// all channel modes - must contain CHANMODE_FREE_INVITE.
+static CHANMODES: &str = "DIPRabcefghiklmnopqstv";
+// maximal nickname and username length in characters.
+const NICKLEN: usize = 200;
+const USERLEN: usize = 200;
 
 struct SupportTokenIntValue {
     name: &'static str,
     value: usize,
 }
 
-static SUPPORT_TOKEN_INT_VALUE: [SupportTokenIntValue; 13] = [
+static SUPPORT_TOKEN_INT_VALUE: [SupportTokenIntValue; 12] = [
     SupportTokenIntValue {
         name: "AWAYLEN",
         value: 1000,
@@ -48,13 +54,9 @@ static SUPPORT_TOKEN_INT_VALUE: [SupportTokenIntValue; 13] = [
         name: "KICKLEN",
         value: 1000,
     },
-    SupportTokenIntValue {
-        name: "LINELEN",
-        value: 2000,
-    },
     SupportTokenIntValue {
         name: "MAXNICKLEN",
-        value: 200,
+        value: NICKLEN,
     },
     SupportTokenIntValue {
         name: "MAXPARA",
@@ -70,7 +72,7 @@ static SUPPORT_TOKEN_INT_VALUE: [SupportTokenIntValue; 13] = [
     },
     SupportTokenIntValue {
         name: "NICKLEN",
-        value: 200,
+        value: NICKLEN,
     },
     SupportTokenIntValue {
         name: "TOPICLEN",
@@ -78,7 +80,7 @@ static SUPPORT_TOKEN_INT_VALUE: [SupportTokenIntValue; 13] = [
     },
     SupportTokenIntValue {
         name: "USERLEN",
-        value: 200,
+        value: USERLEN,
     },
 ];
 
@@ -103,11 +105,11 @@ static SUPPORT_TOKEN_STRING_VALUE: [SupportTokenStringValue; 9] = [
     },
     SupportTokenStringValue {
         name: "CHANMODES",
-        value: "Iabehiklmnopqstv",
+        value: CHANMODES,
     },
     SupportTokenStringValue {
         name: "CHANTYPES",
-        value: "&#",
+        value: CHANTYPES,
     },
     SupportTokenStringValue {
         name: "EXCEPTS",
@@ -131,7 +133,7 @@ static SUPPORT_TOKEN_STRING_VALUE: [SupportTokenStringValue; 9] = [
     },
     SupportTokenStringValue {
         name: "USERMODES",
-        value: "Oiorw",
+        value: "BHOgiorw",
     },
 ];
 
@@ -144,7 +146,13 @@ impl ToString for SupportTokenStringValue {
     }
 }
 
-static SUPPORT_TOKEN_BOOL_VALUE: [&str; 2] = ["FNC", "SAFELIST"];
+static SUPPORT_TOKEN_BOOL_VALUE: [&str; 4] = ["CNOTICE", "CPRIVMSG", "FNC", "SAFELIST"];
+
+// server name reported by listener or configured server name. it takes only field
+// to allow borrowing connection stream at same time.
+fn listener_servername<'a>(servername: &'a Option<String>, config_name: &'a str) -> &'a str {
+    servername.as_deref().unwrap_or(config_name)
+}
 
 impl super::MainState {
     pub(super) async fn process_cap<'a>(
@@ -157,8 +165,26 @@ impl super::MainState {
         match subcommand {
             CapCommand::LS => {
                 conn_state.caps_negotation = true;
-                self.feed_msg(&mut conn_state.stream, "CAP * LS :multi-prefix")
-                    .await
+                let mut caps = vec![
+                    "multi-prefix",
+                    CAP_CHANNEL_RENAME,
+                    CAP_EXTENDED_MONITOR,
+                    CAP_PRE_AWAY,
+                ];
+                // SASL is possible only if users or certificate fingerprints are configured.
+                if self.sasl_available() {
+                    caps.push(CAP_SASL);
+                }
+                if self.config.resume_timeout.is_some() {
+                    caps.push(CAP_RESUME);
+                }
+                // skip capabilities disabled by listener.
+                caps.retain(|c| !conn_state.disabled_caps.contains(*c));
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!("CAP * LS :{}", caps.join(" ")),
+                )
+                .await
             }
             CapCommand::LIST => {
                 self.feed_msg(
@@ -172,9 +198,20 @@ impl super::MainState {
                 if let Some(ref cs) = caps {
                     info!("CAPS REQ for {}: {:?}", conn_state.user_state.source, caps);
                     let mut new_caps = conn_state.caps;
-                    // accept if all capabilities matches
-                    if cs.iter().all(|c| new_caps.apply_cap(c)) {
+                    // accept if all capabilities matches. resume and sasl only if enabled.
+                    // capabilities disabled by listener are not accepted.
+                    if cs.iter().all(|c| {
+                        (*c != CAP_RESUME || self.config.resume_timeout.is_some())
+                            && (*c != CAP_SASL || self.sasl_available())
+                            && !conn_state.disabled_caps.contains(*c)
+                            && new_caps.apply_cap(c)
+                    }) {
                         conn_state.caps = new_caps;
+                        if conn_state.user_state.authenticated {
+                            let mut state = self.state.write().await;
+                            let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+                            state.users.get_mut(user_nick).unwrap().caps = new_caps;
+                        }
                         self.feed_msg(
                             &mut conn_state.stream,
                             format!("CAP * ACK :{}", cs.join(" ")),
@@ -195,7 +232,18 @@ impl super::MainState {
             CapCommand::END => {
                 conn_state.caps_negotation = false;
                 if !conn_state.user_state.authenticated {
-                    self.authenticate(conn_state).await?;
+                    if conn_state.caps.sasl && conn_state.sasl_account.is_none() {
+                        // client requested SASL, but it did not log in.
+                        info!("SASL not completed by {}", conn_state.user_state.source);
+                        conn_state.sasl_mechanism = None;
+                        let client = conn_state.user_state.client_name();
+                        self.feed_msg(&mut conn_state.stream, ErrSaslFail904 { client })
+                            .await?;
+                        self.close_connection(conn_state, "SASL authentication required")
+                            .await?;
+                    } else {
+                        self.authenticate(conn_state).await?;
+                    }
                 }
                 Ok(())
             }
@@ -211,10 +259,34 @@ impl super::MainState {
         let client = conn_state.user_state.client_name();
         // support tokens
         let mut tokens = vec![format!("NETWORK={}", self.config.network)];
+        // the same limit is used by codec of connection.
+        tokens.push(format!(
+            "LINELEN={}",
+            self.config.max_line_length.unwrap_or(DEFAULT_LINELEN)
+        ));
         if let Some(max_joins) = self.config.max_joins {
             tokens.push(format!("CHANLIMIT=&#:{}", max_joins));
             tokens.push(format!("MAXCHANNELS={}", max_joins));
         }
+        if let Some(max_monitor) = self.config.max_monitor {
+            tokens.push(format!("MONITOR={}", max_monitor));
+            tokens.push(format!("WATCH={}", max_monitor));
+        } else {
+            tokens.push("MONITOR".to_string());
+            tokens.push("WATCH".to_string());
+        }
+        if let Some(targmax) = self.config.targmax {
+            // empty limit means no limit for that command.
+            let limits = targmax
+                .limits()
+                .iter()
+                .map(|(name, limit)| match limit {
+                    Some(limit) => format!("{}:{}", name, limit),
+                    None => format!("{}:", name),
+                })
+                .collect::<Vec<_>>();
+            tokens.push(format!("TARGMAX={}", limits.join(",")));
+        }
         SUPPORT_TOKEN_STRING_VALUE.iter().for_each(|t| {
             tokens.push(t.to_string());
         });
@@ -227,9 +299,13 @@ impl super::MainState {
 
         tokens.sort();
 
-        for toks in tokens.chunks(10) {
-            self.feed_msg(
+        let servername = listener_servername(&conn_state.servername, &self.config.name);
+        // at most 13 tokens - message can have 15 parameters with client and
+        // trailing text.
+        for toks in tokens.chunks(13) {
+            self.feed_msg_source(
                 &mut conn_state.stream,
+                servername,
                 RplISupport005 {
                     client,
                     tokens: &toks.join(" "),
@@ -249,7 +325,11 @@ impl super::MainState {
                 // nick must be defined
                 if user_state.nick.is_some() {
                     // username must be defined
-                    if let Some(ref name) = user_state.name {
+                    if user_state.name.is_some() && conn_state.sasl_account.is_some() {
+                        // account has been verified by SASL authentication.
+                        user_state.authenticated = true;
+                        (Some(true), true)
+                    } else if let Some(ref name) = user_state.name {
                         let mut registered = false;
                         // get password option
                         let password_opt = if let Some(uidx) = self.user_config_idxs.get(name) {
@@ -314,21 +394,60 @@ impl super::MainState {
         if let Some(good) = auth_opt {
             if good {
                 let user_nick = conn_state.user_state.nick.clone().unwrap();
-                let user_modes = {
+                conn_state.user_state.registered = registered;
+                if self.reloadable.read().await.banned(&conn_state.user_state.source) {
+                    info!("Banned user {} rejected", conn_state.user_state.source);
+                    conn_state.user_state.authenticated = false;
+                    let client = conn_state.user_state.client_name();
+                    self.feed_msg(&mut conn_state.stream, ErrYoureBannedCreep465 { client })
+                        .await?;
+                    self.close_connection(
+                        conn_state,
+                        "Banned from server, connection will be closed.",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                if self.nick_reserved(&conn_state.user_state, &user_nick).await {
+                    // user must choose other nick
+                    conn_state.user_state.authenticated = false;
+                    let client = conn_state.user_state.client_name();
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        ErrErroneusNickname432 {
+                            client,
+                            nick: &user_nick,
+                        },
+                    )
+                    .await?;
+                    return Ok(());
+                }
+                let country = self.geoip_country(conn_state.user_state.ip_addr);
+                let (user_modes, away) = {
                     // add new user to hash map
-                    let user_state = &mut conn_state.user_state;
-                    user_state.registered = registered;
+                    let user_state = &conn_state.user_state;
                     let mut state = self.state.write().await;
-                    let user = User::new(
+                    let mut user = User::new(
                         &self.config,
                         user_state,
                         conn_state.sender.take().unwrap(),
                         conn_state.quit_sender.take().unwrap(),
+                        conn_state.stream.stats(),
                     );
+                    user.caps = conn_state.caps;
+                    user.uid = conn_state.uid.clone();
+                    if conn_state.sasl_account.is_some() {
+                        user.account = conn_state.sasl_account.clone();
+                    }
+                    user.away = conn_state.pre_away.take();
+                    user.country = country.clone();
                     let umode_str = user.modes.to_string();
                     if !state.users.contains_key(&user_nick) {
                         state.add_user(&user_nick, user);
-                        umode_str
+                        // away can be set before registration or restored from previous
+                        // session of account.
+                        let away = state.users.get(&user_nick).unwrap().away.is_some();
+                        (umode_str, away)
                     } else {
                         // if nick already used
                         let client = conn_state.user_state.client_name();
@@ -344,13 +463,17 @@ impl super::MainState {
                     }
                 };
 
+                conn_state.registration_time = time::Instant::now();
                 {
                     // send message to user: welcome,....
                     let user_state = &conn_state.user_state;
                     let client = user_state.client_name();
+                    // listener can report own server name.
+                    let servername = listener_servername(&conn_state.servername, &self.config.name);
                     // welcome
-                    self.feed_msg(
+                    self.feed_msg_source(
                         &mut conn_state.stream,
+                        servername,
                         RplWelcome001 {
                             client,
                             networkname: &self.config.network,
@@ -360,11 +483,12 @@ impl super::MainState {
                         },
                     )
                     .await?;
-                    self.feed_msg(
+                    self.feed_msg_source(
                         &mut conn_state.stream,
+                        servername,
                         RplYourHost002 {
                             client,
-                            servername: &self.config.name,
+                            servername,
                             version: concat!(
                                 env!("CARGO_PKG_NAME"),
                                 "-",
@@ -373,32 +497,47 @@ impl super::MainState {
                         },
                     )
                     .await?;
-                    self.feed_msg(
+                    self.feed_msg_source(
                         &mut conn_state.stream,
+                        servername,
                         RplCreated003 {
                             client,
                             datetime: &self.created,
                         },
                     )
                     .await?;
-                    self.feed_msg(
+                    self.feed_msg_source(
                         &mut conn_state.stream,
+                        servername,
                         RplMyInfo004 {
                             client,
-                            servername: &self.config.name,
+                            servername,
                             version: concat!(
                                 env!("CARGO_PKG_NAME"),
                                 "-",
                                 env!("CARGO_PKG_VERSION")
                             ),
-                            avail_user_modes: "Oiorw",
-                            avail_chmodes: "Iabehiklmnopqstv",
+                            avail_user_modes: "BHOgiorw",
+                            avail_chmodes: CHANMODES,
                             avail_chmodes_with_params: None,
                         },
                     )
                     .await?;
 
                     self.send_isupport(conn_state).await?;
+
+                    // unique ID of connection
+                    let client = conn_state.user_state.client_name();
+                    let servername = listener_servername(&conn_state.servername, &self.config.name);
+                    self.feed_msg_source(
+                        &mut conn_state.stream,
+                        servername,
+                        RplYourId042 {
+                            client,
+                            id: &conn_state.uid,
+                        },
+                    )
+                    .await?;
                 }
 
                 // send messages from LUSERS and MOTD
@@ -407,44 +546,221 @@ impl super::MainState {
 
                 // send mode reply
                 let client = conn_state.user_state.client_name();
-                self.feed_msg(
+                let servername = listener_servername(&conn_state.servername, &self.config.name);
+                self.feed_msg_source(
                     &mut conn_state.stream,
+                    servername,
                     RplUModeIs221 {
                         client,
                         user_modes: &user_modes,
                     },
                 )
                 .await?;
+                if away {
+                    self.feed_msg(&mut conn_state.stream, RplNowAway306 { client })
+                        .await?;
+                }
+
+                if conn_state.caps.resume {
+                    self.send_resume_token(conn_state).await?;
+                }
 
                 // run ping waker for this connection
                 conn_state.run_ping_waker(&self.config);
-                info!("Auth succeed for {}", conn_state.user_state.source);
+                if let Some(country) = country {
+                    info!(
+                        "Auth succeed for {} with ID {} from country {}",
+                        conn_state.user_state.source, conn_state.uid, country
+                    );
+                } else {
+                    info!(
+                        "Auth succeed for {} with ID {}",
+                        conn_state.user_state.source, conn_state.uid
+                    );
+                }
             } else {
                 // if authentication failed
                 info!("Auth failed for {}", conn_state.user_state.source);
                 let client = conn_state.user_state.client_name();
-                conn_state.quit.store(1, Ordering::SeqCst);
                 self.feed_msg(&mut conn_state.stream, ErrPasswdMismatch464 { client })
                     .await?;
+                self.close_connection(
+                    conn_state,
+                    "Password incorrect, connection will be closed.",
+                )
+                .await?;
             }
         }
         Ok(())
     }
 
-    pub(super) async fn process_authenticate(
+    fn sasl_available(&self) -> bool {
+        self.config.users.is_some() || self.config.cert_fingerprints.is_some()
+    }
+
+    // verify SASL PLAIN payload (authzid, authcid and password separated by NUL)
+    // against configured users. Returns account name if authentication succeeded.
+    async fn sasl_plain_account(&self, user_state: &ConnUserState, data: &str) -> Option<String> {
+        let payload = String::from_utf8(decode_base64(data)?).ok()?;
+        let mut parts = payload.split('\0');
+        let (authzid, authcid, password) = (parts.next()?, parts.next()?, parts.next()?);
+        // authorization identity must be empty or the same as authentication identity.
+        if parts.next().is_some() || (!authzid.is_empty() && authzid != authcid) {
+            return None;
+        }
+        let user = &self.config.users.as_ref()?[*self.user_config_idxs.get(authcid)?];
+        if let Some(ref mask) = user.mask {
+            if !match_wildcard(mask, &user_state.source) {
+                return None;
+            }
+        }
+        // user without password can not log in by SASL.
+        let hash = user.password.clone()?;
+        argon2_verify_password_async(password.to_string(), hash)
+            .await
+            .ok()?;
+        Some(authcid.to_string())
+    }
+
+    // verify SASL EXTERNAL: fingerprint of client certificate must be mapped to account
+    // in config. Payload is authorization identity ("+" if empty).
+    fn sasl_external_account(&self, fingerprint: Option<String>, data: &str) -> Option<String> {
+        let fingerprint = fingerprint?;
+        let authzid = if data == "+" {
+            String::new()
+        } else {
+            String::from_utf8(decode_base64(data)?).ok()?
+        };
+        // fingerprints can be written in config with colons and in upper case.
+        let account = self
+            .config
+            .cert_fingerprints
+            .as_ref()?
+            .iter()
+            .find_map(|(fp, acc)| {
+                if fp.replace(':', "").eq_ignore_ascii_case(&fingerprint) {
+                    Some(acc)
+                } else {
+                    None
+                }
+            })?;
+        // authorization identity must be empty or the same as account.
+        if !authzid.is_empty() && authzid != *account {
+            return None;
+        }
+        Some(account.clone())
+    }
+
+    pub(super) async fn process_authenticate<'a>(
         &self,
         conn_state: &mut ConnState,
+        data: &'a str,
     ) -> Result<(), Box<dyn Error>> {
         let client = conn_state.user_state.client_name();
 
-        self.feed_msg(
-            &mut conn_state.stream,
-            ErrUnknownCommand421 {
-                client,
-                command: "AUTHENTICATE",
-            },
-        )
-        .await?;
+        if !conn_state.caps.sasl {
+            // SASL must be requested by CAP REQ.
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrUnknownCommand421 {
+                    client,
+                    command: "AUTHENTICATE",
+                },
+            )
+            .await?;
+        } else if conn_state.user_state.authenticated || conn_state.sasl_account.is_some() {
+            self.feed_msg(&mut conn_state.stream, ErrSaslAlready907 { client })
+                .await?;
+        } else if data == "*" {
+            conn_state.sasl_mechanism = None;
+            self.feed_msg(&mut conn_state.stream, ErrSaslAborted906 { client })
+                .await?;
+        } else if conn_state.sasl_mechanism.is_none() {
+            let mechanism = if data.eq_ignore_ascii_case("PLAIN") {
+                Some(SaslMechanism::Plain)
+            } else if data.eq_ignore_ascii_case("EXTERNAL") {
+                Some(SaslMechanism::External)
+            } else {
+                None
+            };
+            if mechanism == Some(SaslMechanism::External) && conn_state.cert_fingerprint().is_none()
+            {
+                // EXTERNAL needs client certificate - not available for plaintext connection.
+                info!(
+                    "SASL EXTERNAL without certificate by {}",
+                    conn_state.user_state.source
+                );
+                self.feed_msg(&mut conn_state.stream, ErrSaslFail904 { client })
+                    .await?;
+            } else if mechanism.is_some() {
+                conn_state.sasl_mechanism = mechanism;
+                self.feed_msg(&mut conn_state.stream, "AUTHENTICATE +")
+                    .await?;
+            } else {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    RplSaslMechs908 {
+                        client,
+                        mechanisms: "EXTERNAL,PLAIN",
+                    },
+                )
+                .await?;
+                self.feed_msg(&mut conn_state.stream, ErrSaslFail904 { client })
+                    .await?;
+            }
+        } else if data.len() >= 400 {
+            // payload split into many messages is not supported.
+            conn_state.sasl_mechanism = None;
+            self.feed_msg(&mut conn_state.stream, ErrSaslTooLong905 { client })
+                .await?;
+        } else {
+            let mechanism = conn_state.sasl_mechanism.take();
+            let fingerprint = conn_state.cert_fingerprint();
+            let user_state = &conn_state.user_state;
+            let account_opt = match mechanism {
+                Some(SaslMechanism::External) => self.sasl_external_account(fingerprint, data),
+                _ => self.sasl_plain_account(user_state, data).await,
+            };
+            // number of sessions logged in to account can be limited.
+            let sessions_exceeded = match (&account_opt, self.config.max_account_sessions) {
+                (Some(account), Some(max_sessions)) => {
+                    let state = self.state.read().await;
+                    let sessions = state
+                        .users
+                        .values()
+                        .filter(|u| u.account.as_ref() == Some(account))
+                        .count();
+                    sessions >= max_sessions
+                }
+                _ => false,
+            };
+            if sessions_exceeded {
+                info!("Too many sessions of account for {}", user_state.source);
+                self.feed_msg(&mut conn_state.stream, ErrSaslFail904 { client })
+                    .await?;
+            } else if let Some(account) = account_opt {
+                info!("SASL authentication of {} as {}", user_state.source, account);
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    RplLoggedIn900 {
+                        client,
+                        nick: user_state.nick.as_deref().unwrap_or("*"),
+                        user: user_state.name.as_deref().unwrap_or("*"),
+                        host: &user_state.hostname,
+                        account: &account,
+                        username: &account,
+                    },
+                )
+                .await?;
+                self.feed_msg(&mut conn_state.stream, RplSaslSuccess903 { client })
+                    .await?;
+                conn_state.sasl_account = Some(account);
+            } else {
+                info!("SASL authentication failed for {}", user_state.source);
+                self.feed_msg(&mut conn_state.stream, ErrSaslFail904 { client })
+                    .await?;
+            }
+        }
         Ok(())
     }
 
@@ -471,6 +787,13 @@ impl super::MainState {
         nick: &'a str,
         msg: &'a Message<'a>,
     ) -> Result<(), Box<dyn Error>> {
+        if nick.chars().count() > NICKLEN {
+            // too long nick - client can send other nick.
+            let client = conn_state.user_state.client_name();
+            self.feed_msg(&mut conn_state.stream, ErrErroneusNickname432 { client, nick })
+                .await?;
+            return Ok(());
+        }
         if !conn_state.user_state.authenticated {
             if !self.state.read().await.users.contains_key(nick) {
                 conn_state.user_state.set_nick(nick.to_string());
@@ -487,8 +810,13 @@ impl super::MainState {
             let old_nick = conn_state.user_state.nick.as_ref().unwrap().to_string();
             if nick != old_nick {
                 let nick_str = nick.to_string();
-                // if new nick is not used by other
-                if !state.users.contains_key(&nick_str) {
+                let oper = state.users.get(&old_nick).unwrap().modes.is_local_oper();
+                if !oper && self.nick_reserved(&conn_state.user_state, nick).await {
+                    let client = conn_state.user_state.client_name();
+                    self.feed_msg(&mut conn_state.stream, ErrErroneusNickname432 { client, nick })
+                        .await?;
+                } else if !state.users.contains_key(&nick_str) {
+                    // if new nick is not used by other
                     let old_source = conn_state.user_state.source.clone();
                     let mut user = state.users.remove(&old_nick).unwrap();
                     conn_state.user_state.set_nick(nick_str.clone());
@@ -501,7 +829,11 @@ impl super::MainState {
                             .rename_user(&old_nick, nick_str.clone());
                     }
                     // add nick history
-                    state.insert_to_nick_history(&old_nick, user.history_entry.clone());
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    state.insert_to_nick_history(&old_nick, user.history_entry.clone(), now);
 
                     state.users.insert(nick_str.clone(), user);
                     // wallops users
@@ -513,6 +845,8 @@ impl super::MainState {
                     for u in state.users.values() {
                         u.send_message(msg, &old_source)?;
                     }
+                    // notify watchers of old and new nick
+                    state.rename_user_presence(&old_nick, nick);
                 } else {
                     // if nick in use
                     let client = conn_state.user_state.client_name();
@@ -533,6 +867,29 @@ impl super::MainState {
         realname: &'a str,
     ) -> Result<(), Box<dyn Error>> {
         if !conn_state.user_state.authenticated {
+            if username.chars().count() > USERLEN {
+                let client = conn_state.user_state.client_name();
+                self.feed_msg(&mut conn_state.stream, ErrInputTooLong417 { client })
+                    .await?;
+                return Ok(());
+            }
+            if validate_realname(
+                realname,
+                self.config.max_realname_len,
+                self.config.realname_no_control_chars,
+            )
+            .is_err()
+            {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!(
+                        "ERROR :{}",
+                        CommandError::WrongParameter(CommandId::USERId, 3)
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
             conn_state.user_state.set_name(username.to_string());
             conn_state.user_state.realname = Some(realname.to_string());
             // try authentication
@@ -549,10 +906,26 @@ impl super::MainState {
         &self,
         conn_state: &mut ConnState,
         token: &'a str,
+        server: Option<&'a str>,
     ) -> Result<(), Box<dyn Error>> {
-        self.feed_msg(
+        // listener's server name is used like in registration burst.
+        let servername = listener_servername(&conn_state.servername, &self.config.name);
+        // PING sent to other server
+        if let Some(server) = server {
+            if server != self.config.name && server != servername {
+                let client = conn_state.user_state.client_name();
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrNoSuchServer402 { client, server },
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+        self.feed_msg_source(
             &mut conn_state.stream,
-            format!("PONG {} :{}", self.config.name, token),
+            servername,
+            format!("PONG {} :{}", servername, token),
         )
         .await?;
         Ok(())
@@ -580,38 +953,48 @@ impl super::MainState {
         let user_nick = conn_state.user_state.nick.as_ref().unwrap();
         let client = conn_state.user_state.client_name();
 
-        if let Some(oper_idx) = self.oper_config_idxs.get(nick) {
+        let op_config_opt = self.reloadable.read().await.operators.get(nick).cloned();
+        if let Some(op_config) = op_config_opt {
             // if operator defined in configuration
             let mut state = self.state.write().await;
             let user = state.users.get_mut(user_nick).unwrap();
-            let op_cfg_opt = self.config.operators.as_ref().unwrap().get(*oper_idx);
-            let op_config = op_cfg_opt.as_ref().unwrap();
 
-            // check password
-            let do_it =
-                if argon2_verify_password_async(password.to_string(), op_config.password.clone())
-                    .await
-                    .is_err()
-                {
-                    self.feed_msg(&mut conn_state.stream, ErrPasswdMismatch464 { client })
+            // check TLS connection and password
+            let do_it = if op_config.require_tls && !conn_state.is_secure() {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!("NOTICE {} :OPER requires TLS connection", client),
+                )
+                .await?;
+                self.feed_msg(&mut conn_state.stream, ErrNoPrivileges481 { client })
+                    .await?;
+                false
+            } else if argon2_verify_password_async(password.to_string(), op_config.password.clone())
+                .await
+                .is_err()
+            {
+                self.feed_msg(&mut conn_state.stream, ErrPasswdMismatch464 { client })
+                    .await?;
+                false
+            } else if let Some(ref op_mask) = op_config.mask {
+                if !match_wildcard(op_mask, &conn_state.user_state.source) {
+                    self.feed_msg(&mut conn_state.stream, ErrNoOperHost491 { client })
                         .await?;
                     false
-                } else if let Some(ref op_mask) = op_config.mask {
-                    if !match_wildcard(op_mask, &conn_state.user_state.source) {
-                        self.feed_msg(&mut conn_state.stream, ErrNoOperHost491 { client })
-                            .await?;
-                        false
-                    } else {
-                        true
-                    }
                 } else {
                     true
-                };
+                }
+            } else {
+                true
+            };
 
             if do_it {
                 // do it if all is ok.
+                let was_oper = user.modes.is_local_oper();
                 user.modes.oper = true;
-                state.operators_count += 1;
+                if !was_oper {
+                    state.operators_count += 1;
+                }
                 info!("New IRC operator {}", conn_state.user_state.source);
                 self.feed_msg(&mut conn_state.stream, RplYoureOper381 { client })
                     .await?;
@@ -627,14 +1010,140 @@ impl super::MainState {
         Ok(())
     }
 
-    pub(super) async fn process_quit(
+    pub(super) async fn process_quit<'a>(
         &self,
         conn_state: &mut ConnState,
+        reason: Option<&'a str>,
     ) -> Result<(), Box<dyn Error>> {
-        conn_state.quit.store(1, Ordering::SeqCst);
+        // drop control characters (formatting) and prefix reason to not allow
+        // users to fake server messages like kills.
+        let reason = match reason {
+            Some(reason) => format!(
+                "Quit: {}",
+                reason.chars().filter(|c| !c.is_control()).collect::<String>()
+            ),
+            None => "Client Quit".to_string(),
+        };
         info!("User {} quit", conn_state.user_state.source);
-        self.feed_msg(&mut conn_state.stream, "ERROR: Closing connection")
+        let hostname = conn_state.user_state.hostname.clone();
+        self.close_connection(
+            conn_state,
+            &format!("Closing Link: {} ({})", hostname, reason),
+        )
+        .await?;
+        // users in common channels are informed while removing user.
+        conn_state.quit_reason = Some(reason);
+        Ok(())
+    }
+
+    // generate new resume token for user and send it to client.
+    async fn send_resume_token(&self, conn_state: &mut ConnState) -> Result<(), Box<dyn Error>> {
+        let token = generate_token();
+        {
+            let mut state = self.state.write().await;
+            let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+            state.users.get_mut(user_nick).unwrap().resume_token = Some(token.clone());
+        }
+        self.feed_msg(&mut conn_state.stream, format!("RESUME TOKEN {}", token))
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn process_resume<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        token: &'a str,
+    ) -> Result<(), Box<dyn Error>> {
+        if conn_state.user_state.authenticated {
+            self.feed_msg(
+                &mut conn_state.stream,
+                "FAIL RESUME REGISTRATION_IS_COMPLETED :Cannot resume connection, \
+                registration is completed",
+            )
+            .await?;
+            return Ok(());
+        }
+        if !conn_state.caps.resume {
+            self.feed_msg(
+                &mut conn_state.stream,
+                "FAIL RESUME CANNOT_RESUME :Resume capability is not enabled",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let (nick, mut channels, pending_msgs) = {
+            let mut state = self.state.write().await;
+            let nick = if let Some(nick) = state.find_nick_by_resume_token(token) {
+                nick
+            } else {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    "FAIL RESUME INVALID_TOKEN :Cannot resume connection, token is not valid",
+                )
+                .await?;
+                return Ok(());
+            };
+            let user = state.users.get_mut(&nick).unwrap();
+            let mut pending_msgs = vec![];
+            if let Some(mut receiver) = user.detached_receiver.take() {
+                // get messages sent to user while connection has been lost.
+                while let Ok(msg) = receiver.try_recv() {
+                    pending_msgs.push(msg);
+                }
+            } else if let Some(quit_sender) = user.quit_sender.take() {
+                // old connection is still alive - close it.
+                if quit_sender
+                    .send((self.config.name.clone(), "Session resumed".to_string()))
+                    .is_err()
+                {
+                    info!("Old connection of {} already closed", user.source);
+                }
+            }
+            // splice this connection into user.
+            user.sender = conn_state.sender.take().unwrap();
+            user.quit_sender = conn_state.quit_sender.take();
+            user.conn_stats = conn_state.stream.stats();
+            user.resume_token = None;
+            user.caps = conn_state.caps;
+
+            let user_state = &mut conn_state.user_state;
+            user_state.hostname = user.hostname.clone();
+            user_state.realname = Some(user.realname.clone());
+            user_state.name = Some(user.name.clone());
+            user_state.set_nick(nick.clone());
+            user_state.authenticated = true;
+            (
+                nick,
+                user.channels.iter().cloned().collect::<Vec<_>>(),
+                pending_msgs,
+            )
+        };
+        info!("Session resumed for {}", conn_state.user_state.source);
+
+        self.feed_msg(&mut conn_state.stream, format!("RESUME SUCCESS {}", nick))
+            .await?;
+        // send channels that user joined to.
+        channels.sort();
+        for channel in &channels {
+            self.feed_msg_source(
+                &mut conn_state.stream,
+                &conn_state.user_state.source,
+                format!("JOIN {}", channel),
+            )
             .await?;
+        }
+        if !channels.is_empty() {
+            self.process_names(conn_state, channels.iter().map(|c| c.as_str()).collect())
+                .await?;
+        }
+        for msg in pending_msgs {
+            conn_state.stream.feed(msg).await?;
+        }
+
+        self.send_resume_token(conn_state).await?;
+        // run ping waker for this connection
+        conn_state.run_ping_waker(&self.config);
         Ok(())
     }
 }
@@ -643,6 +1152,7 @@ impl super::MainState {
 mod test {
     use super::super::test::*;
     use super::*;
+    use std::collections::HashSet;
 
     use tokio::net::TcpStream;
 
@@ -665,7 +1175,9 @@ mod test {
             line_stream.send("CAP END".to_string()).await.unwrap();
 
             assert_eq!(
-                ":irc.irc CAP * LS :multi-prefix".to_string(),
+                ":irc.irc CAP * LS :multi-prefix draft/channel-rename \
+                    draft/extended-monitor draft/pre-away"
+                    .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
@@ -697,7 +1209,7 @@ mod test {
                 line_stream.next().await.unwrap().unwrap()
             );
 
-            for _ in 3..18 {
+            for _ in 3..19 {
                 line_stream.next().await.unwrap().unwrap();
             }
 
@@ -725,7 +1237,9 @@ mod test {
             line_stream.send("NICK mati2".to_string()).await.unwrap();
 
             assert_eq!(
-                ":irc.irc CAP * LS :multi-prefix".to_string(),
+                ":irc.irc CAP * LS :multi-prefix draft/channel-rename \
+                    draft/extended-monitor draft/pre-away"
+                    .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
@@ -776,7 +1290,7 @@ mod test {
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -855,7 +1369,7 @@ mod test {
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -899,7 +1413,7 @@ mod test {
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -943,7 +1457,7 @@ mod test {
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -991,57 +1505,334 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_auth_with_user_configs_2() {
+    async fn test_auth_with_sasl_plain() {
         let mut config = MainConfig::default();
-        config.users = Some(vec![
-            UserConfig {
-                name: "lucky".to_string(),
-                nick: "luckboy".to_string(),
-                password: Some(argon2_hash_password("top_secret")),
-                mask: None,
-            },
-            UserConfig {
-                name: "mati".to_string(),
-                nick: "mat".to_string(),
-                password: None,
-                mask: None,
-            },
-            UserConfig {
-                name: "mati2".to_string(),
-                nick: "mat2".to_string(),
-                password: None,
-                mask: Some("mat2!~mati2@*".to_string()),
-            }, // fail
-        ]);
+        config.users = Some(vec![UserConfig {
+            name: "lucky".to_string(),
+            nick: "luckboy".to_string(),
+            password: Some(argon2_hash_password("top_secret")),
+            mask: None,
+        }]);
         let (main_state, handle, port) = run_test_server(config).await;
 
-        for (pass, succeed) in [
-            (None, false),
-            (Some("blamblam2"), false),
-            (Some("blamblam"), false),
-            (Some("top_secret"), true),
-        ] {
+        {
             let mut line_stream = connect_to_test(port).await;
-
-            if let Some(p) = pass {
-                line_stream.send(format!("PASS {}", p)).await.unwrap();
-            }
-            line_stream.send("NICK luckboy".to_string()).await.unwrap();
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc CAP * LS :multi-prefix draft/channel-rename \
+                    draft/extended-monitor draft/pre-away sasl"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("CAP REQ :sasl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc CAP * ACK :sasl".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("NICK bobby".to_string()).await.unwrap();
             line_stream
-                .send("USER lucky 8 * :LuckBoy".to_string())
+                .send("USER bob 8 * :Bobby".to_string())
                 .await
                 .unwrap();
 
-            if succeed {
-                assert_eq!(
-                    ":irc.irc 001 luckboy :Welcome to the IRCnetwork \
-                        Network, luckboy!~lucky@127.0.0.1"
-                        .to_string(),
-                    line_stream.next().await.unwrap().unwrap(),
+            // wrong password
+            line_stream
+                .send("AUTHENTICATE PLAIN".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc AUTHENTICATE +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE AGx1Y2t5AHdyb25n".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 904 bobby :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // aborted exchange
+            line_stream
+                .send("AUTHENTICATE PLAIN".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc AUTHENTICATE +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE *".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 906 bobby :SASL authentication aborted".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // no client certificate in plaintext connection
+            line_stream
+                .send("AUTHENTICATE EXTERNAL".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 904 bobby :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // unsupported mechanism
+            line_stream
+                .send("AUTHENTICATE SCRAM-SHA-256".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 908 bobby EXTERNAL,PLAIN :are available SASL mechanisms".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 904 bobby :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // good password
+            line_stream
+                .send("AUTHENTICATE PLAIN".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc AUTHENTICATE +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE AGx1Y2t5AHRvcF9zZWNyZXQ=".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 900 bobby bobby!~bob@127.0.0.1 lucky :You are now logged in as lucky"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 903 bobby :SASL authentication successful".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE PLAIN".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 907 bobby :You have already authenticated using SASL".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 001 bobby :Welcome to the IRCnetwork \
+                    Network, bobby!~bob@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 1..19 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            {
+                let state = main_state.state.read().await;
+                let user = state.users.get("bobby").unwrap();
+                assert_eq!(Some("lucky".to_string()), user.account);
+                assert!(user.modes.registered);
+            }
+        }
+
+        {
+            // SASL requested but not completed
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream.send("CAP REQ :sasl".to_string()).await.unwrap();
+            line_stream.send("NICK mati".to_string()).await.unwrap();
+            line_stream
+                .send("USER mat 8 * :MatiSzpaki".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("AUTHENTICATE PLAIN".to_string())
+                .await
+                .unwrap();
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc 904 mati :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc ERROR :SASL authentication required".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert!(!main_state.state.read().await.users.contains_key("mati"));
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[cfg(feature = "tls_rustls")]
+    #[tokio::test]
+    async fn test_auth_with_sasl_external() {
+        let mut config = MainConfig::default();
+        // fingerprint in config with colons and in upper case.
+        let fingerprint = get_test_cert_fingerprint()
+            .as_bytes()
+            .chunks(2)
+            .map(|c| String::from_utf8(c.to_vec()).unwrap().to_uppercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        config.cert_fingerprints = Some([(fingerprint, "lucky".to_string())].into());
+        let (main_state, handle, port) = run_test_tls_server(config).await;
+
+        {
+            let mut line_stream = connect_to_test_tls_with_client_cert(port, true).await;
+            line_stream.send("CAP REQ :sasl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc CAP * ACK :sasl".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("NICK bobby".to_string()).await.unwrap();
+            line_stream
+                .send("USER bob 8 * :Bobby".to_string())
+                .await
+                .unwrap();
+
+            // authorization identity other than account
+            line_stream
+                .send("AUTHENTICATE EXTERNAL".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc AUTHENTICATE +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE b3RoZXI=".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 904 bobby :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("AUTHENTICATE EXTERNAL".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc AUTHENTICATE +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("AUTHENTICATE +".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 900 bobby bobby!~bob@127.0.0.1 lucky :You are now logged in as lucky"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 903 bobby :SASL authentication successful".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 001 bobby :Welcome to the IRCnetwork \
+                    Network, bobby!~bob@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 1..19 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            let state = main_state.state.read().await;
+            assert_eq!(
+                Some("lucky".to_string()),
+                state.users.get("bobby").unwrap().account
+            );
+        }
+
+        {
+            // no client certificate
+            let mut line_stream = connect_to_test_tls(port).await;
+            line_stream.send("CAP REQ :sasl".to_string()).await.unwrap();
+            line_stream.send("NICK mati".to_string()).await.unwrap();
+            line_stream
+                .send("USER mat 8 * :MatiSzpaki".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("AUTHENTICATE EXTERNAL".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":irc.irc 904 mati :SASL authentication failed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_auth_with_user_configs_2() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![
+            UserConfig {
+                name: "lucky".to_string(),
+                nick: "luckboy".to_string(),
+                password: Some(argon2_hash_password("top_secret")),
+                mask: None,
+            },
+            UserConfig {
+                name: "mati".to_string(),
+                nick: "mat".to_string(),
+                password: None,
+                mask: None,
+            },
+            UserConfig {
+                name: "mati2".to_string(),
+                nick: "mat2".to_string(),
+                password: None,
+                mask: Some("mat2!~mati2@*".to_string()),
+            }, // fail
+        ]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        for (pass, succeed) in [
+            (None, false),
+            (Some("blamblam2"), false),
+            (Some("blamblam"), false),
+            (Some("top_secret"), true),
+        ] {
+            let mut line_stream = connect_to_test(port).await;
+
+            if let Some(p) = pass {
+                line_stream.send(format!("PASS {}", p)).await.unwrap();
+            }
+            line_stream.send("NICK luckboy".to_string()).await.unwrap();
+            line_stream
+                .send("USER lucky 8 * :LuckBoy".to_string())
+                .await
+                .unwrap();
+
+            if succeed {
+                assert_eq!(
+                    ":irc.irc 001 luckboy :Welcome to the IRCnetwork \
+                        Network, luckboy!~lucky@127.0.0.1"
+                        .to_string(),
+                    line_stream.next().await.unwrap().unwrap(),
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -1085,7 +1876,7 @@ mod test {
                     "AuthTrial: {:?}",
                     pass
                 );
-                for _ in 1..17 {
+                for _ in 1..18 {
                     line_stream.next().await.unwrap().unwrap();
                 }
                 assert_eq!(
@@ -1115,6 +1906,9 @@ mod test {
             local_oper: false,
             oper: false,
             wallops: false,
+            bot: false,
+            hide_oper: false,
+            caller_id: false,
         };
         let (main_state, handle, port) = run_test_server(config).await;
 
@@ -1127,7 +1921,7 @@ mod test {
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-            for _ in 1..7 {
+            for _ in 1..8 {
                 line_stream.next().await.unwrap().unwrap();
             }
 
@@ -1210,7 +2004,7 @@ mod test {
             );
 
             assert_eq!(
-                ":irc.irc 433 127.0.0.1 oliver :Nickname is already in use".to_string(),
+                ":irc.irc 433 * oliver :Nickname is already in use".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
         }
@@ -1270,7 +2064,7 @@ mod test {
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 433 uliverk uliver :Nickname is already in use".to_string(),
+                ":irc.irc 433 * uliver :Nickname is already in use".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
         }
@@ -1279,30 +2073,34 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_auth_after_user_pass_failed() {
+    async fn test_auth_numeric_target() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
-            let mut line_stream =
-                login_to_test_and_skip(port, "oliver", "aliverk", "Oliver Kittson").await;
-
+            let mut line_stream = connect_to_test(port).await;
             line_stream
-                .send("USER aliverk 8 * :Oliver Kittson".to_string())
+                .send("USER brian 8 * :Brian Brown".to_string())
                 .await
                 .unwrap();
+            // nick is not yet known
+            line_stream.send("JOIN #xxx".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 462 oliver :You may not reregister".to_string(),
+                ":irc.irc 451 * :You have not registered".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-        }
-
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "uliver", "aliverk", "Oliver Kittson").await;
-
-            line_stream.send("PASS xxxx".to_string()).await.unwrap();
+            line_stream.send("NICK brian".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 462 uliver :You may not reregister".to_string(),
+                ":irc.irc 001 brian :Welcome to the IRCnetwork \
+                    Network, brian!~brian@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 1..19 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.send("JOIN xxx".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 403 brian xxx :No such channel".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -1311,21 +2109,211 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_nick_rename() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_auth_failed_error_before_close() {
+        let mut config = MainConfig::default();
+        config.password = Some(argon2_hash_password("blamblam"));
+        config.bans = Some(vec!["guru!*@*".to_string()]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
-            let mut line_stream = login_to_test_and_skip(port, "mati", "mat", "MatSzpak").await;
-            let mut line_stream2 = login_to_test_and_skip(port, "lucki", "luck", "LuckBoy").await;
-            let mut line_stream3 = login_to_test_and_skip(port, "dam", "dam", "Damon").await;
-
-            line_stream2.send("NICK luke".to_string()).await.unwrap();
-
-            assert_eq!(
-                ":lucki!~luck@127.0.0.1 NICK luke".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("PASS blamblam2".to_string()).await.unwrap();
+            line_stream.send("NICK mati".to_string()).await.unwrap();
+            line_stream
+                .send("USER mat 8 * :MatiSzpaki".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 464 mati :Password incorrect".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc ERROR :Password incorrect, connection will be closed.".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert!(line_stream.next().await.is_none());
+        }
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("PASS blamblam".to_string()).await.unwrap();
+            line_stream.send("NICK guru".to_string()).await.unwrap();
+            line_stream
+                .send("USER guru 8 * :Guru".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 465 guru :You are banned from this server.".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc ERROR :Banned from server, connection will be closed.".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert!(line_stream.next().await.is_none());
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_auth_after_user_pass_failed() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "oliver", "aliverk", "Oliver Kittson").await;
+
+            line_stream
+                .send("USER aliverk 8 * :Oliver Kittson".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 462 oliver :You may not reregister".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "uliver", "aliverk", "Oliver Kittson").await;
+
+            line_stream.send("PASS xxxx".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 462 uliver :You may not reregister".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_auth_realname_validation() {
+        let mut config = MainConfig::default();
+        config.max_realname_len = Some(20);
+        config.realname_no_control_chars = true;
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("NICK oliver".to_string()).await.unwrap();
+            for realname in [
+                "Oliver\rKittson",
+                "Oliver Kittson from Liverpool",
+                "Oliver \x03Kittson",
+            ] {
+                line_stream
+                    .send(format!("USER aliverk 8 * :{}", realname))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    ":irc.irc ERROR :Wrong parameter 3 in command 'USER'".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            line_stream
+                .send("USER aliverk 8 * :Oliver Kittson".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 001 oliver :Welcome to the IRCnetwork \
+                    Network, oliver!~aliverk@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_auth_too_long_nick_and_username() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            let long_nick = "o".repeat(NICKLEN + 1);
+            line_stream
+                .send(format!("NICK {}", long_nick))
+                .await
+                .unwrap();
+            assert_eq!(
+                format!(":irc.irc 432 * {} :Erroneus nickname", long_nick),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let long_username = "a".repeat(USERLEN + 1);
+            line_stream
+                .send(format!("USER {} 8 * :Oliver Kittson", long_username))
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 417 * :Input line was too long".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // registration can be continued with valid nick and username
+            line_stream.send("NICK oliver".to_string()).await.unwrap();
+            line_stream
+                .send("USER aliverk 8 * :Oliver Kittson".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 001 oliver :Welcome to the IRCnetwork \
+                    Network, oliver!~aliverk@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_auth_your_id() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut ids = vec![];
+            for (nick, name) in [("oliver", "oliverk"), ("emma", "emmak")] {
+                let mut line_stream = login_to_test(port, nick, name, "Kittson").await;
+                // skip 001-005
+                for _ in 0..7 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+                let line = line_stream.next().await.unwrap().unwrap();
+                let prefix = format!(":irc.irc 042 {} ", nick);
+                assert!(line.starts_with(&prefix), "{}", line);
+                assert!(line.ends_with(" :your unique ID"), "{}", line);
+                let id = line[prefix.len()..line.len() - " :your unique ID".len()].to_string();
+
+                time::sleep(Duration::from_millis(50)).await;
+                {
+                    let state = main_state.state.read().await;
+                    assert_eq!(id, state.users.get(nick).unwrap().uid);
+                }
+                ids.push((id, line_stream));
+            }
+            assert_ne!(ids[0].0, ids[1].0);
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_nick_rename() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "mati", "mat", "MatSzpak").await;
+            let mut line_stream2 = login_to_test_and_skip(port, "lucki", "luck", "LuckBoy").await;
+            let mut line_stream3 = login_to_test_and_skip(port, "dam", "dam", "Damon").await;
+
+            line_stream2.send("NICK luke".to_string()).await.unwrap();
+
+            assert_eq!(
+                ":lucki!~luck@127.0.0.1 NICK luke".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
                 ":lucki!~luck@127.0.0.1 NICK luke".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
@@ -1366,6 +2354,73 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_nick_reserved() {
+        let mut config = MainConfig::default();
+        config.reserved_nicks = Some(vec!["guru*".to_string()]);
+        config.users = Some(vec![UserConfig {
+            name: "lucky".to_string(),
+            nick: "guruman".to_string(),
+            password: None,
+            mask: None,
+        }]);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test(port, "gurux", "gurux", "Guru X").await;
+            assert_eq!(
+                ":irc.irc 432 gurux gurux :Erroneus nickname".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("NICK bobby".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 001 bobby :Welcome to the IRCnetwork \
+                    Network, bobby!~gurux@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 0..18 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            // owner of nick can use it
+            let mut lucky_stream = login_to_test(port, "guruman", "lucky", "Lucky").await;
+            assert_eq!(
+                ":irc.irc 001 guruman :Welcome to the IRCnetwork \
+                    Network, guruman!~lucky@127.0.0.1"
+                    .to_string(),
+                lucky_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("NICK guru2".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 432 bobby guru2 :Erroneus nickname".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // operator can use reserved nick
+            let mut oper_stream = login_to_test_and_skip(port, "fanny", "fanny", "Fanny").await;
+            oper_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            oper_stream.next().await.unwrap().unwrap();
+            oper_stream.send("NICK guru3".to_string()).await.unwrap();
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 NICK guru3".to_string(),
+                oper_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_nick_rename_at_channel() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -1402,16 +2457,19 @@ mod test {
                 name: "guru".to_string(),
                 password: argon2_hash_password("NoWay"),
                 mask: None,
+                require_tls: false,
             },
             OperatorConfig {
                 name: "guru2".to_string(),
                 password: argon2_hash_password("NoWay2"),
                 mask: Some("guruv*@*".to_string()),
+                require_tls: false,
             },
             OperatorConfig {
                 name: "guru3".to_string(),
                 password: argon2_hash_password("NoWay3"),
                 mask: Some("guru4*@*".to_string()),
+                require_tls: false,
             },
         ]);
         let (main_state, handle, port) = run_test_server(config).await;
@@ -1515,6 +2573,77 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_oper_require_tls() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "guru".to_string(),
+            password: argon2_hash_password("NoWay"),
+            mask: None,
+            require_tls: true,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "guruv", "guruvx", "SuperGuruV").await;
+            line_stream
+                .send("OPER guru NoWay".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc NOTICE guruv :OPER requires TLS connection".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 481 guruv :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(
+                !main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("guruv")
+                    .unwrap()
+                    .modes
+                    .oper
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
+    #[tokio::test]
+    async fn test_command_oper_require_tls_secure() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "guru".to_string(),
+            password: argon2_hash_password("NoWay"),
+            mask: None,
+            require_tls: true,
+        }]);
+        let (main_state, handle, port) = run_test_tls_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_tls_and_skip(port, "guruv", "guruvx", "SuperGuruV").await;
+            line_stream
+                .send("OPER guru NoWay".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 381 guruv :You are now an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_quit() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -1524,7 +2653,7 @@ mod test {
 
             line_stream.send("QUIT :Bye".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc ERROR: Closing connection".to_string(),
+                ":irc.irc ERROR :Closing Link: 127.0.0.1 (Quit: Bye)".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             time::sleep(Duration::from_millis(50)).await;
@@ -1539,7 +2668,7 @@ mod test {
 
             line_stream.send("QUIT :Bye".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc ERROR: Closing connection".to_string(),
+                ":irc.irc ERROR :Closing Link: 127.0.0.1 (Quit: Bye)".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -1550,9 +2679,197 @@ mod test {
 
             line_stream.send("QUIT :Bye".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc ERROR: Closing connection".to_string(),
+                ":irc.irc ERROR :Closing Link: 127.0.0.1 (Quit: Bye)".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_quit_peers() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "brian", "brianx", "BrianX").await;
+            let mut amy_stream = login_to_test_and_skip(port, "amy", "amyx", "AmyX").await;
+
+            line_stream
+                .send("JOIN #one,#two".to_string())
+                .await
+                .unwrap();
+            for _ in 0..6 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            amy_stream.send("JOIN #one,#two".to_string()).await.unwrap();
+            for _ in 0..6 {
+                amy_stream.next().await.unwrap().unwrap();
+            }
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            line_stream
+                .send("QUIT :Bye \x02now".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc ERROR :Closing Link: 127.0.0.1 (Quit: Bye now)".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // only one QUIT for peer in two common channels.
+            assert_eq!(
+                ":brian!~brianx@127.0.0.1 QUIT :Quit: Bye now".to_string(),
+                amy_stream.next().await.unwrap().unwrap()
+            );
+            amy_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                amy_stream.next().await.unwrap().unwrap()
+            );
+
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.users.contains_key("brian"));
+                for chname in ["#one", "#two"] {
+                    let channel = state.channels.get(chname).unwrap();
+                    assert_eq!(
+                        HashSet::from(["amy".to_string()]),
+                        channel.users.keys().cloned().collect::<HashSet<_>>()
+                    );
+                }
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_quit_restore_away() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![UserConfig {
+            name: "roland".to_string(),
+            nick: "roland".to_string(),
+            password: None,
+            mask: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        for (name, nick) in [("roland", "roland"), ("harry", "harry")] {
+            let mut line_stream = login_to_test_and_skip(port, nick, name, "Some Guy").await;
+            line_stream
+                .send("AWAY :Gone fishing".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                format!(":irc.irc 306 {} :You have been marked as being away", nick),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+        }
+        time::sleep(Duration::from_millis(50)).await;
+
+        {
+            // same account - away restored
+            let mut line_stream =
+                login_to_test_and_skip(port, "rolly", "roland", "Some Guy").await;
+            assert_eq!(
+                ":irc.irc 306 rolly :You have been marked as being away".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert_eq!(
+                    Some("Gone fishing".to_string()),
+                    state.users.get("rolly").unwrap().away
+                );
+            }
+            // unset away is also remembered
+            line_stream.send("AWAY".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+        }
+        time::sleep(Duration::from_millis(50)).await;
+
+        {
+            let _line_stream = login_to_test_and_skip(port, "roland", "roland", "Some Guy").await;
+            // anonymous user has no account
+            let _line_stream2 = login_to_test_and_skip(port, "harry", "harry", "Some Guy").await;
+            time::sleep(Duration::from_millis(50)).await;
+            let state = main_state.state.read().await;
+            assert_eq!(None, state.users.get("roland").unwrap().away);
+            assert_eq!(None, state.users.get("harry").unwrap().away);
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_pre_away() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            line_stream
+                .send("CAP REQ :draft/extended-monitor".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream
+                .send("MONITOR + bowie".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            // no away before registration without capability.
+            let mut bowie_stream = connect_to_test(port).await;
+            bowie_stream.send("AWAY :Gone".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 451 * :You have not registered".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+
+            bowie_stream
+                .send("CAP REQ :draft/pre-away".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc CAP * ACK :draft/pre-away".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            bowie_stream.send("AWAY :Gone".to_string()).await.unwrap();
+            bowie_stream.send("NICK bowie".to_string()).await.unwrap();
+            bowie_stream
+                .send("USER bowie 8 * :Bowie Catcher".to_string())
+                .await
+                .unwrap();
+            bowie_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..19 {
+                bowie_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc 306 bowie :You have been marked as being away".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+
+            // monitor sees user online before its away state.
+            assert_eq!(
+                ":irc.irc 730 alan :bowie!~bowie@127.0.0.1".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 AWAY :Gone".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                Some("Gone".to_string()),
+                main_state.state.read().await.users.get("bowie").unwrap().away
+            );
         }
 
         quit_test_server(main_state, handle).await;
@@ -1565,6 +2882,7 @@ mod test {
             name: "#carrots".to_string(),
             topic: None,
             modes: ChannelModes::default(),
+            access: None,
         }]);
         let (main_state, handle, port) = run_test_server(config).await;
 
@@ -1602,6 +2920,258 @@ mod test {
                 ":irc.irc PONG irc.irc :aarrgghhh!!!".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            // blank lines and lines with only spaces are ignored
+            line_stream.send("".to_string()).await.unwrap();
+            line_stream.send("     ".to_string()).await.unwrap();
+            line_stream
+                .send("PING welcome   ".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :welcome".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("PING :abc".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :abc".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("PING abc irc.irc".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :abc".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("PING abc other.irc".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 402 brian other.irc :No such server".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // frequent PINGs are not throttled
+            for i in 0..100 {
+                line_stream.send(format!("PING :k{}", i)).await.unwrap();
+            }
+            for i in 0..100 {
+                assert_eq!(
+                    format!(":irc.irc PONG irc.irc :k{}", i),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    async fn login_with_resume(port: u16) -> (Framed<TcpStream, IRCLinesCodec>, String) {
+        let mut line_stream = connect_to_test(port).await;
+        line_stream.send("CAP LS 302".to_string()).await.unwrap();
+        line_stream.send("NICK mati".to_string()).await.unwrap();
+        line_stream
+            .send("USER mat 8 * :MatiSzpaki".to_string())
+            .await
+            .unwrap();
+        line_stream
+            .send("CAP REQ :draft/resume-0.5".to_string())
+            .await
+            .unwrap();
+        line_stream.send("CAP END".to_string()).await.unwrap();
+        assert_eq!(
+            ":irc.irc CAP * LS :multi-prefix draft/channel-rename draft/extended-monitor \
+                draft/pre-away draft/resume-0.5"
+                .to_string(),
+            line_stream.next().await.unwrap().unwrap()
+        );
+        assert_eq!(
+            ":irc.irc CAP * ACK :draft/resume-0.5".to_string(),
+            line_stream.next().await.unwrap().unwrap()
+        );
+        for _ in 0..19 {
+            line_stream.next().await.unwrap().unwrap();
+        }
+        let token_line = line_stream.next().await.unwrap().unwrap();
+        assert!(token_line.starts_with(":irc.irc RESUME TOKEN "));
+        let token = token_line[":irc.irc RESUME TOKEN ".len()..].to_string();
+        (line_stream, token)
+    }
+
+    #[tokio::test]
+    async fn test_command_resume() {
+        let mut config = MainConfig::default();
+        config.resume_timeout = Some(10);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let (mut line_stream, token) = login_with_resume(port).await;
+            line_stream.send("JOIN #resumed".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let mut peer_stream = login_to_test_and_skip(port, "peer", "peer", "Peer Peer").await;
+            peer_stream.send("JOIN #resumed".to_string()).await.unwrap();
+            for _ in 0..3 {
+                peer_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap(); // skip JOIN peer
+
+            // lost connection
+            drop(line_stream);
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(state.users.get("mati").unwrap().detached_receiver.is_some());
+                assert!(state
+                    .channels
+                    .get("#resumed")
+                    .unwrap()
+                    .users
+                    .contains_key("mati"));
+            }
+            peer_stream
+                .send("PRIVMSG #resumed :Are you here?".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream
+                .send("CAP REQ :draft/resume-0.5".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send(format!("RESUME {}", token))
+                .await
+                .unwrap();
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc RESUME SUCCESS mati".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":mati!~mat@127.0.0.1 JOIN #resumed".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap(); // skip NAMES
+            }
+            assert_eq!(
+                ":peer!~peer@127.0.0.1 PRIVMSG #resumed :Are you here?".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let token_line = line_stream.next().await.unwrap().unwrap();
+            assert!(token_line.starts_with(":irc.irc RESUME TOKEN "));
+            assert_ne!(format!(":irc.irc RESUME TOKEN {}", token), token_line);
+
+            {
+                let state = main_state.state.read().await;
+                assert!(state.users.get("mati").unwrap().detached_receiver.is_none());
+            }
+
+            // peer doesn't see quit and rejoin.
+            line_stream
+                .send("PRIVMSG #resumed :I am back".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":mati!~mat@127.0.0.1 PRIVMSG #resumed :I am back".to_string(),
+                peer_stream.next().await.unwrap().unwrap()
+            );
+
+            // old token is not valid
+            let mut line_stream2 = connect_to_test(port).await;
+            line_stream2.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream2
+                .send("CAP REQ :draft/resume-0.5".to_string())
+                .await
+                .unwrap();
+            line_stream2
+                .send(format!("RESUME {}", token))
+                .await
+                .unwrap();
+            for _ in 0..2 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc FAIL RESUME INVALID_TOKEN :Cannot resume connection, \
+                    token is not valid"
+                    .to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_resume_failures() {
+        let mut config = MainConfig::default();
+        config.resume_timeout = Some(1);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let (mut line_stream, token) = login_with_resume(port).await;
+            let mut line_stream2 = login_to_test_and_skip(port, "bobby", "bob", "Bobby").await;
+            line_stream.send("JOIN #lost".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("JOIN #lost".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            drop(line_stream);
+            time::sleep(Duration::from_millis(1500)).await;
+            // resume timeout
+            assert!(!main_state.state.read().await.users.contains_key("mati"));
+            // peers get QUIT of user whose session has not been resumed.
+            assert_eq!(
+                ":mati!~mat@127.0.0.1 QUIT :Connection closed".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            let mut line_stream = connect_to_test(port).await;
+            line_stream
+                .send(format!("RESUME {}", token))
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RESUME CANNOT_RESUME :Resume capability is not enabled"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CAP REQ :draft/resume-0.5".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send(format!("RESUME {}", token))
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RESUME INVALID_TOKEN :Cannot resume connection, \
+                    token is not valid"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // no resuming after QUIT
+            let (mut line_stream, _) = login_with_resume(port).await;
+            line_stream.send("QUIT".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(!main_state.state.read().await.users.contains_key("mati"));
         }
 
         quit_test_server(main_state, handle).await;
@@ -22,7 +22,7 @@ use futures::future::Fuse;
 #[cfg(feature = "dns_lookup")]
 use lazy_static::lazy_static;
 #[cfg(feature = "tls_openssl")]
-use openssl::ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod};
+use openssl::ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
@@ -33,11 +33,14 @@ use std::io::BufReader;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpSocket};
 #[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
 use tokio::net::TcpStream;
+use tokio::sync::mpsc::unbounded_channel;
 use tokio::sync::{oneshot, RwLock};
 use tokio::task::JoinHandle;
+use tokio::time;
 #[cfg(feature = "tls_openssl")]
 use tokio_openssl::SslStream;
 #[cfg(feature = "tls_rustls")]
@@ -60,17 +63,120 @@ use Reply::*;
 mod structs;
 pub(crate) use structs::*;
 
+// prefix of unique connection IDs. Server is not linked, so its ID is constant.
+const UID_PREFIX: &str = "0AA";
+
+// commands that can be used only by operators. They are checked by dispatcher
+// before command is processed. More commands can be added by oper_commands in config.
+const OPER_COMMANDS: [&str; 4] = ["CLEARCHAN", "OPERWALL", "REHASH", "WALLOPS"];
+
+// lookup of country code of IP address.
+type GeoIpLookup = Box<dyn Fn(IpAddr) -> Option<String> + Send + Sync>;
+
 pub(crate) struct MainState {
     config: MainConfig,
     // key is user name
     user_config_idxs: HashMap<String, usize>,
-    // key is oper name
-    oper_config_idxs: HashMap<String, usize>,
+    // parts of configuration that can be reloaded by REHASH.
+    reloadable: RwLock<ReloadableConfig>,
     conns_count: Arc<AtomicUsize>,
     state: RwLock<VolatileState>,
     created: String,
     created_time: DateTime<Local>,
     command_counts: [AtomicU64; NUM_COMMANDS],
+    // counter used to generate unique IDs of connections.
+    next_uid: AtomicU64,
+    // GeoIP lookup. If not set then country of client is not resolved.
+    geoip_lookup: Option<GeoIpLookup>,
+}
+
+// parts of configuration that can be reloaded by REHASH command.
+struct ReloadableConfig {
+    motd: String,
+    // key is oper name
+    operators: HashMap<String, OperatorConfig>,
+    bans: Vec<String>,
+    reserved_nicks: Vec<String>,
+    reserved_channels: Vec<String>,
+}
+
+impl ReloadableConfig {
+    fn new(config: &MainConfig) -> Self {
+        let mut reloadable = ReloadableConfig {
+            motd: config.motd.clone(),
+            operators: HashMap::new(),
+            bans: config.bans.clone().unwrap_or_default(),
+            reserved_nicks: config.reserved_nicks.clone().unwrap_or_default(),
+            reserved_channels: config.reserved_channels.clone().unwrap_or_default(),
+        };
+        reloadable.set_operators(config.operators.clone().unwrap_or_default());
+        reloadable
+    }
+
+    fn set_operators(&mut self, operators: Vec<OperatorConfig>) {
+        self.operators = operators
+            .into_iter()
+            .map(|o| (o.name.clone(), o))
+            .collect::<HashMap<_, _>>();
+    }
+
+    // apply loaded included files - only given parts are changed.
+    fn apply(&mut self, files: IncludedFiles) {
+        if let Some(motd) = files.motd {
+            self.motd = motd;
+        }
+        if let Some(operators) = files.operators {
+            self.set_operators(operators);
+        }
+        if let Some(bans) = files.bans {
+            self.bans = bans;
+        }
+        if let Some(reserved_nicks) = files.reserved_nicks {
+            self.reserved_nicks = reserved_nicks;
+        }
+        if let Some(reserved_channels) = files.reserved_channels {
+            self.reserved_channels = reserved_channels;
+        }
+    }
+
+    fn banned(&self, source: &str) -> bool {
+        self.bans.iter().any(|mask| match_wildcard(mask, source))
+    }
+}
+
+// included files loaded by REHASH command.
+struct IncludedFiles {
+    motd: Option<String>,
+    operators: Option<Vec<OperatorConfig>>,
+    bans: Option<Vec<String>>,
+    reserved_nicks: Option<Vec<String>>,
+    reserved_channels: Option<Vec<String>>,
+}
+
+impl IncludedFiles {
+    // load included files if their paths are given. All files are loaded before
+    // change to keep current configuration if any file is bad.
+    fn load(
+        motd_path: Option<&str>,
+        operators_path: Option<&str>,
+        bans_path: Option<&str>,
+        reserved_nicks_path: Option<&str>,
+        reserved_channels_path: Option<&str>,
+    ) -> Result<IncludedFiles, Box<dyn Error>> {
+        Ok(IncludedFiles {
+            motd: motd_path.map(IncludeConfig::load_motd).transpose()?,
+            operators: operators_path
+                .map(IncludeConfig::load_operators)
+                .transpose()?,
+            bans: bans_path.map(IncludeConfig::load_bans).transpose()?,
+            reserved_nicks: reserved_nicks_path
+                .map(IncludeConfig::load_reserved_nicks)
+                .transpose()?,
+            reserved_channels: reserved_channels_path
+                .map(IncludeConfig::load_reserved_channels)
+                .transpose()?,
+        })
+    }
 }
 
 impl MainState {
@@ -82,18 +188,14 @@ impl MainState {
                 user_config_idxs.insert(u.name.clone(), i);
             });
         }
-        let mut oper_config_idxs = HashMap::new();
-        if let Some(ref opers) = config.operators {
-            opers.iter().enumerate().for_each(|(i, o)| {
-                oper_config_idxs.insert(o.name.clone(), i);
-            });
-        }
+        let reloadable = RwLock::new(ReloadableConfig::new(&config));
         let state = RwLock::new(VolatileState::new_from_config(&config));
+        let geoip_lookup = open_geoip_lookup(&config);
         let now = Local::now();
         MainState {
             config,
             user_config_idxs,
-            oper_config_idxs,
+            reloadable,
             state,
             conns_count: Arc::new(AtomicUsize::new(0)),
             created: now.to_rfc2822(),
@@ -140,40 +242,279 @@ impl MainState {
                 AtomicU64::new(0),
                 AtomicU64::new(0),
                 AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
             ],
+            next_uid: AtomicU64::new(0),
+            geoip_lookup,
         }
     }
 
+    // get country code of IP address from GeoIP database.
+    fn geoip_country(&self, ip: IpAddr) -> Option<String> {
+        self.geoip_lookup.as_ref().and_then(|lookup| lookup(ip))
+    }
+
     fn count_command(&self, cmd: &Command) {
         self.command_counts[cmd.index()].fetch_add(1, Ordering::SeqCst);
     }
 
+    // check whether command can be used only by operators.
+    fn oper_command(&self, cmd: &Command) -> bool {
+        let name = cmd.name();
+        OPER_COMMANDS.contains(&name)
+            || self
+                .config
+                .oper_commands
+                .as_ref()
+                .map_or(false, |cmds| cmds.iter().any(|c| c.eq_ignore_ascii_case(name)))
+    }
+
+    // check whether nick is reserved for this user. Reserved nick can be used only by
+    // operators and registered user that has this nick in configuration.
+    async fn nick_reserved(&self, user_state: &ConnUserState, nick: &str) -> bool {
+        let reserved = self
+            .reloadable
+            .read()
+            .await
+            .reserved_nicks
+            .iter()
+            .any(|mask| match_wildcard(mask, nick));
+        let owner = user_state.registered
+            && user_state
+                .name
+                .as_ref()
+                .and_then(|name| self.user_config_idxs.get(name))
+                .map_or(false, |uidx| self.config.users.as_ref().unwrap()[*uidx].nick == nick);
+        reserved && !owner
+    }
+
+    async fn channel_reserved(&self, channel: &str) -> bool {
+        self.reloadable
+            .read()
+            .await
+            .reserved_channels
+            .iter()
+            .any(|mask| match_wildcard(mask, channel))
+    }
+
+    // check whether user can create channel with this name (channel_creation policy
+    // and reserved channels). IRC operators can create any channel.
+    async fn can_create_channel(&self, user: &User, channel: &str) -> bool {
+        if user.modes.is_local_oper() {
+            return true;
+        }
+        let allowed = match self.config.channel_creation {
+            Some(ChannelCreation::Opers) => false,
+            Some(ChannelCreation::Registered) => user.modes.registered,
+            _ => true,
+        };
+        allowed && !self.channel_reserved(channel).await
+    }
+
+    // users (bots, bridges) whose source matches rate_limit_exempt masks are not limited.
+    fn rate_limit_exempt(&self, source: &str) -> bool {
+        self.config
+            .rate_limit_exempt
+            .as_ref()
+            .map_or(false, |masks| masks.iter().any(|mask| match_wildcard(mask, source)))
+    }
+
+    // check target lists against TARGMAX limits. Returns false if command must be rejected.
+    // USERHOST and ISON just ignore excess nicknames like other servers do.
+    async fn enforce_targmax(
+        &self,
+        conn_state: &mut ConnState,
+        cmd: &mut Command<'_>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let targmax = match self.config.targmax {
+            Some(targmax) => targmax,
+            None => return Ok(true),
+        };
+        use crate::Command::*;
+        // last item: reject silently (NOTICE must not generate automatic replies).
+        let (targets, limit, silent) = match cmd {
+            JOIN { channels, .. } => (channels, targmax.join, false),
+            PART { channels, .. } => (channels, targmax.part, false),
+            KICK { users, .. } => (users, targmax.kick, false),
+            PRIVMSG { targets, .. } => (targets, targmax.privmsg, false),
+            NOTICE { targets, .. } => (targets, targmax.notice, true),
+            WHOIS { nickmasks, .. } => (nickmasks, targmax.whois, false),
+            USERHOST { nicknames } => {
+                let len = split_targets_limited(nicknames, targmax.userhost).0.len();
+                nicknames.truncate(len);
+                return Ok(true);
+            }
+            ISON { nicknames } => {
+                let len = split_targets_limited(nicknames, targmax.ison).0.len();
+                nicknames.truncate(len);
+                return Ok(true);
+            }
+            _ => return Ok(true),
+        };
+        if let Some(&target) = split_targets_limited(targets, limit).1.first() {
+            if !silent {
+                let client = conn_state.user_state.client_name();
+                self.feed_msg(&mut conn_state.stream, ErrTooManyTargets407 { client, target })
+                    .await?;
+            }
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
     // try to register connection state - print error if too many connections.
+    // If connection is refused then stream is returned to inform client.
     pub(crate) fn register_conn_state(
         &self,
         ip_addr: IpAddr,
         stream: Framed<DualTcpStream, IRCLinesCodec>,
-    ) -> Option<ConnState> {
-        if let Some(max_conns) = self.config.max_connections {
+        listener: Option<ListenerConfig>,
+    ) -> Result<ConnState, Box<Framed<DualTcpStream, IRCLinesCodec>>> {
+        // get server name, disabled capabilities and host display of listener.
+        let (servername, disabled_caps, host_display) = if let Some(listener) = listener {
+            (
+                listener.name,
+                listener.disabled_caps,
+                listener.host_display.or(self.config.host_display),
+            )
+        } else {
+            (
+                None,
+                self.config.disabled_caps.clone(),
+                self.config.host_display,
+            )
+        };
+        let disabled_caps = disabled_caps.unwrap_or_default().into_iter().collect();
+        let host_display = host_display.unwrap_or(HostDisplay::Hostname);
+        // exempt hosts are not limited by max_connections.
+        let exempt = self.config.max_connections_exempt.as_ref().map_or(false, |masks| {
+            let ip = ip_addr.to_string();
+            masks.iter().any(|mask| match_wildcard(mask, &ip))
+        });
+        if let Some(max_conns) = self.config.max_connections.filter(|_| !exempt) {
             // increment counter of connections count.
-            if self.conns_count.fetch_add(1, Ordering::SeqCst) < max_conns {
-                Some(ConnState::new(ip_addr, stream, self.conns_count.clone()))
-            } else {
+            if self.conns_count.fetch_add(1, Ordering::SeqCst) >= max_conns {
                 self.conns_count.fetch_sub(1, Ordering::SeqCst);
                 error!("Too many connections for IP {}", ip_addr);
-                None
+                return Err(Box::new(stream));
             }
         } else {
             self.conns_count.fetch_add(1, Ordering::SeqCst);
-            Some(ConnState::new(ip_addr, stream, self.conns_count.clone()))
         }
+        let mut conn_state = ConnState::new(
+            ip_addr,
+            stream,
+            self.conns_count.clone(),
+            servername,
+            disabled_caps,
+            self.new_uid(),
+        );
+        #[cfg(feature = "dns_lookup")]
+        {
+            conn_state.host_display = host_display;
+        }
+        if host_display == HostDisplay::Cloak {
+            // config validation does not allow cloaked hosts without key.
+            let key = self.config.cloak_key.as_deref().expect("No cloak key");
+            conn_state.user_state.set_hostname(cloak_host(ip_addr, key));
+        }
+        Ok(conn_state)
+    }
+
+    // generate new unique ID of connection.
+    fn new_uid(&self) -> String {
+        let id = self.next_uid.fetch_add(1, Ordering::SeqCst);
+        format!("{}{:06X}", UID_PREFIX, id)
+    }
+
+    // send error to client whose connection has been refused.
+    async fn refuse_connection(
+        &self,
+        stream: Framed<DualTcpStream, IRCLinesCodec>,
+        reason: &str,
+    ) -> Result<(), LinesCodecError> {
+        let mut stream = BufferedLineStream::new(stream);
+        self.feed_msg(&mut stream, format!("ERROR :{}", reason)).await?;
+        stream.flush().await
     }
 
-    pub(crate) async fn remove_user(&self, conn_state: &ConnState) {
+    // send ERROR with reason to client and mark connection to close. Message is flushed
+    // before connection is closed. All disconnections made by server should use it.
+    pub(super) async fn close_connection(
+        &self,
+        conn_state: &mut ConnState,
+        reason: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        conn_state.quit.store(1, Ordering::SeqCst);
+        self.feed_msg(&mut conn_state.stream, format!("ERROR :{}", reason))
+            .await?;
+        Ok(())
+    }
+
+    // send configured banner to client just after connection.
+    pub(crate) async fn send_banner(
+        &self,
+        conn_state: &mut ConnState,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(ref banner) = self.config.banner {
+            for line in banner.lines() {
+                self.feed_msg(&mut conn_state.stream, format!("NOTICE * :{}", line))
+                    .await?;
+            }
+            conn_state.stream.flush().await?;
+        }
+        Ok(())
+    }
+
+    // remove user or detach it if its session can be resumed later.
+    // Returns resume token if user has been detached.
+    pub(crate) async fn remove_user(&self, conn_state: &ConnState) -> Option<String> {
         if let Some(ref nick) = conn_state.user_state.nick {
             let mut state = self.state.write().await;
+            if let Some(user) = state.users.get_mut(nick) {
+                // do not remove user if its session has been resumed by other connection.
+                if !Arc::ptr_eq(&user.conn_stats, &conn_state.stream.stats()) {
+                    return None;
+                }
+                if let Some(ref reason) = conn_state.quit_reason {
+                    // session can not be resumed after quit.
+                    state.send_quit_to_peers(nick, reason);
+                } else if let Some(ref token) = user.resume_token {
+                    // keep user and collect messages sent to it until session resume.
+                    let (sender, receiver) = unbounded_channel();
+                    user.sender = sender;
+                    user.quit_sender = None;
+                    user.detached_receiver = Some(receiver);
+                    return Some(token.clone());
+                }
+            }
             state.remove_user(nick);
         }
+        None
+    }
+
+    // remove detached user if its session has not been resumed.
+    pub(crate) async fn remove_detached_user(&self, token: &str) {
+        let mut state = self.state.write().await;
+        if let Some(nick) = state.find_nick_by_resume_token(token) {
+            if state.users.get(&nick).unwrap().detached_receiver.is_some() {
+                info!("Session of {} has not been resumed", nick);
+                state.send_quit_to_peers(&nick, "Connection closed");
+                state.remove_user(&nick);
+            }
+        }
     }
 
     pub(crate) async fn process(&self, conn_state: &mut ConnState) -> Result<(), String> {
@@ -198,23 +539,29 @@ impl MainState {
                 Ok(())
             },
             Some(_) = conn_state.ping_receiver.recv() => {
+                if let Some(idle_timeout) = self.config.idle_timeout {
+                    if conn_state.last_active.elapsed() >= Duration::from_secs(idle_timeout) {
+                        info!("Idle timeout for {}", conn_state.user_state.source);
+                        self.close_connection(conn_state,
+                                "Idle timeout, connection will be closed.").await?;
+                        return Ok(())
+                    }
+                }
                 self.feed_msg(&mut conn_state.stream, "PING :LALAL").await?;
                 conn_state.run_pong_timeout(&self.config);
                 Ok(())
             }
             Some(_) = conn_state.timeout_receiver.recv() => {
                 info!("Pong timeout for {}", conn_state.user_state.source);
-                self.feed_msg(&mut conn_state.stream,
-                            "ERROR :Pong timeout, connection will be closed.").await?;
-                conn_state.quit.store(1, Ordering::SeqCst);
+                self.close_connection(conn_state,
+                            "Pong timeout, connection will be closed.").await?;
                 Ok(())
             }
             Ok((killer, comment)) = &mut conn_state.quit_receiver => {
                 info!("User {} killed by {}: {}", conn_state.user_state.source,
                             killer, comment);
-                self.feed_msg(&mut conn_state.stream,
-                        format!("ERROR :User killed by {}: {}", killer, comment)).await?;
-                conn_state.quit.store(1, Ordering::SeqCst);
+                self.close_connection(conn_state,
+                        &format!("User killed by {}: {}", killer, comment)).await?;
                 Ok(())
             }
             Ok(hostname_opt) = &mut conn_state.dns_lookup_receiver => {
@@ -264,7 +611,11 @@ impl MainState {
                                     ErrInputTooLong417{ client }).await?;
                         return Ok(())
                     },
-                    Some(Err(e)) => return Err(Box::new(e)),
+                    // underlying I/O error (also invalid UTF-8) - connection is unusable.
+                    Some(Err(LinesCodecError::Io(e))) => {
+                        conn_state.quit.store(1, Ordering::SeqCst);
+                        return Err(Box::new(e))
+                    }
                     // if end of stream
                     None => {
                         conn_state.quit.store(1, Ordering::SeqCst);
@@ -273,7 +624,7 @@ impl MainState {
                     }
                 };
 
-                let cmd = match Command::from_message(&msg) {
+                let mut cmd = match Command::from_message(&msg) {
                     Ok(cmd) => cmd,
                     // handle errors while parsing command.
                     Err(e) => {
@@ -285,8 +636,7 @@ impl MainState {
                                         ErrUnknownCommand421{ client,
                                         command: cmd_name }).await?;
                             }
-                            UnknownSubcommand(_, _)|ParameterDoesntMatch(_, _)|
-                                    WrongParameter(_, _) => {
+                            UnknownSubcommand(_, _)|WrongParameter(_, _) => {
                                 self.feed_msg(&mut conn_state.stream,
                                         format!("ERROR :{}", e)).await?;
                             }
@@ -317,14 +667,20 @@ impl MainState {
                 };
 
                 self.count_command(&cmd);
+                // replies to server's PING doesn't count as activity.
+                if !matches!(cmd, Command::PONG{ .. }) {
+                    conn_state.last_active = time::Instant::now();
+                }
 
                 use crate::Command::*;
                 // if user not authenticated
                 match cmd {
-                    CAP{ .. } | AUTHENTICATE{ } | PASS{ .. } | NICK{ .. } |
-                            USER{ .. } | QUIT{ } => {},
+                    CAP{ .. } | AUTHENTICATE{ .. } | PASS{ .. } | NICK{ .. } |
+                            USER{ .. } | QUIT{ .. } | RESUME{ .. } => {},
+                    // away can be set before registration with pre-away capability.
+                    AWAY{ .. } if conn_state.caps.pre_away => {},
                     _ => {
-                        // expect CAP, AUTHENTICATE, PASS, NICK, USER, QUIT -
+                        // expect CAP, AUTHENTICATE, PASS, NICK, USER, QUIT, RESUME -
                         // other commands need authenication.
                         if !conn_state.user_state.authenticated {
                             self.feed_msg(&mut conn_state.stream, ErrNotRegistered451{
@@ -334,11 +690,29 @@ impl MainState {
                     }
                 }
 
+                // commands only for operators
+                if conn_state.user_state.authenticated && self.oper_command(&cmd) {
+                    let oper = {
+                        let state = self.state.read().await;
+                        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+                        state.users.get(user_nick).map_or(false, |u| u.modes.is_local_oper())
+                    };
+                    if !oper {
+                        self.feed_msg(&mut conn_state.stream, ErrNoPrivileges481{
+                                client: conn_state.user_state.client_name() }).await?;
+                        return Ok(())
+                    }
+                }
+
+                if !self.enforce_targmax(conn_state, &mut cmd).await? {
+                    return Ok(())
+                }
+
                 match cmd {
                     CAP{ subcommand, caps, version } =>
                         self.process_cap(conn_state, subcommand, caps, version).await,
-                    AUTHENTICATE{ } =>
-                        self.process_authenticate(conn_state).await,
+                    AUTHENTICATE{ data } =>
+                        self.process_authenticate(conn_state, data).await,
                     PASS{ password } =>
                         self.process_pass(conn_state, password).await,
                     NICK{ nickname } =>
@@ -346,11 +720,12 @@ impl MainState {
                     USER{ username, hostname, servername, realname } =>
                         self.process_user(conn_state, username, hostname,
                                 servername, realname).await,
-                    PING{ token } => self.process_ping(conn_state, token).await,
+                    PING{ token, server } =>
+                        self.process_ping(conn_state, token, server).await,
                     PONG{ token } => self.process_pong(conn_state, token).await,
                     OPER{ name, password } =>
                         self.process_oper(conn_state, name, password).await,
-                    QUIT{ } => self.process_quit(conn_state).await,
+                    QUIT{ reason } => self.process_quit(conn_state, reason).await,
                     JOIN{ channels, keys } =>
                         self.process_join(conn_state, channels, keys).await,
                     PART{ channels, reason } =>
@@ -397,7 +772,8 @@ impl MainState {
                         self.process_whowas(conn_state, nickname, count, server).await,
                     KILL{ nickname, comment } =>
                         self.process_kill(conn_state, nickname, comment).await,
-                    REHASH{ } => self.process_rehash(conn_state).await,
+                    REHASH{ subcommand } =>
+                        self.process_rehash(conn_state, subcommand).await,
                     RESTART{ } => self.process_restart(conn_state).await,
                     SQUIT{ server, comment } =>
                         self.process_squit(conn_state, server, comment).await,
@@ -411,6 +787,30 @@ impl MainState {
                         self.process_ison(conn_state, nicknames).await,
                     DIE{ message } =>
                         self.process_die(conn_state, message).await,
+                    RESUME{ token } =>
+                        self.process_resume(conn_state, token).await,
+                    OPERWALL{ .. } =>
+                        self.process_operwall(conn_state, &msg).await,
+                    MONITOR{ subcommand, targets } =>
+                        self.process_monitor(conn_state, subcommand, targets).await,
+                    WATCH{ entries } =>
+                        self.process_watch(conn_state, entries).await,
+                    CPRIVMSG{ nickname, channel, text } =>
+                        self.process_cprivmsg(conn_state, nickname, channel, text).await,
+                    CNOTICE{ nickname, channel, text } =>
+                        self.process_cnotice(conn_state, nickname, channel, text).await,
+                    RENAME{ channel, new_channel, reason } =>
+                        self.process_rename(conn_state, channel, new_channel, reason).await,
+                    CLEARCHAN{ channel, all, lock } =>
+                        self.process_clearchan(conn_state, channel, all, lock).await,
+                    ACCEPT{ nicknames } =>
+                        self.process_accept(conn_state, nicknames).await,
+                    ACCESS{ channel, subcommand } =>
+                        self.process_access(conn_state, channel, subcommand).await,
+                    RELAYMSG{ channel, nick, text } =>
+                        self.process_relaymsg(conn_state, channel, nick, text).await,
+                    SESSIONS{ subcommand } =>
+                        self.process_sessions(conn_state, subcommand).await,
                 }
             },
         }
@@ -437,28 +837,78 @@ impl MainState {
 }
 
 // main process to handle commands from client.
-async fn user_state_process(main_state: Arc<MainState>, stream: DualTcpStream, addr: SocketAddr) {
-    let line_stream = Framed::new(stream, IRCLinesCodec::new_with_max_length(2000));
-    if let Some(mut conn_state) = main_state.register_conn_state(addr.ip(), line_stream) {
-        #[cfg(feature = "dns_lookup")]
-        if main_state.config.dns_lookup {
-            conn_state.run_dns_lookup();
-        }
-        #[cfg(not(feature = "dns_lookup"))]
-        if main_state.config.dns_lookup {
-            error!("DNS lookup is not enabled!");
-        }
-
-        while !conn_state.is_quit() {
-            if let Err(e) = main_state.process(&mut conn_state).await {
-                error!("Error for {}: {}", conn_state.user_state.source, e);
+async fn user_state_process(
+    main_state: Arc<MainState>,
+    stream: DualTcpStream,
+    addr: SocketAddr,
+    listener: Option<ListenerConfig>,
+) {
+    let max_line_length = main_state.config.max_line_length.unwrap_or(DEFAULT_LINELEN);
+    let line_stream = Framed::new(stream, IRCLinesCodec::new_with_max_length(max_line_length));
+    let mut conn_state =
+        match main_state.register_conn_state(addr.ip(), line_stream, listener) {
+            Ok(conn_state) => conn_state,
+            Err(line_stream) => {
+                // inform client that server is full before closing connection.
+                if let Err(e) = main_state.refuse_connection(*line_stream, "Server full").await {
+                    error!("Can't refuse connection for {}: {}", addr.ip(), e);
+                }
+                return;
             }
+        };
+
+    // DNS lookup is useless if hostname is not displayed.
+    #[cfg(feature = "dns_lookup")]
+    if main_state.config.dns_lookup && conn_state.host_display == HostDisplay::Hostname {
+        conn_state.run_dns_lookup();
+    }
+    #[cfg(not(feature = "dns_lookup"))]
+    if main_state.config.dns_lookup {
+        error!("DNS lookup is not enabled!");
+    }
+
+    if let Err(e) = main_state.send_banner(&mut conn_state).await {
+        error!("Can't send banner to {}: {}", addr.ip(), e);
+    }
+
+    while !conn_state.is_quit() {
+        if let Err(e) = main_state.process(&mut conn_state).await {
+            error!("Error for {}: {}", conn_state.user_state.source, e);
         }
-        info!(
-            "User {} gone from from server",
-            conn_state.user_state.source
-        );
-        main_state.remove_user(&conn_state).await;
+    }
+    info!("User {} gone from from server", conn_state.user_state.source);
+    if let Some(token) = main_state.remove_user(&conn_state).await {
+        // wait for resuming session.
+        let resume_timeout = main_state.config.resume_timeout.unwrap_or_default();
+        tokio::spawn(async move {
+            time::sleep(Duration::from_secs(resume_timeout)).await;
+            main_state.remove_detached_user(&token).await;
+        });
+    }
+}
+
+// accepts any client certificate or no certificate. Certificate is used only to get
+// its fingerprint for SASL EXTERNAL, so it is not verified against any CA.
+#[cfg(feature = "tls_rustls")]
+struct AnyClientCertVerifier;
+
+#[cfg(feature = "tls_rustls")]
+impl rustls::server::ClientCertVerifier for AnyClientCertVerifier {
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        Some(false)
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<rustls::DistinguishedNames> {
+        Some(vec![])
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::server::ClientCertVerified, rustls::Error> {
+        Ok(rustls::server::ClientCertVerified::assertion())
     }
 }
 
@@ -475,6 +925,7 @@ async fn user_state_process_tls(
                 main_state,
                 DualTcpStream::SecureStream(Box::new(tls_stream)),
                 addr,
+                None,
             )
             .await
         }
@@ -506,7 +957,7 @@ async fn user_state_process_tls(
 ) {
     match user_state_process_tls_prepare(stream, acceptor).await {
         Ok(stream) => {
-            user_state_process(main_state, DualTcpStream::SecureStream(stream), addr).await
+            user_state_process(main_state, DualTcpStream::SecureStream(stream), addr, None).await
         }
         Err(e) => error!("Can't accept TLS connection: {}", e),
     };
@@ -603,17 +1054,89 @@ async fn dns_lookup_process(
     }
 }
 
+// open GeoIP database given in configuration. If database can't be opened then
+// server works without GeoIP.
+#[cfg(feature = "geoip")]
+fn open_geoip_lookup(config: &MainConfig) -> Option<GeoIpLookup> {
+    let path = config.geoip_db.as_ref()?;
+    match maxminddb::Reader::open_readfile(path) {
+        Ok(reader) => Some(Box::new(move |ip| {
+            reader
+                .lookup::<maxminddb::geoip2::Country>(ip)
+                .ok()
+                .and_then(|c| c.country)
+                .and_then(|c| c.iso_code)
+                .map(|c| c.to_string())
+        })),
+        Err(e) => {
+            error!("Can't open GeoIP database {}: {}", path, e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "geoip"))]
+fn open_geoip_lookup(config: &MainConfig) -> Option<GeoIpLookup> {
+    if config.geoip_db.is_some() {
+        error!("GeoIP is not enabled!");
+    }
+    None
+}
+
+// bind listener with socket options. Without options it works like TcpListener::bind.
+async fn bind_listener(
+    listen: IpAddr,
+    port: u16,
+    socket_config: Option<SocketConfig>,
+) -> io::Result<TcpListener> {
+    let socket_config = match socket_config {
+        Some(socket_config) => socket_config,
+        None => return TcpListener::bind((listen, port)).await,
+    };
+    let socket = if listen.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(socket_config.reuseaddr.unwrap_or(cfg!(unix)))?;
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    socket.set_reuseport(socket_config.reuseport)?;
+    #[cfg(not(all(unix, not(target_os = "solaris"), not(target_os = "illumos"))))]
+    if socket_config.reuseport {
+        warn!("SO_REUSEPORT is not supported on this system");
+    }
+    socket.set_nodelay(socket_config.nodelay)?;
+    socket.set_keepalive(socket_config.keepalive)?;
+    socket.bind(SocketAddr::new(listen, port))?;
+    socket.listen(socket_config.backlog.unwrap_or(1024))
+}
+
 // main routine to run server
 pub(crate) async fn run_server(
     config: MainConfig,
 ) -> Result<(Arc<MainState>, JoinHandle<()>), Box<dyn Error>> {
+    run_server_with_state(MainState::new_from_config(config)).await
+}
+
+async fn run_server_with_state(
+    main_state: MainState,
+) -> Result<(Arc<MainState>, JoinHandle<()>), Box<dyn Error>> {
+    let config = &main_state.config;
     #[cfg(feature = "dns_lookup")]
     if config.dns_lookup {
         initialize_dns_resolver();
     }
-    let listener = TcpListener::bind((config.listen, config.port)).await?;
+    let listener = bind_listener(config.listen, config.port, config.socket).await?;
+    // bind additional listeners.
+    let mut extra_listeners = vec![];
+    if let Some(ref listeners) = config.listeners {
+        for l in listeners {
+            let listener = bind_listener(l.listen, l.port, l.socket).await?;
+            extra_listeners.push((listener, l.clone()));
+        }
+    }
     let cloned_tls = config.tls.clone();
-    let main_state = Arc::new(MainState::new_from_config(config));
+    let main_state = Arc::new(main_state);
     let main_state_to_return = main_state.clone();
     let handle = if cloned_tls.is_some() {
         #[cfg(feature = "tls_rustls")]
@@ -630,7 +1153,7 @@ pub(crate) async fn run_server(
 
                 rustls::ServerConfig::builder()
                     .with_safe_defaults()
-                    .with_no_client_auth()
+                    .with_client_cert_verifier(Arc::new(AnyClientCertVerifier))
                     .with_single_cert(certs, keys.remove(0))
                     .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
             };
@@ -665,6 +1188,8 @@ pub(crate) async fn run_server(
             let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
             acceptor.set_private_key_file(tlsconfig.cert_key_file, SslFiletype::PEM)?;
             acceptor.set_certificate_chain_file(tlsconfig.cert_file)?;
+            // request client certificate (for SASL EXTERNAL), but do not verify it.
+            acceptor.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
             let acceptor = Arc::new(acceptor.build());
 
             tokio::spawn(async move {
@@ -702,7 +1227,7 @@ pub(crate) async fn run_server(
                         match res {
                             Ok((stream, addr)) => {
                                 tokio::spawn(user_state_process(main_state.clone(),
-                                        DualTcpStream::PlainStream(stream), addr)); }
+                                        DualTcpStream::PlainStream(stream), addr, None)); }
                             Err(e) => { error!("Accept connection error: {}", e); }
                         };
                     }
@@ -714,6 +1239,41 @@ pub(crate) async fn run_server(
             }
         })
     };
+
+    // run additional listeners - they will be stopped after main listener.
+    let extra_handles = extra_listeners
+        .into_iter()
+        .map(|(listener, listener_config)| {
+            let main_state = main_state_to_return.clone();
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, addr)) => {
+                            tokio::spawn(user_state_process(
+                                main_state.clone(),
+                                DualTcpStream::PlainStream(stream),
+                                addr,
+                                Some(listener_config.clone()),
+                            ));
+                        }
+                        Err(e) => {
+                            error!("Accept connection error: {}", e);
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let handle = if !extra_handles.is_empty() {
+        tokio::spawn(async move {
+            if let Err(e) = handle.await {
+                error!("Main listener error: {}", e);
+            }
+            extra_handles.iter().for_each(|h| h.abort());
+        })
+    } else {
+        handle
+    };
     Ok((main_state_to_return, handle))
 }
 
@@ -724,10 +1284,13 @@ mod test {
     pub(crate) use std::collections::HashSet;
     pub(crate) use std::iter::FromIterator;
     pub(crate) use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
     use tokio::net::TcpStream;
     pub(crate) use tokio::time;
 
     use std::sync::atomic::AtomicU16;
+    #[cfg(feature = "tls_rustls")]
+    use sha2::{Digest, Sha256};
 
     static PORT_COUNTER: AtomicU16 = AtomicU16::new(7888);
     //use std::sync::Once;
@@ -787,12 +1350,37 @@ mod test {
         realname: &'a str,
     ) -> Framed<TcpStream, IRCLinesCodec> {
         let mut line_stream = login_to_test(port, nick, name, realname).await;
-        for _ in 0..18 {
+        for _ in 0..19 {
             line_stream.next().await.unwrap().unwrap();
         }
         line_stream
     }
 
+    // login by SASL PLAIN with base64 payload. Caps negotiation must be finished by CAP END.
+    pub(crate) async fn login_to_test_with_sasl<'a>(
+        port: u16,
+        nick: &'a str,
+        name: &'a str,
+        payload: &'a str,
+    ) -> Framed<TcpStream, IRCLinesCodec> {
+        let mut line_stream = connect_to_test(port).await;
+        line_stream.send("CAP REQ :sasl".to_string()).await.unwrap();
+        line_stream.send(format!("NICK {}", nick)).await.unwrap();
+        line_stream
+            .send(format!("USER {} 8 * :{}", name, nick))
+            .await
+            .unwrap();
+        line_stream
+            .send("AUTHENTICATE PLAIN".to_string())
+            .await
+            .unwrap();
+        line_stream
+            .send(format!("AUTHENTICATE {}", payload))
+            .await
+            .unwrap();
+        line_stream
+    }
+
     #[cfg(any(feature = "tls_rustls", feature = "openssl"))]
     use std::path::PathBuf;
 
@@ -841,7 +1429,16 @@ mod test {
     pub(crate) async fn connect_to_test_tls(
         port: u16,
     ) -> Framed<tokio_rustls::client::TlsStream<TcpStream>, IRCLinesCodec> {
-        let mut certs: Vec<Certificate> = rustls_pemfile::certs(&mut BufReader::new(
+        connect_to_test_tls_with_client_cert(port, false).await
+    }
+
+    // client can send test certificate as its own certificate (for SASL EXTERNAL).
+    #[cfg(feature = "tls_rustls")]
+    pub(crate) async fn connect_to_test_tls_with_client_cert(
+        port: u16,
+        client_cert: bool,
+    ) -> Framed<tokio_rustls::client::TlsStream<TcpStream>, IRCLinesCodec> {
+        let certs: Vec<Certificate> = rustls_pemfile::certs(&mut BufReader::new(
             File::open(get_cert_file_path()).unwrap(),
         ))
         .map(|mut certs| certs.drain(..).map(Certificate).collect())
@@ -849,13 +1446,20 @@ mod test {
         let dnsname = rustls::client::ServerName::try_from("localhost").unwrap();
 
         let mut cert_store = rustls::RootCertStore { roots: vec![] };
-        cert_store.add(&certs.remove(0)).unwrap();
-        let config = Arc::new(
-            rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(cert_store)
-                .with_no_client_auth(),
-        );
+        cert_store.add(&certs[0]).unwrap();
+        let builder = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(cert_store);
+        let config = Arc::new(if client_cert {
+            let mut keys: Vec<PrivateKey> = rustls_pemfile::pkcs8_private_keys(
+                &mut BufReader::new(File::open(get_cert_key_file_path()).unwrap()),
+            )
+            .map(|mut keys| keys.drain(..).map(PrivateKey).collect())
+            .unwrap();
+            builder.with_single_cert(certs, keys.remove(0)).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        });
         let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
         Framed::new(
             TlsConnector::from(config)
@@ -866,6 +1470,16 @@ mod test {
         )
     }
 
+    // SHA-256 fingerprint of test certificate.
+    #[cfg(feature = "tls_rustls")]
+    pub(crate) fn get_test_cert_fingerprint() -> String {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(
+            File::open(get_cert_file_path()).unwrap(),
+        ))
+        .unwrap();
+        hex_string(&Sha256::digest(&certs[0]))
+    }
+
     #[cfg(feature = "tls_rustls")]
     pub(crate) async fn login_to_test_tls<'a>(
         port: u16,
@@ -890,7 +1504,7 @@ mod test {
         realname: &'a str,
     ) -> Framed<tokio_rustls::client::TlsStream<TcpStream>, IRCLinesCodec> {
         let mut line_stream = login_to_test_tls(port, nick, name, realname).await;
-        for _ in 0..18 {
+        for _ in 0..19 {
             line_stream.next().await.unwrap().unwrap();
         }
         line_stream
@@ -944,7 +1558,7 @@ mod test {
         realname: &'a str,
     ) -> Framed<SslStream<TcpStream>, IRCLinesCodec> {
         let mut line_stream = login_to_test_tls(port, nick, name, realname).await;
-        for _ in 0..18 {
+        for _ in 0..19 {
             line_stream.next().await.unwrap().unwrap();
         }
         line_stream
@@ -959,7 +1573,7 @@ mod test {
             let mut line_stream = Framed::new(stream, IRCLinesCodec::new_with_max_length(10000));
             line_stream.send("POG :welcome".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 421 127.0.0.1 POG :Unknown command".to_string(),
+                ":irc.irc 421 * POG :Unknown command".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             line_stream.send("".to_string()).await.unwrap();
@@ -984,7 +1598,7 @@ mod test {
             );
             line_stream.send("PING :welcome".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 451 127.0.0.1 :You have not registered".to_string(),
+                ":irc.irc 451 * :You have not registered".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             line_stream.send("CAP XXX".to_string()).await.unwrap();
@@ -994,17 +1608,17 @@ mod test {
             );
             line_stream.send("PRIVMSG".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 461 127.0.0.1 PRIVMSG :Not enough parameters".to_string(),
+                ":irc.irc 461 * PRIVMSG :Not enough parameters".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             line_stream.send("MODE lol +T".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 501 127.0.0.1 :Unknown MODE flag".to_string(),
+                ":irc.irc 501 * :Unknown MODE flag".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             line_stream.send("MODE #bum +T".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 472 127.0.0.1 T :is unknown mode char for #bum".to_string(),
+                ":irc.irc 472 * T :is unknown mode char for #bum".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             line_stream
@@ -1012,7 +1626,7 @@ mod test {
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 696 127.0.0.1 #bum l xxx :invalid digit found in string".to_string(),
+                ":irc.irc 696 * #bum l xxx :invalid digit found in string".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             let mut toolong = String::new();
@@ -1021,11 +1635,49 @@ mod test {
             }
             line_stream.send(toolong).await.unwrap();
             assert_eq!(
-                ":irc.irc 417 127.0.0.1 :Input line was too long".to_string(),
+                ":irc.irc 417 * :Input line was too long".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_codec_errors() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "oliver", "oliver", "Oliver Twist").await;
+            let toolong = "c".repeat(4000);
+            line_stream.send(toolong).await.unwrap();
+            assert_eq!(
+                ":irc.irc 417 oliver :Input line was too long".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // connection still alive after length violation
+            line_stream.send("PING alive".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :alive".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            // invalid UTF-8 is I/O error - connection must be closed
+            line_stream
+                .get_mut()
+                .write_all(b"PING \xff\xfe\r\n")
+                .await
+                .unwrap();
+            let next = time::timeout(Duration::from_secs(2), line_stream.next())
+                .await
+                .unwrap();
+            assert!(next.is_none());
         }
 
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!main_state.state.read().await.users.contains_key("oliver"));
+
         quit_test_server(main_state, handle).await;
     }
 
@@ -1065,30 +1717,36 @@ mod test {
                     env!("CARGO_PKG_NAME"),
                     "-",
                     env!("CARGO_PKG_VERSION"),
-                    " Oiorw Iabehiklmnopqstv"
+                    " BHOgiorw DIPRabcefghiklmnopqstv"
                 ),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
                 ":irc.irc 005 mati AWAYLEN=1000 CASEMAPPING=ascii \
-                    CHANMODES=Iabehiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# EXCEPTS=e FNC \
-                    HOSTLEN=1000 INVEX=I KEYLEN=1000 :are supported by this server"
+                    CHANMODES=DIPRabcefghiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# CNOTICE \
+                    CPRIVMSG EXCEPTS=e FNC HOSTLEN=1000 INVEX=I KEYLEN=1000 KICKLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 mati KICKLEN=1000 LINELEN=2000 MAXLIST=beI:1000 \
-                    MAXNICKLEN=200 MAXPARA=500 MAXTARGETS=500 MODES=500 NETWORK=IRCnetwork \
-                    NICKLEN=200 PREFIX=(qaohv)~&@%+ :are supported by this server"
+                ":irc.irc 005 mati LINELEN=2000 MAXLIST=beI:1000 MAXNICKLEN=200 \
+                    MAXPARA=500 MAXTARGETS=500 MODES=500 MONITOR NETWORK=IRCnetwork \
+                    NICKLEN=200 PREFIX=(qaohv)~&@%+ SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 mati SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 USERLEN=200 \
-                    USERMODES=Oiorw :are supported by this server"
+                ":irc.irc 005 mati USERLEN=200 USERMODES=BHOgiorw WATCH \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            assert_eq!(
+                ":irc.irc 042 mati 0AA000000 :your unique ID".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
             assert_eq!(
                 ":irc.irc 251 mati :There are 1 users and 0 invisible \
                     on 1 servers"
@@ -1158,7 +1816,7 @@ mod test {
 
             line_stream.send("QUIT :Bye".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc ERROR: Closing connection".to_string(),
+                ":irc.irc ERROR :Closing Link: 127.0.0.1 (Quit: Bye)".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -1175,6 +1833,312 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_server_listener_name() {
+        let mut config = MainConfig::default();
+        let listener_port = PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
+        config.listeners = Some(vec![ListenerConfig {
+            listen: "127.0.0.1".parse().unwrap(),
+            port: listener_port,
+            name: Some("chat.example.net".to_string()),
+            disabled_caps: None,
+            host_display: None,
+            socket: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            for (port, servername) in [(port, "irc.irc"), (listener_port, "chat.example.net")] {
+                let mut line_stream = login_to_test(port, "mati", "mat", "MatiSzpaki").await;
+                assert_eq!(
+                    format!(
+                        ":{} 001 mati :Welcome to the IRCnetwork \
+                        Network, mati!~mat@127.0.0.1",
+                        servername
+                    ),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                assert_eq!(
+                    format!(
+                        ":{} 002 mati :Your host is {}, running version {}-{}",
+                        servername,
+                        servername,
+                        env!("CARGO_PKG_NAME"),
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                assert_eq!(
+                    format!(
+                        ":{} 003 mati :This server was created {}",
+                        servername, main_state.created
+                    ),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                assert_eq!(
+                    format!(
+                        ":{} 004 mati {} {}-{} BHOgiorw DIPRabcefghiklmnopqstv",
+                        servername,
+                        servername,
+                        env!("CARGO_PKG_NAME"),
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                let line = line_stream.next().await.unwrap().unwrap();
+                assert!(
+                    line.starts_with(&format!(":{} 005 mati ", servername)),
+                    "{}",
+                    line
+                );
+                line_stream.send("PING :x".to_string()).await.unwrap();
+                let pong = format!(":{} PONG {} :x", servername, servername);
+                // skip rest of registration burst
+                while line_stream.next().await.unwrap().unwrap() != pong {}
+                line_stream.send("QUIT :Bye".to_string()).await.unwrap();
+                // wait for removing user
+                time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_host_display() {
+        let mut config = MainConfig::default();
+        config.host_display = Some(HostDisplay::Cloak);
+        config.cloak_key = Some("secret".to_string());
+        let listener_port = PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
+        config.listeners = Some(vec![ListenerConfig {
+            listen: "127.0.0.1".parse().unwrap(),
+            port: listener_port,
+            name: None,
+            disabled_caps: None,
+            host_display: Some(HostDisplay::Ip),
+            socket: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+        let cloak = cloak_host("127.0.0.1".parse().unwrap(), "secret");
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(listener_port, "bowie", "bowie", "Bowie Catcher").await;
+
+            line_stream.send("JOIN #hosts".to_string()).await.unwrap();
+            assert_eq!(
+                format!(":alan!~alan@{} JOIN #hosts", cloak),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("JOIN #hosts".to_string()).await.unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 JOIN #hosts".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_listener_disabled_caps() {
+        let mut config = MainConfig::default();
+        let listener_port = PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
+        config.listeners = Some(vec![ListenerConfig {
+            listen: "127.0.0.1".parse().unwrap(),
+            port: listener_port,
+            name: None,
+            disabled_caps: Some(vec!["draft/channel-rename".to_string()]),
+            host_display: None,
+            socket: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc CAP * LS :multi-prefix draft/channel-rename \
+                    draft/extended-monitor draft/pre-away"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            let mut line_stream = connect_to_test(listener_port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc CAP * LS :multi-prefix draft/extended-monitor draft/pre-away"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CAP REQ :draft/channel-rename".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc CAP * NAK :draft/channel-rename".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CAP REQ :multi-prefix".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc CAP * ACK :multi-prefix".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_isupport_targmax() {
+        let mut config = MainConfig::default();
+        config.targmax = Some(TargMaxConfig {
+            join: Some(4),
+            privmsg: Some(3),
+            whois: Some(1),
+            ..TargMaxConfig::default()
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test(port, "mati", "mat", "MatiSzpaki").await;
+            for _ in 0..5 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc 005 mati LINELEN=2000 MAXLIST=beI:1000 MAXNICKLEN=200 \
+                    MAXPARA=500 MAXTARGETS=500 MODES=500 MONITOR NETWORK=IRCnetwork \
+                    NICKLEN=200 PREFIX=(qaohv)~&@%+ SAFELIST STATUSMSG=~&@%+ \
+                    TARGMAX=ISON:,JOIN:4,KICK:,NOTICE:,PART:,PRIVMSG:3,USERHOST:,WHOIS:1 \
+                    :are supported by this server"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_banner() {
+        let mut config = MainConfig::default();
+        config.banner = Some("*** Welcome to ExampleNet\n*** Be nice".to_string());
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            // banner is sent before registration
+            assert_eq!(
+                ":irc.irc NOTICE * :*** Welcome to ExampleNet".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc NOTICE * :*** Be nice".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("NICK mati".to_string()).await.unwrap();
+            line_stream
+                .send("USER mat 8 * :MatiSzpaki".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 001 mati :Welcome to the IRCnetwork Network, mati!~mat@127.0.0.1"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_isupport_linelen() {
+        let mut config = MainConfig::default();
+        config.max_line_length = Some(1000);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test(port, "mati", "mat", "MatiSzpaki").await;
+            for _ in 0..4 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let mut tokens = vec![];
+            for _ in 0..3 {
+                let line = line_stream.next().await.unwrap().unwrap();
+                tokens.extend(line.split(' ').map(|t| t.to_string()));
+            }
+            assert!(tokens.contains(&"LINELEN=1000".to_string()));
+            for _ in 0..12 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            line_stream
+                .send(format!("PING :{}", "c".repeat(900)))
+                .await
+                .unwrap();
+            assert_eq!(
+                format!(":irc.irc PONG irc.irc :{}", "c".repeat(900)),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("c".repeat(1001)).await.unwrap();
+            assert_eq!(
+                ":irc.irc 417 mati :Input line was too long".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    #[tokio::test]
+    async fn test_server_bind_listener_reuseport() {
+        let socket_config = SocketConfig {
+            reuseport: true,
+            ..SocketConfig::default()
+        };
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+        let listener = bind_listener(localhost, 0, Some(socket_config))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // second listener can bind the same port.
+        let listener2 = bind_listener(localhost, port, Some(socket_config))
+            .await
+            .unwrap();
+        assert_eq!(port, listener2.local_addr().unwrap().port());
+        // but not without SO_REUSEPORT.
+        assert!(bind_listener(localhost, port, None).await.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_server_bind_listener_options() {
+        let socket_config = SocketConfig {
+            backlog: Some(16),
+            nodelay: true,
+            keepalive: true,
+            ..SocketConfig::default()
+        };
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+        let listener = bind_listener(localhost, 0, Some(socket_config))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        // accepted connection inherits options from listening socket.
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(stream.nodelay().unwrap());
+    }
+
     #[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
     #[tokio::test]
     async fn test_server_tls_first() {
@@ -1211,30 +2175,36 @@ mod test {
                     env!("CARGO_PKG_NAME"),
                     "-",
                     env!("CARGO_PKG_VERSION"),
-                    " Oiorw Iabehiklmnopqstv"
+                    " BHOgiorw DIPRabcefghiklmnopqstv"
                 ),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
                 ":irc.irc 005 mati AWAYLEN=1000 CASEMAPPING=ascii \
-                    CHANMODES=Iabehiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# EXCEPTS=e FNC \
-                    HOSTLEN=1000 INVEX=I KEYLEN=1000 :are supported by this server"
+                    CHANMODES=DIPRabcefghiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# CNOTICE \
+                    CPRIVMSG EXCEPTS=e FNC HOSTLEN=1000 INVEX=I KEYLEN=1000 KICKLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 mati KICKLEN=1000 LINELEN=2000 MAXLIST=beI:1000 \
-                    MAXNICKLEN=200 MAXPARA=500 MAXTARGETS=500 MODES=500 NETWORK=IRCnetwork \
-                    NICKLEN=200 PREFIX=(qaohv)~&@%+ :are supported by this server"
+                ":irc.irc 005 mati LINELEN=2000 MAXLIST=beI:1000 MAXNICKLEN=200 \
+                    MAXPARA=500 MAXTARGETS=500 MODES=500 MONITOR NETWORK=IRCnetwork \
+                    NICKLEN=200 PREFIX=(qaohv)~&@%+ SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 mati SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 USERLEN=200 \
-                    USERMODES=Oiorw :are supported by this server"
+                ":irc.irc 005 mati USERLEN=200 USERMODES=BHOgiorw WATCH \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            assert_eq!(
+                ":irc.irc 042 mati 0AA000000 :your unique ID".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
             assert_eq!(
                 ":irc.irc 251 mati :There are 1 users and 0 invisible \
                     on 1 servers"
@@ -1329,6 +2299,215 @@ mod test {
 
         quit_test_server(main_state, handle).await;
     }
+
+    #[tokio::test]
+    async fn test_server_idle_timeout() {
+        let mut config = MainConfig::default();
+        config.idle_timeout = Some(300);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut idle_stream = login_to_test_and_skip(port, "mati", "mat", "MatiSzpaki").await;
+            let mut line_stream =
+                login_to_test_and_skip(port, "tommy", "tommy", "Tommy Active").await;
+
+            for round in 0..3 {
+                time::pause();
+                time::advance(Duration::from_secs(120)).await;
+                time::resume();
+                if round < 2 {
+                    // only replies to PING
+                    assert_eq!(
+                        ":irc.irc PING :LALAL".to_string(),
+                        idle_stream.next().await.unwrap().unwrap()
+                    );
+                    idle_stream.send("PONG :LALAL".to_string()).await.unwrap();
+                } else {
+                    assert_eq!(
+                        ":irc.irc ERROR :Idle timeout, connection will be closed.".to_string(),
+                        idle_stream.next().await.unwrap().unwrap()
+                    );
+                }
+
+                assert_eq!(
+                    ":irc.irc PING :LALAL".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                line_stream.send("PONG :LALAL".to_string()).await.unwrap();
+                line_stream.send("PING :alive".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc PONG irc.irc :alive".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+                time::sleep(Duration::from_millis(50)).await;
+            }
+
+            let state = main_state.state.read().await;
+            assert!(!state.users.contains_key("mati"));
+            assert!(state.users.contains_key("tommy"));
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_geoip() {
+        let mut config = MainConfig::default();
+        config.port = PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let port = config.port;
+        let mut main_state = MainState::new_from_config(config);
+        // stub lookup instead of GeoIP database.
+        main_state.geoip_lookup = Some(Box::new(|ip| {
+            if ip == IpAddr::from([127, 0, 0, 1]) {
+                Some("PL".to_string())
+            } else {
+                None
+            }
+        }));
+        let (main_state, handle) = run_server_with_state(main_state).await.unwrap();
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            assert_eq!(
+                Some("PL".to_string()),
+                main_state.state.read().await.users["fanny"].country
+            );
+
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("WHOIS fanny".to_string()).await.unwrap();
+            let mut country_line = false;
+            loop {
+                let line = line_stream.next().await.unwrap().unwrap();
+                if line == ":irc.irc 320 fanny fanny :is connecting from country PL" {
+                    country_line = true;
+                }
+                if line.starts_with(":irc.irc 318 ") {
+                    break;
+                }
+            }
+            assert!(country_line);
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_oper_commands() {
+        let mut config = MainConfig::default();
+        config.oper_commands = Some(vec!["admin".to_string()]);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            // configured command
+            line_stream.send("ADMIN".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 fanny :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // built-in operator command
+            line_stream.send("REHASH".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 fanny :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("ADMIN".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 256 fanny irc.irc :Administrative info".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_max_connections() {
+        let mut config = MainConfig::default();
+        config.max_connections = Some(2);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "mati", "mat", "MatiSzpaki").await;
+            let line_stream2 = connect_to_test(port).await;
+            time::sleep(Duration::from_millis(50)).await;
+
+            let mut line_stream3 = connect_to_test(port).await;
+            assert_eq!(
+                ":irc.irc ERROR :Server full".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            assert!(line_stream3.next().await.is_none());
+
+            // disconnect frees slot
+            drop(line_stream2);
+            time::sleep(Duration::from_millis(50)).await;
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "lucas", "luke", "LucasSkywalker").await;
+            line_stream3.send("PING :bumbum".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :bumbum".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :bumbum".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :bumbum".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_max_connections_exempt() {
+        let mut config = MainConfig::default();
+        config.max_connections = Some(1);
+        config.max_connections_exempt = Some(vec!["127.0.0.*".to_string()]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream = login_to_test_and_skip(port, "mati", "mat", "MatiSzpaki").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "lucas", "luke", "LucasSkywalker").await;
+            line_stream2.send("PING :bumbum".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :bumbum".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :bumbum".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :bumbum".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
 }
 
 mod channel_cmds;
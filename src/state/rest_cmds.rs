@@ -20,10 +20,36 @@
 use super::*;
 use std::collections::HashSet;
 use std::error::Error;
-use std::iter::FromIterator;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::ops::DerefMut;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// minimal interval between caller-ID notifications sent to the same target.
+const CALLERID_NOTIFY_INTERVAL: Duration = Duration::from_secs(60);
+// separator that must be in nicks of users relayed by RELAYMSG (for example discord/alice).
+const RELAYMSG_SEPARATOR: char = '/';
 
 impl super::MainState {
+    // check whether user can send message to channel: external messages (+n and +s),
+    // bans, moderation (+m) and colors (+c) are checked.
+    fn can_send_to_channel(
+        &self,
+        state: &VolatileState,
+        channel: &str,
+        user_nick: &str,
+        source: &str,
+        text: &str,
+    ) -> bool {
+        let chanobj = state.channels.get(channel).unwrap();
+        let chum = chanobj.users.get(user_nick);
+        let account = state.users.get(user_nick).unwrap().account.as_deref();
+        ((!chanobj.modes.no_external_messages && !chanobj.modes.secret) || chum.is_some())
+            && !state.banned_cached(channel, user_nick, source, account)
+            && (!chanobj.modes.moderated || chum.map_or(false, |chum| chum.is_voice()))
+            && (!chanobj.modes.no_colors
+                || !has_formatting_codes(text)
+                || chum.map_or(false, |chum| chum.is_operator()))
+    }
+
     async fn process_privmsg_notice<'a>(
         &self,
         conn_state: &mut ConnState,
@@ -34,82 +60,117 @@ impl super::MainState {
         let client = conn_state.user_state.client_name();
         let user_nick = conn_state.user_state.nick.as_ref().unwrap();
 
+        // message is delivered once to duplicated target - report duplicates.
+        let mut unique_targets = HashSet::<&&str>::new();
+        for target in &targets {
+            if !unique_targets.insert(target) && !notice {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrTooManyTargets407 { client, target },
+                )
+                .await?;
+            }
+        }
+
         let mut something_done = false;
+        let mut revealed = vec![];
         {
             let state = self.state.read().await;
 
-            for target in HashSet::<&&str>::from_iter(targets.iter()) {
+            // CTCP requests (except ACTION) are not allowed shortly after registration.
+            if !notice && is_ctcp(text) && !text.starts_with("\x01ACTION") {
+                if let Some(grace_period) = self.config.ctcp_grace_period {
+                    let user = state.users.get(user_nick).unwrap();
+                    if conn_state.registration_time.elapsed() < Duration::from_secs(grace_period)
+                        && user.account.is_none()
+                        && !user.modes.is_local_oper()
+                        && !self.rate_limit_exempt(&conn_state.user_state.source)
+                    {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            "FAIL PRIVMSG CTCP_NOT_ALLOWED :CTCP requests are not allowed \
+                            shortly after connection",
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+
+            // excess notices are silently dropped - operators and bots are not limited.
+            if notice {
+                if let Some(rate_limit) = self.config.notice_rate_limit {
+                    let user = state.users.get(user_nick).unwrap();
+                    if !user.modes.is_local_oper()
+                        && !user.modes.bot
+                        && !self.rate_limit_exempt(&conn_state.user_state.source)
+                        && !check_rate_limit(
+                            &mut conn_state.notice_times,
+                            rate_limit.count,
+                            Duration::from_secs(rate_limit.period),
+                            Instant::now(),
+                        )
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+
+            for target in unique_targets {
                 let msg_str = if notice {
                     format!("NOTICE {} :{}", target, text)
                 } else {
                     format!("PRIVMSG {} :{}", target, text)
                 };
                 let (target_type, chan_str) = get_privmsg_target_type(target);
-                if target_type.contains(PrivMsgTargetType::Channel) {
-                    // to channel
-                    if let Some(chanobj) = state.channels.get(chan_str) {
-                        let chanuser_mode = chanobj.users.get(user_nick);
-                        // check whether can send from outside channel
-                        let can_send = {
-                            if (!chanobj.modes.no_external_messages && !chanobj.modes.secret)
-                                || chanuser_mode.is_some()
-                            {
-                                true
-                            } else {
-                                if !notice {
-                                    self.feed_msg(
-                                        &mut conn_state.stream,
-                                        ErrCannotSendToChain404 {
-                                            client,
-                                            channel: chan_str,
-                                        },
-                                    )
-                                    .await?;
-                                }
-                                false
-                            }
-                        };
-                        // check whether user in channel is banned
-                        let can_send = can_send && {
-                            if !chanobj.modes.banned(&conn_state.user_state.source) {
-                                true
+                if let Some((host_mask, mask)) = get_masked_target(target) {
+                    // to users by host mask or server mask - only for operators
+                    if state.users.get(user_nick).unwrap().modes.is_local_oper() {
+                        state.users.iter().try_for_each(|(nick, u)| {
+                            let matched = if host_mask {
+                                match_wildcard(mask, &u.hostname)
                             } else {
-                                if !notice {
-                                    self.feed_msg(
-                                        &mut conn_state.stream,
-                                        ErrCannotSendToChain404 {
-                                            client,
-                                            channel: chan_str,
-                                        },
-                                    )
-                                    .await?;
-                                }
-                                false
-                            }
-                        };
-                        // check whether is channel moderated and user have voice permissions
-                        let can_send = can_send && {
-                            if !chanobj.modes.moderated
-                                || chanuser_mode.map_or(false, |chum| chum.is_voice())
-                            {
-                                true
+                                match_wildcard(mask, &self.config.name)
+                            };
+                            if nick != user_nick && matched {
+                                u.send_msg_display(&conn_state.user_state.source, &msg_str)
                             } else {
-                                if !notice {
-                                    self.feed_msg(
-                                        &mut conn_state.stream,
-                                        ErrCannotSendToChain404 {
-                                            client,
-                                            channel: chan_str,
-                                        },
-                                    )
-                                    .await?;
-                                }
-                                false
+                                Ok(())
                             }
-                        };
+                        })?;
+                        something_done = true;
+                    } else if !notice {
+                        self.feed_msg(&mut conn_state.stream, ErrNoPrivileges481 { client })
+                            .await?;
+                    }
+                } else if target_type.contains(PrivMsgTargetType::Channel) {
+                    // to channel
+                    if let Some(chanobj) = state.channels.get(chan_str) {
+                        let can_send = self.can_send_to_channel(
+                            &state,
+                            chan_str,
+                            user_nick,
+                            &conn_state.user_state.source,
+                            text,
+                        );
+                        if !can_send && !notice {
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                ErrCannotSendToChain404 {
+                                    client,
+                                    channel: chan_str,
+                                },
+                            )
+                            .await?;
+                        }
 
                         if can_send {
                             use PrivMsgTargetType::*;
+                            // hidden user becomes visible after first message.
+                            if chanobj.delayed_users.contains_key(user_nick) {
+                                chanobj.send_delayed_join(chan_str, user_nick, &state.users);
+                                revealed.push(chan_str);
+                            }
                             if !(target_type & ChannelAllSpecial).is_empty() {
                                 // send to special users
                                 if !(target_type & ChannelFounder).is_empty() {
@@ -208,8 +269,72 @@ impl super::MainState {
                         .await?;
                     }
                 } else {
-                    // to user
-                    if let Some(cur_user) = state.users.get(*target) {
+                    // to user, by nick or by unique ID
+                    let cur_user = state
+                        .users
+                        .get_key_value(*target)
+                        .or_else(|| state.users.iter().find(|(_, u)| u.uid == *target));
+                    if let Some((cur_nick, cur_user)) = cur_user {
+                        // drop excess CTCP replies - they can loop between bots.
+                        if notice && is_ctcp(text) {
+                            if let Some(rate_limit) = self.config.ctcp_reply_rate_limit {
+                                if !self.rate_limit_exempt(&conn_state.user_state.source)
+                                    && !check_rate_limit(
+                                        conn_state
+                                            .ctcp_reply_times
+                                            .entry(cur_nick.clone())
+                                            .or_default(),
+                                        rate_limit.count,
+                                        Duration::from_secs(rate_limit.period),
+                                        Instant::now(),
+                                    )
+                                {
+                                    continue;
+                                }
+                            }
+                        }
+                        // caller-ID (+g) - only accepted users and operators can send.
+                        if cur_user.modes.caller_id
+                            && cur_nick != user_nick
+                            && !cur_user.accept.contains(user_nick)
+                            && !state.users.get(user_nick).unwrap().modes.is_local_oper()
+                        {
+                            if !notice {
+                                self.feed_msg(
+                                    &mut conn_state.stream,
+                                    ErrTargUmodeG716 {
+                                        client,
+                                        nick: cur_nick,
+                                    },
+                                )
+                                .await?;
+                            }
+                            // inform target about attempt, but not too often.
+                            if cur_user.callerid_notify_due(CALLERID_NOTIFY_INTERVAL) {
+                                let source = &conn_state.user_state.source;
+                                let user_host =
+                                    source.split_once('!').map_or(source.as_str(), |x| x.1);
+                                cur_user.send_msg_display(
+                                    &self.config.name,
+                                    RplUmodeGMsg718 {
+                                        client: cur_nick,
+                                        nick: user_nick,
+                                        user_host,
+                                    },
+                                )?;
+                                if !notice {
+                                    self.feed_msg(
+                                        &mut conn_state.stream,
+                                        RplTargNotify717 {
+                                            client,
+                                            nick: cur_nick,
+                                        },
+                                    )
+                                    .await?;
+                                }
+                            }
+                            continue;
+                        }
                         cur_user.send_msg_display(&conn_state.user_state.source, msg_str)?;
                         if !notice {
                             // if user away
@@ -218,7 +343,7 @@ impl super::MainState {
                                     &mut conn_state.stream,
                                     RplAway301 {
                                         client,
-                                        nick: target,
+                                        nick: cur_nick,
                                         message: away,
                                     },
                                 )
@@ -244,6 +369,11 @@ impl super::MainState {
             // update last activity if something sent
             if something_done {
                 let mut state = self.state.write().await;
+                for chname in revealed {
+                    if let Some(chanobj) = state.channels.get_mut(chname) {
+                        chanobj.delayed_users.remove(user_nick);
+                    }
+                }
                 let user = state.users.get_mut(user_nick).unwrap();
                 user.last_activity = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -274,100 +404,406 @@ impl super::MainState {
             .await
     }
 
-    // routine to send who info about user
-    pub(super) async fn send_who_info<'a>(
+    // CPRIVMSG and CNOTICE - message to user in channel sent by channel operator or voice.
+    async fn process_cprivmsg_cnotice<'a>(
         &self,
         conn_state: &mut ConnState,
-        channel: Option<(&'a str, &ChannelUserModes)>,
-        user_nick: &'a str,
-        user: &User,
-        cmd_user: &User,
+        nickname: &'a str,
+        channel: &'a str,
+        text: &'a str,
+        notice: bool,
     ) -> Result<(), Box<dyn Error>> {
-        if !user.modes.invisible || !user.channels.is_disjoint(&cmd_user.channels) {
-            let client = conn_state.user_state.client_name();
-            let mut flags = String::new();
-            // if user away
-            if user.away.is_some() {
-                flags.push('G');
+        let client = conn_state.user_state.client_name();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+
+        let sent = {
+            let state = self.state.read().await;
+            if let Some(chanobj) = state.channels.get(channel) {
+                if let Some(chum) = chanobj.users.get(user_nick) {
+                    if !chum.is_voice() {
+                        if !notice {
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                ErrChanOpPrivsNeeded482 { client, channel },
+                            )
+                            .await?;
+                        }
+                        false
+                    } else if !chanobj.users.contains_key(nickname) {
+                        if !notice {
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                ErrUserNotInChannel441 {
+                                    client,
+                                    nick: nickname,
+                                    channel,
+                                },
+                            )
+                            .await?;
+                        }
+                        false
+                    } else {
+                        let cur_user = state.users.get(nickname).unwrap();
+                        let msg_str = if notice {
+                            format!("NOTICE {} :{}", nickname, text)
+                        } else {
+                            format!("PRIVMSG {} :{}", nickname, text)
+                        };
+                        cur_user.send_msg_display(&conn_state.user_state.source, msg_str)?;
+                        if !notice {
+                            // if user away
+                            if let Some(ref away) = cur_user.away {
+                                self.feed_msg(
+                                    &mut conn_state.stream,
+                                    RplAway301 {
+                                        client,
+                                        nick: nickname,
+                                        message: away,
+                                    },
+                                )
+                                .await?;
+                            }
+                        }
+                        true
+                    }
+                } else {
+                    if !notice {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            ErrNotOnChannel442 { client, channel },
+                        )
+                        .await?;
+                    }
+                    false
+                }
             } else {
-                flags.push('H');
-            }
-            // if user is operator
-            if user.modes.is_local_oper() {
-                flags.push('*');
+                if !notice {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        ErrNoSuchChannel403 { client, channel },
+                    )
+                    .await?;
+                }
+                false
             }
-            if let Some((_, chum)) = channel {
-                flags += &chum.to_string(&conn_state.caps);
+        };
+
+        // update last activity if something sent
+        if sent {
+            let mut state = self.state.write().await;
+            let user = state.users.get_mut(user_nick).unwrap();
+            user.last_activity = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+        }
+        Ok(())
+    }
+
+    pub(super) async fn process_cprivmsg<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        nickname: &'a str,
+        channel: &'a str,
+        text: &'a str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.process_cprivmsg_cnotice(conn_state, nickname, channel, text, false)
+            .await
+    }
+
+    pub(super) async fn process_cnotice<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        nickname: &'a str,
+        channel: &'a str,
+        text: &'a str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.process_cprivmsg_cnotice(conn_state, nickname, channel, text, true)
+            .await
+    }
+
+    // RELAYMSG - message relayed to channel by bridge bot under spoofed nick.
+    pub(super) async fn process_relaymsg<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        channel: &'a str,
+        nick: &'a str,
+        text: &'a str,
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+        let state = self.state.read().await;
+        let user = state.users.get(user_nick).unwrap();
+
+        // only bridge bots logged in to configured accounts can relay messages.
+        let permitted = user.account.as_ref().map_or(false, |account| {
+            self.config
+                .relay_accounts
+                .as_ref()
+                .map_or(false, |accounts| accounts.contains(account))
+        });
+        if !permitted {
+            self.feed_msg(
+                &mut conn_state.stream,
+                "FAIL RELAYMSG PRIVS_NEEDED :You cannot relay messages",
+            )
+            .await?;
+        } else if !nick.contains(RELAYMSG_SEPARATOR) || state.users.contains_key(nick) {
+            // spoofed nick must be distinct from nicks of real users.
+            self.feed_msg(
+                &mut conn_state.stream,
+                format!(
+                    "FAIL RELAYMSG INVALID_NICK {} :Nick must contain '{}' and must not be in use",
+                    nick, RELAYMSG_SEPARATOR
+                ),
+            )
+            .await?;
+        } else if let Some(chanobj) = state.channels.get(channel) {
+            let source = format!("{}!relay@{}", nick, self.config.name);
+            if !chanobj.users.contains_key(user_nick) {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrNotOnChannel442 { client, channel },
+                )
+                .await?;
+            } else if !self.can_send_to_channel(
+                &state,
+                channel,
+                user_nick,
+                &conn_state.user_state.source,
+                text,
+            ) || chanobj.modes.banned(&source, None)
+            {
+                // the same checks as for PRIVMSG of bot. Relayed nick can be banned too.
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    ErrCannotSendToChain404 { client, channel },
+                )
+                .await?;
+            } else {
+                let msg_str = format!("PRIVMSG {} :{}", channel, text);
+                chanobj
+                    .users
+                    .keys()
+                    .filter(|u| *u != user_nick)
+                    .try_for_each(|u| {
+                        state.users.get(u).unwrap().send_msg_display(&source, &msg_str)
+                    })?;
             }
+        } else {
             self.feed_msg(
                 &mut conn_state.stream,
-                RplWhoReply352 {
-                    client,
-                    channel: channel.map(|(c, _)| c).unwrap_or("*"),
-                    username: &user.name,
-                    host: &user.hostname,
-                    server: &self.config.name,
-                    nick: user_nick,
-                    flags: &flags,
-                    hopcount: 0,
-                    realname: &user.realname,
-                },
+                ErrNoSuchChannel403 { client, channel },
             )
             .await?;
         }
         Ok(())
     }
 
-    pub(super) async fn process_who<'a>(
+    // SESSIONS - list or terminate other sessions logged in to the same account.
+    pub(super) async fn process_sessions<'a>(
         &self,
         conn_state: &mut ConnState,
-        mask: &'a str,
+        subcommand: SessionsCommand<'a>,
     ) -> Result<(), Box<dyn Error>> {
-        let state = self.state.read().await;
+        let client = conn_state.user_state.client_name();
         let user_nick = conn_state.user_state.nick.as_ref().unwrap();
-        let user = state.users.get(user_nick).unwrap();
+        let mut state = self.state.write().await;
+        let account = if let Some(ref account) = state.users.get(user_nick).unwrap().account {
+            account.clone()
+        } else {
+            self.feed_msg(
+                &mut conn_state.stream,
+                "FAIL SESSIONS ACCOUNT_REQUIRED :You are not logged in to an account",
+            )
+            .await?;
+            return Ok(());
+        };
 
-        if mask.contains('*') || mask.contains('?') {
-            // if wilcards
-            for (unick, u) in &state.users {
-                if match_wildcard(mask, unick)
-                    || match_wildcard(mask, &u.source)
-                    || match_wildcard(mask, &u.realname)
-                {
-                    self.send_who_info(conn_state, None, unick, u, user).await?;
-                }
-            }
-        } else if validate_channel(mask).is_ok() {
-            // if channel
-            if let Some(channel) = state.channels.get(mask) {
-                for (u, chum) in &channel.users {
-                    self.send_who_info(
-                        conn_state,
-                        Some((mask, chum)),
-                        u,
-                        state.users.get(u).unwrap(),
-                        user,
+        match subcommand {
+            SessionsCommand::List => {
+                let mut sessions = state
+                    .users
+                    .iter()
+                    .filter(|(_, u)| u.account.as_ref() == Some(&account))
+                    .collect::<Vec<_>>();
+                sessions.sort_unstable_by_key(|(n, u)| (u.signon, *n));
+                for (nick, user) in sessions {
+                    let current = if nick == user_nick { " (current)" } else { "" };
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        format!(
+                            "NOTICE {} :{} {}@{} {}{}",
+                            client, nick, user.name, user.hostname, user.signon, current
+                        ),
                     )
                     .await?;
                 }
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!("NOTICE {} :End of sessions list", client),
+                )
+                .await?;
             }
-        } else if validate_username(mask).is_ok() {
-            if let Some(arg_user) = state.users.get(mask) {
-                self.send_who_info(conn_state, None, mask, arg_user, user)
+            SessionsCommand::Kill { nick } => {
+                let other_session = nick != user_nick
+                    && state
+                        .users
+                        .get(nick)
+                        .map_or(false, |u| u.account.as_ref() == Some(&account));
+                if other_session {
+                    info!(
+                        "Session {} of account {} terminated by {}",
+                        nick, account, conn_state.user_state.source
+                    );
+                    let victim = state.users.get(nick).unwrap();
+                    let peers = victim
+                        .channels
+                        .iter()
+                        .filter_map(|ch| state.channels.get(ch))
+                        .flat_map(|ch| ch.users.keys())
+                        .filter(|n| *n != nick)
+                        .collect::<HashSet<_>>();
+                    for peer in peers {
+                        // ignore errors - peer can be during disconnection.
+                        let _ = state
+                            .users
+                            .get(peer)
+                            .unwrap()
+                            .send_msg_display(&victim.source, "QUIT :Session terminated");
+                    }
+
+                    let victim = state.users.get_mut(nick).unwrap();
+                    // terminated session can not be resumed.
+                    victim.resume_token = None;
+                    if victim.detached_receiver.is_some() {
+                        // connection of session has been already lost.
+                        state.remove_user(nick);
+                    } else if let Some(sender) = victim.quit_sender.take() {
+                        sender
+                            .send((user_nick.to_string(), "Session terminated".to_string()))
+                            .map_err(|_| "error".to_string())?;
+                    }
+                } else {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        format!(
+                            "FAIL SESSIONS NO_SUCH_SESSION {} :No such session of your account",
+                            nick
+                        ),
+                    )
                     .await?;
+                }
             }
         }
-        let client = conn_state.user_state.client_name();
-        self.feed_msg(&mut conn_state.stream, RplEndOfWho315 { client, mask })
-            .await?;
         Ok(())
     }
 
-    pub(super) async fn process_whois<'a>(
+    // routine to send who info about user
+    pub(super) async fn send_who_info<'a>(
         &self,
         conn_state: &mut ConnState,
-        target: Option<&'a str>,
-        nickmasks: Vec<&'a str>,
+        channel: Option<(&'a str, &ChannelUserModes)>,
+        user_nick: &'a str,
+        user: &User,
+        cmd_user: &User,
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let flags = user.who_flags(
+            channel.map(|(_, chum)| chum),
+            &conn_state.caps,
+            cmd_user.modes.is_local_oper(),
+        );
+        self.feed_msg(
+            &mut conn_state.stream,
+            RplWhoReply352 {
+                client,
+                channel: channel.map(|(c, _)| c).unwrap_or("*"),
+                username: &user.name,
+                host: &user.hostname,
+                server: &self.config.name,
+                nick: user_nick,
+                flags: &flags,
+                hopcount: 0,
+                realname: &user.realname,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(super) async fn process_who<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        mask: &'a str,
+    ) -> Result<(), Box<dyn Error>> {
+        let state = self.state.read().await;
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
+        let user = state.users.get(user_nick).unwrap();
+
+        let mut replies = vec![];
+        if mask.contains('*') || mask.contains('?') {
+            // if wilcards
+            for (unick, u) in &state.users {
+                if match_wildcard(mask, unick)
+                    || match_wildcard(mask, &u.source)
+                    || match_wildcard(mask, &u.realname)
+                {
+                    replies.push((None, unick.as_str(), u));
+                }
+            }
+        } else if validate_channel(mask).is_ok() {
+            // if channel
+            if let Some(channel) = state.channels.get(mask) {
+                for (u, chum) in &channel.users {
+                    replies.push((
+                        Some((mask, chum)),
+                        u.as_str(),
+                        state.users.get(u).unwrap(),
+                    ));
+                }
+            }
+        } else if validate_username(mask).is_ok() {
+            if let Some(arg_user) = state.users.get(mask) {
+                replies.push((None, mask, arg_user));
+            }
+        }
+        // skip invisible users that do not share channel with user.
+        replies.retain(|(_, _, u)| {
+            !u.modes.invisible || !u.channels.is_disjoint(&user.channels)
+        });
+
+        // limit number of replies for non-operators.
+        let limit = self
+            .config
+            .max_who_results
+            .filter(|_| !user.modes.is_local_oper())
+            .unwrap_or(replies.len());
+        for (channel, unick, u) in replies.iter().take(limit) {
+            self.send_who_info(conn_state, *channel, unick, u, user).await?;
+        }
+        let client = conn_state.user_state.client_name();
+        if replies.len() > limit {
+            self.feed_msg(
+                &mut conn_state.stream,
+                ErrTooManyMatches416 {
+                    client,
+                    command: "WHO",
+                    mask,
+                },
+            )
+            .await?;
+        }
+        self.feed_msg(&mut conn_state.stream, RplEndOfWho315 { client, mask })
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn process_whois<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        target: Option<&'a str>,
+        nickmasks: Vec<&'a str>,
     ) -> Result<(), Box<dyn Error>> {
         let client = conn_state.user_state.client_name();
 
@@ -387,6 +823,26 @@ impl super::MainState {
             let user_nick = conn_state.user_state.nick.as_ref().unwrap();
             let user = state.users.get(user_nick).unwrap();
 
+            // operators and exempt users are not limited
+            if let Some(rate_limit) = self.config.whois_rate_limit {
+                if !user.modes.is_local_oper()
+                    && !self.rate_limit_exempt(&conn_state.user_state.source)
+                    && !check_rate_limit(
+                        &mut conn_state.whois_times,
+                        rate_limit.count,
+                        Duration::from_secs(rate_limit.period),
+                        Instant::now(),
+                    )
+                {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        "FAIL WHOIS RATE_LIMITED :Too many WHOIS requests, try again later",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+
             let mut nicks = HashSet::<String>::new();
             let mut real_nickmasks = vec![];
 
@@ -462,8 +918,8 @@ impl super::MainState {
                     .iter()
                     .filter_map(|chname| {
                         let ch = state.channels.get(chname).unwrap();
-                        if !ch.modes.secret {
-                            // put channel only if not secret
+                        if !ch.modes.secret || &nick == user_nick {
+                            // put channel only if not secret, user sees all own channels
                             Some(WhoIsChannelStruct {
                                 prefix: Some(
                                     ch.users.get(&nick).unwrap().to_string(&conn_state.caps),
@@ -523,6 +979,40 @@ impl super::MainState {
                     )
                     .await?;
                 }
+                if user.modes.is_local_oper() {
+                    // unique ID of connection helps to find user in logs.
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplWhoIsSpecial320 {
+                            client,
+                            nick: &nick,
+                            special_info: &format!("has unique ID {}", arg_user.uid),
+                        },
+                    )
+                    .await?;
+                    if let Some(ref country) = arg_user.country {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            RplWhoIsSpecial320 {
+                                client,
+                                nick: &nick,
+                                special_info: &format!("is connecting from country {}", country),
+                            },
+                        )
+                        .await?;
+                    }
+                }
+                if let Some(ref account) = arg_user.account {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplWhoIsAccount330 {
+                            client,
+                            nick: &nick,
+                            account,
+                        },
+                    )
+                    .await?;
+                }
                 // if you connected through TLS connection, then server is working with TLS.
                 // then all users is using secure connection.
                 if conn_state.is_secure() {
@@ -569,7 +1059,12 @@ impl super::MainState {
             )
             .await?;
         } else {
-            let state = self.state.read().await;
+            let mut state = self.state.write().await;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            state.expire_nick_histories(now);
             if let Some(hist) = state.nick_histories.get(&nickname.to_string()) {
                 // get hist_count - length if zero or not given
                 let hist_count = if let Some(c) = count {
@@ -647,8 +1142,36 @@ impl super::MainState {
 
         if user.modes.oper {
             // only operator can kill user
-            if let Some(user_to_kill) = state.users.get_mut(nickname) {
-                if let Some(sender) = user_to_kill.quit_sender.take() {
+            if let Some(victim) = state.users.get(nickname) {
+                // inform users in common channels. If kill_hide_oper is set then
+                // killer is shown only to operators.
+                let full_msg = format!("QUIT :Killed ({} ({}))", user_nick, comment);
+                let generic_msg = format!("QUIT :Killed ({})", comment);
+                let peers = victim
+                    .channels
+                    .iter()
+                    .filter_map(|ch| state.channels.get(ch))
+                    .flat_map(|ch| ch.users.keys())
+                    .filter(|n| *n != nickname)
+                    .collect::<HashSet<_>>();
+                for peer in peers {
+                    let peer_user = state.users.get(peer).unwrap();
+                    let msg = if self.config.kill_hide_oper && !peer_user.modes.is_local_oper() {
+                        &generic_msg
+                    } else {
+                        &full_msg
+                    };
+                    // ignore errors - peer can be during disconnection.
+                    let _ = peer_user.send_msg_display(&victim.source, msg);
+                }
+
+                let user_to_kill = state.users.get_mut(nickname).unwrap();
+                // session of killed user can not be resumed.
+                user_to_kill.resume_token = None;
+                if user_to_kill.detached_receiver.is_some() {
+                    // connection of user has been already lost.
+                    state.remove_user(nickname);
+                } else if let Some(sender) = user_to_kill.quit_sender.take() {
                     sender
                         .send((user_nick.to_string(), comment.to_string()))
                         .map_err(|_| "error".to_string())?;
@@ -670,21 +1193,100 @@ impl super::MainState {
         Ok(())
     }
 
-    pub(super) async fn process_rehash(
+    // reload included files. Main configuration file is not reloaded.
+    pub(super) async fn process_rehash<'a>(
         &self,
         conn_state: &mut ConnState,
+        subcommand: Option<&'a str>,
     ) -> Result<(), Box<dyn Error>> {
         let client = conn_state.user_state.client_name();
-        self.feed_msg(
-            &mut conn_state.stream,
-            ErrUnknownError400 {
-                client,
-                command: "REHASH",
-                subcommand: None,
-                info: "Server unsupported",
-            },
-        )
+
+        // choose included files to reload
+        let (motd, operators, bans, reserved_nicks, reserved_channels) =
+            match subcommand.map(|sc| sc.to_ascii_uppercase()).as_deref() {
+                None => (true, true, true, true, true),
+                Some("MOTD") => (true, false, false, false, false),
+                Some("OPERS") => (false, true, false, false, false),
+                Some("BANS") => (false, false, true, false, false),
+                Some("NICKS") => (false, false, false, true, false),
+                Some("CHANNELS") => (false, false, false, false, true),
+                Some(_) => {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        ErrUnknownError400 {
+                            client,
+                            command: "REHASH",
+                            subcommand,
+                            info: "Unknown subcommand",
+                        },
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+        let include = self.config.include.clone().unwrap_or_default();
+        let motd_path = include.motd.as_deref().filter(|_| motd);
+        let operators_path = include.operators.as_deref().filter(|_| operators);
+        let bans_path = include.bans.as_deref().filter(|_| bans);
+        let reserved_nicks_path = include.reserved_nicks.as_deref().filter(|_| reserved_nicks);
+        let reserved_channels_path = include
+            .reserved_channels
+            .as_deref()
+            .filter(|_| reserved_channels);
+        let paths = [
+            motd_path,
+            operators_path,
+            bans_path,
+            reserved_nicks_path,
+            reserved_channels_path,
+        ];
+        if paths.iter().all(|path| path.is_none()) {
+            self.feed_msg(
+                &mut conn_state.stream,
+                "FAIL REHASH NO_FILE :No included file to reload",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        // files are read without lock - lock is taken only to change configuration.
+        let [motd_path, operators_path, bans_path, reserved_nicks_path, reserved_channels_path] =
+            paths.map(|path| path.map(|p| p.to_string()));
+        let loaded = tokio::task::spawn_blocking(move || {
+            IncludedFiles::load(
+                motd_path.as_deref(),
+                operators_path.as_deref(),
+                bans_path.as_deref(),
+                reserved_nicks_path.as_deref(),
+                reserved_channels_path.as_deref(),
+            )
+            .map_err(|e| e.to_string())
+        })
         .await?;
+        match loaded {
+            Ok(files) => {
+                self.reloadable.write().await.apply(files);
+                for path in paths.iter().flatten() {
+                    info!("Rehashing {} by {}", path, conn_state.user_state.source);
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplRehashing382 {
+                            client,
+                            config_file: path,
+                        },
+                    )
+                    .await?;
+                }
+            }
+            Err(e) => {
+                error!("Rehashing failed: {}", e);
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    format!("FAIL REHASH INVALID_FILE :{}", e.replace('\n', " ")),
+                )
+                .await?;
+            }
+        }
         Ok(())
     }
 
@@ -765,6 +1367,11 @@ impl super::MainState {
         conn_state: &mut ConnState,
         text: Option<&'a str>,
     ) -> Result<(), Box<dyn Error>> {
+        if !conn_state.user_state.authenticated {
+            // pre-away - away will be set during registration.
+            conn_state.pre_away = text.map(|t| t.to_string());
+            return Ok(());
+        }
         let client = conn_state.user_state.client_name();
         let mut state = self.state.write().await;
         let user_nick = conn_state.user_state.nick.as_ref().unwrap();
@@ -780,6 +1387,22 @@ impl super::MainState {
             self.feed_msg(&mut conn_state.stream, RplUnAway305 { client })
                 .await?;
         }
+        // state is always changed, but too frequent changes are not notified to monitors.
+        if let Some(rate_limit) = self.config.away_rate_limit {
+            if !user.modes.is_local_oper()
+                && !self.rate_limit_exempt(&conn_state.user_state.source)
+                && !check_rate_limit(
+                    &mut conn_state.away_times,
+                    rate_limit.count,
+                    Duration::from_secs(rate_limit.period),
+                    Instant::now(),
+                )
+            {
+                return Ok(());
+            }
+        }
+        let user = state.users.get(user_nick).unwrap();
+        state.presence.notify_away(&state.users, user_nick, user);
         Ok(())
     }
 
@@ -822,23 +1445,30 @@ impl super::MainState {
         msg: &'a Message<'a>,
     ) -> Result<(), Box<dyn Error>> {
         let state = self.state.read().await;
-        let user_nick = conn_state.user_state.nick.as_ref().unwrap();
-        let user = state.users.get(user_nick).unwrap();
+        // only local operator or higher can send message to wallops (checked by dispatcher).
+        state.wallops_users.iter().try_for_each(|wu| {
+            state
+                .users
+                .get(wu)
+                .unwrap()
+                .send_message(msg, &conn_state.user_state.source)
+        })?;
+        Ok(())
+    }
 
-        // only local operator or higher can send message to wallops
-        if user.modes.is_local_oper() {
-            state.wallops_users.iter().try_for_each(|wu| {
-                state
-                    .users
-                    .get(wu)
-                    .unwrap()
-                    .send_message(msg, &conn_state.user_state.source)
-            })?;
-        } else {
-            let client = conn_state.user_state.client_name();
-            self.feed_msg(&mut conn_state.stream, ErrNoPrivileges481 { client })
-                .await?;
-        }
+    pub(super) async fn process_operwall<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        msg: &'a Message<'a>,
+    ) -> Result<(), Box<dyn Error>> {
+        let state = self.state.read().await;
+        // only local operator or higher can send message to operators (checked by
+        // dispatcher). server doesn't have links - just send to all operators in this server.
+        state
+            .users
+            .values()
+            .filter(|u| u.modes.is_local_oper())
+            .try_for_each(|u| u.send_message(msg, &conn_state.user_state.source))?;
         Ok(())
     }
 
@@ -866,134 +1496,351 @@ impl super::MainState {
         }
         Ok(())
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::super::test::*;
-    use super::*;
-
-    #[tokio::test]
-    async fn test_command_privmsg_user() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
-
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
-            let mut line_stream2 =
-                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
-
-            line_stream
-                .send("PRIVMSG bowie :Hello guy!".to_string())
-                .await
-                .unwrap();
-            assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy!".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
-            );
-            line_stream2
-                .send("PRIVMSG alan :Hello too!".to_string())
-                .await
-                .unwrap();
-            assert_eq!(
-                ":bowie!~bowie@127.0.0.1 PRIVMSG alan :Hello too!".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
 
-            line_stream
-                .send("PRIVMSG boxie :Hello guy!".to_string())
-                .await
-                .unwrap();
-            assert_eq!(
-                ":irc.irc 401 alan boxie :No such nick/channel".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            // away
-            time::sleep(Duration::from_millis(50)).await;
-            {
-                let mut state = main_state.state.write().await;
-                state.users.get_mut("bowie").unwrap().away = Some("Bye".to_string());
+    // send statuses (online or offline) of monitored targets.
+    async fn send_monitor_status<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        state: &VolatileState,
+        targets: &[&'a str],
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let mut online = vec![];
+        let mut offline = vec![];
+        targets.iter().for_each(|t| {
+            if let Some(user) = state.users.get(*t) {
+                online.push(user.source.as_str());
+            } else {
+                offline.push(*t);
             }
-
-            line_stream
-                .send("PRIVMSG bowie :Hello guy too!".to_string())
-                .await
-                .unwrap();
-            assert_eq!(
-                ":irc.irc 301 alan bowie :Bye".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy too!".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
-            );
+        });
+        for targets in online.chunks(20) {
+            self.feed_msg(
+                &mut conn_state.stream,
+                RplMonOnline730 {
+                    client,
+                    targets: &targets.join(","),
+                },
+            )
+            .await?;
         }
-
-        quit_test_server(main_state, handle).await;
+        for targets in offline.chunks(20) {
+            self.feed_msg(
+                &mut conn_state.stream,
+                RplMonOffline731 {
+                    client,
+                    targets: &targets.join(","),
+                },
+            )
+            .await?;
+        }
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_command_privmsg_channel() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    pub(super) async fn process_monitor<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        subcommand: MonitorCommand,
+        targets: Vec<&'a str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap().clone();
+        let mut statem = self.state.write().await;
+        let state = statem.deref_mut();
+
+        match subcommand {
+            MonitorCommand::Add => {
+                let mut added = vec![];
+                let mut user_limit_targets = vec![];
+                let mut global_limit_targets = vec![];
+                for target in targets {
+                    let user = state.users.get(&user_nick).unwrap();
+                    if user.monitor.contains(target) {
+                        added.push(target);
+                    } else if self
+                        .config
+                        .max_monitor
+                        .map_or(false, |m| user.monitor.len() >= m)
+                    {
+                        user_limit_targets.push(target);
+                    } else if self
+                        .config
+                        .max_monitor_global
+                        .map_or(false, |m| state.monitor_count >= m)
+                    {
+                        // global limit protects memory of whole server.
+                        global_limit_targets.push(target);
+                    } else {
+                        state.add_presence_target(PresenceKind::Monitor, &user_nick, target);
+                        added.push(target);
+                    }
+                }
 
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
-            let mut line_stream2 =
-                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
-            let mut line_stream3 =
-                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
-
-            for line_stream in [&mut line_stream, &mut line_stream2, &mut line_stream3] {
-                line_stream
-                    .send("JOIN #channelx".to_string())
-                    .await
-                    .unwrap();
-                for _ in 0..3 {
-                    line_stream.next().await.unwrap().unwrap();
+                if !user_limit_targets.is_empty() {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        ErrMonListIsFull734 {
+                            client,
+                            limit: self.config.max_monitor.unwrap(),
+                            targets: &user_limit_targets.join(","),
+                        },
+                    )
+                    .await?;
+                }
+                if !global_limit_targets.is_empty() {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        format!(
+                            "FAIL MONITOR LIMIT_EXCEEDED {} :Server monitor limit \
+                            has been reached",
+                            global_limit_targets.join(",")
+                        ),
+                    )
+                    .await?;
                 }
+                self.send_monitor_status(conn_state, state, &added).await?;
             }
-
-            for _ in 0..2 {
-                line_stream.next().await.unwrap().unwrap();
+            MonitorCommand::Remove => {
+                for target in targets {
+                    state.remove_presence_target(PresenceKind::Monitor, &user_nick, target);
+                }
             }
-            line_stream2.next().await.unwrap().unwrap();
+            MonitorCommand::Clear => {
+                state.clear_presence_targets(PresenceKind::Monitor, &user_nick);
+            }
+            MonitorCommand::List => {
+                let user = state.users.get(&user_nick).unwrap();
+                let mut monitored = user.monitor.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                monitored.sort_unstable();
+                for targets in monitored.chunks(20) {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplMonList732 {
+                            client,
+                            targets: &targets.join(","),
+                        },
+                    )
+                    .await?;
+                }
+                self.feed_msg(&mut conn_state.stream, RplEndOfMonList733 { client }).await?;
+            }
+            MonitorCommand::Status => {
+                let user = state.users.get(&user_nick).unwrap();
+                let mut monitored = user.monitor.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                monitored.sort_unstable();
+                self.send_monitor_status(conn_state, state, &monitored).await?;
+            }
+        }
+        Ok(())
+    }
 
-            line_stream
-                .send("PRIVMSG #channelx :Hello guy!".to_string())
-                .await
-                .unwrap();
-            for line_stream in [&mut line_stream2, &mut line_stream3] {
-                assert_eq!(
-                    ":alan!~alan@127.0.0.1 PRIVMSG #channelx :Hello guy!".to_string(),
-                    line_stream.next().await.unwrap().unwrap()
-                );
+    pub(super) async fn process_watch<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        entries: Vec<&'a str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap().clone();
+        let mut statem = self.state.write().await;
+        let state = statem.deref_mut();
+        // if no entries then list online watched users
+        let entries = if !entries.is_empty() {
+            entries
+        } else {
+            vec!["l"]
+        };
+
+        for entry in entries {
+            if let Some(target) = entry.strip_prefix('+') {
+                let user = state.users.get(&user_nick).unwrap();
+                if !user.watch.contains(target) {
+                    if let Some(limit) =
+                        self.config.max_monitor.filter(|m| user.watch.len() >= *m)
+                    {
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            ErrTooManyWatch512 {
+                                client,
+                                nick: target,
+                                limit,
+                            },
+                        )
+                        .await?;
+                        continue;
+                    } else if self
+                        .config
+                        .max_monitor_global
+                        .map_or(false, |m| state.monitor_count >= m)
+                    {
+                        // global limit protects memory of whole server.
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            format!(
+                                "FAIL WATCH LIMIT_EXCEEDED {} :Server monitor limit \
+                                has been reached",
+                                target
+                            ),
+                        )
+                        .await?;
+                        continue;
+                    }
+                    state.add_presence_target(PresenceKind::Watch, &user_nick, target);
+                }
+                self.feed_msg(&mut conn_state.stream, watch_status(client, state, target))
+                    .await?;
+            } else if let Some(target) = entry.strip_prefix('-') {
+                if state.remove_presence_target(PresenceKind::Watch, &user_nick, target) {
+                    let reply = if let Some(u) = state.users.get(target) {
+                        RplWatchOff602 {
+                            client,
+                            nick: target,
+                            user: &u.name,
+                            host: &u.hostname,
+                            ts: u.signon,
+                        }
+                    } else {
+                        RplWatchOff602 {
+                            client,
+                            nick: target,
+                            user: "*",
+                            host: "*",
+                            ts: 0,
+                        }
+                    };
+                    self.feed_msg(&mut conn_state.stream, reply).await?;
+                }
+            } else {
+                match entry {
+                    "C" | "c" => {
+                        state.clear_presence_targets(PresenceKind::Watch, &user_nick);
+                    }
+                    "S" | "s" => {
+                        let user = state.users.get(&user_nick).unwrap();
+                        let mut watched = user.watch.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                        watched.sort_unstable();
+                        self.feed_msg(
+                            &mut conn_state.stream,
+                            RplWatchStat603 {
+                                client,
+                                watches: watched.len(),
+                                watched_by: state
+                                    .presence
+                                    .watchers_count(PresenceKind::Watch, &user_nick),
+                            },
+                        )
+                        .await?;
+                        for nicks in watched.chunks(20) {
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                RplWatchList606 {
+                                    client,
+                                    nicks: &nicks.join(" "),
+                                },
+                            )
+                            .await?;
+                        }
+                        self.feed_msg(&mut conn_state.stream, RplEndOfWatchList607 { client })
+                            .await?;
+                    }
+                    "L" | "l" => {
+                        let user = state.users.get(&user_nick).unwrap();
+                        let mut watched = user.watch.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                        watched.sort_unstable();
+                        for target in watched {
+                            // 'l' - list only online users
+                            if entry == "L" || state.users.contains_key(target) {
+                                self.feed_msg(
+                                    &mut conn_state.stream,
+                                    watch_status(client, state, target),
+                                )
+                                .await?;
+                            }
+                        }
+                        self.feed_msg(&mut conn_state.stream, RplEndOfWatchList607 { client })
+                            .await?;
+                    }
+                    _ => (),
+                }
             }
-            line_stream3
-                .send("PRIVMSG #channelx :Hi!".to_string())
-                .await
-                .unwrap();
-            for line_stream in [&mut line_stream, &mut line_stream2] {
-                assert_eq!(
-                    ":cedric!~cedric@127.0.0.1 PRIVMSG #channelx :Hi!".to_string(),
-                    line_stream.next().await.unwrap().unwrap()
-                );
+        }
+        Ok(())
+    }
+
+    // ACCEPT - manage list of users that can send private messages if user has mode +g.
+    pub(super) async fn process_accept<'a>(
+        &self,
+        conn_state: &mut ConnState,
+        nicknames: Vec<&'a str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let client = conn_state.user_state.client_name();
+        let user_nick = conn_state.user_state.nick.as_ref().unwrap().clone();
+        let mut state = self.state.write().await;
+
+        for nickname in nicknames {
+            if nickname == "*" {
+                let user = state.users.get(&user_nick).unwrap();
+                let mut accepted = user.accept.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                accepted.sort_unstable();
+                for nicks in accepted.chunks(20) {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplAcceptList281 {
+                            client,
+                            nicks: &nicks.join(" "),
+                        },
+                    )
+                    .await?;
+                }
+                self.feed_msg(&mut conn_state.stream, RplEndOfAccept282 { client }).await?;
+            } else if let Some(nick) = nickname.strip_prefix('-') {
+                let user = state.users.get_mut(&user_nick).unwrap();
+                if !user.accept.remove(nick) {
+                    self.feed_msg(&mut conn_state.stream, ErrAcceptNot458 { client, nick })
+                        .await?;
+                }
+            } else {
+                let nick = nickname.strip_prefix('+').unwrap_or(nickname);
+                if !state.users.contains_key(nick) {
+                    self.feed_msg(&mut conn_state.stream, ErrNoSuchNick401 { client, nick })
+                        .await?;
+                } else {
+                    let user = state.users.get_mut(&user_nick).unwrap();
+                    if !user.accept.insert(nick.to_string()) {
+                        self.feed_msg(&mut conn_state.stream, ErrAcceptExist457 { client, nick })
+                            .await?;
+                    }
+                }
             }
-            line_stream
-                .send("PRIVMSG #channely :Hello guy!".to_string())
-                .await
-                .unwrap();
-            assert_eq!(
-                ":irc.irc 403 alan #channely :No such channel".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
         }
+        Ok(())
+    }
+}
 
-        quit_test_server(main_state, handle).await;
+// get WATCH status reply for target.
+fn watch_status<'a>(client: &'a str, state: &'a VolatileState, target: &'a str) -> Reply<'a> {
+    if let Some(user) = state.users.get(target) {
+        RplNowOn604 {
+            client,
+            nick: target,
+            user: &user.name,
+            host: &user.hostname,
+            ts: user.signon,
+        }
+    } else {
+        RplNowOff605 {
+            client,
+            nick: target,
+        }
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
 
     #[tokio::test]
-    async fn test_command_privmsg_channel_external_messages() {
+    async fn test_command_privmsg_user() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1003,42 +1850,47 @@ mod test {
                 login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
             line_stream
-                .send("JOIN #channelx".to_string())
+                .send("PRIVMSG bowie :Hello guy!".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
-            }
-
-            // send message to channel
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy!".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
             line_stream2
-                .send("PRIVMSG #channelx :I want to join!".to_string())
+                .send("PRIVMSG alan :Hello too!".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want to join!".to_string(),
+                ":bowie!~bowie@127.0.0.1 PRIVMSG alan :Hello too!".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
 
+            line_stream
+                .send("PRIVMSG boxie :Hello guy!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 401 alan boxie :No such nick/channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // away
             time::sleep(Duration::from_millis(50)).await;
             {
-                main_state
-                    .state
-                    .write()
-                    .await
-                    .channels
-                    .get_mut("#channelx")
-                    .unwrap()
-                    .modes
-                    .no_external_messages = true;
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("bowie").unwrap().away = Some("Bye".to_string());
             }
 
-            line_stream2
-                .send("PRIVMSG #channelx :I want to join!".to_string())
+            line_stream
+                .send("PRIVMSG bowie :Hello guy too!".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                ":irc.irc 301 alan bowie :Bye".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy too!".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
         }
@@ -1047,7 +1899,7 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_privmsg_channel_moderated() {
+    async fn test_command_privmsg_user_by_uid() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1055,84 +1907,163 @@ mod test {
                 login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
             let mut line_stream2 =
                 login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
-            let mut line_stream3 =
-                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
-
-            line_stream
-                .send("JOIN #channelx".to_string())
-                .await
-                .unwrap();
-            for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
-            }
-            line_stream2
-                .send("JOIN #channelx".to_string())
-                .await
-                .unwrap();
-            for _ in 0..3 {
-                line_stream2.next().await.unwrap().unwrap();
-            }
-            line_stream.next().await.unwrap().unwrap();
 
             time::sleep(Duration::from_millis(50)).await;
-            {
-                main_state
-                    .state
-                    .write()
-                    .await
-                    .channels
-                    .get_mut("#channelx")
-                    .unwrap()
-                    .modes
-                    .moderated = true;
+            let uid = {
+                let state = main_state.state.read().await;
+                state.users.get("bowie").unwrap().uid.clone()
+            };
+            assert_eq!("0AA000001", uid);
+
+            line_stream2.send("NICK robbie".to_string()).await.unwrap();
+            // nick change is sent to all users
+            for ls in [&mut line_stream, &mut line_stream2] {
+                assert_eq!(
+                    ":bowie!~bowie@127.0.0.1 NICK robbie".to_string(),
+                    ls.next().await.unwrap().unwrap()
+                );
             }
 
-            // send message to channel
             line_stream
-                .send("PRIVMSG #channelx :I want you!".to_string())
+                .send(format!("PRIVMSG {} :Hello guy!", uid))
                 .await
                 .unwrap();
             assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG #channelx :I want you!".to_string(),
+                ":alan!~alan@127.0.0.1 PRIVMSG 0AA000001 :Hello guy!".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
-
-            line_stream2
-                .send("PRIVMSG #channelx :I want you!".to_string())
+            line_stream
+                .send(format!("NOTICE {} :Hello notice!", uid))
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                ":alan!~alan@127.0.0.1 NOTICE 0AA000001 :Hello notice!".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
 
-            line_stream3
-                .send("PRIVMSG #channelx :I want you too!".to_string())
+            line_stream
+                .send("PRIVMSG 0AA0000FF :Hello guy!".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 404 cedric #channelx :Cannot send to channel".to_string(),
-                line_stream3.next().await.unwrap().unwrap()
+                ":irc.irc 401 alan 0AA0000FF :No such nick/channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_masks() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("harry").unwrap().hostname = "harry.example.org".to_string();
+            }
+
+            line_stream
+                .send("PRIVMSG $#127.0.0.* :Hello hosts".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("NOTICE $$irc.* :Hello server".to_string())
+                .await
+                .unwrap();
+            for expected in [
+                ":fanny!~fanny@127.0.0.1 PRIVMSG $#127.0.0.* :Hello hosts",
+                ":fanny!~fanny@127.0.0.1 NOTICE $$irc.* :Hello server",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
+            }
+            // harry doesn't match host mask
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 NOTICE $$irc.* :Hello server",
+                line_stream3.next().await.unwrap().unwrap()
             );
 
-            time::sleep(Duration::from_millis(50)).await;
-            {
-                main_state
-                    .state
-                    .write()
-                    .await
-                    .channels
-                    .get_mut("#channelx")
-                    .unwrap()
-                    .add_voice("bowie");
-            }
-            // if have voice
             line_stream2
-                .send("PRIVMSG #channelx :I want you too!".to_string())
+                .send("PRIVMSG $#*.org :Hello hosts".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want you too!".to_string(),
+                ":irc.irc 481 jerry :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream3.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_duplicate_targets() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
+            line_stream
+                .send("PRIVMSG bowie,bowie :Hello guy!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 407 alan bowie :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("PRIVMSG bowie :Next".to_string())
+                .await
+                .unwrap();
+            // delivered only once
+            for expected in [
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy!",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Next",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
+            }
+
+            // no error replies for NOTICE
+            line_stream
+                .send("NOTICE bowie,bowie :Hello notice!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Hello notice!".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -1141,7 +2072,7 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_privmsg_channel_banned() {
+    async fn test_command_accept() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1150,54 +2081,157 @@ mod test {
             let mut line_stream2 =
                 login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
+            line_stream2.send("MODE bowie +g".to_string()).await.unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 MODE bowie +g".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            // not accepted sender is blocked
             line_stream
-                .send("JOIN #channelx".to_string())
+                .send("PRIVMSG bowie :Hello guy!".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
+            for expected in [
+                ":irc.irc 716 alan bowie :is in +g mode (server-side ignore)",
+                ":irc.irc 717 alan bowie :has been informed that you messaged them.",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+            assert_eq!(
+                ":irc.irc 718 bowie alan ~alan@127.0.0.1 :is messaging you, and you have \
+                user mode +g set. Use /ACCEPT +alan to allow."
+                    .to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            // target is not notified again too early
+            line_stream
+                .send("PRIVMSG bowie :Hello again!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 716 alan bowie :is in +g mode (server-side ignore)".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // and not by other sender
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
+            line_stream3
+                .send("PRIVMSG bowie :Hello from me!".to_string())
+                .await
+                .unwrap();
+            line_stream3.send("PING :w".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 716 cedric bowie :is in +g mode (server-side ignore)",
+                ":irc.irc PONG irc.irc :w",
+            ] {
+                assert_eq!(expected, line_stream3.next().await.unwrap().unwrap());
             }
+
             line_stream2
-                .send("JOIN #channelx".to_string())
+                .send("ACCEPT -alan,+alan,alan,fanny".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                line_stream2.next().await.unwrap().unwrap();
+            for expected in [
+                ":irc.irc 458 bowie alan :is not on your accept list",
+                ":irc.irc 457 bowie alan :is already on your accept list",
+                ":irc.irc 401 bowie fanny :No such nick/channel",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
+            }
+            line_stream2.send("ACCEPT *".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 281 bowie alan",
+                ":irc.irc 282 bowie :End of /ACCEPT list",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
             }
 
+            // accepted sender gets through
             line_stream
-                .send("MODE #channelx +b bowie".to_string())
+                .send("PRIVMSG bowie :Hello accepted!".to_string())
                 .await
                 .unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-            line_stream.next().await.unwrap().unwrap();
-            line_stream2.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello accepted!".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
 
-            line_stream2
-                .send("PRIVMSG #channelx :I want you!".to_string())
+            line_stream2.send("ACCEPT -alan".to_string()).await.unwrap();
+            line_stream2.send("PING :y".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :y".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("NOTICE bowie :Hello notice!".to_string())
                 .await
                 .unwrap();
+            // no error replies for NOTICE
+            line_stream.send("PING :x".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream2.send("PING :z".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :z".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_ctcp_grace_period() {
+        let mut config = MainConfig::default();
+        config.ctcp_grace_period = Some(30);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
             line_stream
-                .send("MODE #channelx +e bowie".to_string())
+                .send("PRIVMSG bowie :\x01VERSION\x01".to_string())
                 .await
                 .unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-            line_stream.next().await.unwrap().unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            line_stream2.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":irc.irc FAIL PRIVMSG CTCP_NOT_ALLOWED :CTCP requests are not allowed \
+                shortly after connection"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // normal messages and actions are not blocked
+            line_stream
+                .send("PRIVMSG bowie :Hello guy!".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("PRIVMSG bowie :\x01ACTION waves\x01".to_string())
+                .await
+                .unwrap();
+            for expected in [
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello guy!",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :\x01ACTION waves\x01",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
+            }
 
-            line_stream2
-                .send("PRIVMSG #channelx :I want you!".to_string())
+            // after grace period CTCP is allowed
+            time::pause();
+            time::advance(Duration::from_secs(31)).await;
+            time::resume();
+            line_stream
+                .send("PRIVMSG bowie :\x01VERSION\x01".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want you!".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :\x01VERSION\x01".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
             );
         }
 
@@ -1205,162 +2239,125 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_privmsg_channel_prefixed() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_notice_rate_limit() {
+        let mut config = MainConfig::default();
+        config.notice_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
-            let mut founder_stream1 =
-                login_to_test_and_skip(port, "founder1", "founder1", "Founder1").await;
-            let mut founder_stream2 =
-                login_to_test_and_skip(port, "founder2", "founder2", "Founder2").await;
-            let mut protected_stream1 =
-                login_to_test_and_skip(port, "protected1", "protected1", "Protected1").await;
-            let mut protected_stream2 =
-                login_to_test_and_skip(port, "protected2", "protected2", "Protected2").await;
-            let mut operator_stream1 =
-                login_to_test_and_skip(port, "operator1", "operator1", "Operator1").await;
-            let mut operator_stream2 =
-                login_to_test_and_skip(port, "operator2", "operator2", "Operator2").await;
-            let mut halfoper_stream1 =
-                login_to_test_and_skip(port, "halfoper1", "halfoper1", "HalfOper1").await;
-            let mut halfoper_stream2 =
-                login_to_test_and_skip(port, "halfoper2", "halfoper2", "HalfOper2").await;
-            let mut voice_stream1 =
-                login_to_test_and_skip(port, "voice1", "voice1", "Voice1").await;
-            let mut voice_stream2 =
-                login_to_test_and_skip(port, "voice2", "voice2", "Voice2").await;
-
-            for line_stream in [
-                &mut line_stream,
-                &mut founder_stream1,
-                &mut founder_stream2,
-                &mut protected_stream1,
-                &mut protected_stream2,
-                &mut operator_stream1,
-                &mut operator_stream2,
-                &mut halfoper_stream1,
-                &mut halfoper_stream2,
-                &mut voice_stream1,
-                &mut voice_stream2,
-            ] {
+            for i in 0..4 {
                 line_stream
-                    .send("JOIN #channely".to_string())
+                    .send(format!("NOTICE bowie :Notice {}", i))
+                    .await
+                    .unwrap();
+                line_stream
+                    .send(format!("PRIVMSG bowie :Message {}", i))
                     .await
                     .unwrap();
-                for _ in 0..3 {
-                    line_stream.next().await.unwrap().unwrap();
-                }
             }
-            // skip joins
-            for (n, line_stream) in [
-                &mut line_stream,
-                &mut founder_stream1,
-                &mut founder_stream2,
-                &mut protected_stream1,
-                &mut protected_stream2,
-                &mut operator_stream1,
-                &mut operator_stream2,
-                &mut halfoper_stream1,
-                &mut halfoper_stream2,
-                &mut voice_stream1,
-                &mut voice_stream2,
-            ]
-            .iter_mut()
-            .enumerate()
-            {
-                for _ in 0..(10 - n) {
-                    line_stream.next().await.unwrap().unwrap();
-                }
+            // PRIVMSG is not limited by NOTICE rate limit
+            for expected in [
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Notice 0",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Message 0",
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Notice 1",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Message 1",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Message 2",
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Message 3",
+            ] {
+                assert_eq!(expected, line_stream2.next().await.unwrap().unwrap());
             }
 
+            // bots are not limited
+            line_stream.send("MODE alan +B".to_string()).await.unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 MODE alan +B".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
             line_stream
-                .send(
-                    "MODE #channely +q founder1 +q founder2 \
-                        +a protected1 +a protected2 +o operator1 +o operator2 \
-                        +h halfoper1 +h halfoper2 +v voice1 +v voice2"
-                        .to_string(),
-                )
+                .send("NOTICE bowie :Bot notice".to_string())
                 .await
                 .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Bot notice".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
 
-            // skip joins
-            for line_stream in [
-                &mut line_stream,
-                &mut founder_stream1,
-                &mut founder_stream2,
-                &mut protected_stream1,
-                &mut protected_stream2,
-                &mut operator_stream1,
-                &mut operator_stream2,
-                &mut halfoper_stream1,
-                &mut halfoper_stream2,
-                &mut voice_stream1,
-                &mut voice_stream2,
-            ] {
-                line_stream.next().await.unwrap().unwrap();
-            }
+        quit_test_server(main_state, handle).await;
+    }
 
-            for (ch, send_stream, send_nick, recv_stream, recv_nick) in [
-                (
-                    '~',
-                    &mut founder_stream1,
-                    "founder1",
-                    &mut founder_stream2,
-                    "founder2",
-                ),
-                (
-                    '&',
-                    &mut protected_stream1,
-                    "protected1",
-                    &mut protected_stream2,
-                    "protected2",
-                ),
-                (
-                    '@',
-                    &mut operator_stream1,
-                    "operator1",
-                    &mut operator_stream2,
-                    "operator2",
-                ),
-                (
-                    '%',
-                    &mut halfoper_stream1,
-                    "halfoper1",
-                    &mut halfoper_stream2,
-                    "halfoper2",
-                ),
-                (
-                    '+',
-                    &mut voice_stream1,
-                    "voice1",
-                    &mut voice_stream2,
-                    "voice2",
-                ),
-            ] {
-                send_stream
-                    .send(format!("PRIVMSG {}#channely :Hello guys", ch))
+    #[tokio::test]
+    async fn test_command_notice_ctcp_reply_rate_limit() {
+        let mut config = MainConfig::default();
+        config.ctcp_reply_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
+            // normal CTCP request and reply
+            line_stream
+                .send("PRIVMSG bowie :\x01VERSION\x01".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :\x01VERSION\x01".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream2
+                .send("NOTICE alan :\x01VERSION bowiebot\x01".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 NOTICE alan :\x01VERSION bowiebot\x01".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // reply storm - excess replies are dropped
+            for _ in 0..3 {
+                line_stream
+                    .send("NOTICE bowie :\x01PING 1\x01".to_string())
                     .await
                     .unwrap();
+            }
+            line_stream
+                .send("NOTICE bowie :Stop it!".to_string())
+                .await
+                .unwrap();
+            for _ in 0..2 {
                 assert_eq!(
-                    format!(
-                        ":{0}!~{0}@127.0.0.1 PRIVMSG {1}#channely :Hello guys",
-                        send_nick, ch
-                    ),
-                    recv_stream.next().await.unwrap().unwrap()
+                    ":alan!~alan@127.0.0.1 NOTICE bowie :\x01PING 1\x01".to_string(),
+                    line_stream2.next().await.unwrap().unwrap()
                 );
-                recv_stream
-                    .send(format!("PRIVMSG {}#channely :Hello guys", ch))
+            }
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Stop it!".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            // CTCP requests are not limited
+            for _ in 0..3 {
+                line_stream
+                    .send("PRIVMSG bowie :\x01PING 2\x01".to_string())
                     .await
                     .unwrap();
                 assert_eq!(
-                    format!(
-                        ":{0}!~{0}@127.0.0.1 PRIVMSG {1}#channely :Hello guys",
-                        recv_nick, ch
-                    ),
-                    send_stream.next().await.unwrap().unwrap()
+                    ":alan!~alan@127.0.0.1 PRIVMSG bowie :\x01PING 2\x01".to_string(),
+                    line_stream2.next().await.unwrap().unwrap()
                 );
             }
         }
@@ -1369,7 +2366,7 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_privmsg_multiple() {
+    async fn test_command_privmsg_channel() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1380,34 +2377,48 @@ mod test {
             let mut line_stream3 =
                 login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
 
+            for line_stream in [&mut line_stream, &mut line_stream2, &mut line_stream3] {
+                line_stream
+                    .send("JOIN #channelx".to_string())
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2.next().await.unwrap().unwrap();
+
             line_stream
-                .send("JOIN #channelx".to_string())
+                .send("PRIVMSG #channelx :Hello guy!".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                line_stream.next().await.unwrap().unwrap();
+            for line_stream in [&mut line_stream2, &mut line_stream3] {
+                assert_eq!(
+                    ":alan!~alan@127.0.0.1 PRIVMSG #channelx :Hello guy!".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
             }
-            line_stream2
-                .send("JOIN #channelx".to_string())
+            line_stream3
+                .send("PRIVMSG #channelx :Hi!".to_string())
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                line_stream2.next().await.unwrap().unwrap();
+            for line_stream in [&mut line_stream, &mut line_stream2] {
+                assert_eq!(
+                    ":cedric!~cedric@127.0.0.1 PRIVMSG #channelx :Hi!".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
             }
-
-            line_stream.next().await.unwrap().unwrap();
-
             line_stream
-                .send("PRIVMSG #channelx,cedric :Hello boys".to_string())
+                .send("PRIVMSG #channely :Hello guy!".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG #channelx :Hello boys".to_string(),
-                line_stream2.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG cedric :Hello boys".to_string(),
-                line_stream3.next().await.unwrap().unwrap()
+                ":irc.irc 403 alan #channely :No such channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
         }
 
@@ -1415,7 +2426,7 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_privmsg_activity() {
+    async fn test_command_privmsg_channel_external_messages() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1424,46 +2435,52 @@ mod test {
             let mut line_stream2 =
                 login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
-            time::sleep(Duration::from_millis(50)).await;
-            let activity = {
-                let mut state = main_state.state.write().await;
-                state.users.get_mut("alan").unwrap().last_activity -= 10;
-                state.users.get("alan").unwrap().last_activity
-            };
             line_stream
-                .send("PRIVMSG guru :Hello boys".to_string())
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            // send message to channel
+            line_stream2
+                .send("PRIVMSG #channelx :I want to join!".to_string())
                 .await
                 .unwrap();
-            time::sleep(Duration::from_millis(50)).await;
             assert_eq!(
-                ":irc.irc 401 alan guru :No such nick/channel".to_string(),
+                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want to join!".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            time::sleep(Duration::from_millis(50)).await;
             {
-                let state = main_state.state.read().await;
-                assert_eq!(activity, state.users.get("alan").unwrap().last_activity);
+                main_state
+                    .state
+                    .write()
+                    .await
+                    .channels
+                    .get_mut("#channelx")
+                    .unwrap()
+                    .modes
+                    .no_external_messages = true;
             }
 
-            line_stream
-                .send("PRIVMSG bowie :Hello boys".to_string())
+            line_stream2
+                .send("PRIVMSG #channelx :I want to join!".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello boys".to_string(),
+                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
-            time::sleep(Duration::from_millis(50)).await;
-            {
-                let state = main_state.state.read().await;
-                assert_ne!(activity, state.users.get("alan").unwrap().last_activity);
-            }
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_notice() {
+    async fn test_command_privmsg_channel_moderated() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
@@ -1471,95 +2488,166 @@ mod test {
                 login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
             let mut line_stream2 =
                 login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
-            let mut cedric_stream =
+            let mut line_stream3 =
                 login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
 
+            line_stream
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+
             time::sleep(Duration::from_millis(50)).await;
             {
-                let mut state = main_state.state.write().await;
-                state.users.get_mut("cedric").unwrap().away = Some("Bye".to_string());
+                main_state
+                    .state
+                    .write()
+                    .await
+                    .channels
+                    .get_mut("#channelx")
+                    .unwrap()
+                    .modes
+                    .moderated = true;
             }
 
+            // send message to channel
             line_stream
-                .send("NOTICE #chan1,guru,cedric :Hello boys".to_string())
+                .send("PRIVMSG #channelx :I want you!".to_string())
                 .await
                 .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG #channelx :I want you!".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
             line_stream2
-                .send("PRIVMSG alan :Hello boys".to_string())
+                .send("PRIVMSG #channelx :I want you!".to_string())
                 .await
                 .unwrap();
-            // no other error replies (NOTICE - doesn't send error messages)
-            // directly this message
             assert_eq!(
-                ":bowie!~bowie@127.0.0.1 PRIVMSG alan :Hello boys".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            line_stream3
+                .send("PRIVMSG #channelx :I want you too!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 404 cedric #channelx :Cannot send to channel".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                main_state
+                    .state
+                    .write()
+                    .await
+                    .channels
+                    .get_mut("#channelx")
+                    .unwrap()
+                    .add_voice("bowie");
+            }
+            // if have voice
+            line_stream2
+                .send("PRIVMSG #channelx :I want you too!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want you too!".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
-            cedric_stream.send("QUIT :Bye".to_string()).await.unwrap();
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_who() {
-        let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
-        let (main_state, handle, port) = run_test_server(config).await;
+    async fn test_command_privmsg_channel_no_colors() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
             line_stream
-                .send("OPER fanny Funny".to_string())
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2
+                .send("JOIN #channelx".to_string())
                 .await
                 .unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
             line_stream.next().await.unwrap().unwrap();
 
-            let mut line_stream2 =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-            line_stream.send("WHO jerry".to_string()).await.unwrap();
+            line_stream
+                .send("MODE #channelx +c".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 352 fanny * ~jerry 127.0.0.1 irc.irc jerry H :0 \
-                    Jerry Lazy"
-                    .to_string(),
+                ":alan!~alan@127.0.0.1 MODE #channelx +c".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 315 fanny jerry :End of WHO list".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+                ":alan!~alan@127.0.0.1 MODE #channelx +c".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
             );
 
-            time::sleep(Duration::from_millis(50)).await;
-            {
-                let mut state = main_state.state.write().await;
-                state.users.get_mut("jerry").unwrap().away = Some("Bye".to_string());
+            for text in [
+                "\x0304,12red on blue\x03",
+                "\x03 color code without digits",
+                "\x02bold\x02",
+                "\x1funderline\x1f",
+                "\x16reverse\x16",
+            ] {
+                line_stream2
+                    .send(format!("PRIVMSG #channelx :{}", text))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                    line_stream2.next().await.unwrap().unwrap()
+                );
             }
-
-            line_stream.send("WHO jerry".to_string()).await.unwrap();
-            assert_eq!(
-                ":irc.irc 352 fanny * ~jerry 127.0.0.1 irc.irc jerry G :0 \
-                    Jerry Lazy"
-                    .to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
+            // notice with colors is silently dropped
+            line_stream2
+                .send("NOTICE #channelx :\x0304red".to_string())
+                .await
+                .unwrap();
+            line_stream2
+                .send("PRIVMSG #channelx :plain text".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 315 fanny jerry :End of WHO list".to_string(),
+                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :plain text".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-
-            line_stream2.send("WHO fanny".to_string()).await.unwrap();
-            assert_eq!(
-                ":irc.irc 352 jerry * ~fanny 127.0.0.1 irc.irc fanny H* :0 \
-                    Fanny BumBumBum"
-                    .to_string(),
-                line_stream2.next().await.unwrap().unwrap()
-            );
+            // channel operator can send colors
+            line_stream
+                .send("PRIVMSG #channelx :\x0304red".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 315 jerry fanny :End of WHO list".to_string(),
+                ":alan!~alan@127.0.0.1 PRIVMSG #channelx :\x0304red".to_string(),
                 line_stream2.next().await.unwrap().unwrap()
             );
         }
@@ -1567,140 +2655,1065 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
-    fn equal_list<'a>(msg_start: &'a str, expected: &'a [&'a str], results: &'a [&'a str]) -> bool {
-        let mut expected_sorted = Vec::from(expected);
-        expected_sorted.sort();
-        let mut touched = vec![false; expected.len()];
-
-        results.iter().all(|res| {
-            if res.starts_with(msg_start) {
-                let rest = &res[msg_start.len()..];
-                if let Ok(p) = expected_sorted.binary_search(&rest) {
-                    touched[p] = true;
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        }) && touched.iter().all(|x| *x)
-    }
-
     #[tokio::test]
-    async fn test_command_who_channel() {
+    async fn test_command_privmsg_channel_moderated_modes() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
             let mut line_stream2 =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
 
-            for line_stream in [&mut line_stream, &mut line_stream2] {
+            line_stream
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+            line_stream3
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream3.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+
+            // operator, moderated and exception for cedric
+            for mode in ["+o bowie", "+m", "+e cedric!*@*"] {
                 line_stream
-                    .send("JOIN #channelz".to_string())
+                    .send(format!("MODE #channelx {}", mode))
                     .await
                     .unwrap();
-                for _ in 0..3 {
+                for line_stream in [&mut line_stream, &mut line_stream2, &mut line_stream3] {
                     line_stream.next().await.unwrap().unwrap();
                 }
             }
-            line_stream.next().await.unwrap().unwrap();
 
-            line_stream.send("WHO #channelz".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny #channelz ",
-                &[
-                    "~fanny 127.0.0.1 irc.irc fanny H~ :0 Fanny BumBumBum",
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy"
-                ],
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
+            // regular member can't speak, also if has ban exception.
+            line_stream3
+                .send("PRIVMSG #channelx :Hello!".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 315 fanny #channelz :End of WHO list".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+                ":irc.irc 404 cedric #channelx :Cannot send to channel".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream3
+                .send("NOTICE #channelx :Hello!".to_string())
+                .await
+                .unwrap();
+            line_stream3.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
             );
-        }
-
-        quit_test_server(main_state, handle).await;
-    }
-
-    #[tokio::test]
-    async fn test_command_who_channel_multi_prefix() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
-        {
-            let mut line_stream = connect_to_test(port).await;
-            line_stream.send("CAP LS 302".to_string()).await.unwrap();
-            line_stream.send("NICK fanny".to_string()).await.unwrap();
-            line_stream
-                .send("USER fanny 8 * :Fanny BumBumBum".to_string())
+            // channel operator can speak
+            line_stream2
+                .send("PRIVMSG #channelx :I am operator!".to_string())
                 .await
                 .unwrap();
+            for line_stream in [&mut line_stream, &mut line_stream3] {
+                assert_eq!(
+                    ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I am operator!".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            // voiced member can speak
             line_stream
-                .send("CAP REQ :multi-prefix".to_string())
+                .send("MODE #channelx +v cedric".to_string())
                 .await
                 .unwrap();
-            line_stream.send("CAP END".to_string()).await.unwrap();
-            for _ in 0..20 {
+            for line_stream in [&mut line_stream, &mut line_stream2, &mut line_stream3] {
                 line_stream.next().await.unwrap().unwrap();
             }
-
-            let mut line_stream2 =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-
+            line_stream3
+                .send("PRIVMSG #channelx :I have voice!".to_string())
+                .await
+                .unwrap();
             for line_stream in [&mut line_stream, &mut line_stream2] {
-                line_stream
-                    .send("JOIN #channelz".to_string())
-                    .await
-                    .unwrap();
-                for _ in 0..3 {
-                    line_stream.next().await.unwrap().unwrap();
-                }
+                assert_eq!(
+                    ":cedric!~cedric@127.0.0.1 PRIVMSG #channelx :I have voice!".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
             }
-            line_stream.next().await.unwrap().unwrap();
-
-            line_stream.send("WHO #channelz".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny #channelz ",
-                &[
-                    "~fanny 127.0.0.1 irc.irc fanny H~@ :0 Fanny BumBumBum",
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy"
-                ],
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
-            assert_eq!(
-                ":irc.irc 315 fanny #channelz :End of WHO list".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_who_wildcards() {
+    async fn test_command_privmsg_channel_banned() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut stream1 = login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-            let mut stream2 = login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
-            let mut stream3 = login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
-            line_stream.send("WHO j*rry".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny * ",
-                &[
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
+            line_stream
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+
+            line_stream
+                .send("MODE #channelx +b bowie".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            line_stream.next().await.unwrap().unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+
+            line_stream2
+                .send("PRIVMSG #channelx :I want you!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 404 bowie #channelx :Cannot send to channel".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("MODE #channelx +e bowie".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+
+            line_stream2
+                .send("PRIVMSG #channelx :I want you!".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 PRIVMSG #channelx :I want you!".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_channel_prefixed() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+
+            let mut founder_stream1 =
+                login_to_test_and_skip(port, "founder1", "founder1", "Founder1").await;
+            let mut founder_stream2 =
+                login_to_test_and_skip(port, "founder2", "founder2", "Founder2").await;
+            let mut protected_stream1 =
+                login_to_test_and_skip(port, "protected1", "protected1", "Protected1").await;
+            let mut protected_stream2 =
+                login_to_test_and_skip(port, "protected2", "protected2", "Protected2").await;
+            let mut operator_stream1 =
+                login_to_test_and_skip(port, "operator1", "operator1", "Operator1").await;
+            let mut operator_stream2 =
+                login_to_test_and_skip(port, "operator2", "operator2", "Operator2").await;
+            let mut halfoper_stream1 =
+                login_to_test_and_skip(port, "halfoper1", "halfoper1", "HalfOper1").await;
+            let mut halfoper_stream2 =
+                login_to_test_and_skip(port, "halfoper2", "halfoper2", "HalfOper2").await;
+            let mut voice_stream1 =
+                login_to_test_and_skip(port, "voice1", "voice1", "Voice1").await;
+            let mut voice_stream2 =
+                login_to_test_and_skip(port, "voice2", "voice2", "Voice2").await;
+
+            for line_stream in [
+                &mut line_stream,
+                &mut founder_stream1,
+                &mut founder_stream2,
+                &mut protected_stream1,
+                &mut protected_stream2,
+                &mut operator_stream1,
+                &mut operator_stream2,
+                &mut halfoper_stream1,
+                &mut halfoper_stream2,
+                &mut voice_stream1,
+                &mut voice_stream2,
+            ] {
+                line_stream
+                    .send("JOIN #channely".to_string())
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+            // skip joins
+            for (n, line_stream) in [
+                &mut line_stream,
+                &mut founder_stream1,
+                &mut founder_stream2,
+                &mut protected_stream1,
+                &mut protected_stream2,
+                &mut operator_stream1,
+                &mut operator_stream2,
+                &mut halfoper_stream1,
+                &mut halfoper_stream2,
+                &mut voice_stream1,
+                &mut voice_stream2,
+            ]
+            .iter_mut()
+            .enumerate()
+            {
+                for _ in 0..(10 - n) {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            line_stream
+                .send(
+                    "MODE #channely +q founder1 +q founder2 \
+                        +a protected1 +a protected2 +o operator1 +o operator2 \
+                        +h halfoper1 +h halfoper2 +v voice1 +v voice2"
+                        .to_string(),
+                )
+                .await
+                .unwrap();
+
+            // skip joins
+            for line_stream in [
+                &mut line_stream,
+                &mut founder_stream1,
+                &mut founder_stream2,
+                &mut protected_stream1,
+                &mut protected_stream2,
+                &mut operator_stream1,
+                &mut operator_stream2,
+                &mut halfoper_stream1,
+                &mut halfoper_stream2,
+                &mut voice_stream1,
+                &mut voice_stream2,
+            ] {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            for (ch, send_stream, send_nick, recv_stream, recv_nick) in [
+                (
+                    '~',
+                    &mut founder_stream1,
+                    "founder1",
+                    &mut founder_stream2,
+                    "founder2",
+                ),
+                (
+                    '&',
+                    &mut protected_stream1,
+                    "protected1",
+                    &mut protected_stream2,
+                    "protected2",
+                ),
+                (
+                    '@',
+                    &mut operator_stream1,
+                    "operator1",
+                    &mut operator_stream2,
+                    "operator2",
+                ),
+                (
+                    '%',
+                    &mut halfoper_stream1,
+                    "halfoper1",
+                    &mut halfoper_stream2,
+                    "halfoper2",
+                ),
+                (
+                    '+',
+                    &mut voice_stream1,
+                    "voice1",
+                    &mut voice_stream2,
+                    "voice2",
+                ),
+            ] {
+                send_stream
+                    .send(format!("PRIVMSG {}#channely :Hello guys", ch))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    format!(
+                        ":{0}!~{0}@127.0.0.1 PRIVMSG {1}#channely :Hello guys",
+                        send_nick, ch
+                    ),
+                    recv_stream.next().await.unwrap().unwrap()
+                );
+                recv_stream
+                    .send(format!("PRIVMSG {}#channely :Hello guys", ch))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    format!(
+                        ":{0}!~{0}@127.0.0.1 PRIVMSG {1}#channely :Hello guys",
+                        recv_nick, ch
+                    ),
+                    send_stream.next().await.unwrap().unwrap()
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_multiple() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
+
+            line_stream
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2
+                .send("JOIN #channelx".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream
+                .send("PRIVMSG #channelx,cedric :Hello boys".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG #channelx :Hello boys".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG cedric :Hello boys".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_privmsg_activity() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            let activity = {
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("alan").unwrap().last_activity -= 10;
+                state.users.get("alan").unwrap().last_activity
+            };
+            line_stream
+                .send("PRIVMSG guru :Hello boys".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(
+                ":irc.irc 401 alan guru :No such nick/channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            {
+                let state = main_state.state.read().await;
+                assert_eq!(activity, state.users.get("alan").unwrap().last_activity);
+            }
+
+            line_stream
+                .send("PRIVMSG bowie :Hello boys".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello boys".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert_ne!(activity, state.users.get("alan").unwrap().last_activity);
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_notice() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let mut cedric_stream =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("cedric").unwrap().away = Some("Bye".to_string());
+            }
+
+            line_stream
+                .send("NOTICE #chan1,guru,cedric :Hello boys".to_string())
+                .await
+                .unwrap();
+            line_stream2
+                .send("PRIVMSG alan :Hello boys".to_string())
+                .await
+                .unwrap();
+            // no other error replies (NOTICE - doesn't send error messages)
+            // directly this message
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 PRIVMSG alan :Hello boys".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            cedric_stream.send("QUIT :Bye".to_string()).await.unwrap();
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_cprivmsg() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let mut cedric_stream =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
+
+            line_stream.send("JOIN #chat".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            bowie_stream.send("JOIN #chat".to_string()).await.unwrap();
+            for _ in 0..3 {
+                bowie_stream.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream
+                .send("CPRIVMSG bowie #chat :Hello Bowie".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello Bowie".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CNOTICE bowie #chat :Hello again".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 NOTICE bowie :Hello again".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            // caller-ID (+g) of target is bypassed
+            bowie_stream
+                .send("MODE bowie +g".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 MODE bowie +g".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CPRIVMSG bowie #chat :Hello quiet Bowie".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello quiet Bowie".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("CPRIVMSG cedric #chat :Hello Cedric".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 441 alan cedric #chat :They aren't on that channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // ordinary channel user can not use CPRIVMSG
+            bowie_stream
+                .send("CPRIVMSG alan #chat :Hello Alan".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 482 bowie #chat :You're not channel operator".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            cedric_stream
+                .send("CPRIVMSG alan #chat :Hello Alan".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 442 cedric #chat :You're not on that channel".to_string(),
+                cedric_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("PING xxx".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :xxx".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_relaymsg() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![UserConfig {
+            name: "relaybot".to_string(),
+            nick: "relaybot".to_string(),
+            password: None,
+            mask: None,
+        }]);
+        config.relay_accounts = Some(vec!["relaybot".to_string()]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "relaybot", "relaybot", "Relay Bot").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+
+            line_stream.send("JOIN #bridge".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("JOIN #bridge".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream
+                .send("RELAYMSG #bridge discord/alice :Hello from Discord".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":discord/alice!relay@irc.irc PRIVMSG #bridge :Hello from Discord".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("RELAYMSG #bridge alice :Hello".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RELAYMSG INVALID_NICK alice :Nick must contain '/' and \
+                    must not be in use"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("RELAYMSG #nowhere discord/alice :Hello".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 403 relaybot #nowhere :No such channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // relayed nick can be banned
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                let chanobj = state.channels.get_mut("#bridge").unwrap();
+                chanobj.modes.ban = Some(["discord/*!*@*".to_string()].into());
+            }
+            line_stream
+                .send("RELAYMSG #bridge discord/alice :Hello".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 404 relaybot #bridge :Cannot send to channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // bot without voice can not relay to moderated channel
+            {
+                let mut state = main_state.state.write().await;
+                let chanobj = state.channels.get_mut("#bridge").unwrap();
+                chanobj.modes.ban = None;
+                chanobj.remove_founder("relaybot");
+                chanobj.remove_operator("relaybot");
+                chanobj.modes.moderated = true;
+            }
+            line_stream
+                .send("RELAYMSG #bridge discord/alice :Hello".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 404 relaybot #bridge :Cannot send to channel".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // user that is not bridge bot can not relay messages.
+            line_stream2
+                .send("RELAYMSG #bridge discord/bob :Hello".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL RELAYMSG PRIVS_NEEDED :You cannot relay messages".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            line_stream.send("WHO jerry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 fanny * ~jerry 127.0.0.1 irc.irc jerry H :0 \
+                    Jerry Lazy"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 315 fanny jerry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let mut state = main_state.state.write().await;
+                state.users.get_mut("jerry").unwrap().away = Some("Bye".to_string());
+            }
+
+            line_stream.send("WHO jerry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 fanny * ~jerry 127.0.0.1 irc.irc jerry G :0 \
+                    Jerry Lazy"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 315 fanny jerry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream2.send("WHO fanny".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 jerry * ~fanny 127.0.0.1 irc.irc fanny H* :0 \
+                    Fanny BumBumBum"
+                    .to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 315 jerry fanny :End of WHO list".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_flags() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            line_stream2.send("MODE jerry +B".to_string()).await.unwrap();
+            assert_eq!(
+                ":jerry!~jerry@127.0.0.1 MODE jerry +B".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            // only operator can hide operator status
+            line_stream2.send("MODE jerry +H".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 jerry :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            let mut line_stream3 =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            for line_stream in [&mut line_stream, &mut line_stream2] {
+                line_stream.send("JOIN #flags".to_string()).await.unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+            line_stream.next().await.unwrap().unwrap();
+            line_stream
+                .send("MODE #flags +o jerry".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+
+            line_stream3.send("WHO jerry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 harry * ~jerry 127.0.0.1 irc.irc jerry HB :0 Jerry Lazy",
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream3.next().await.unwrap().unwrap();
+            line_stream2.send("AWAY :Bye".to_string()).await.unwrap();
+            line_stream2.next().await.unwrap().unwrap();
+            line_stream3.send("WHO jerry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 harry * ~jerry 127.0.0.1 irc.irc jerry GB :0 Jerry Lazy",
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream3.next().await.unwrap().unwrap();
+
+            line_stream3.send("WHO fanny".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 harry * ~fanny 127.0.0.1 irc.irc fanny H* :0 Fanny BumBumBum",
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream3.next().await.unwrap().unwrap();
+            line_stream.send("MODE fanny +H".to_string()).await.unwrap();
+            assert_eq!(
+                ":fanny!~fanny@127.0.0.1 MODE fanny +H".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            // hidden operator - visible only for operators
+            line_stream3.send("WHO fanny".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 harry * ~fanny 127.0.0.1 irc.irc fanny H :0 Fanny BumBumBum",
+                line_stream3.next().await.unwrap().unwrap()
+            );
+            line_stream3.next().await.unwrap().unwrap();
+            line_stream.send("WHO fanny".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 352 fanny * ~fanny 127.0.0.1 irc.irc fanny H* :0 Fanny BumBumBum",
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream3.send("WHO #flags".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 harry #flags ",
+                &[
+                    "~fanny 127.0.0.1 irc.irc fanny H~ :0 Fanny BumBumBum",
+                    "~jerry 127.0.0.1 irc.irc jerry GB@ :0 Jerry Lazy"
+                ],
+                &[
+                    &line_stream3.next().await.unwrap().unwrap(),
+                    &line_stream3.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 harry #flags :End of WHO list".to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    fn equal_list<'a>(msg_start: &'a str, expected: &'a [&'a str], results: &'a [&'a str]) -> bool {
+        let mut expected_sorted = Vec::from(expected);
+        expected_sorted.sort();
+        let mut touched = vec![false; expected.len()];
+
+        results.iter().all(|res| {
+            if res.starts_with(msg_start) {
+                let rest = &res[msg_start.len()..];
+                if let Ok(p) = expected_sorted.binary_search(&rest) {
+                    touched[p] = true;
+                    true
+                } else {
+                    false
+                }
+            } else {
+                false
+            }
+        }) && touched.iter().all(|x| *x)
+    }
+
+    #[tokio::test]
+    async fn test_command_who_channel() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+
+            for line_stream in [&mut line_stream, &mut line_stream2] {
+                line_stream
+                    .send("JOIN #channelz".to_string())
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream.send("WHO #channelz".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny #channelz ",
+                &[
+                    "~fanny 127.0.0.1 irc.irc fanny H~ :0 Fanny BumBumBum",
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy"
+                ],
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 fanny #channelz :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_channel_large() {
+        let mut config = MainConfig::default();
+        config.max_who_results = Some(5);
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+
+            let mut member_streams = vec![];
+            for i in 0..12 {
+                let nick = format!("member{}", i);
+                let mut member_stream = login_to_test_and_skip(port, &nick, &nick, "Member").await;
+                member_stream
+                    .send("JOIN #bigchannel".to_string())
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    member_stream.next().await.unwrap().unwrap();
+                }
+                member_streams.push(member_stream);
+            }
+
+            // operator gets all users
+            line_stream.send("WHO #bigchannel".to_string()).await.unwrap();
+            for _ in 0..12 {
+                assert!(line_stream
+                    .next()
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .starts_with(":irc.irc 352 fanny #bigchannel ~member"));
+            }
+            assert_eq!(
+                ":irc.irc 315 fanny #bigchannel :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // non-operator gets limited number of replies
+            line_stream2.send("WHO #bigchannel".to_string()).await.unwrap();
+            for _ in 0..5 {
+                assert!(line_stream2
+                    .next()
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .starts_with(":irc.irc 352 jerry #bigchannel ~member"));
+            }
+            assert_eq!(
+                ":irc.irc 416 jerry WHO #bigchannel :Output too long (try locally)".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 315 jerry #bigchannel :End of WHO list".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+            line_stream2.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_channel_multi_prefix() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream.send("NICK fanny".to_string()).await.unwrap();
+            line_stream
+                .send("USER fanny 8 * :Fanny BumBumBum".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("CAP REQ :multi-prefix".to_string())
+                .await
+                .unwrap();
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..21 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+
+            for line_stream in [&mut line_stream, &mut line_stream2] {
+                line_stream
+                    .send("JOIN #channelz".to_string())
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    line_stream.next().await.unwrap().unwrap();
+                }
+            }
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream.send("WHO #channelz".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny #channelz ",
+                &[
+                    "~fanny 127.0.0.1 irc.irc fanny H~@ :0 Fanny BumBumBum",
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy"
+                ],
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 fanny #channelz :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_wildcards() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut stream1 = login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            let mut stream2 = login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
+            let mut stream3 = login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            line_stream.send("WHO j*rry".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny * ",
+                &[
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
                     "~jarry 127.0.0.1 irc.irc jarry H :0 Jarry Lazy"
                 ],
                 &[
@@ -1709,158 +3722,1435 @@ mod test {
                 ]
             ));
             assert_eq!(
-                ":irc.irc 315 fanny j*rry :End of WHO list".to_string(),
+                ":irc.irc 315 fanny j*rry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("WHO *rry".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny * ",
+                &[
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
+                    "~jarry 127.0.0.1 irc.irc jarry H :0 Jarry Lazy",
+                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
+                ],
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            for stream in [&mut stream1, &mut stream2, &mut stream3] {
+                stream.send("QUIT :Bye".to_string()).await.unwrap();
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_invisible() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+
+            let mut jerry_stream =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            let mut jarry_stream =
+                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            jarry_stream
+                .send("MODE jarry +i".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+
+            line_stream.send("WHO *rry".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny * ",
+                &[
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
+                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
+                ],
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            for stream in [&mut jerry_stream, &mut jarry_stream, &mut harry_stream] {
+                stream.send("QUIT :Bye".to_string()).await.unwrap();
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_who_invisible_channel() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+
+            let mut jerry_stream =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            let mut jarry_stream =
+                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            line_stream
+                .send("JOIN #mychannel".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(50)).await;
+            jarry_stream
+                .send("JOIN #superchannel".to_string())
+                .await
+                .unwrap();
+            jarry_stream
+                .send("MODE jarry +i".to_string())
+                .await
+                .unwrap();
+            harry_stream
+                .send("JOIN #mychannel".to_string())
+                .await
+                .unwrap();
+            harry_stream
+                .send("MODE harry +i".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+
+            for _ in 0..(3 + 1) {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            line_stream.send("WHO *rry".to_string()).await.unwrap();
+            assert!(equal_list(
+                ":irc.irc 352 fanny * ",
+                &[
+                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
+                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
+                ],
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            jerry_stream.send("QUIT :Bye".to_string()).await.unwrap();
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            let signon = {
+                main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("harry")
+                    .unwrap()
+                    .signon
+            };
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    now - signon,
+                    signon
+                ),
+                ":irc.irc 320 fanny harry :has unique ID 0AA000001",
+                ":irc.irc 318 fanny harry :End of /WHOIS list",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+
+            let signon = {
+                main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("fanny")
+                    .unwrap()
+                    .signon
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            harry_stream.send("WHOIS fanny".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 311 harry fanny ~fanny 127.0.0.1 * :Fanny BumBumBum",
+                ":irc.irc 312 harry fanny irc.irc :This is IRC server",
+                ":irc.irc 313 harry fanny :is an IRC operator",
+                &format!(
+                    ":irc.irc 317 harry fanny {} {} :seconds idle, signon time",
+                    now - signon,
+                    signon
+                ),
+                ":irc.irc 378 harry fanny :is connecting from 127.0.0.1",
+                ":irc.irc 379 harry fanny :is using modes +o",
+                ":irc.irc 318 harry fanny :End of /WHOIS list",
+            ] {
+                assert_eq!(expected, harry_stream.next().await.unwrap().unwrap());
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_sessions() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![UserConfig {
+            name: "lucky".to_string(),
+            nick: "luckboy".to_string(),
+            password: Some(argon2_hash_password("top_secret")),
+            mask: None,
+        }]);
+        config.max_account_sessions = Some(2);
+        let (main_state, handle, port) = run_test_server(config).await;
+        // base64 of "\0lucky\0top_secret"
+        let payload = "AGx1Y2t5AHRvcF9zZWNyZXQ=";
+
+        {
+            let mut line_stream = login_to_test_with_sasl(port, "bobby", "bob", payload).await;
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..23 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let mut line_stream2 = login_to_test_with_sasl(port, "bobby2", "bob2", payload).await;
+            line_stream2.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..23 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            let mut line_stream3 = login_to_test_and_skip(port, "robby", "rob", "Robby").await;
+
+            // session limit reached
+            let mut line_stream4 = login_to_test_with_sasl(port, "bobby3", "bob3", payload).await;
+            for _ in 0..2 {
+                line_stream4.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc 904 bobby3 :SASL authentication failed".to_string(),
+                line_stream4.next().await.unwrap().unwrap()
+            );
+
+            let (signon, signon2) = {
+                let state = main_state.state.read().await;
+                (
+                    state.users.get("bobby").unwrap().signon,
+                    state.users.get("bobby2").unwrap().signon,
+                )
+            };
+            line_stream.send("SESSIONS".to_string()).await.unwrap();
+            assert_eq!(
+                format!(
+                    ":irc.irc NOTICE bobby :bobby bob@127.0.0.1 {} (current)",
+                    signon
+                ),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                format!(":irc.irc NOTICE bobby :bobby2 bob2@127.0.0.1 {}", signon2),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc NOTICE bobby :End of sessions list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("SESSIONS KILL robby".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL SESSIONS NO_SUCH_SESSION robby :No such session of \
+                    your account"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("SESSIONS KILL bobby".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL SESSIONS NO_SUCH_SESSION bobby :No such session of \
+                    your account"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("SESSIONS KILL bobby2".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc ERROR :User killed by bobby: Session terminated".to_string(),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+
+            // user without account
+            line_stream3.send("SESSIONS".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc FAIL SESSIONS ACCOUNT_REQUIRED :You are not logged in to \
+                    an account"
+                    .to_string(),
+                line_stream3.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_account() {
+        let mut config = MainConfig::default();
+        config.users = Some(vec![UserConfig {
+            name: "roland".to_string(),
+            nick: "roland".to_string(),
+            password: None,
+            mask: None,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "roland", "roland", "Roland TechnoMusic").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            harry_stream.send("WHOIS roland".to_string()).await.unwrap();
+            let mut replies = vec![];
+            loop {
+                let reply = harry_stream.next().await.unwrap().unwrap();
+                if reply.starts_with(":irc.irc 318 ") {
+                    break;
+                }
+                replies.push(reply);
+            }
+            let expected = ":irc.irc 330 harry roland roland :is logged in as".to_string();
+            assert!(replies.contains(&expected));
+
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            let mut replies = vec![];
+            loop {
+                let reply = line_stream.next().await.unwrap().unwrap();
+                if reply.starts_with(":irc.irc 318 ") {
+                    break;
+                }
+                replies.push(reply);
+            }
+            assert!(!replies.iter().any(|r| r.starts_with(":irc.irc 330 ")));
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_rate_limit() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        config.whois_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            for _ in 0..2 {
+                harry_stream.send("WHOIS nobody".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc 318 harry nobody :End of /WHOIS list",
+                    harry_stream.next().await.unwrap().unwrap()
+                );
+            }
+            harry_stream.send("WHOIS nobody".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc FAIL WHOIS RATE_LIMITED :Too many WHOIS requests, try again later",
+                harry_stream.next().await.unwrap().unwrap()
+            );
+
+            // operators are not limited
+            for _ in 0..3 {
+                line_stream.send("WHOIS nobody".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc 318 fanny nobody :End of /WHOIS list",
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_rate_limit_exempt() {
+        let mut config = MainConfig::default();
+        config.whois_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
+        config.rate_limit_exempt = Some(vec!["*!~robot@127.0.0.*".to_string()]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut robot_stream =
+                login_to_test_and_skip(port, "robby", "robot", "Robby Robot").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            for _ in 0..4 {
+                robot_stream.send("WHOIS nobody".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc 318 robby nobody :End of /WHOIS list",
+                    robot_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            for _ in 0..2 {
+                harry_stream.send("WHOIS nobody".to_string()).await.unwrap();
+                assert_eq!(
+                    ":irc.irc 318 harry nobody :End of /WHOIS list",
+                    harry_stream.next().await.unwrap().unwrap()
+                );
+            }
+            harry_stream.send("WHOIS nobody".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc FAIL WHOIS RATE_LIMITED :Too many WHOIS requests, try again later",
+                harry_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
+    #[tokio::test]
+    async fn test_command_whois_tls() {
+        let (main_state, handle, port) = run_test_tls_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_tls_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut harry_stream =
+                login_to_test_tls_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            let signon = {
+                main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("harry")
+                    .unwrap()
+                    .signon
+            };
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    now - signon,
+                    signon
+                ),
+                ":irc.irc 671 fanny harry :is using a secure connection",
+                ":irc.irc 318 fanny harry :End of /WHOIS list",
+            ] {
+                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
+            }
+            harry_stream.send("QUIT :Bye".to_string()).await.unwrap();
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_wilcards() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut stream1 = login_to_test_and_skip(port, "henry", "henry", "Henry Solo").await;
+            let mut stream2 = login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+            let mut stream3 = login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            let (signon, signon2) = {
+                let state = main_state.state.read().await;
+                (
+                    state.users.get("harry").unwrap().signon,
+                    state.users.get("henry").unwrap().signon,
+                )
+            };
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            line_stream.send("WHOIS *ry".to_string()).await.unwrap();
+            let mut expecteds = [
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    now - signon,
+                    signon
+                ),
+                ":irc.irc 311 fanny henry ~henry 127.0.0.1 * :Henry Solo",
+                ":irc.irc 312 fanny henry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny henry {} {} :seconds idle, signon time",
+                    now - signon2,
+                    signon2
+                ),
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+            expecteds.sort();
+            let mut results = vec![];
+            for _ in 0..6 {
+                results.push(line_stream.next().await.unwrap().unwrap());
+            }
+            results.sort();
+            assert_eq!(expecteds, results);
+            assert_eq!(
+                ":irc.irc 318 fanny *ry :End of /WHOIS list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for stream in [&mut stream1, &mut stream2, &mut stream3] {
+                stream.send("QUIT :Bye".to_string()).await.unwrap();
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_invisible_channel() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+
+            let mut jerry_stream =
+                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
+            let mut jarry_stream =
+                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            line_stream
+                .send("JOIN #mychannel".to_string())
+                .await
+                .unwrap();
+
+            time::sleep(Duration::from_millis(50)).await;
+            jarry_stream
+                .send("JOIN #superchannel".to_string())
+                .await
+                .unwrap();
+            jarry_stream
+                .send("MODE jarry +i".to_string())
+                .await
+                .unwrap();
+            harry_stream
+                .send("JOIN #mychannel".to_string())
+                .await
+                .unwrap();
+            harry_stream
+                .send("MODE harry +i".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+
+            for _ in 0..(3 + 1) {
+                line_stream.next().await.unwrap().unwrap();
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            let (signon, signon2) = {
+                let state = main_state.state.read().await;
+                (
+                    state.users.get("harry").unwrap().signon,
+                    state.users.get("jerry").unwrap().signon,
+                )
+            };
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            line_stream.send("WHOIS *rry".to_string()).await.unwrap();
+            let mut expecteds = [
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
+                ":irc.irc 319 fanny harry :#mychannel",
+                &format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    now - signon,
+                    signon
+                ),
+                ":irc.irc 311 fanny jerry ~jerry 127.0.0.1 * :Jerry Lazy",
+                ":irc.irc 312 fanny jerry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny jerry {} {} :seconds idle, signon time",
+                    now - signon2,
+                    signon2
+                ),
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+            expecteds.sort();
+            let mut results = vec![];
+            for _ in 0..7 {
+                results.push(line_stream.next().await.unwrap().unwrap());
+            }
+            results.sort();
+            assert_eq!(expecteds, results);
+            assert_eq!(
+                ":irc.irc 318 fanny *rry :End of /WHOIS list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            jerry_stream.send("QUIT :Bye".to_string()).await.unwrap();
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    fn equal_channel_names<'a>(
+        exp_msg: &'a str,
+        exp_names: &'a [&'a str],
+        names_replies: &'a [&'a str],
+    ) -> bool {
+        let mut exp_names_sorted = Vec::from(exp_names);
+        exp_names_sorted.sort();
+        let mut touched = vec![false; exp_names.len()];
+        names_replies.iter().all(|reply| {
+            if reply.starts_with(exp_msg) {
+                reply[exp_msg.len()..].split_terminator(" ").all(|c| {
+                    if let Ok(p) = exp_names_sorted.binary_search(&c) {
+                        touched[p] = true;
+                        true
+                    } else {
+                        false
+                    }
+                })
+            } else {
+                false
+            }
+        }) && touched.iter().all(|x| *x)
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_channels() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+            for i in 0..45 {
+                harry_stream
+                    .send(format!("JOIN #channel{}", i))
+                    .await
+                    .unwrap();
+                for _ in 0..3 {
+                    harry_stream.next().await.unwrap().unwrap();
+                }
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            let signon = {
+                main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("harry")
+                    .unwrap()
+                    .signon
+            };
+
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let channel_list = (0..45)
+                .map(|x| format!("~#channel{}", x))
+                .collect::<Vec<_>>();
+            let channel_str_list = channel_list.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+            assert!(equal_channel_names(
+                ":irc.irc 319 fanny harry :",
+                channel_str_list.as_slice(),
+                &[
+                    &line_stream.next().await.unwrap().unwrap(),
+                    &line_stream.next().await.unwrap().unwrap()
+                ]
+            ));
+            assert_eq!(
+                format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - signon,
+                    signon
+                ),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 318 fanny harry :End of /WHOIS list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_secret_channels() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+            for chname in ["#public", "#secret"] {
+                harry_stream.send(format!("JOIN {}", chname)).await.unwrap();
+                for _ in 0..3 {
+                    harry_stream.next().await.unwrap().unwrap();
+                }
+            }
+            harry_stream
+                .send("MODE #secret +s".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":harry!~harry@127.0.0.1 MODE #secret +s".to_string(),
+                harry_stream.next().await.unwrap().unwrap()
+            );
+
+            // user sees own secret channels
+            harry_stream.send("WHOIS harry".to_string()).await.unwrap();
+            for _ in 0..2 {
+                harry_stream.next().await.unwrap().unwrap();
+            }
+            assert!(equal_channel_names(
+                ":irc.irc 319 harry harry :",
+                &["~#public", "~#secret"],
+                &[&harry_stream.next().await.unwrap().unwrap()]
+            ));
+            for _ in 0..2 {
+                harry_stream.next().await.unwrap().unwrap();
+            }
+
+            // secret channels are hidden from non-members
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            for _ in 0..2 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(
+                ":irc.irc 319 fanny harry :~#public".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_channel_multi_prefix() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream = connect_to_test(port).await;
+            line_stream.send("CAP LS 302".to_string()).await.unwrap();
+            line_stream.send("NICK fanny".to_string()).await.unwrap();
+            line_stream
+                .send("USER fanny 8 * :Fanny BumBumBum".to_string())
+                .await
+                .unwrap();
+            line_stream
+                .send("CAP REQ :multi-prefix".to_string())
+                .await
+                .unwrap();
+            line_stream.send("CAP END".to_string()).await.unwrap();
+            for _ in 0..21 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+
+            harry_stream
+                .send("JOIN #channel2".to_string())
+                .await
+                .unwrap();
+            for _ in 0..3 {
+                harry_stream.next().await.unwrap().unwrap();
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            let signon = {
+                main_state
+                    .state
+                    .read()
+                    .await
+                    .users
+                    .get("harry")
+                    .unwrap()
+                    .signon
+            };
+
+            line_stream.send("WHOIS harry".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 319 fanny harry :~@#channel2".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - signon,
+                    signon
+                ),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 318 fanny harry :End of /WHOIS list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whois_multiple() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut henry_stream =
+                login_to_test_and_skip(port, "henry", "henry", "Henry Solo").await;
+            let mut harry_stream =
+                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+
+            time::sleep(Duration::from_millis(50)).await;
+            let (signon, signon2, signon3) = {
+                let state = main_state.state.read().await;
+                (
+                    state.users.get("harry").unwrap().signon,
+                    state.users.get("henry").unwrap().signon,
+                    state.users.get("dizzy").unwrap().signon,
+                )
+            };
+
+            line_stream
+                .send("WHOIS *ry,dizzy".to_string())
+                .await
+                .unwrap();
+            let mut expecteds = [
+                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
+                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - signon,
+                    signon
+                ),
+                ":irc.irc 311 fanny henry ~henry 127.0.0.1 * :Henry Solo",
+                ":irc.irc 312 fanny henry irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny henry {} {} :seconds idle, signon time",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - signon2,
+                    signon2
+                ),
+                ":irc.irc 311 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
+                ":irc.irc 312 fanny dizzy irc.irc :This is IRC server",
+                &format!(
+                    ":irc.irc 317 fanny dizzy {} {} :seconds idle, signon time",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                        - signon3,
+                    signon3
+                ),
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+            expecteds.sort();
+            let mut results = vec![];
+            for _ in 0..9 {
+                results.push(line_stream.next().await.unwrap().unwrap());
+            }
+            results.sort();
+            assert_eq!(expecteds, results);
+            assert_eq!(
+                ":irc.irc 318 fanny *ry,dizzy :End of /WHOIS list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            for stream in [&mut henry_stream, &mut harry_stream, &mut dizzy_stream] {
+                stream.send("QUIT :Bye".to_string()).await.unwrap();
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_whowas() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+            dizzy_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy MultiX").await;
+            dizzy_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+
+            let (signon1, signon2) = {
+                let state = main_state.state.read().await;
+                let history = state.nick_histories.get("dizzy").unwrap();
+                (history[1].signon, history[0].signon)
+            };
+
+            line_stream.send("WHOWAS dizzy".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
+                        Utc
+                    )
+                ),
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon2 as i64, 0),
+                        Utc
+                    )
+                ),
+            ] {
+                assert_eq!(
+                    expected.to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            assert_eq!(
+                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("WHOWAS dizzy 1".to_string())
+                .await
+                .unwrap();
+            for expected in [
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
+                        Utc
+                    )
+                ),
+            ] {
+                assert_eq!(
+                    expected.to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            assert_eq!(
+                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
 
-            line_stream.send("WHO *rry".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny * ",
-                &[
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
-                    "~jarry 127.0.0.1 irc.irc jarry H :0 Jarry Lazy",
-                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
-                ],
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy MultiZ").await;
+            dizzy_stream.send("NICK gizzy".to_string()).await.unwrap();
+            dizzy_stream.next().await.unwrap().unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            let signon0 = {
+                let state = main_state.state.read().await;
+                let history = state.nick_histories.get("dizzy").unwrap();
+                history[2].signon
+            };
+
+            line_stream.send("WHOWAS dizzy".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiZ",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon0 as i64, 0),
+                        Utc
+                    )
+                ),
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
+                        Utc
+                    )
+                ),
+                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
+                &format!(
+                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
+                    DateTime::<Utc>::from_utc(
+                        NaiveDateTime::from_timestamp(signon2 as i64, 0),
+                        Utc
+                    )
+                ),
+            ] {
+                assert_eq!(
+                    expected.to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
             assert_eq!(
-                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
 
-            for stream in [&mut stream1, &mut stream2, &mut stream3] {
-                stream.send("QUIT :Bye".to_string()).await.unwrap();
-            }
+            line_stream.send("WHOWAS zizzy".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 406 fanny zizzy :There was no such nickname".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 369 fanny zizzy :End of WHOWAS".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_who_invisible() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_kill() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
 
-            let mut jerry_stream =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-            let mut jarry_stream =
-                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-
-            time::sleep(Duration::from_millis(50)).await;
-            jarry_stream
-                .send("MODE jarry +i".to_string())
+            line_stream
+                .send("KILL dizzy :Not polite".to_string())
                 .await
                 .unwrap();
+            assert_eq!(
+                ":irc.irc ERROR :User killed by fanny: Not polite".to_string(),
+                dizzy_stream.next().await.unwrap().unwrap()
+            );
             time::sleep(Duration::from_millis(50)).await;
-
-            line_stream.send("WHO *rry".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny * ",
-                &[
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
-                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
-                ],
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
+            assert!(!main_state.state.read().await.users.contains_key("dizzy"));
+            line_stream
+                .send("KILL dizzy :Not polite".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                ":irc.irc 401 fanny dizzy :No such nick/channel".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-
-            for stream in [&mut jerry_stream, &mut jarry_stream, &mut harry_stream] {
-                stream.send("QUIT :Bye".to_string()).await.unwrap();
-            }
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_who_invisible_channel() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_kill_hide_oper() {
+        let mut config = MainConfig::default();
+        config.kill_hide_oper = true;
+        config.operators = Some(vec![
+            OperatorConfig {
+                name: "fanny".to_string(),
+                password: argon2_hash_password("Funny"),
+                mask: None,
+                require_tls: false,
+            },
+            OperatorConfig {
+                name: "gandalf".to_string(),
+                password: argon2_hash_password("Wizard"),
+                mask: None,
+                require_tls: false,
+            },
+        ]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-
-            let mut jerry_stream =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-            let mut jarry_stream =
-                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            let mut gandalf_stream =
+                login_to_test_and_skip(port, "gandalf", "gandalf", "Gandalf Grey").await;
+            gandalf_stream
+                .send("OPER gandalf Wizard".to_string())
+                .await
+                .unwrap();
+            gandalf_stream.next().await.unwrap().unwrap();
             let mut harry_stream =
                 login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+
+            // every user gets own JOIN and JOINs of users that joined later.
+            let mut streams = [
+                &mut line_stream,
+                &mut gandalf_stream,
+                &mut harry_stream,
+                &mut dizzy_stream,
+            ];
+            for i in 0..streams.len() {
+                let (joined, rest) = streams.split_at_mut(i);
+                rest[0].send("JOIN #room".to_string()).await.unwrap();
+                for _ in 0..3 {
+                    rest[0].next().await.unwrap().unwrap();
+                }
+                for stream in joined {
+                    stream.next().await.unwrap().unwrap();
+                }
+            }
 
             line_stream
-                .send("JOIN #mychannel".to_string())
+                .send("KILL dizzy :Not polite".to_string())
                 .await
                 .unwrap();
+            assert_eq!(
+                ":irc.irc ERROR :User killed by fanny: Not polite".to_string(),
+                dizzy_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":dizzy!~dizzy@127.0.0.1 QUIT :Killed (fanny (Not polite))".to_string(),
+                gandalf_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":dizzy!~dizzy@127.0.0.1 QUIT :Killed (Not polite)".to_string(),
+                harry_stream.next().await.unwrap().unwrap()
+            );
+        }
 
-            time::sleep(Duration::from_millis(50)).await;
-            jarry_stream
-                .send("JOIN #superchannel".to_string())
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_kill_no_privileges() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
                 .await
                 .unwrap();
-            jarry_stream
-                .send("MODE jarry +i".to_string())
+            line_stream.next().await.unwrap().unwrap();
+            login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+
+            line_stream
+                .send("KILL dizzy :Not polite".to_string())
                 .await
                 .unwrap();
-            harry_stream
-                .send("JOIN #mychannel".to_string())
+            assert_eq!(
+                ":irc.irc 481 fanny :Permission Denied- You're not an IRC \
+                        operator"
+                    .to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(!main_state.state.read().await.users.contains_key("dizzy"));
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_rehash() {
+        let opers_path = std::env::temp_dir()
+            .join("simple_irc_rehash_opers.toml")
+            .to_string_lossy()
+            .to_string();
+        let bans_path = std::env::temp_dir()
+            .join("simple_irc_rehash_bans.toml")
+            .to_string_lossy()
+            .to_string();
+        let channels_path = std::env::temp_dir()
+            .join("simple_irc_rehash_channels.toml")
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(
+            &opers_path,
+            format!(
+                "[[operators]]\nname = \"bobby\"\npassword = \"{}\"\n",
+                argon2_hash_password("Bobby")
+            ),
+        )
+        .unwrap();
+        std::fs::write(&bans_path, "bans = [ \"guru!*@*\" ]\n").unwrap();
+        std::fs::write(&channels_path, "reserved_channels = [ \"#staff*\" ]\n").unwrap();
+
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        config.bans = Some(vec!["*!*@10.0.0.1".to_string()]);
+        config.include = Some(IncludeConfig {
+            motd: None,
+            operators: Some(opers_path.clone()),
+            bans: Some(bans_path.clone()),
+            reserved_nicks: None,
+            reserved_channels: Some(channels_path.clone()),
+        });
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            let mut dizzy_stream =
+                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+            dizzy_stream.send("REHASH".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 dizzy :Permission Denied- You're not an IRC operator".to_string(),
+                dizzy_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("REHASH BANS".to_string()).await.unwrap();
+            assert_eq!(
+                format!(":irc.irc 382 fanny {} :Rehashing", bans_path),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            {
+                // only bans are reloaded
+                let reloadable = main_state.reloadable.read().await;
+                assert_eq!(vec!["guru!*@*".to_string()], reloadable.bans);
+                assert!(reloadable.operators.contains_key("fanny"));
+                assert!(!reloadable.operators.contains_key("bobby"));
+            }
+
+            // new ban applies to new connections
+            let mut guru_stream = login_to_test(port, "guru", "guru", "Guru").await;
+            assert_eq!(
+                ":irc.irc 465 guru :You are banned from this server.".to_string(),
+                guru_stream.next().await.unwrap().unwrap()
+            );
+
+            // bad file does not change current bans
+            std::fs::write(&bans_path, "bans = [ \"xxx!*@*\" \n").unwrap();
+            line_stream.send("REHASH BANS".to_string()).await.unwrap();
+            assert!(line_stream
+                .next()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with(&format!(":irc.irc FAIL REHASH INVALID_FILE :{}: ", bans_path)));
+            assert_eq!(
+                vec!["guru!*@*".to_string()],
+                main_state.reloadable.read().await.bans
+            );
+
+            line_stream.send("REHASH OPERS".to_string()).await.unwrap();
+            assert_eq!(
+                format!(":irc.irc 382 fanny {} :Rehashing", opers_path),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            {
+                let reloadable = main_state.reloadable.read().await;
+                assert!(!reloadable.operators.contains_key("fanny"));
+                assert!(reloadable.operators.contains_key("bobby"));
+                assert_eq!(vec!["guru!*@*".to_string()], reloadable.bans);
+            }
+
+            line_stream
+                .send("REHASH CHANNELS".to_string())
                 .await
                 .unwrap();
-            harry_stream
-                .send("MODE harry +i".to_string())
+            assert_eq!(
+                format!(":irc.irc 382 fanny {} :Rehashing", channels_path),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            dizzy_stream
+                .send("JOIN #staffroom".to_string())
                 .await
                 .unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-
-            for _ in 0..(3 + 1) {
-                line_stream.next().await.unwrap().unwrap();
-            }
+            assert_eq!(
+                ":irc.irc 403 dizzy #staffroom :No such channel".to_string(),
+                dizzy_stream.next().await.unwrap().unwrap()
+            );
 
-            line_stream.send("WHO *rry".to_string()).await.unwrap();
-            assert!(equal_list(
-                ":irc.irc 352 fanny * ",
-                &[
-                    "~jerry 127.0.0.1 irc.irc jerry H :0 Jerry Lazy",
-                    "~harry 127.0.0.1 irc.irc harry H :0 Harry Lazy"
-                ],
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
+            line_stream.send("REHASH MOTD".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 315 fanny *rry :End of WHO list".to_string(),
+                ":irc.irc FAIL REHASH NO_FILE :No included file to reload".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("REHASH XXX".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 400 fanny REHASH XXX :Unknown subcommand".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-            jerry_stream.send("QUIT :Bye".to_string()).await.unwrap();
         }
 
         quit_test_server(main_state, handle).await;
+        std::fs::remove_file(&opers_path).unwrap();
+        std::fs::remove_file(&bans_path).unwrap();
+        std::fs::remove_file(&channels_path).unwrap();
     }
 
     #[tokio::test]
-    async fn test_command_whois() {
+    async fn test_command_squit() {
         let mut config = MainConfig::default();
         config.operators = Some(vec![OperatorConfig {
             name: "fanny".to_string(),
             password: argon2_hash_password("Funny"),
             mask: None,
+            require_tls: false,
         }]);
-        let (main_state, handle, port) = run_test_server(config).await;
+        let (_, _, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
@@ -1870,445 +5160,223 @@ mod test {
                 .await
                 .unwrap();
             line_stream.next().await.unwrap().unwrap();
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-
-            time::sleep(Duration::from_millis(50)).await;
-            let signon = {
-                main_state
-                    .state
-                    .read()
-                    .await
-                    .users
-                    .get("harry")
-                    .unwrap()
-                    .signon
-            };
-
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            line_stream.send("WHOIS harry".to_string()).await.unwrap();
-            for expected in [
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    now - signon,
-                    signon
-                ),
-                ":irc.irc 318 fanny harry :End of /WHOIS list",
-            ] {
-                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
-            }
-
-            let signon = {
-                main_state
-                    .state
-                    .read()
-                    .await
-                    .users
-                    .get("fanny")
-                    .unwrap()
-                    .signon
-            };
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            harry_stream.send("WHOIS fanny".to_string()).await.unwrap();
-            for expected in [
-                ":irc.irc 311 harry fanny ~fanny 127.0.0.1 * :Fanny BumBumBum",
-                ":irc.irc 312 harry fanny irc.irc :This is IRC server",
-                ":irc.irc 313 harry fanny :is an IRC operator",
-                &format!(
-                    ":irc.irc 317 harry fanny {} {} :seconds idle, signon time",
-                    now - signon,
-                    signon
-                ),
-                ":irc.irc 378 harry fanny :is connecting from 127.0.0.1",
-                ":irc.irc 379 harry fanny :is using modes +o",
-                ":irc.irc 318 harry fanny :End of /WHOIS list",
-            ] {
-                assert_eq!(expected, harry_stream.next().await.unwrap().unwrap());
-            }
+            line_stream
+                .send("SQUIT irc.irc :Blabla".to_string())
+                .await
+                .unwrap();
         }
-
-        quit_test_server(main_state, handle).await;
     }
 
-    #[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
     #[tokio::test]
-    async fn test_command_whois_tls() {
-        let (main_state, handle, port) = run_test_tls_server(MainConfig::default()).await;
+    async fn test_command_die() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (_, _, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_tls_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut harry_stream =
-                login_to_test_tls_and_skip(port, "harry", "harry", "Harry Lazy").await;
-
-            time::sleep(Duration::from_millis(50)).await;
-            let signon = {
-                main_state
-                    .state
-                    .read()
-                    .await
-                    .users
-                    .get("harry")
-                    .unwrap()
-                    .signon
-            };
-
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            line_stream.send("WHOIS harry".to_string()).await.unwrap();
-            for expected in [
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    now - signon,
-                    signon
-                ),
-                ":irc.irc 671 fanny harry :is using a secure connection",
-                ":irc.irc 318 fanny harry :End of /WHOIS list",
-            ] {
-                assert_eq!(expected, line_stream.next().await.unwrap().unwrap());
-            }
-            harry_stream.send("QUIT :Bye".to_string()).await.unwrap();
+                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("DIE :Blabla".to_string()).await.unwrap();
         }
-
-        quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_whois_wilcards() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_die_no_message() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (_, _, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut stream1 = login_to_test_and_skip(port, "henry", "henry", "Henry Solo").await;
-            let mut stream2 = login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-            let mut stream3 = login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
-
-            time::sleep(Duration::from_millis(50)).await;
-            let (signon, signon2) = {
-                let state = main_state.state.read().await;
-                (
-                    state.users.get("harry").unwrap().signon,
-                    state.users.get("henry").unwrap().signon,
-                )
-            };
-
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            line_stream.send("WHOIS *ry".to_string()).await.unwrap();
-            let mut expecteds = [
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    now - signon,
-                    signon
-                ),
-                ":irc.irc 311 fanny henry ~henry 127.0.0.1 * :Henry Solo",
-                ":irc.irc 312 fanny henry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny henry {} {} :seconds idle, signon time",
-                    now - signon2,
-                    signon2
-                ),
-            ]
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<_>>();
-            expecteds.sort();
-            let mut results = vec![];
-            for _ in 0..6 {
-                results.push(line_stream.next().await.unwrap().unwrap());
-            }
-            results.sort();
-            assert_eq!(expecteds, results);
-            assert_eq!(
-                ":irc.irc 318 fanny *ry :End of /WHOIS list".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            for stream in [&mut stream1, &mut stream2, &mut stream3] {
-                stream.send("QUIT :Bye".to_string()).await.unwrap();
-            }
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            line_stream.send("DIE".to_string()).await.unwrap();
         }
-
-        quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_whois_invisible_channel() {
+    async fn test_command_squit_no_privileges() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-
-            let mut jerry_stream =
-                login_to_test_and_skip(port, "jerry", "jerry", "Jerry Lazy").await;
-            let mut jarry_stream =
-                login_to_test_and_skip(port, "jarry", "jarry", "Jarry Lazy").await;
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-
             line_stream
-                .send("JOIN #mychannel".to_string())
-                .await
-                .unwrap();
-
-            time::sleep(Duration::from_millis(50)).await;
-            jarry_stream
-                .send("JOIN #superchannel".to_string())
-                .await
-                .unwrap();
-            jarry_stream
-                .send("MODE jarry +i".to_string())
-                .await
-                .unwrap();
-            harry_stream
-                .send("JOIN #mychannel".to_string())
-                .await
-                .unwrap();
-            harry_stream
-                .send("MODE harry +i".to_string())
+                .send("SQUIT irc.irc :Blabla".to_string())
                 .await
                 .unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-
-            for _ in 0..(3 + 1) {
-                line_stream.next().await.unwrap().unwrap();
-            }
-
-            time::sleep(Duration::from_millis(50)).await;
-            let (signon, signon2) = {
-                let state = main_state.state.read().await;
-                (
-                    state.users.get("harry").unwrap().signon,
-                    state.users.get("jerry").unwrap().signon,
-                )
-            };
-
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            line_stream.send("WHOIS *rry".to_string()).await.unwrap();
-            let mut expecteds = [
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
-                ":irc.irc 319 fanny harry :#mychannel",
-                &format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    now - signon,
-                    signon
-                ),
-                ":irc.irc 311 fanny jerry ~jerry 127.0.0.1 * :Jerry Lazy",
-                ":irc.irc 312 fanny jerry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny jerry {} {} :seconds idle, signon time",
-                    now - signon2,
-                    signon2
-                ),
-            ]
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<_>>();
-            expecteds.sort();
-            let mut results = vec![];
-            for _ in 0..7 {
-                results.push(line_stream.next().await.unwrap().unwrap());
-            }
-            results.sort();
-            assert_eq!(expecteds, results);
             assert_eq!(
-                ":irc.irc 318 fanny *rry :End of /WHOIS list".to_string(),
+                ":irc.irc 483 fanny :You cant kill a server!".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-            jerry_stream.send("QUIT :Bye".to_string()).await.unwrap();
         }
 
         quit_test_server(main_state, handle).await;
     }
 
-    fn equal_channel_names<'a>(
-        exp_msg: &'a str,
-        exp_names: &'a [&'a str],
-        names_replies: &'a [&'a str],
-    ) -> bool {
-        let mut exp_names_sorted = Vec::from(exp_names);
-        exp_names_sorted.sort();
-        let mut touched = vec![false; exp_names.len()];
-        names_replies.iter().all(|reply| {
-            if reply.starts_with(exp_msg) {
-                reply[exp_msg.len()..].split_terminator(" ").all(|c| {
-                    if let Ok(p) = exp_names_sorted.binary_search(&c) {
-                        touched[p] = true;
-                        true
-                    } else {
-                        false
-                    }
-                })
-            } else {
-                false
-            }
-        }) && touched.iter().all(|x| *x)
-    }
-
     #[tokio::test]
-    async fn test_command_whois_channels() {
+    async fn test_command_away() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
                 login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-            for i in 0..45 {
-                harry_stream
-                    .send(format!("JOIN #channel{}", i))
-                    .await
-                    .unwrap();
-                for _ in 0..3 {
-                    harry_stream.next().await.unwrap().unwrap();
-                }
-            }
-
+            line_stream
+                .send("AWAY :See later".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 306 fanny :You have been marked as being away".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
             time::sleep(Duration::from_millis(50)).await;
-            let signon = {
+            assert_eq!(
+                Some("See later".to_string()),
                 main_state
                     .state
                     .read()
                     .await
                     .users
-                    .get("harry")
+                    .get("fanny")
                     .unwrap()
-                    .signon
-            };
-
-            line_stream.send("WHOIS harry".to_string()).await.unwrap();
-            assert_eq!(
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            let channel_list = (0..45)
-                .map(|x| format!("~#channel{}", x))
-                .collect::<Vec<_>>();
-            let channel_str_list = channel_list.iter().map(|x| x.as_str()).collect::<Vec<_>>();
-            assert!(equal_channel_names(
-                ":irc.irc 319 fanny harry :",
-                channel_str_list.as_slice(),
-                &[
-                    &line_stream.next().await.unwrap().unwrap(),
-                    &line_stream.next().await.unwrap().unwrap()
-                ]
-            ));
-            assert_eq!(
-                format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - signon,
-                    signon
-                ),
-                line_stream.next().await.unwrap().unwrap()
+                    .away
             );
+
+            line_stream.send("AWAY".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 318 fanny harry :End of /WHOIS list".to_string(),
+                ":irc.irc 305 fanny :You are no longer marked as being away".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            time::sleep(Duration::from_millis(50)).await;
+            assert!(main_state
+                .state
+                .read()
+                .await
+                .users
+                .get("fanny")
+                .unwrap()
+                .away
+                .is_none());
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_whois_channel_multi_prefix() {
+    async fn test_command_userhost() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
-            let mut line_stream = connect_to_test(port).await;
-            line_stream.send("CAP LS 302".to_string()).await.unwrap();
-            line_stream.send("NICK fanny".to_string()).await.unwrap();
-            line_stream
-                .send("USER fanny 8 * :Fanny BumBumBum".to_string())
-                .await
-                .unwrap();
-            line_stream
-                .send("CAP REQ :multi-prefix".to_string())
-                .await
-                .unwrap();
-            line_stream.send("CAP END".to_string()).await.unwrap();
-            for _ in 0..20 {
-                line_stream.next().await.unwrap().unwrap();
+            let mut line_stream =
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
+            let mut xstreams = vec![];
+            for i in 0..50 {
+                xstreams.push(
+                    login_to_test_and_skip(
+                        port,
+                        &format!("binny{}", i),
+                        &format!("binny{}", i),
+                        &format!("Binny{} BigBang", i),
+                    )
+                    .await,
+                );
             }
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
 
-            harry_stream
-                .send("JOIN #channel2".to_string())
+            line_stream
+                .send(format!(
+                    "USERHOST {}",
+                    (0..50)
+                        .map(|x| format!("binny{}", x))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ))
                 .await
                 .unwrap();
-            for _ in 0..3 {
-                harry_stream.next().await.unwrap().unwrap();
+            for range in [(0..20), (20..40), (40..50)] {
+                assert_eq!(
+                    ":irc.irc 302 funny :".to_string()
+                        + &(range
+                            .map(|x| format!("binny{0}=+~binny{0}@127.0.0.1", x))
+                            .collect::<Vec<_>>()
+                            .join(" ")),
+                    line_stream.next().await.unwrap().unwrap()
+                );
             }
+        }
 
-            time::sleep(Duration::from_millis(50)).await;
-            let signon = {
-                main_state
-                    .state
-                    .read()
-                    .await
-                    .users
-                    .get("harry")
-                    .unwrap()
-                    .signon
-            };
+        quit_test_server(main_state, handle).await;
+    }
 
-            line_stream.send("WHOIS harry".to_string()).await.unwrap();
-            assert_eq!(
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
-            assert_eq!(
-                ":irc.irc 319 fanny harry :~@#channel2".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
+    #[tokio::test]
+    async fn test_command_userhost_away() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
+
+            line_stream.send("AWAY :blablam".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream
+                .send("USERHOST funny".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - signon,
-                    signon
-                ),
+                ":irc.irc 302 funny :funny=-~funny@127.0.0.1".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_command_userhost_oper() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "fanny".to_string(),
+            password: argon2_hash_password("Funny"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "funny", "funny", "Funny BumBumBum").await;
+            line_stream
+                .send("OPER fanny Funny".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            line_stream
+                .send("USERHOST funny".to_string())
+                .await
+                .unwrap();
             assert_eq!(
-                ":irc.irc 318 fanny harry :End of /WHOIS list".to_string(),
+                ":irc.irc 302 funny :funny*=+~funny@127.0.0.1".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -2317,222 +5385,82 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_whois_multiple() {
+    async fn test_command_userhost_notfound() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut henry_stream =
-                login_to_test_and_skip(port, "henry", "henry", "Henry Solo").await;
-            let mut harry_stream =
-                login_to_test_and_skip(port, "harry", "harry", "Harry Lazy").await;
-            let mut dizzy_stream =
-                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
-
-            time::sleep(Duration::from_millis(50)).await;
-            let (signon, signon2, signon3) = {
-                let state = main_state.state.read().await;
-                (
-                    state.users.get("harry").unwrap().signon,
-                    state.users.get("henry").unwrap().signon,
-                    state.users.get("dizzy").unwrap().signon,
-                )
-            };
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
 
             line_stream
-                .send("WHOIS *ry,dizzy".to_string())
+                .send("USERHOST ziggy".to_string())
                 .await
                 .unwrap();
-            let mut expecteds = [
-                ":irc.irc 311 fanny harry ~harry 127.0.0.1 * :Harry Lazy",
-                ":irc.irc 312 fanny harry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny harry {} {} :seconds idle, signon time",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - signon,
-                    signon
-                ),
-                ":irc.irc 311 fanny henry ~henry 127.0.0.1 * :Henry Solo",
-                ":irc.irc 312 fanny henry irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny henry {} {} :seconds idle, signon time",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - signon2,
-                    signon2
-                ),
-                ":irc.irc 311 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
-                ":irc.irc 312 fanny dizzy irc.irc :This is IRC server",
-                &format!(
-                    ":irc.irc 317 fanny dizzy {} {} :seconds idle, signon time",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - signon3,
-                    signon3
-                ),
-            ]
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<_>>();
-            expecteds.sort();
-            let mut results = vec![];
-            for _ in 0..9 {
-                results.push(line_stream.next().await.unwrap().unwrap());
-            }
-            results.sort();
-            assert_eq!(expecteds, results);
             assert_eq!(
-                ":irc.irc 318 fanny *ry,dizzy :End of /WHOIS list".to_string(),
+                ":irc.irc 302 funny :".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-
-            for stream in [&mut henry_stream, &mut harry_stream, &mut dizzy_stream] {
-                stream.send("QUIT :Bye".to_string()).await.unwrap();
-            }
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_whowas() {
+    async fn test_command_ison() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
-            let mut dizzy_stream =
-                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
-            dizzy_stream.send("QUIT :Bye".to_string()).await.unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-            let mut dizzy_stream =
-                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy MultiX").await;
-            dizzy_stream.send("QUIT :Bye".to_string()).await.unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-
-            let (signon1, signon2) = {
-                let state = main_state.state.read().await;
-                let history = state.nick_histories.get("dizzy").unwrap();
-                (history[1].signon, history[0].signon)
-            };
-
-            line_stream.send("WHOWAS dizzy".to_string()).await.unwrap();
-            for expected in [
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
-                        Utc
-                    )
-                ),
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon2 as i64, 0),
-                        Utc
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
+            let mut xstreams = vec![];
+            for i in 0..50 {
+                xstreams.push(
+                    login_to_test_and_skip(
+                        port,
+                        &format!("binny{}", i),
+                        &format!("binny{}", i),
+                        &format!("Binny{} BigBang", i),
                     )
-                ),
-            ] {
-                assert_eq!(
-                    expected.to_string(),
-                    line_stream.next().await.unwrap().unwrap()
+                    .await,
                 );
             }
-            assert_eq!(
-                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
 
             line_stream
-                .send("WHOWAS dizzy 1".to_string())
+                .send(format!(
+                    "ISON {}",
+                    (0..50)
+                        .map(|x| format!("binny{}", x))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ))
                 .await
                 .unwrap();
-            for expected in [
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
-                        Utc
-                    )
-                ),
-            ] {
+            for range in [(0..20), (20..40), (40..50)] {
                 assert_eq!(
-                    expected.to_string(),
+                    ":irc.irc 303 funny :".to_string()
+                        + &(range
+                            .map(|x| format!("binny{0}", x))
+                            .collect::<Vec<_>>()
+                            .join(" ")),
                     line_stream.next().await.unwrap().unwrap()
                 );
             }
-            assert_eq!(
-                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
+        }
 
-            let mut dizzy_stream =
-                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy MultiZ").await;
-            dizzy_stream.send("NICK gizzy".to_string()).await.unwrap();
-            dizzy_stream.next().await.unwrap().unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            time::sleep(Duration::from_millis(50)).await;
-            let signon0 = {
-                let state = main_state.state.read().await;
-                let history = state.nick_histories.get("dizzy").unwrap();
-                history[2].signon
-            };
+        quit_test_server(main_state, handle).await;
+    }
 
-            line_stream.send("WHOWAS dizzy".to_string()).await.unwrap();
-            for expected in [
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiZ",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon0 as i64, 0),
-                        Utc
-                    )
-                ),
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy MultiX",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon1 as i64, 0),
-                        Utc
-                    )
-                ),
-                ":irc.irc 314 fanny dizzy ~dizzy 127.0.0.1 * :Dizzy Multi",
-                &format!(
-                    ":irc.irc 312 fanny dizzy irc.irc :Logged in at {}",
-                    DateTime::<Utc>::from_utc(
-                        NaiveDateTime::from_timestamp(signon2 as i64, 0),
-                        Utc
-                    )
-                ),
-            ] {
-                assert_eq!(
-                    expected.to_string(),
-                    line_stream.next().await.unwrap().unwrap()
-                );
-            }
-            assert_eq!(
-                ":irc.irc 369 fanny dizzy :End of WHOWAS".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
+    #[tokio::test]
+    async fn test_command_ison_notfound() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
-            line_stream.send("WHOWAS zizzy".to_string()).await.unwrap();
-            assert_eq!(
-                ":irc.irc 406 fanny zizzy :There was no such nickname".to_string(),
-                line_stream.next().await.unwrap().unwrap()
-            );
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
+
+            line_stream.send("ISON ziggy".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 369 fanny zizzy :End of WHOWAS".to_string(),
+                ":irc.irc 303 funny :".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -2541,163 +5469,219 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_kill() {
+    async fn test_command_targmax() {
         let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
+        config.targmax = Some(TargMaxConfig {
+            ison: Some(2),
+            notice: Some(2),
+            privmsg: Some(2),
+            userhost: Some(2),
+            whois: Some(1),
+            ..TargMaxConfig::default()
+        });
         let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let _cedric_stream =
+                login_to_test_and_skip(port, "cedric", "cedric", "Cedric Maximus").await;
+
             line_stream
-                .send("OPER fanny Funny".to_string())
+                .send("PRIVMSG bowie,cedric,alan :Hello boys".to_string())
                 .await
                 .unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            let mut dizzy_stream =
-                login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
-
+            assert_eq!(
+                ":irc.irc 407 alan alan :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
             line_stream
-                .send("KILL dizzy :Not polite".to_string())
+                .send("PRIVMSG bowie,cedric :Hello boys".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc ERROR :User killed by fanny: Not polite".to_string(),
-                dizzy_stream.next().await.unwrap().unwrap()
+                ":alan!~alan@127.0.0.1 PRIVMSG bowie :Hello boys".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
             );
-            time::sleep(Duration::from_millis(50)).await;
-            assert!(!main_state.state.read().await.users.contains_key("dizzy"));
+
+            // NOTICE is rejected silently
             line_stream
-                .send("KILL dizzy :Not polite".to_string())
+                .send("NOTICE bowie,cedric,alan :Hello boys".to_string())
                 .await
                 .unwrap();
+            line_stream.send("PING xxx".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 401 fanny dizzy :No such nick/channel".to_string(),
+                ":irc.irc PONG irc.irc :xxx".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-        }
-
-        quit_test_server(main_state, handle).await;
-    }
-
-    #[tokio::test]
-    async fn test_command_kill_no_privileges() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
             line_stream
-                .send("OPER fanny Funny".to_string())
+                .send("WHOIS bowie,cedric".to_string())
                 .await
                 .unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            login_to_test_and_skip(port, "dizzy", "dizzy", "Dizzy Multi").await;
+            assert_eq!(
+                ":irc.irc 407 alan cedric :Too many targets".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
 
+            // USERHOST and ISON ignore excess nicknames
             line_stream
-                .send("KILL dizzy :Not polite".to_string())
+                .send("USERHOST bowie cedric alan".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 481 fanny :Permission Denied- You're not an IRC \
-                        operator"
+                ":irc.irc 302 alan :bowie=+~bowie@127.0.0.1 cedric=+~cedric@127.0.0.1"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-            time::sleep(Duration::from_millis(50)).await;
-            assert!(!main_state.state.read().await.users.contains_key("dizzy"));
+            line_stream
+                .send("ISON bowie cedric alan".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 303 alan :bowie cedric".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_squit() {
+    async fn test_command_wallops() {
         let mut config = MainConfig::default();
         config.operators = Some(vec![OperatorConfig {
             name: "fanny".to_string(),
             password: argon2_hash_password("Funny"),
             mask: None,
+            require_tls: false,
         }]);
-        let (_, _, port) = run_test_server(config).await;
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
             line_stream
                 .send("OPER fanny Funny".to_string())
                 .await
                 .unwrap();
             line_stream.next().await.unwrap().unwrap();
-            line_stream
-                .send("SQUIT irc.irc :Blabla".to_string())
+            let mut hanna_stream =
+                login_to_test_and_skip(port, "hanna", "hanna", "Hanna-Barbera").await;
+            let mut gena_stream =
+                login_to_test_and_skip(port, "gena", "gena", "Gena Sphinxs").await;
+            let mut keith_stream =
+                login_to_test_and_skip(port, "keith", "keith", "Keith North").await;
+
+            line_stream.send("MODE funny +w".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            hanna_stream
+                .send("MODE hanna +w".to_string())
                 .await
                 .unwrap();
-        }
-    }
-
-    #[tokio::test]
-    async fn test_command_die() {
-        let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
-        let (_, _, port) = run_test_server(config).await;
+            hanna_stream.next().await.unwrap().unwrap();
+            gena_stream.send("MODE gena +w".to_string()).await.unwrap();
+            gena_stream.next().await.unwrap().unwrap();
 
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
             line_stream
-                .send("OPER fanny Funny".to_string())
+                .send("WALLOPS :Hello people".to_string())
                 .await
                 .unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            line_stream.send("DIE :Blabla".to_string()).await.unwrap();
+            for line_stream in [&mut line_stream, &mut hanna_stream, &mut gena_stream] {
+                assert_eq!(
+                    ":funny!~funny@127.0.0.1 WALLOPS :Hello people".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            hanna_stream
+                .send("WALLOPS :Hello people".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 481 hanna :Permission Denied- You're not an IRC \
+                    operator"
+                    .to_string(),
+                hanna_stream.next().await.unwrap().unwrap()
+            );
+            keith_stream
+                .send("WALLOPS :Hello people".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 481 keith :Permission Denied- You're not an IRC \
+                    operator"
+                    .to_string(),
+                keith_stream.next().await.unwrap().unwrap()
+            );
         }
+
+        quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_die_no_message() {
+    async fn test_command_operwall() {
         let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
-        let (_, _, port) = run_test_server(config).await;
+        config.operators = Some(vec![
+            OperatorConfig {
+                name: "fanny".to_string(),
+                password: argon2_hash_password("Funny"),
+                mask: None,
+                require_tls: false,
+            },
+            OperatorConfig {
+                name: "gina".to_string(),
+                password: argon2_hash_password("Ginny"),
+                mask: None,
+                require_tls: false,
+            },
+        ]);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
             line_stream
                 .send("OPER fanny Funny".to_string())
                 .await
                 .unwrap();
             line_stream.next().await.unwrap().unwrap();
-            line_stream.send("DIE".to_string()).await.unwrap();
-        }
-    }
-
-    #[tokio::test]
-    async fn test_command_squit_no_privileges() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+            let mut gena_stream =
+                login_to_test_and_skip(port, "gena", "gena", "Gena Sphinxs").await;
+            gena_stream
+                .send("OPER gina Ginny".to_string())
+                .await
+                .unwrap();
+            gena_stream.next().await.unwrap().unwrap();
+            let mut hanna_stream =
+                login_to_test_and_skip(port, "hanna", "hanna", "Hanna-Barbera").await;
+            // wallops user that is not operator
+            hanna_stream
+                .send("MODE hanna +w".to_string())
+                .await
+                .unwrap();
+            hanna_stream.next().await.unwrap().unwrap();
 
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
             line_stream
-                .send("SQUIT irc.irc :Blabla".to_string())
+                .send("OPERWALL :Hello operators".to_string())
+                .await
+                .unwrap();
+            for line_stream in [&mut line_stream, &mut gena_stream] {
+                assert_eq!(
+                    ":funny!~funny@127.0.0.1 OPERWALL :Hello operators".to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+            hanna_stream
+                .send("OPERWALL :Hello operators".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 483 fanny :You cant kill a server!".to_string(),
-                line_stream.next().await.unwrap().unwrap()
+                ":irc.irc 481 hanna :Permission Denied- You're not an IRC \
+                    operator"
+                    .to_string(),
+                hanna_stream.next().await.unwrap().unwrap()
             );
         }
 
@@ -2705,304 +5689,446 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_command_away() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_monitor() {
+        let mut config = MainConfig::default();
+        config.max_monitor = Some(3);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "fanny", "fanny", "Fanny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let _bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
             line_stream
-                .send("AWAY :See later".to_string())
+                .send("MONITOR + bowie,carl".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 306 fanny :You have been marked as being away".to_string(),
+                ":irc.irc 730 alan :bowie!~bowie@127.0.0.1".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-            time::sleep(Duration::from_millis(50)).await;
-            assert_eq!(
-                Some("See later".to_string()),
-                main_state
-                    .state
-                    .read()
-                    .await
-                    .users
-                    .get("fanny")
-                    .unwrap()
-                    .away
-            );
-
-            line_stream.send("AWAY".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 305 fanny :You are no longer marked as being away".to_string(),
+                ":irc.irc 731 alan :carl".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
-
-            time::sleep(Duration::from_millis(50)).await;
-            assert!(main_state
-                .state
-                .read()
-                .await
-                .users
-                .get("fanny")
-                .unwrap()
-                .away
-                .is_none());
-        }
-
-        quit_test_server(main_state, handle).await;
-    }
-
-    #[tokio::test]
-    async fn test_command_userhost() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
-
-        {
-            let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
-            let mut xstreams = vec![];
-            for i in 0..50 {
-                xstreams.push(
-                    login_to_test_and_skip(
-                        port,
-                        &format!("binny{}", i),
-                        &format!("binny{}", i),
-                        &format!("Binny{} BigBang", i),
-                    )
-                    .await,
-                );
-            }
-
             line_stream
-                .send(format!(
-                    "USERHOST {}",
-                    (0..50)
-                        .map(|x| format!("binny{}", x))
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                ))
+                .send("MONITOR + dave,eddie".to_string())
                 .await
                 .unwrap();
-            for range in [(0..20), (20..40), (40..50)] {
-                assert_eq!(
-                    ":irc.irc 302 funny :".to_string()
-                        + &(range
-                            .map(|x| format!("binny{0}=+~binny{0}@127.0.0.1", x))
-                            .collect::<Vec<_>>()
-                            .join(" ")),
-                    line_stream.next().await.unwrap().unwrap()
-                );
-            }
+            assert_eq!(
+                ":irc.irc 734 alan 3 eddie :Monitor list is full.".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 731 alan :dave".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("MONITOR L".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 732 alan :bowie,carl,dave".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 733 alan :End of MONITOR list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(3, main_state.state.read().await.monitor_count);
+
+            line_stream
+                .send("MONITOR - carl".to_string())
+                .await
+                .unwrap();
+            line_stream.send("MONITOR S".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 730 alan :bowie!~bowie@127.0.0.1".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 731 alan :dave".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("MONITOR C".to_string()).await.unwrap();
+            line_stream.send("MONITOR L".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 733 alan :End of MONITOR list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(0, main_state.state.read().await.monitor_count);
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_userhost_away() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_monitor_extended() {
+        let mut config = MainConfig::default();
+        config.max_monitor = Some(3);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
-
-            line_stream.send("AWAY :blablam".to_string()).await.unwrap();
-            line_stream.next().await.unwrap().unwrap();
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            let mut carl_stream = login_to_test_and_skip(port, "carl", "carl", "Carl Brown").await;
 
             line_stream
-                .send("USERHOST funny".to_string())
+                .send("CAP REQ :draft/extended-monitor".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 302 funny :funny=-~funny@127.0.0.1".to_string(),
+                ":irc.irc CAP * ACK :draft/extended-monitor".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            for stream in [&mut line_stream, &mut carl_stream] {
+                stream.send("MONITOR + bowie".to_string()).await.unwrap();
+                stream.next().await.unwrap().unwrap();
+            }
+
+            bowie_stream.send("AWAY :Gone".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 306 bowie :You have been marked as being away".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 AWAY :Gone".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            bowie_stream.send("AWAY".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 305 bowie :You are no longer marked as being away".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 AWAY".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            // carl has not extended-monitor capability.
+            carl_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
+                carl_stream.next().await.unwrap().unwrap()
+            );
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_userhost_oper() {
+    async fn test_command_away_rate_limit() {
         let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
+        config.away_rate_limit = Some(RateLimitConfig {
+            count: 2,
+            period: 100,
+        });
         let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Funny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+
             line_stream
-                .send("OPER fanny Funny".to_string())
+                .send("CAP REQ :draft/extended-monitor".to_string())
                 .await
                 .unwrap();
             line_stream.next().await.unwrap().unwrap();
-
             line_stream
-                .send("USERHOST funny".to_string())
+                .send("MONITOR + bowie".to_string())
                 .await
                 .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            bowie_stream.send("AWAY :Gone".to_string()).await.unwrap();
+            bowie_stream.next().await.unwrap().unwrap();
             assert_eq!(
-                ":irc.irc 302 funny :funny*=+~funny@127.0.0.1".to_string(),
+                ":bowie!~bowie@127.0.0.1 AWAY :Gone".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            bowie_stream.send("AWAY".to_string()).await.unwrap();
+            bowie_stream.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":bowie!~bowie@127.0.0.1 AWAY".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // state is changed, but monitors are not notified.
+            bowie_stream.send("AWAY :Again".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 306 bowie :You have been marked as being away".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("PING :x".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc PONG irc.irc :x".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            assert_eq!(
+                Some("Again".to_string()),
+                main_state.state.read().await.users.get("bowie").unwrap().away
+            );
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_userhost_notfound() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_monitor_global_limit() {
+        let mut config = MainConfig::default();
+        config.max_monitor_global = Some(2);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
-
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
             line_stream
-                .send("USERHOST ziggy".to_string())
+                .send("MONITOR + carl,dave".to_string())
                 .await
                 .unwrap();
             assert_eq!(
-                ":irc.irc 302 funny :".to_string(),
+                ":irc.irc 731 alan :carl,dave".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            bowie_stream
+                .send("MONITOR + eddie".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc FAIL MONITOR LIMIT_EXCEEDED eddie :Server monitor limit \
+                    has been reached"
+                    .to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            bowie_stream.send("MONITOR L".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 733 bowie :End of MONITOR list".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+
+            // free some entries
+            line_stream
+                .send("MONITOR - dave".to_string())
+                .await
+                .unwrap();
+            time::sleep(Duration::from_millis(50)).await;
+            bowie_stream
+                .send("MONITOR + eddie".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 731 bowie :eddie".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(2, main_state.state.read().await.monitor_count);
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_ison() {
-        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+    async fn test_command_watch() {
+        let mut config = MainConfig::default();
+        config.max_monitor = Some(2);
+        let (main_state, handle, port) = run_test_server(config).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
-            let mut xstreams = vec![];
-            for i in 0..50 {
-                xstreams.push(
-                    login_to_test_and_skip(
-                        port,
-                        &format!("binny{}", i),
-                        &format!("binny{}", i),
-                        &format!("Binny{} BigBang", i),
-                    )
-                    .await,
-                );
-            }
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            let _bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
 
             line_stream
-                .send(format!(
-                    "ISON {}",
-                    (0..50)
-                        .map(|x| format!("binny{}", x))
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                ))
+                .send("WATCH +bowie +carl".to_string())
                 .await
                 .unwrap();
-            for range in [(0..20), (20..40), (40..50)] {
+            let bowie_signon = main_state.state.read().await.users.get("bowie").unwrap().signon;
+            assert_eq!(
+                format!(
+                    ":irc.irc 604 alan bowie bowie 127.0.0.1 {} :is online",
+                    bowie_signon
+                ),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 605 alan carl * * 0 :is offline".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("WATCH +dave".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 512 alan dave :Maximum size for WATCH-list is 2 entries".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("WATCH S".to_string()).await.unwrap();
+            for expected in [
+                ":irc.irc 603 alan :You have 2 and are on 0 WATCH entries",
+                ":irc.irc 606 alan :bowie carl",
+                ":irc.irc 607 alan :End of WATCH list",
+            ] {
                 assert_eq!(
-                    ":irc.irc 303 funny :".to_string()
-                        + &(range
-                            .map(|x| format!("binny{0}", x))
-                            .collect::<Vec<_>>()
-                            .join(" ")),
+                    expected.to_string(),
                     line_stream.next().await.unwrap().unwrap()
                 );
             }
+            line_stream.send("WATCH".to_string()).await.unwrap();
+            assert_eq!(
+                format!(
+                    ":irc.irc 604 alan bowie bowie 127.0.0.1 {} :is online",
+                    bowie_signon
+                ),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(
+                ":irc.irc 607 alan :End of WATCH list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("WATCH -carl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 602 alan carl * * 0 :stopped watching".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream.send("WATCH C L".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 607 alan :End of WATCH list".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            assert_eq!(0, main_state.state.read().await.monitor_count);
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_ison_notfound() {
+    async fn test_presence_notifications() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            line_stream.send("MONITOR + carl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 731 alan :carl".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            bowie_stream.send("WATCH +carl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 605 bowie carl * * 0 :is offline".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
 
-            line_stream.send("ISON ziggy".to_string()).await.unwrap();
+            // carl goes online
+            let mut carl_stream =
+                login_to_test_and_skip(port, "carl", "carl", "Carl Bigfoot").await;
             assert_eq!(
-                ":irc.irc 303 funny :".to_string(),
+                ":irc.irc 730 alan :carl!~carl@127.0.0.1".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let line = bowie_stream.next().await.unwrap().unwrap();
+            assert!(
+                line.starts_with(":irc.irc 600 bowie carl carl 127.0.0.1 "),
+                "{}",
+                line
+            );
+            assert!(line.ends_with(" :logged online"), "{}", line);
+
+            // nick change - old nick goes offline
+            carl_stream.send("NICK carly".to_string()).await.unwrap();
+            for stream in [&mut line_stream, &mut bowie_stream] {
+                assert_eq!(
+                    ":carl!~carl@127.0.0.1 NICK carly".to_string(),
+                    stream.next().await.unwrap().unwrap()
+                );
+            }
+            assert_eq!(
+                ":irc.irc 731 alan :carl".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let line = bowie_stream.next().await.unwrap().unwrap();
+            assert!(
+                line.starts_with(":irc.irc 601 bowie carl carl 127.0.0.1 "),
+                "{}",
+                line
+            );
+            assert!(line.ends_with(" :logged offline"), "{}", line);
+
+            carl_stream.send("NICK carl".to_string()).await.unwrap();
+            for stream in [&mut line_stream, &mut bowie_stream] {
+                assert_eq!(
+                    ":carly!~carl@127.0.0.1 NICK carl".to_string(),
+                    stream.next().await.unwrap().unwrap()
+                );
+            }
+            assert_eq!(
+                ":irc.irc 730 alan :carl!~carl@127.0.0.1".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let line = bowie_stream.next().await.unwrap().unwrap();
+            assert!(line.starts_with(":irc.irc 600 bowie carl "), "{}", line);
+
+            // disconnection
+            carl_stream.send("QUIT :Bye".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 731 alan :carl".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
+            let line = bowie_stream.next().await.unwrap().unwrap();
+            assert!(line.starts_with(":irc.irc 601 bowie carl "), "{}", line);
         }
 
         quit_test_server(main_state, handle).await;
     }
 
     #[tokio::test]
-    async fn test_command_wallops() {
-        let mut config = MainConfig::default();
-        config.operators = Some(vec![OperatorConfig {
-            name: "fanny".to_string(),
-            password: argon2_hash_password("Funny"),
-            mask: None,
-        }]);
-        let (main_state, handle, port) = run_test_server(config).await;
+    async fn test_presence_notifications_only_watchers() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
 
         {
             let mut line_stream =
-                login_to_test_and_skip(port, "funny", "funny", "Bunny BumBumBum").await;
-            line_stream
-                .send("OPER fanny Funny".to_string())
-                .await
-                .unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            let mut hanna_stream =
-                login_to_test_and_skip(port, "hanna", "hanna", "Hanna-Barbera").await;
-            let mut gena_stream =
-                login_to_test_and_skip(port, "gena", "gena", "Gena Sphinxs").await;
-            let mut keith_stream =
-                login_to_test_and_skip(port, "keith", "keith", "Keith North").await;
-
-            line_stream.send("MODE funny +w".to_string()).await.unwrap();
-            line_stream.next().await.unwrap().unwrap();
-            hanna_stream
-                .send("MODE hanna +w".to_string())
+                login_to_test_and_skip(port, "alan", "alan", "Alan Bodarski").await;
+            line_stream.send("MONITOR + carl".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 731 alan :carl".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            let mut bowie_stream =
+                login_to_test_and_skip(port, "bowie", "bowie", "Bowie Catcher").await;
+            bowie_stream
+                .send("MONITOR + dave".to_string())
                 .await
                 .unwrap();
-            hanna_stream.next().await.unwrap().unwrap();
-            gena_stream.send("MODE gena +w".to_string()).await.unwrap();
-            gena_stream.next().await.unwrap().unwrap();
+            assert_eq!(
+                ":irc.irc 731 bowie :dave".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
+            );
 
-            line_stream
-                .send("WALLOPS :Hello people".to_string())
-                .await
-                .unwrap();
-            for line_stream in [&mut line_stream, &mut hanna_stream, &mut gena_stream] {
-                assert_eq!(
-                    ":funny!~funny@127.0.0.1 WALLOPS :Hello people".to_string(),
-                    line_stream.next().await.unwrap().unwrap()
-                );
-            }
-            hanna_stream
-                .send("WALLOPS :Hello people".to_string())
-                .await
-                .unwrap();
+            let _carl_stream =
+                login_to_test_and_skip(port, "carl", "carl", "Carl Bigfoot").await;
             assert_eq!(
-                ":irc.irc 481 hanna :Permission Denied- You're not an IRC \
-                    operator"
-                    .to_string(),
-                hanna_stream.next().await.unwrap().unwrap()
+                ":irc.irc 730 alan :carl!~carl@127.0.0.1".to_string(),
+                line_stream.next().await.unwrap().unwrap()
             );
-            keith_stream
-                .send("WALLOPS :Hello people".to_string())
-                .await
-                .unwrap();
+            // bowie doesn't watch carl - next reply is reply for MONITOR status.
+            bowie_stream.send("MONITOR S".to_string()).await.unwrap();
             assert_eq!(
-                ":irc.irc 481 keith :Permission Denied- You're not an IRC \
-                    operator"
-                    .to_string(),
-                keith_stream.next().await.unwrap().unwrap()
+                ":irc.irc 731 bowie :dave".to_string(),
+                bowie_stream.next().await.unwrap().unwrap()
             );
+            {
+                let state = main_state.state.read().await;
+                assert_eq!(1, state.presence.watchers_count(PresenceKind::Monitor, "carl"));
+                assert_eq!(1, state.presence.watchers_count(PresenceKind::Monitor, "dave"));
+            }
         }
 
         quit_test_server(main_state, handle).await;
@@ -52,14 +52,18 @@ impl super::MainState {
                 },
             )
             .await?;
-            self.feed_msg(
-                &mut conn_state.stream,
-                RplMotd372 {
-                    client,
-                    motd: &self.config.motd,
-                },
-            )
-            .await?;
+            let motd = self.reloadable.read().await.motd.clone();
+            // MOTD loaded from file can have many lines
+            for motd_line in motd.split('\n') {
+                self.feed_msg(
+                    &mut conn_state.stream,
+                    RplMotd372 {
+                        client,
+                        motd: motd_line.trim_end_matches('\r'),
+                    },
+                )
+                .await?;
+            }
             self.feed_msg(&mut conn_state.stream, RplEndOfMotd376 { client })
                 .await?;
         }
@@ -314,6 +318,27 @@ impl super::MainState {
                         )
                         .await?;
                     }
+                    'l' => {
+                        // connection statistics for all users sorted by nick.
+                        let mut nicks = state.users.keys().collect::<Vec<_>>();
+                        nicks.sort();
+                        for nick in nicks {
+                            let u = state.users.get(nick).unwrap();
+                            let stats = &u.conn_stats;
+                            self.feed_msg(
+                                &mut conn_state.stream,
+                                RplStatsLinkInfo211 {
+                                    client,
+                                    linkname: &u.source,
+                                    sendq: stats.sendq.load(Ordering::Relaxed),
+                                    recvq: stats.recvq.load(Ordering::Relaxed),
+                                    sent_bytes: stats.bytes_sent.load(Ordering::Relaxed),
+                                    recv_bytes: stats.bytes_received.load(Ordering::Relaxed),
+                                },
+                            )
+                            .await?;
+                        }
+                    }
                     'm' => {
                         for (i, x) in CommandId::iter().enumerate() {
                             let count = self.command_counts[i].load(Ordering::SeqCst);
@@ -453,6 +478,7 @@ impl super::MainState {
         Ok(())
     }
 
+    // process channel modes. Returns true if bans or exceptions have been changed.
     async fn process_mode_channel<'a>(
         &self,
         conn_state: &mut ConnState,
@@ -461,10 +487,14 @@ impl super::MainState {
         target: &'a str,
         modes: Vec<(&'a str, Vec<&'a str>)>,
         chum: &ChannelUserModes,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<bool, Box<dyn Error>> {
         let client = conn_state.user_state.client_name();
         let if_op = chum.is_operator();
         let if_half_op = chum.is_half_operator();
+        let if_oper = users
+            .get(conn_state.user_state.nick.as_ref().unwrap())
+            .map_or(false, |user| user.modes.is_local_oper());
+        let mut bans_changed = false;
 
         if modes.is_empty() {
             self.feed_msg(
@@ -534,7 +564,18 @@ impl super::MainState {
                                 .await?;
                             }
                         }
-                        'i' | 'm' | 't' | 'n' | 's' | 'l' | 'k' | 'v' => {
+                        'P' => {
+                            // only operators can set permanent channel
+                            if !if_oper {
+                                self.feed_msg(
+                                    &mut conn_state.stream,
+                                    ErrNoPrivileges481 { client },
+                                )
+                                .await?;
+                            }
+                        }
+                        'i' | 'm' | 't' | 'n' | 's' | 'l' | 'k' | 'f' | 'v' | 'D' | 'R' | 'c'
+                        | CHANMODE_FREE_INVITE => {
                             if !if_half_op {
                                 self.feed_msg(
                                     &mut conn_state.stream,
@@ -588,6 +629,7 @@ impl super::MainState {
                                         chanobj.ban_info.remove(&norm_bmask);
                                     }
                                     chanobj.modes.ban = Some(ban);
+                                    bans_changed = true;
                                 } else {
                                     self.feed_msg(
                                         &mut conn_state.stream,
@@ -660,6 +702,7 @@ impl super::MainState {
                                         exp.remove(&norm_emask);
                                     }
                                     chanobj.modes.exception = Some(exp);
+                                    bans_changed = true;
                                 } else {
                                     self.feed_msg(
                                         &mut conn_state.stream,
@@ -885,6 +928,22 @@ impl super::MainState {
                                 };
                             }
                         }
+                        'f' => {
+                            if if_half_op {
+                                chanobj.modes.forward = if mode_set {
+                                    let arg = margs_it.next().unwrap();
+                                    // put to applied modes
+                                    modes_params_string += " +f ";
+                                    modes_params_string += arg;
+
+                                    Some(arg.to_string())
+                                } else {
+                                    // put to applied modes
+                                    unset_modes_string.push('f');
+                                    None
+                                };
+                            }
+                        }
                         'i' => {
                             if if_half_op {
                                 chanobj.modes.invite_only = mode_set;
@@ -940,6 +999,68 @@ impl super::MainState {
                                 }
                             }
                         }
+                        CHANMODE_FREE_INVITE => {
+                            if if_half_op {
+                                chanobj.modes.free_invite = mode_set;
+                                // put to applied modes
+                                if mode_set {
+                                    set_modes_string.push(CHANMODE_FREE_INVITE);
+                                } else {
+                                    unset_modes_string.push(CHANMODE_FREE_INVITE);
+                                }
+                            }
+                        }
+                        'P' => {
+                            if if_oper {
+                                chanobj.modes.permanent = mode_set;
+                                // put to applied modes
+                                if mode_set {
+                                    set_modes_string.push('P');
+                                } else {
+                                    unset_modes_string.push('P');
+                                }
+                            }
+                        }
+                        'D' => {
+                            if if_half_op {
+                                chanobj.modes.delayed_join = mode_set;
+                                // put to applied modes
+                                if mode_set {
+                                    set_modes_string.push('D');
+                                } else {
+                                    unset_modes_string.push('D');
+                                    // show all users hidden by delayed join
+                                    for (nick, _) in
+                                        chanobj.delayed_users.iter().filter(|(_, ops)| !**ops)
+                                    {
+                                        chanobj.send_delayed_join(target, nick, users);
+                                    }
+                                    chanobj.delayed_users.retain(|_, ops| *ops);
+                                }
+                            }
+                        }
+                        'R' => {
+                            if if_half_op {
+                                chanobj.modes.registered_only = mode_set;
+                                // put to applied modes
+                                if mode_set {
+                                    set_modes_string.push('R');
+                                } else {
+                                    unset_modes_string.push('R');
+                                }
+                            }
+                        }
+                        'c' => {
+                            if if_half_op {
+                                chanobj.modes.no_colors = mode_set;
+                                // put to applied modes
+                                if mode_set {
+                                    set_modes_string.push('c');
+                                } else {
+                                    unset_modes_string.push('c');
+                                }
+                            }
+                        }
                         _ => (),
                     }
                 }
@@ -979,7 +1100,7 @@ impl super::MainState {
                 }
             }
         } // if modes.len() == 0
-        Ok(())
+        Ok(bans_changed)
     }
 
     async fn process_mode_user<'a>(
@@ -1066,16 +1187,27 @@ impl super::MainState {
                                 unset_modes_string.push('w');
                             }
                         }
-                        'o' => {
+                        'B' => {
                             if mode_set {
-                                if !user.modes.oper {
-                                    if self.oper_config_idxs.contains_key(user_nick) {
-                                        user.modes.oper = true;
-                                        if !user.modes.local_oper {
-                                            state.operators_count += 1;
-                                            // put to applied modes
-                                            set_modes_string.push('o');
-                                        }
+                                if !user.modes.bot {
+                                    user.modes.bot = true;
+                                    // put to applied modes
+                                    set_modes_string.push('B');
+                                }
+                            } else if user.modes.bot {
+                                user.modes.bot = false;
+                                // put to applied modes
+                                unset_modes_string.push('B');
+                            }
+                        }
+                        'H' => {
+                            if mode_set {
+                                if !user.modes.hide_oper {
+                                    // only operator can hide its operator status.
+                                    if user.modes.is_local_oper() {
+                                        user.modes.hide_oper = true;
+                                        // put to applied modes
+                                        set_modes_string.push('H');
                                     } else {
                                         self.feed_msg(
                                             &mut conn_state.stream,
@@ -1084,40 +1216,62 @@ impl super::MainState {
                                         .await?;
                                     }
                                 }
+                            } else if user.modes.hide_oper {
+                                user.modes.hide_oper = false;
+                                // put to applied modes
+                                unset_modes_string.push('H');
+                            }
+                        }
+                        'g' => {
+                            if mode_set {
+                                if !user.modes.caller_id {
+                                    user.modes.caller_id = true;
+                                    // put to applied modes
+                                    set_modes_string.push('g');
+                                }
+                            } else if user.modes.caller_id {
+                                user.modes.caller_id = false;
+                                // put to applied modes
+                                unset_modes_string.push('g');
+                            }
+                        }
+                        'o' => {
+                            if mode_set {
+                                // operator mode can be set only by server (OPER command).
+                                if !user.modes.oper {
+                                    self.feed_msg(
+                                        &mut conn_state.stream,
+                                        ErrNoPrivileges481 { client },
+                                    )
+                                    .await?;
+                                }
                             } else if user.modes.oper {
+                                // de-oper: drop operator privileges, not only flag.
                                 user.modes.oper = false;
                                 if !user.modes.local_oper {
                                     state.operators_count -= 1;
-                                    // put to applied modes
-                                    unset_modes_string.push('o');
                                 }
+                                // put to applied modes
+                                unset_modes_string.push('o');
                             }
                         }
                         'O' => {
                             if mode_set {
+                                // operator mode can be set only by server (OPER command).
                                 if !user.modes.local_oper {
-                                    if self.oper_config_idxs.contains_key(user_nick) {
-                                        user.modes.oper = true;
-                                        if !user.modes.oper {
-                                            state.operators_count += 1;
-                                            // put to applied modes
-                                            set_modes_string.push('O');
-                                        }
-                                    } else {
-                                        self.feed_msg(
-                                            &mut conn_state.stream,
-                                            ErrNoPrivileges481 { client },
-                                        )
-                                        .await?;
-                                    }
+                                    self.feed_msg(
+                                        &mut conn_state.stream,
+                                        ErrNoPrivileges481 { client },
+                                    )
+                                    .await?;
                                 }
-                            } else if user.modes.oper {
+                            } else if user.modes.is_local_oper() {
+                                // de-oper: local operator loses all operator privileges.
                                 user.modes.oper = false;
-                                if !user.modes.oper {
-                                    state.operators_count -= 1;
-                                    // put to applied modes
-                                    unset_modes_string.push('O');
-                                }
+                                user.modes.local_oper = false;
+                                state.operators_count -= 1;
+                                // put to applied modes
+                                unset_modes_string.push('O');
                             }
                         }
                         _ => (),
@@ -1175,15 +1329,19 @@ impl super::MainState {
                     (ChannelUserModes::default(), true)
                 };
                 if !error {
-                    self.process_mode_channel(
-                        conn_state,
-                        &state.users,
-                        chanobj,
-                        target,
-                        modes,
-                        &chum,
-                    )
-                    .await?;
+                    let bans_changed = self
+                        .process_mode_channel(
+                            conn_state,
+                            &state.users,
+                            chanobj,
+                            target,
+                            modes,
+                            &chum,
+                        )
+                        .await?;
+                    if bans_changed {
+                        state.clear_ban_cache(target);
+                    }
                 }
             } else {
                 self.feed_msg(
@@ -1196,13 +1354,29 @@ impl super::MainState {
                 .await?;
             }
         } else {
-            // user
-            if user_nick == target {
-                self.process_mode_user(conn_state, state, target, modes)
+            // user - only own modes can be changed (nicks compared with ascii casemapping).
+            if user_nick.eq_ignore_ascii_case(target) {
+                let user_nick = user_nick.clone();
+                self.process_mode_user(conn_state, state, &user_nick, modes)
                     .await?;
-            } else if state.users.contains_key(target) {
-                self.feed_msg(&mut conn_state.stream, ErrUsersDontMatch502 { client })
+            } else if let Some(target_user) = state.users.get(target) {
+                let user = state.users.get(user_nick).unwrap();
+                if modes.is_empty()
+                    && self.config.oper_view_user_modes
+                    && user.modes.is_local_oper()
+                {
+                    self.feed_msg(
+                        &mut conn_state.stream,
+                        RplUModeIs221 {
+                            client,
+                            user_modes: &target_user.modes.to_string(),
+                        },
+                    )
                     .await?;
+                } else {
+                    self.feed_msg(&mut conn_state.stream, ErrUsersDontMatch502 { client })
+                        .await?;
+                }
             } else {
                 self.feed_msg(
                     &mut conn_state.stream,
@@ -1267,21 +1441,23 @@ mod test {
             );
             assert_eq!(
                 ":irc.irc 005 tommy AWAYLEN=1000 CASEMAPPING=ascii \
-                    CHANMODES=Iabehiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# EXCEPTS=e FNC \
-                    HOSTLEN=1000 INVEX=I KEYLEN=1000 :are supported by this server"
+                    CHANMODES=DIPRabcefghiklmnopqstv CHANNELLEN=1000 CHANTYPES=&# CNOTICE \
+                    CPRIVMSG EXCEPTS=e FNC HOSTLEN=1000 INVEX=I KEYLEN=1000 KICKLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 tommy KICKLEN=1000 LINELEN=2000 MAXLIST=beI:1000 \
-                    MAXNICKLEN=200 MAXPARA=500 MAXTARGETS=500 MODES=500 NETWORK=IRCnetwork \
-                    NICKLEN=200 PREFIX=(qaohv)~&@%+ :are supported by this server"
+                ":irc.irc 005 tommy LINELEN=2000 MAXLIST=beI:1000 MAXNICKLEN=200 \
+                    MAXPARA=500 MAXTARGETS=500 MODES=500 MONITOR NETWORK=IRCnetwork \
+                    NICKLEN=200 PREFIX=(qaohv)~&@%+ SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 005 tommy SAFELIST STATUSMSG=~&@%+ TOPICLEN=1000 USERLEN=200 \
-                    USERMODES=Oiorw :are supported by this server"
+                ":irc.irc 005 tommy USERLEN=200 USERMODES=BHOgiorw WATCH \
+                    :are supported by this server"
                     .to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
@@ -1353,6 +1529,7 @@ mod test {
             name: "tommy".to_string(),
             password: argon2_hash_password("zzzzz"),
             mask: None,
+            require_tls: false,
         }]);
         let (main_state, handle, port) = run_test_server(config).await;
 
@@ -1461,6 +1638,7 @@ mod test {
             name: "timmy".to_string(),
             password: argon2_hash_password("zzzzz"),
             mask: None,
+            require_tls: false,
         }]);
         let (main_state, handle, port) = run_test_server(config).await;
 
@@ -1511,6 +1689,57 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_stats_conn_info() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "timmy".to_string(),
+            password: argon2_hash_password("zzzzz"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "timmy", "tim", "Timmy Greater").await;
+            line_stream
+                .send("OPER timmy zzzzz".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+
+            let _teddy_stream =
+                login_to_test_and_skip(port, "teddy", "teddy", "Teddy Bear").await;
+            time::sleep(Duration::from_millis(50)).await;
+
+            line_stream.send("STATS l".to_string()).await.unwrap();
+            // received: "NICK teddy\r\n" and "USER teddy 8 * :Teddy Bear\r\n".
+            let reply = line_stream.next().await.unwrap().unwrap();
+            let prefix = ":irc.irc 211 timmy teddy!~teddy@127.0.0.1 0 0 ";
+            assert!(reply.starts_with(prefix), "{}", reply);
+            let counts = reply[prefix.len()..]
+                .split(' ')
+                .map(|x| x.parse::<u64>().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(2, counts.len());
+            assert!(counts[0] != 0);
+            assert_eq!(40, counts[1]);
+            assert!(line_stream
+                .next()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with(":irc.irc 211 timmy timmy!~tim@127.0.0.1 "));
+            assert_eq!(
+                ":irc.irc 219 timmy l :End of STATS report".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_links() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -1592,7 +1821,7 @@ mod test {
                 line_stream.next().await.unwrap().unwrap()
             );
             assert_eq!(
-                ":irc.irc 705 timmy COMMANDS :ADMIN".to_string(),
+                ":irc.irc 705 timmy COMMANDS :ACCEPT".to_string(),
                 line_stream.next().await.unwrap().unwrap()
             );
         }
@@ -1673,6 +1902,63 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_mode_user_oper_view() {
+        let mut config = MainConfig::default();
+        config.oper_view_user_modes = true;
+        config.operators = Some(vec![OperatorConfig {
+            name: "expert".to_string(),
+            password: argon2_hash_password("NoWay"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "roland", "roland", "Roland TechnoMusic").await;
+            let mut norton_stream =
+                login_to_test_and_skip(port, "norton", "norton", "Norton Norton2").await;
+            line_stream
+                .send("OPER expert NoWay".to_string())
+                .await
+                .unwrap();
+            line_stream.next().await.unwrap().unwrap();
+            norton_stream
+                .send("MODE norton +w".to_string())
+                .await
+                .unwrap();
+            norton_stream.next().await.unwrap().unwrap();
+
+            // own nick is matched with ascii casemapping.
+            line_stream.send("MODE ROLAND".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 221 roland +o".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            line_stream.send("MODE norton".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 221 roland +w".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("MODE norton -w".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ":irc.irc 502 roland :Cant change mode for other users".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            norton_stream.send("MODE roland".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 502 norton :Cant change mode for other users".to_string(),
+                norton_stream.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_mode_user_registered() {
         let mut config = MainConfig::default();
@@ -1755,6 +2041,7 @@ mod test {
             name: "expert".to_string(),
             password: argon2_hash_password("NoWay"),
             mask: None,
+            require_tls: false,
         }]);
         let (main_state, handle, port) = run_test_server(config).await;
         {
@@ -1785,10 +2072,28 @@ mod test {
             time::sleep(Duration::from_millis(50)).await;
             {
                 let state = main_state.state.read().await;
-                assert!(!state.users.get("roland").unwrap().modes.wallops);
+                let user = state.users.get("roland").unwrap();
+                assert!(!user.modes.wallops);
+                assert!(!user.modes.is_local_oper());
                 assert_eq!(0, state.operators_count);
             }
 
+            // operator commands are rejected after de-oper
+            line_stream.send("REHASH".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 roland :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("MODE roland -o".to_string())
+                .await
+                .unwrap();
+            line_stream.send("MODE roland".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 221 roland +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
             line_stream
                 .send("MODE roland +o".to_string())
                 .await
@@ -1809,6 +2114,7 @@ mod test {
             name: "expert".to_string(),
             password: argon2_hash_password("NoWay"),
             mask: None,
+            require_tls: false,
         }]);
         let (main_state, handle, port) = run_test_server(config).await;
         {
@@ -1839,10 +2145,28 @@ mod test {
             time::sleep(Duration::from_millis(50)).await;
             {
                 let state = main_state.state.read().await;
-                assert!(!state.users.get("roland").unwrap().modes.wallops);
+                let user = state.users.get("roland").unwrap();
+                assert!(!user.modes.wallops);
+                assert!(!user.modes.is_local_oper());
                 assert_eq!(0, state.operators_count);
             }
 
+            // operator commands are rejected after de-oper
+            line_stream.send("REHASH".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 481 roland :Permission Denied- You're not an IRC operator".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+            line_stream
+                .send("MODE roland -O".to_string())
+                .await
+                .unwrap();
+            line_stream.send("MODE roland".to_string()).await.unwrap();
+            assert_eq!(
+                ":irc.irc 221 roland +".to_string(),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
             line_stream
                 .send("MODE roland +O".to_string())
                 .await
@@ -1856,6 +2180,43 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_mode_user_set_operator() {
+        let mut config = MainConfig::default();
+        config.operators = Some(vec![OperatorConfig {
+            name: "expert".to_string(),
+            password: argon2_hash_password("NoWay"),
+            mask: None,
+            require_tls: false,
+        }]);
+        let (main_state, handle, port) = run_test_server(config).await;
+        {
+            // nick same as operator name doesn't give operator privileges
+            let mut line_stream =
+                login_to_test_and_skip(port, "expert", "expert", "Expert TechnoMusic").await;
+            for mode in ["+o", "+O"] {
+                line_stream
+                    .send(format!("MODE expert {}", mode))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    ":irc.irc 481 expert :Permission Denied- You're not an IRC operator"
+                        .to_string(),
+                    line_stream.next().await.unwrap().unwrap()
+                );
+            }
+
+            time::sleep(Duration::from_millis(50)).await;
+            {
+                let state = main_state.state.read().await;
+                assert!(!state.users.get("expert").unwrap().modes.is_local_oper());
+                assert_eq!(0, state.operators_count);
+            }
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_mode_user_multiple() {
         let mut config = MainConfig::default();
@@ -2060,6 +2421,67 @@ mod test {
         quit_test_server(main_state, handle).await;
     }
 
+    #[tokio::test]
+    async fn test_command_mode_channel_creation_time() {
+        let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
+
+        {
+            let mut line_stream =
+                login_to_test_and_skip(port, "sonny", "sonnyx", "Sonny Gray").await;
+            line_stream.send("JOIN #times".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream.next().await.unwrap().unwrap();
+            }
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            time::sleep(Duration::from_millis(50)).await;
+            let creation_time = main_state
+                .state
+                .read()
+                .await
+                .channels
+                .get("#times")
+                .unwrap()
+                .creation_time;
+            assert!(now - 2 <= creation_time && creation_time <= now);
+
+            line_stream.send("MODE #times".to_string()).await.unwrap();
+            line_stream.next().await.unwrap().unwrap(); // 324
+            assert_eq!(
+                format!(":irc.irc 329 sonny #times {}", creation_time),
+                line_stream.next().await.unwrap().unwrap()
+            );
+
+            // creation time does not change after membership changes
+            let mut line_stream2 =
+                login_to_test_and_skip(port, "goofy", "goofy", "Goofy Grey").await;
+            line_stream2.send("JOIN #times".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("PART #times".to_string()).await.unwrap();
+            line_stream2.next().await.unwrap().unwrap(); // PART
+            line_stream.next().await.unwrap().unwrap(); // JOIN
+            line_stream.next().await.unwrap().unwrap(); // PART
+
+            time::sleep(Duration::from_millis(1100)).await;
+            line_stream2.send("JOIN #times".to_string()).await.unwrap();
+            for _ in 0..3 {
+                line_stream2.next().await.unwrap().unwrap();
+            }
+            line_stream2.send("MODE #times".to_string()).await.unwrap();
+            line_stream2.next().await.unwrap().unwrap(); // 324
+            assert_eq!(
+                format!(":irc.irc 329 goofy #times {}", creation_time),
+                line_stream2.next().await.unwrap().unwrap()
+            );
+        }
+
+        quit_test_server(main_state, handle).await;
+    }
+
     #[tokio::test]
     async fn test_command_mode_channel_modes() {
         let (main_state, handle, port) = run_test_server(MainConfig::default()).await;
@@ -19,13 +19,13 @@
 
 use flagset::{flags, FlagSet};
 use futures::{future::Fuse, future::FutureExt};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::net::IpAddr;
 use std::ops::Drop;
 use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot;
@@ -35,8 +35,11 @@ use tracing::*;
 
 use crate::command::*;
 use crate::config::*;
+use crate::reply::*;
 use crate::utils::*;
 
+use Reply::*;
+
 #[derive(Debug)]
 pub(super) struct User {
     pub(super) hostname: String,
@@ -49,10 +52,33 @@ pub(super) struct User {
     pub(super) modes: UserModes,
     pub(super) away: Option<String>,
     pub(super) channels: HashSet<String>,
-    pub(super) invited_to: HashSet<String>, // invited in channels
+    pub(super) invited_to: HashMap<String, u64>, // invited in channels with invite time
     pub(super) last_activity: u64,
     pub(super) signon: u64,
     pub(super) history_entry: NickHistoryEntry,
+    // connection statistics - used by STATS command.
+    pub(super) conn_stats: Arc<ConnStats>,
+    // token to resume session (draft/resume).
+    pub(super) resume_token: Option<String>,
+    // receiver for messages sent to user while its connection has been lost.
+    // If it is set then user waits for resuming session.
+    pub(super) detached_receiver: Option<UnboundedReceiver<String>>,
+    // nicknames monitored by user (MONITOR command).
+    pub(super) monitor: HashSet<String>,
+    // nicknames watched by user (WATCH command).
+    pub(super) watch: HashSet<String>,
+    // capabilities negotiated by user's connection.
+    pub(super) caps: CapState,
+    // unique ID of user's connection (RPL_YOURID).
+    pub(super) uid: String,
+    // account - name of user defined in configuration that user logged in as.
+    pub(super) account: Option<String>,
+    // nicknames that can send private messages if user has caller-ID mode (+g).
+    pub(super) accept: HashSet<String>,
+    // country code of user's IP address resolved by GeoIP.
+    pub(super) country: Option<String>,
+    // time of last caller-ID notification (+g) sent to user by any sender.
+    pub(super) callerid_notify_time: Mutex<Option<Instant>>,
 }
 
 impl User {
@@ -61,6 +87,7 @@ impl User {
         user_state: &ConnUserState,
         sender: UnboundedSender<String>,
         quit_sender: oneshot::Sender<(String, String)>,
+        conn_stats: Arc<ConnStats>,
     ) -> User {
         let mut user_modes = config.default_user_modes;
         user_modes.registered = user_modes.registered || user_state.registered;
@@ -78,7 +105,7 @@ impl User {
             modes: user_modes,
             away: None,
             channels: HashSet::new(),
-            invited_to: HashSet::new(),
+            invited_to: HashMap::new(),
             last_activity: now_ts,
             signon: now_ts,
             history_entry: NickHistoryEntry {
@@ -86,10 +113,50 @@ impl User {
                 hostname: user_state.hostname.clone(),
                 realname: user_state.realname.as_ref().unwrap().clone(),
                 signon: now_ts,
+                left_time: 0,
             },
+            conn_stats,
+            resume_token: None,
+            detached_receiver: None,
+            monitor: HashSet::new(),
+            watch: HashSet::new(),
+            caps: CapState::default(),
+            uid: String::new(),
+            account: user_state.name.clone().filter(|_| user_state.registered),
+            accept: HashSet::new(),
+            country: None,
+            callerid_notify_time: Mutex::new(None),
         }
     }
 
+    // flags of WHO reply: away state, operator status (hidden by +H for non-operators),
+    // bot mode and channel prefixes.
+    pub(super) fn who_flags(
+        &self,
+        chum: Option<&ChannelUserModes>,
+        caps: &CapState,
+        viewer_oper: bool,
+    ) -> String {
+        let mut flags = String::new();
+        // if user away
+        if self.away.is_some() {
+            flags.push('G');
+        } else {
+            flags.push('H');
+        }
+        // if user is operator
+        if self.modes.is_local_oper() && (!self.modes.hide_oper || viewer_oper) {
+            flags.push('*');
+        }
+        if self.modes.bot {
+            flags.push('B');
+        }
+        if let Some(chum) = chum {
+            flags += &chum.to_string(caps);
+        }
+        flags
+    }
+
     // update nick - mainly source
     pub(super) fn update_nick(&mut self, user_state: &ConnUserState) {
         self.source = user_state.source.clone();
@@ -117,6 +184,34 @@ impl User {
     ) -> Result<(), SendError<String>> {
         self.sender.send(format!(":{} {}", source, t))
     }
+
+    // check whether caller-ID notification can be sent to user now. Notifications
+    // are sent not more often than once per interval, regardless of senders.
+    pub(super) fn callerid_notify_due(&self, interval: Duration) -> bool {
+        let mut last = self.callerid_notify_time.lock().unwrap();
+        let now = Instant::now();
+        if last.map_or(true, |t| now - t >= interval) {
+            *last = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    // get nicknames watched by user by MONITOR or WATCH.
+    pub(super) fn presence_targets(&self, kind: PresenceKind) -> &HashSet<String> {
+        match kind {
+            PresenceKind::Monitor => &self.monitor,
+            PresenceKind::Watch => &self.watch,
+        }
+    }
+
+    pub(super) fn presence_targets_mut(&mut self, kind: PresenceKind) -> &mut HashSet<String> {
+        match kind {
+            PresenceKind::Monitor => &mut self.monitor,
+            PresenceKind::Watch => &mut self.watch,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
@@ -208,8 +303,9 @@ pub(super) fn get_privmsg_target_type(target: &str) -> (FlagSet<PrivMsgTargetTyp
             b'@' => out |= Channel | ChannelOper,
             b'%' => out |= Channel | ChannelHalfOper,
             b'+' => out |= Channel | ChannelVoice,
-            b'#' => {
-                // if global channel
+            // channel type that is not status prefix ('&' is resolved by counting
+            // ampersands).
+            c if CHANTYPES.as_bytes().contains(&c) => {
                 if i + 1 < target.len() {
                     out_str = &target[i..];
                 } else {
@@ -304,6 +400,11 @@ impl ChannelDefaultModes {
     }
 }
 
+// cached results of ban checks of users that send messages to channel:
+// nick -> (source, account, banned). Entry is valid until user's source or account
+// is changed.
+pub(super) type BanMatchCache = HashMap<String, (String, Option<String>, bool)>;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(super) struct Channel {
     pub(super) topic: Option<ChannelTopic>,
@@ -314,6 +415,11 @@ pub(super) struct Channel {
     pub(super) creation_time: u64,
     // if channel is preconfigured - it comes from configuration
     pub(super) preconfigured: bool,
+    // access list - masks with channel statuses granted on join (ACCESS command).
+    pub(super) access: HashMap<String, ChannelAccessLevel>,
+    // users hidden by delayed join (+D) or by quiet join in large channel - they did not
+    // send message to channel yet. value is true if JOIN was sent to channel operators.
+    pub(super) delayed_users: HashMap<String, bool>,
 }
 
 impl Channel {
@@ -334,6 +440,8 @@ impl Channel {
                 .unwrap()
                 .as_secs(),
             preconfigured: false,
+            access: HashMap::new(),
+            delayed_users: HashMap::new(),
         }
     }
 
@@ -373,9 +481,37 @@ impl Channel {
         self.users.insert(user_nick.clone(), chum);
     }
 
+    // apply access list for user that joined to channel. Returns granted status
+    // if user matches any entry and has not that status yet.
+    pub(super) fn apply_access(
+        &mut self,
+        nick: &str,
+        source: &str,
+        account: Option<&str>,
+    ) -> Option<ChannelAccessLevel> {
+        let level = self
+            .access
+            .iter()
+            .filter(|(mask, _)| match_user_mask(mask, source, account))
+            .map(|(_, level)| *level)
+            .max()?;
+        let chum = *self.users.get(nick).unwrap();
+        match level {
+            ChannelAccessLevel::Founder if !chum.founder => self.add_founder(nick),
+            ChannelAccessLevel::Op if !chum.operator => self.add_operator(nick),
+            ChannelAccessLevel::HalfOp if !chum.half_oper => self.add_half_operator(nick),
+            ChannelAccessLevel::Voice if !chum.voice => self.add_voice(nick),
+            _ => return None,
+        }
+        Some(level)
+    }
+
     pub(super) fn rename_user(&mut self, old_nick: &String, nick: String) {
         let oldchumode = self.users.remove(old_nick).unwrap();
         self.users.insert(nick.clone(), oldchumode);
+        if let Some(ops_notified) = self.delayed_users.remove(old_nick) {
+            self.delayed_users.insert(nick.clone(), ops_notified);
+        }
         self.modes.rename_user(old_nick, nick);
     }
 
@@ -387,6 +523,34 @@ impl Channel {
         self.remove_voice(nick);
         self.remove_protected(nick);
         self.users.remove(nick);
+        self.delayed_users.remove(nick);
+    }
+
+    // check whether JOIN, PART and QUIT of user should be sent to other user of channel.
+    // hidden user is shown only to channel operators that got its quiet JOIN.
+    pub(super) fn is_shown_to(&self, nick: &str, chum: &ChannelUserModes) -> bool {
+        match self.delayed_users.get(nick) {
+            Some(ops_notified) => *ops_notified && chum.is_half_operator(),
+            None => true,
+        }
+    }
+
+    // send JOIN of hidden user to other users of channel that did not get it yet.
+    pub(super) fn send_delayed_join(
+        &self,
+        chname: &str,
+        nick: &str,
+        users: &HashMap<String, User>,
+    ) {
+        let source = &users.get(nick).unwrap().source;
+        let join_msg = format!("JOIN {}", chname);
+        self.users
+            .iter()
+            .filter(|(n, chum)| *n != nick && !self.is_shown_to(nick, chum))
+            .for_each(|(n, _)| {
+                // ignore errors - user can be during disconnection.
+                let _ = users.get(n).unwrap().send_msg_display(source, &join_msg);
+            });
     }
 
     // add/remove user from list
@@ -458,20 +622,47 @@ pub(super) struct NickHistoryEntry {
     pub(super) hostname: String,
     pub(super) realname: String,
     pub(super) signon: u64,
+    pub(super) left_time: u64, // time when user left nick (changed nick or quit)
 }
 
+pub(super) const CAP_RESUME: &str = "draft/resume-0.5";
+pub(super) const CAP_CHANNEL_RENAME: &str = "draft/channel-rename";
+pub(super) const CAP_EXTENDED_MONITOR: &str = "draft/extended-monitor";
+pub(super) const CAP_PRE_AWAY: &str = "draft/pre-away";
+pub(super) const CAP_SASL: &str = "sasl";
+
 #[derive(Copy, Clone, Debug, Default)]
 pub(crate) struct CapState {
     pub(super) multi_prefix: bool,
+    pub(super) channel_rename: bool,
+    pub(super) extended_monitor: bool,
+    pub(super) pre_away: bool,
+    pub(super) sasl: bool,
+    pub(super) resume: bool,
 }
 
 impl fmt::Display for CapState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut caps = vec![];
         if self.multi_prefix {
-            f.write_str("multi-prefix")
-        } else {
-            Ok(())
+            caps.push("multi-prefix");
+        }
+        if self.channel_rename {
+            caps.push(CAP_CHANNEL_RENAME);
         }
+        if self.extended_monitor {
+            caps.push(CAP_EXTENDED_MONITOR);
+        }
+        if self.pre_away {
+            caps.push(CAP_PRE_AWAY);
+        }
+        if self.sasl {
+            caps.push(CAP_SASL);
+        }
+        if self.resume {
+            caps.push(CAP_RESUME);
+        }
+        f.write_str(&caps.join(" "))
     }
 }
 
@@ -479,6 +670,11 @@ impl CapState {
     pub(super) fn apply_cap(&mut self, cap: &str) -> bool {
         match cap {
             "multi-prefix" => self.multi_prefix = true,
+            CAP_CHANNEL_RENAME => self.channel_rename = true,
+            CAP_EXTENDED_MONITOR => self.extended_monitor = true,
+            CAP_PRE_AWAY => self.pre_away = true,
+            CAP_SASL => self.sasl = true,
+            CAP_RESUME => self.resume = true,
             _ => return false,
         };
         true
@@ -515,14 +711,9 @@ impl ConnUserState {
         }
     }
 
+    // target of numeric replies - nick or '*' if nick is not yet known.
     pub(super) fn client_name(&self) -> &str {
-        if let Some(ref n) = self.nick {
-            n
-        } else if let Some(ref n) = self.name {
-            n
-        } else {
-            &self.hostname
-        }
+        self.nick.as_deref().unwrap_or("*")
     }
 
     pub(super) fn update_source(&mut self) {
@@ -541,7 +732,6 @@ impl ConnUserState {
         self.source = s;
     }
 
-    #[cfg(feature = "dns_lookup")]
     pub(super) fn set_hostname(&mut self, hostname: String) {
         self.hostname = hostname;
         self.update_source();
@@ -556,6 +746,13 @@ impl ConnUserState {
     }
 }
 
+// SASL mechanisms supported by AUTHENTICATE command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum SaslMechanism {
+    Plain,
+    External,
+}
+
 #[derive(Debug)]
 pub(crate) struct ConnState {
     // use BufferedLineStream to avoid deadlocks when sending is not still finished.
@@ -587,6 +784,37 @@ pub(crate) struct ConnState {
     pub(super) caps: CapState,
     pub(super) quit: Arc<AtomicI32>,
     pub(super) conns_count: Arc<AtomicUsize>,
+    // server name reported by listener that accepted connection.
+    pub(super) servername: Option<String>,
+    // capabilities disabled by listener that accepted connection.
+    pub(super) disabled_caps: HashSet<String>,
+    // display of host of client (configured globally or by listener).
+    #[cfg(feature = "dns_lookup")]
+    pub(super) host_display: HostDisplay,
+    // unique ID of connection (RPL_YOURID).
+    pub(super) uid: String,
+    // times of recent WHOIS queries (for WHOIS rate limit).
+    pub(super) whois_times: VecDeque<Instant>,
+    // times of recent CTCP replies to targets (for CTCP reply rate limit).
+    pub(super) ctcp_reply_times: HashMap<String, VecDeque<Instant>>,
+    // times of recent NOTICE messages (for NOTICE rate limit).
+    pub(super) notice_times: VecDeque<Instant>,
+    // times of recent channel creations (for channel creation rate limit).
+    pub(super) channel_creation_times: VecDeque<Instant>,
+    // away message set before registration (pre-away capability).
+    pub(super) pre_away: Option<String>,
+    // times of recent AWAY changes (for AWAY rate limit).
+    pub(super) away_times: VecDeque<Instant>,
+    // time of last command other than PONG (for idle timeout).
+    pub(super) last_active: time::Instant,
+    // time of registration (for CTCP grace period).
+    pub(super) registration_time: time::Instant,
+    // mechanism of SASL exchange started by AUTHENTICATE.
+    pub(super) sasl_mechanism: Option<SaslMechanism>,
+    // account logged in by SASL authentication.
+    pub(super) sasl_account: Option<String>,
+    // reason given by QUIT - user quit and its session can not be resumed.
+    pub(super) quit_reason: Option<String>,
 }
 
 impl ConnState {
@@ -594,6 +822,9 @@ impl ConnState {
         ip_addr: IpAddr,
         stream: Framed<DualTcpStream, IRCLinesCodec>,
         conns_count: Arc<AtomicUsize>,
+        servername: Option<String>,
+        disabled_caps: HashSet<String>,
+        uid: String,
     ) -> ConnState {
         let (sender, receiver) = unbounded_channel();
         let (ping_sender, ping_receiver) = unbounded_channel();
@@ -623,6 +854,22 @@ impl ConnState {
             caps: CapState::default(),
             quit: Arc::new(AtomicI32::new(0)),
             conns_count,
+            servername,
+            disabled_caps,
+            #[cfg(feature = "dns_lookup")]
+            host_display: HostDisplay::Hostname,
+            uid,
+            whois_times: VecDeque::new(),
+            ctcp_reply_times: HashMap::new(),
+            notice_times: VecDeque::new(),
+            channel_creation_times: VecDeque::new(),
+            pre_away: None,
+            away_times: VecDeque::new(),
+            last_active: time::Instant::now(),
+            registration_time: time::Instant::now(),
+            sasl_mechanism: None,
+            sasl_account: None,
+            quit_reason: None,
         }
     }
 
@@ -664,6 +911,10 @@ impl ConnState {
     pub(crate) fn is_secure(&self) -> bool {
         self.stream.get_ref().is_secure()
     }
+
+    pub(crate) fn cert_fingerprint(&self) -> Option<String> {
+        self.stream.get_ref().cert_fingerprint()
+    }
 }
 
 impl Drop for ConnState {
@@ -694,6 +945,178 @@ async fn pong_client_timeout(
     }
 }
 
+// kind of watching of user presence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum PresenceKind {
+    Monitor,
+    Watch,
+}
+
+// Presence registry - index of watchers (MONITOR and WATCH) by watched nick.
+// It is notified about connections, disconnections and nick changes and
+// it sends notifications to watchers with numerics specific for their kind.
+// Thanks the index, presence change visits only watchers of changed nick.
+#[derive(Debug)]
+pub(super) struct PresenceRegistry {
+    servername: String,
+    // key - watched nick, value - nicks of watchers.
+    monitors: HashMap<String, HashSet<String>>,
+    watches: HashMap<String, HashSet<String>>,
+}
+
+impl PresenceRegistry {
+    pub(super) fn new(servername: &str) -> PresenceRegistry {
+        PresenceRegistry {
+            servername: servername.to_string(),
+            monitors: HashMap::new(),
+            watches: HashMap::new(),
+        }
+    }
+
+    fn watchers(&self, kind: PresenceKind) -> &HashMap<String, HashSet<String>> {
+        match kind {
+            PresenceKind::Monitor => &self.monitors,
+            PresenceKind::Watch => &self.watches,
+        }
+    }
+
+    fn watchers_mut(&mut self, kind: PresenceKind) -> &mut HashMap<String, HashSet<String>> {
+        match kind {
+            PresenceKind::Monitor => &mut self.monitors,
+            PresenceKind::Watch => &mut self.watches,
+        }
+    }
+
+    pub(super) fn add(&mut self, kind: PresenceKind, watcher: &str, target: &str) {
+        self.watchers_mut(kind)
+            .entry(target.to_string())
+            .or_default()
+            .insert(watcher.to_string());
+    }
+
+    pub(super) fn remove(&mut self, kind: PresenceKind, watcher: &str, target: &str) {
+        let watchers = self.watchers_mut(kind);
+        if let Some(target_watchers) = watchers.get_mut(target) {
+            target_watchers.remove(watcher);
+            if target_watchers.is_empty() {
+                watchers.remove(target);
+            }
+        }
+    }
+
+    // get number of watchers of target.
+    pub(super) fn watchers_count(&self, kind: PresenceKind, target: &str) -> usize {
+        self.watchers(kind).get(target).map_or(0, |w| w.len())
+    }
+
+    // rename watcher in entries of its targets.
+    pub(super) fn rename_watcher(
+        &mut self,
+        kind: PresenceKind,
+        targets: &HashSet<String>,
+        old_nick: &str,
+        new_nick: &str,
+    ) {
+        let watchers = self.watchers_mut(kind);
+        targets.iter().for_each(|t| {
+            if let Some(target_watchers) = watchers.get_mut(t) {
+                if target_watchers.remove(old_nick) {
+                    target_watchers.insert(new_nick.to_string());
+                }
+            }
+        });
+    }
+
+    // notify watchers about user that goes online.
+    pub(super) fn notify_online(&self, users: &HashMap<String, User>, nick: &str, user: &User) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.watcher_users(users, PresenceKind::Monitor, nick).for_each(|(client, wu)| {
+            // ignore errors - watcher can be during disconnection.
+            let _ = wu.send_msg_display(
+                &self.servername,
+                RplMonOnline730 {
+                    client,
+                    targets: &user.source,
+                },
+            );
+        });
+        self.watcher_users(users, PresenceKind::Watch, nick).for_each(|(client, wu)| {
+            let _ = wu.send_msg_display(
+                &self.servername,
+                RplLogOn600 {
+                    client,
+                    nick,
+                    user: &user.name,
+                    host: &user.hostname,
+                    ts,
+                },
+            );
+        });
+    }
+
+    // notify watchers about user that goes offline.
+    pub(super) fn notify_offline(&self, users: &HashMap<String, User>, nick: &str, user: &User) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.watcher_users(users, PresenceKind::Monitor, nick).for_each(|(client, wu)| {
+            // ignore errors - watcher can be during disconnection.
+            let _ = wu.send_msg_display(
+                &self.servername,
+                RplMonOffline731 {
+                    client,
+                    targets: nick,
+                },
+            );
+        });
+        self.watcher_users(users, PresenceKind::Watch, nick).for_each(|(client, wu)| {
+            let _ = wu.send_msg_display(
+                &self.servername,
+                RplLogOff601 {
+                    client,
+                    nick,
+                    user: &user.name,
+                    host: &user.hostname,
+                    ts,
+                },
+            );
+        });
+    }
+
+    // notify monitors that have extended-monitor capability about away state change of user.
+    pub(super) fn notify_away(&self, users: &HashMap<String, User>, nick: &str, user: &User) {
+        let msg = if let Some(ref text) = user.away {
+            format!(":{}!~{}@{} AWAY :{}", nick, user.name, user.hostname, text)
+        } else {
+            format!(":{}!~{}@{} AWAY", nick, user.name, user.hostname)
+        };
+        self.watcher_users(users, PresenceKind::Monitor, nick)
+            .filter(|(_, wu)| wu.caps.extended_monitor)
+            .for_each(|(_, wu)| {
+                // ignore errors - watcher can be during disconnection.
+                let _ = wu.sender.send(msg.clone());
+            });
+    }
+
+    // get watchers (nick and user) of target.
+    fn watcher_users<'a>(
+        &'a self,
+        users: &'a HashMap<String, User>,
+        kind: PresenceKind,
+        target: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a User)> {
+        self.watchers(kind)
+            .get(target)
+            .into_iter()
+            .flat_map(|w| w.iter())
+            .filter_map(move |w| users.get(w).map(|wu| (w.as_str(), wu)))
+    }
+}
+
 pub(super) struct VolatileState {
     pub(super) users: HashMap<String, User>,
     pub(super) channels: HashMap<String, Channel>,
@@ -702,6 +1125,15 @@ pub(super) struct VolatileState {
     pub(super) operators_count: usize,
     pub(super) max_users_count: usize,
     pub(super) nick_histories: HashMap<String, Vec<NickHistoryEntry>>,
+    // time in seconds after which nick history entries are removed.
+    pub(super) nick_history_retention: Option<u64>,
+    // total number of MONITOR and WATCH entries of all users.
+    pub(super) monitor_count: usize,
+    pub(super) presence: PresenceRegistry,
+    // last away messages of accounts - restored when user logs in again.
+    pub(super) account_aways: HashMap<String, String>,
+    // ban check caches of channels - used by messages sent under read lock.
+    pub(super) ban_caches: Mutex<HashMap<String, BanMatchCache>>,
     pub(super) quit_sender: Option<oneshot::Sender<String>>,
     pub(super) quit_receiver: Option<Fuse<oneshot::Receiver<String>>>,
 }
@@ -728,6 +1160,8 @@ impl VolatileState {
                             .unwrap()
                             .as_secs(),
                         preconfigured: true,
+                        access: c.access.clone().unwrap_or_default(),
+                        delayed_users: HashMap::new(),
                     },
                 );
             });
@@ -742,13 +1176,26 @@ impl VolatileState {
             operators_count: 0,
             max_users_count: 0,
             nick_histories: HashMap::new(),
+            nick_history_retention: config.whowas_retention,
+            monitor_count: 0,
+            presence: PresenceRegistry::new(&config.name),
+            account_aways: HashMap::new(),
+            ban_caches: Mutex::new(HashMap::new()),
             quit_sender: Some(quit_sender),
             quit_receiver: Some(quit_receiver.fuse()),
         }
     }
 
     // add user to volatile state - includes stats likes invisible users count, etc.
-    pub(super) fn add_user(&mut self, unick: &str, user: User) {
+    pub(super) fn add_user(&mut self, unick: &str, mut user: User) {
+        // away set before registration (pre-away) takes precedence.
+        if user.away.is_none() {
+            if let Some(ref account) = user.account {
+                if let Some(away) = self.account_aways.get(account) {
+                    user.away = Some(away.clone());
+                }
+            }
+        }
         if user.modes.invisible {
             self.invisible_users_count += 1;
         }
@@ -758,6 +1205,11 @@ impl VolatileState {
         if user.modes.is_local_oper() {
             self.operators_count += 1;
         }
+        // watchers must see user online before its away state.
+        self.presence.notify_online(&self.users, unick, &user);
+        if user.away.is_some() {
+            self.presence.notify_away(&self.users, unick, &user);
+        }
         self.users.insert(unick.to_string(), user);
         if self.users.len() > self.max_users_count {
             self.max_users_count = self.users.len();
@@ -765,13 +1217,18 @@ impl VolatileState {
     }
 
     // remove user from channel and remove channel from user.
-    // remove same channel if no more users at channel.
+    // remove same channel if no more users at channel (except preconfigured and
+    // permanent channels).
     pub(super) fn remove_user_from_channel<'a>(&mut self, channel: &'a str, nick: &'a str) {
         if let Some(chanobj) = self.channels.get_mut(channel) {
             chanobj.remove_user(nick);
-            if chanobj.users.is_empty() && !chanobj.preconfigured {
+            if let Some(cache) = self.ban_caches.get_mut().unwrap().get_mut(channel) {
+                cache.remove(nick);
+            }
+            if chanobj.users.is_empty() && !chanobj.preconfigured && !chanobj.modes.permanent {
                 info!("Channel {} has been removed", channel);
                 self.channels.remove(channel);
+                self.clear_ban_cache(channel);
             }
         }
         if let Some(user) = self.users.get_mut(nick) {
@@ -779,6 +1236,82 @@ impl VolatileState {
         }
     }
 
+    // rename channel - change channel name in channels of its users and in invitations.
+    pub(super) fn rename_channel(&mut self, channel: &str, new_channel: &str) {
+        self.clear_ban_cache(channel);
+        if let Some(chanobj) = self.channels.remove(channel) {
+            for nick in chanobj.users.keys() {
+                if let Some(user) = self.users.get_mut(nick) {
+                    user.channels.remove(channel);
+                    user.channels.insert(new_channel.to_string());
+                }
+            }
+            self.channels.insert(new_channel.to_string(), chanobj);
+        }
+        for user in self.users.values_mut() {
+            if let Some(time) = user.invited_to.remove(channel) {
+                user.invited_to.insert(new_channel.to_string(), time);
+            }
+        }
+    }
+
+    // check whether user is banned in channel. Result is cached until user's source or
+    // account is changed or until channel bans are changed.
+    pub(super) fn banned_cached(
+        &self,
+        channel: &str,
+        nick: &str,
+        source: &str,
+        account: Option<&str>,
+    ) -> bool {
+        let mut caches = self.ban_caches.lock().unwrap();
+        let cache = caches.entry(channel.to_string()).or_default();
+        if let Some((csource, caccount, banned)) = cache.get(nick) {
+            if csource == source && caccount.as_deref() == account {
+                return *banned;
+            }
+        }
+        let chanobj = self.channels.get(channel).unwrap();
+        let banned = chanobj.modes.banned(source, account);
+        cache.insert(
+            nick.to_string(),
+            (source.to_string(), account.map(|a| a.to_string()), banned),
+        );
+        banned
+    }
+
+    // clear ban cache of channel - must be called after change of bans or exceptions.
+    pub(super) fn clear_ban_cache(&mut self, channel: &str) {
+        self.ban_caches.get_mut().unwrap().remove(channel);
+    }
+
+    // send QUIT of user to users in common channels - every peer gets only one QUIT.
+    // users that do not see hidden user in channel are skipped.
+    pub(super) fn send_quit_to_peers(&self, nick: &str, reason: &str) {
+        if let Some(user) = self.users.get(nick) {
+            let msg = format!("QUIT :{}", reason);
+            let peers = user
+                .channels
+                .iter()
+                .filter_map(|ch| self.channels.get(ch))
+                .flat_map(|ch| {
+                    ch.users
+                        .iter()
+                        .filter(move |(n, chum)| *n != nick && ch.is_shown_to(nick, chum))
+                        .map(|(n, _)| n)
+                })
+                .collect::<HashSet<_>>();
+            for peer in peers {
+                // ignore errors - peer can be during disconnection.
+                let _ = self
+                    .users
+                    .get(peer)
+                    .unwrap()
+                    .send_msg_display(&user.source, &msg);
+            }
+        }
+    }
+
     // remove user - including stats like invisible users.
     pub(super) fn remove_user(&mut self, nick: &str) {
         if let Some(user) = self.users.remove(nick) {
@@ -789,20 +1322,119 @@ impl VolatileState {
                 self.invisible_users_count -= 1;
             }
             self.wallops_users.remove(nick);
+            if let Some(ref account) = user.account {
+                match user.away {
+                    Some(ref away) => {
+                        self.account_aways.insert(account.clone(), away.clone());
+                    }
+                    None => {
+                        self.account_aways.remove(account);
+                    }
+                }
+            }
+            for kind in [PresenceKind::Monitor, PresenceKind::Watch] {
+                let targets = user.presence_targets(kind);
+                targets.iter().for_each(|t| self.presence.remove(kind, nick, t));
+                self.monitor_count -= targets.len();
+            }
+            self.presence.notify_offline(&self.users, nick, &user);
             user.channels.iter().for_each(|chname| {
                 self.remove_user_from_channel(chname, nick);
             });
-            self.insert_to_nick_history(&nick.to_string(), user.history_entry);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            self.insert_to_nick_history(&nick.to_string(), user.history_entry, now);
         }
     }
 
+    // add target to MONITOR or WATCH list of watcher. Returns true if target has been added.
+    pub(super) fn add_presence_target(
+        &mut self,
+        kind: PresenceKind,
+        watcher: &str,
+        target: &str,
+    ) -> bool {
+        let user = self.users.get_mut(watcher).unwrap();
+        if user.presence_targets_mut(kind).insert(target.to_string()) {
+            self.presence.add(kind, watcher, target);
+            self.monitor_count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // remove target from MONITOR or WATCH list of watcher. Returns true if target has been
+    // removed.
+    pub(super) fn remove_presence_target(
+        &mut self,
+        kind: PresenceKind,
+        watcher: &str,
+        target: &str,
+    ) -> bool {
+        let user = self.users.get_mut(watcher).unwrap();
+        if user.presence_targets_mut(kind).remove(target) {
+            self.presence.remove(kind, watcher, target);
+            self.monitor_count -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // clear MONITOR or WATCH list of watcher.
+    pub(super) fn clear_presence_targets(&mut self, kind: PresenceKind, watcher: &str) {
+        let user = self.users.get_mut(watcher).unwrap();
+        let targets = std::mem::take(user.presence_targets_mut(kind));
+        targets.iter().for_each(|t| self.presence.remove(kind, watcher, t));
+        self.monitor_count -= targets.len();
+    }
+
+    // update presence registry after nick change - notify watchers of old nick and new nick.
+    pub(super) fn rename_user_presence(&mut self, old_nick: &str, new_nick: &str) {
+        let user = self.users.get(new_nick).unwrap();
+        for kind in [PresenceKind::Monitor, PresenceKind::Watch] {
+            self.presence.rename_watcher(kind, user.presence_targets(kind), old_nick, new_nick);
+        }
+        self.presence.notify_offline(&self.users, old_nick, user);
+        self.presence.notify_online(&self.users, new_nick, user);
+    }
+
+    // find user nick by resume token.
+    pub(super) fn find_nick_by_resume_token(&self, token: &str) -> Option<String> {
+        self.users
+            .iter()
+            .find(|(_, u)| u.resume_token.as_deref() == Some(token))
+            .map(|(nick, _)| nick.clone())
+    }
+
     // used to maintain nick history that is read by WHOWAS command.
-    pub(super) fn insert_to_nick_history(&mut self, old_nick: &String, nhe: NickHistoryEntry) {
+    // now - time when user left nick.
+    pub(super) fn insert_to_nick_history(
+        &mut self,
+        old_nick: &String,
+        mut nhe: NickHistoryEntry,
+        now: u64,
+    ) {
         if !self.nick_histories.contains_key(old_nick) {
             self.nick_histories.insert(old_nick.to_string(), vec![]);
         }
         let nick_hist = self.nick_histories.get_mut(old_nick).unwrap();
+        nhe.left_time = now;
         nick_hist.push(nhe);
+        self.expire_nick_histories(now);
+    }
+
+    // remove nick history entries older than retention time.
+    pub(super) fn expire_nick_histories(&mut self, now: u64) {
+        if let Some(retention) = self.nick_history_retention {
+            self.nick_histories.retain(|_, hist| {
+                hist.retain(|e| e.left_time + retention > now);
+                !hist.is_empty()
+            });
+        }
     }
 }
 
@@ -820,6 +1452,9 @@ mod test {
             local_oper: false,
             registered: true,
             wallops: false,
+            bot: false,
+            hide_oper: false,
+            caller_id: false,
         };
         let user_state = ConnUserState {
             ip_addr: "127.0.0.1".parse().unwrap(),
@@ -834,7 +1469,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let user = User::new(&config, &user_state, sender, quit_sender);
+        let user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
 
         let user_nick = user_state.nick.clone().unwrap();
         assert_eq!(user_state.hostname, user.hostname);
@@ -849,7 +1490,8 @@ mod test {
                 username: user.name.clone(),
                 hostname: user.hostname.clone(),
                 realname: user.realname.clone(),
-                signon: user.signon
+                signon: user.signon,
+                left_time: 0
             },
             user.history_entry
         );
@@ -961,10 +1603,25 @@ mod test {
         assert_eq!(
             "~",
             chum.to_string(&CapState {
-                multi_prefix: false
+                multi_prefix: false,
+                channel_rename: false,
+                extended_monitor: false,
+                pre_away: false,
+                sasl: false,
+                resume: false
+            })
+        );
+        assert_eq!(
+            "~&@",
+            chum.to_string(&CapState {
+                multi_prefix: true,
+                channel_rename: false,
+                extended_monitor: false,
+                pre_away: false,
+                sasl: false,
+                resume: false
             })
         );
-        assert_eq!("~&@", chum.to_string(&CapState { multi_prefix: true }));
 
         let chum = ChannelUserModes {
             founder: false,
@@ -976,10 +1633,25 @@ mod test {
         assert_eq!(
             "%",
             chum.to_string(&CapState {
-                multi_prefix: false
+                multi_prefix: false,
+                channel_rename: false,
+                extended_monitor: false,
+                pre_away: false,
+                sasl: false,
+                resume: false
+            })
+        );
+        assert_eq!(
+            "%+",
+            chum.to_string(&CapState {
+                multi_prefix: true,
+                channel_rename: false,
+                extended_monitor: false,
+                pre_away: false,
+                sasl: false,
+                resume: false
             })
         );
-        assert_eq!("%+", chum.to_string(&CapState { multi_prefix: true }));
     }
 
     #[test]
@@ -1101,7 +1773,9 @@ mod test {
                 )]
                 .into(),
                 creation_time: channel.creation_time,
-                preconfigured: false
+                preconfigured: false,
+                access: HashMap::new(),
+                delayed_users: HashMap::new()
             },
             channel
         );
@@ -1237,7 +1911,9 @@ mod test {
                 )]
                 .into(),
                 creation_time: channel.creation_time,
-                preconfigured: false
+                preconfigured: false,
+                access: HashMap::new(),
+                delayed_users: HashMap::new()
             },
             channel
         );
@@ -1266,6 +1942,8 @@ mod test {
             .into(),
             creation_time: channel.creation_time,
             preconfigured: false,
+            access: HashMap::new(),
+            delayed_users: HashMap::new(),
         };
 
         channel
@@ -1407,7 +2085,7 @@ mod test {
             },
             cus
         );
-        assert_eq!("192.168.1.7", cus.client_name());
+        assert_eq!("*", cus.client_name());
         cus.set_name("boro".to_string());
         assert_eq!(
             ConnUserState {
@@ -1423,7 +2101,7 @@ mod test {
             },
             cus
         );
-        assert_eq!("boro", cus.client_name());
+        assert_eq!("*", cus.client_name());
         cus.set_nick("buru".to_string());
         assert_eq!(
             ConnUserState {
@@ -1456,7 +2134,7 @@ mod test {
             },
             cus
         );
-        assert_eq!("192.168.1.7", cus.client_name());
+        assert_eq!("*", cus.client_name());
         cus.set_nick("boro".to_string());
         assert_eq!(
             ConnUserState {
@@ -1499,16 +2177,19 @@ mod test {
                 name: "#gooddays".to_string(),
                 topic: Some("About good days".to_string()),
                 modes: ChannelModes::default(),
+                access: None,
             },
             ChannelConfig {
                 name: "#pets".to_string(),
                 topic: Some("About pets".to_string()),
                 modes: ChannelModes::default(),
+                access: None,
             },
             ChannelConfig {
                 name: "&cactuses".to_string(),
                 topic: None,
                 modes: ChannelModes::default(),
+                access: None,
             },
         ]);
         let state = VolatileState::new_from_config(&config);
@@ -1523,7 +2204,9 @@ mod test {
                         ban_info: HashMap::new(),
                         users: HashMap::new(),
                         creation_time: state.channels.get("#gooddays").unwrap().creation_time,
-                        preconfigured: true
+                        preconfigured: true,
+                        access: HashMap::new(),
+                        delayed_users: HashMap::new()
                     }
                 ),
                 (
@@ -1535,7 +2218,9 @@ mod test {
                         ban_info: HashMap::new(),
                         users: HashMap::new(),
                         creation_time: state.channels.get("#pets").unwrap().creation_time,
-                        preconfigured: true
+                        preconfigured: true,
+                        access: HashMap::new(),
+                        delayed_users: HashMap::new()
                     }
                 ),
                 (
@@ -1547,7 +2232,9 @@ mod test {
                         ban_info: HashMap::new(),
                         users: HashMap::new(),
                         creation_time: state.channels.get("&cactuses").unwrap().creation_time,
-                        preconfigured: true
+                        preconfigured: true,
+                        access: HashMap::new(),
+                        delayed_users: HashMap::new()
                     }
                 )
             ]),
@@ -1562,6 +2249,7 @@ mod test {
             name: "#something".to_string(),
             topic: None,
             modes: ChannelModes::default(),
+            access: None,
         }]);
         let mut state = VolatileState::new_from_config(&config);
         let user_state = ConnUserState {
@@ -1577,7 +2265,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let user = User::new(&config, &user_state, sender, quit_sender);
+        let user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         state.add_user(&user_state.nick.clone().unwrap(), user);
 
         // create channels and add channel to user structure
@@ -1620,6 +2314,38 @@ mod test {
         assert!(!state.channels.contains_key("#tulipan"));
     }
 
+    #[test]
+    fn test_volatile_state_banned_cached() {
+        let mut state = VolatileState::new_from_config(&MainConfig::default());
+        let mut channel = Channel::new_on_user_join("dizzy".to_string());
+        channel.modes.ban = Some(["*!*@bad.host".to_string()].into());
+        state.channels.insert("#chan".to_string(), channel);
+        assert!(state.banned_cached("#chan", "mati", "mati!~mati@bad.host", None));
+        assert!(!state.banned_cached("#chan", "dizzy", "dizzy!~dizzy@good.host", None));
+        // cached result is used while source is not changed
+        let channel = state.channels.get_mut("#chan").unwrap();
+        channel.modes.ban = Some(["*!*@good.host".to_string()].into());
+        assert!(state.banned_cached("#chan", "mati", "mati!~mati@bad.host", None));
+        assert!(!state.banned_cached("#chan", "dizzy", "dizzy!~dizzy@good.host", None));
+        // host changed (for example by cloak) - result is no longer valid
+        assert!(state.banned_cached("#chan", "mati", "mati!~mati@good.host", None));
+        assert!(!state.banned_cached("#chan", "mati", "mati!~mati@bad.host", None));
+        // after change of bans cache must be cleared
+        state.clear_ban_cache("#chan");
+        assert!(state.banned_cached("#chan", "dizzy", "dizzy!~dizzy@good.host", None));
+        // account changed
+        let channel = state.channels.get_mut("#chan").unwrap();
+        channel.modes.ban = Some(["$a:spam*".to_string()].into());
+        state.clear_ban_cache("#chan");
+        assert!(!state.banned_cached("#chan", "mati", "mati!~mati@good.host", None));
+        assert!(state.banned_cached("#chan", "mati", "mati!~mati@good.host", Some("spammer")));
+        assert!(!state.banned_cached("#chan", "mati", "mati!~mati@good.host", Some("matix")));
+        // cache of removed channel is removed
+        state.remove_user_from_channel("#chan", "dizzy");
+        assert!(!state.channels.contains_key("#chan"));
+        assert!(state.ban_caches.get_mut().unwrap().is_empty());
+    }
+
     #[test]
     fn test_volatile_state_add_remove_user() {
         let mut config = MainConfig::default();
@@ -1627,6 +2353,7 @@ mod test {
             name: "#something".to_string(),
             topic: None,
             modes: ChannelModes::default(),
+            access: None,
         }]);
         let mut state = VolatileState::new_from_config(&config);
 
@@ -1643,7 +2370,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let user = User::new(&config, &user_state, sender, quit_sender);
+        let user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         state.add_user(&user_state.nick.clone().unwrap(), user);
         assert_eq!(1, state.max_users_count);
 
@@ -1660,7 +2393,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let user = User::new(&config, &user_state, sender, quit_sender);
+        let user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         state.add_user(&user_state.nick.clone().unwrap(), user);
         assert_eq!(2, state.max_users_count);
 
@@ -1677,7 +2416,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let mut user = User::new(&config, &user_state, sender, quit_sender);
+        let mut user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         user.modes.invisible = true;
         state.add_user(&user_state.nick.clone().unwrap(), user);
         assert_eq!(3, state.max_users_count);
@@ -1696,7 +2441,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let mut user = User::new(&config, &user_state, sender, quit_sender);
+        let mut user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         user.modes.wallops = true;
         state.add_user(&user_state.nick.clone().unwrap(), user);
         assert_eq!(4, state.max_users_count);
@@ -1716,7 +2467,13 @@ mod test {
         };
         let (sender, _) = unbounded_channel();
         let (quit_sender, _) = oneshot::channel();
-        let mut user = User::new(&config, &user_state, sender, quit_sender);
+        let mut user = User::new(
+            &config,
+            &user_state,
+            sender,
+            quit_sender,
+            Arc::new(ConnStats::default()),
+        );
         user.modes.oper = true;
         state.add_user(&user_state.nick.clone().unwrap(), user);
         assert_eq!(5, state.max_users_count);
@@ -1883,7 +2640,9 @@ mod test {
                 hostname: "gugg.com".to_string(),
                 realname: "Mati1".to_string(),
                 signon: 12344555555,
+                left_time: 0,
             },
+            12344556000,
         );
         state.insert_to_nick_history(
             &"mati".to_string(),
@@ -1892,7 +2651,9 @@ mod test {
                 hostname: "bip.com".to_string(),
                 realname: "Mati2".to_string(),
                 signon: 12377411100,
+                left_time: 0,
             },
+            12377412000,
         );
         assert_eq!(
             HashMap::from([(
@@ -1902,17 +2663,77 @@ mod test {
                         username: "mati1".to_string(),
                         hostname: "gugg.com".to_string(),
                         realname: "Mati1".to_string(),
-                        signon: 12344555555
+                        signon: 12344555555,
+                        left_time: 12344556000
                     },
                     NickHistoryEntry {
                         username: "mati2".to_string(),
                         hostname: "bip.com".to_string(),
                         realname: "Mati2".to_string(),
-                        signon: 12377411100
+                        signon: 12377411100,
+                        left_time: 12377412000
                     }
                 ]
             )]),
             state.nick_histories
         );
     }
+
+    #[test]
+    fn test_volatile_state_expire_nick_histories() {
+        let mut config = MainConfig::default();
+        config.whowas_retention = Some(100);
+        let mut state = VolatileState::new_from_config(&config);
+        let entry = NickHistoryEntry {
+            username: "mati1".to_string(),
+            hostname: "gugg.com".to_string(),
+            realname: "Mati1".to_string(),
+            signon: 500,
+            left_time: 0,
+        };
+        state.insert_to_nick_history(&"mati".to_string(), entry.clone(), 1000);
+        state.insert_to_nick_history(&"bobby".to_string(), entry.clone(), 1050);
+        state.expire_nick_histories(1099);
+        assert_eq!(2, state.nick_histories.len());
+        state.expire_nick_histories(1100);
+        assert!(!state.nick_histories.contains_key("mati"));
+        assert_eq!(1, state.nick_histories.get("bobby").unwrap().len());
+        // insertion also removes entries past retention window
+        state.insert_to_nick_history(&"mati".to_string(), entry, 1150);
+        assert!(!state.nick_histories.contains_key("bobby"));
+        assert_eq!(1150, state.nick_histories.get("mati").unwrap()[0].left_time);
+    }
+
+    #[test]
+    fn test_presence_registry() {
+        let mut registry = PresenceRegistry::new("irc.irc");
+        registry.add(PresenceKind::Monitor, "alan", "carl");
+        registry.add(PresenceKind::Monitor, "bowie", "carl");
+        registry.add(PresenceKind::Watch, "bowie", "dave");
+        assert_eq!(2, registry.watchers_count(PresenceKind::Monitor, "carl"));
+        assert_eq!(0, registry.watchers_count(PresenceKind::Watch, "carl"));
+        assert_eq!(1, registry.watchers_count(PresenceKind::Watch, "dave"));
+        assert_eq!(0, registry.watchers_count(PresenceKind::Monitor, "dave"));
+
+        registry.rename_watcher(
+            PresenceKind::Monitor,
+            &HashSet::from(["carl".to_string()]),
+            "bowie",
+            "bowie2",
+        );
+        assert_eq!(
+            HashMap::from([(
+                "carl".to_string(),
+                HashSet::from(["alan".to_string(), "bowie2".to_string()])
+            )]),
+            registry.monitors
+        );
+
+        registry.remove(PresenceKind::Monitor, "alan", "carl");
+        registry.remove(PresenceKind::Monitor, "bowie2", "carl");
+        // no empty entries in index
+        assert!(registry.monitors.is_empty());
+        registry.remove(PresenceKind::Watch, "bowie", "dave");
+        assert!(registry.watches.is_empty());
+    }
 }
@@ -23,11 +23,20 @@ use argon2::{self, Argon2};
 use bytes::{BufMut, BytesMut};
 use futures::task::{Context, Poll};
 use futures::{SinkExt, Stream};
+use hmac::{Hmac, Mac};
 use lazy_static::lazy_static;
+#[cfg(any(feature = "tls_rustls", feature = "tls_openssl"))]
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::error::Error;
 use std::io;
+use std::net::IpAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::ReadBuf;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
@@ -55,6 +64,23 @@ impl DualTcpStream {
     pub(crate) fn is_secure(&self) -> bool {
         !matches!(*self, DualTcpStream::PlainStream(_))
     }
+
+    // SHA-256 fingerprint (lowercase hex) of certificate sent by client.
+    pub(crate) fn cert_fingerprint(&self) -> Option<String> {
+        match *self {
+            DualTcpStream::PlainStream(_) => None,
+            #[cfg(feature = "tls_rustls")]
+            DualTcpStream::SecureStream(ref t) => {
+                let cert = t.get_ref().1.peer_certificates()?.first()?;
+                Some(hex_string(&Sha256::digest(&cert.0)))
+            }
+            #[cfg(feature = "tls_openssl")]
+            DualTcpStream::SecureStream(ref t) => {
+                let cert = t.ssl().peer_certificate()?.to_der().ok()?;
+                Some(hex_string(&Sha256::digest(&cert)))
+            }
+        }
+    }
 }
 
 impl AsyncRead for DualTcpStream {
@@ -101,11 +127,24 @@ impl AsyncWrite for DualTcpStream {
     }
 }
 
+// connection statistics - shared between connection and user structure to be
+// read by STATS command.
+#[derive(Debug, Default)]
+pub(crate) struct ConnStats {
+    // bytes fed to stream that have not been yet flushed.
+    pub(crate) sendq: AtomicUsize,
+    // bytes received from stream that have not been yet decoded.
+    pub(crate) recvq: AtomicUsize,
+    pub(crate) bytes_sent: AtomicU64,
+    pub(crate) bytes_received: AtomicU64,
+}
+
 // BufferedStream - to avoid deadlocks if no immediately data sent
 #[derive(Debug)]
 pub(crate) struct BufferedLineStream {
     stream: Framed<DualTcpStream, IRCLinesCodec>,
     buffer: Vec<String>,
+    stats: Arc<ConnStats>,
 }
 
 impl BufferedLineStream {
@@ -113,10 +152,13 @@ impl BufferedLineStream {
         BufferedLineStream {
             stream,
             buffer: vec![],
+            stats: Arc::new(ConnStats::default()),
         }
     }
 
     pub(crate) async fn feed(&mut self, msg: String) -> Result<(), LinesCodecError> {
+        // include "\r\n"
+        self.stats.sendq.fetch_add(msg.len() + 2, Ordering::Relaxed);
         self.buffer.push(msg);
         Ok(())
     }
@@ -126,19 +168,44 @@ impl BufferedLineStream {
             self.stream.feed(msg).await?;
         }
         self.stream.flush().await?;
+        let sent = self.stats.sendq.swap(0, Ordering::Relaxed);
+        self.stats.bytes_sent.fetch_add(sent as u64, Ordering::Relaxed);
         Ok(())
     }
 
     pub(crate) fn get_ref(&self) -> &DualTcpStream {
         self.stream.get_ref()
     }
+
+    pub(crate) fn stats(&self) -> Arc<ConnStats> {
+        self.stats.clone()
+    }
 }
 
 impl Stream for BufferedLineStream {
     type Item = Result<String, LinesCodecError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.get_mut().stream).poll_next(cx)
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.stream).poll_next(cx);
+        match result {
+            Poll::Ready(Some(Ok(ref line))) => {
+                // line terminator is not in line - assume "\r\n".
+                this.stats
+                    .bytes_received
+                    .fetch_add(line.len() as u64 + 2, Ordering::Relaxed);
+            }
+            Poll::Ready(Some(Err(LinesCodecError::MaxLineLengthExceeded))) => {
+                // Framed returns None once after decoder error. Skip it - codec
+                // discards rest of too long line and stream is still usable.
+                let _ = Pin::new(&mut this.stream).poll_next(cx);
+            }
+            _ => (),
+        }
+        this.stats
+            .recvq
+            .store(this.stream.read_buffer().len(), Ordering::Relaxed);
+        result
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -146,6 +213,9 @@ impl Stream for BufferedLineStream {
     }
 }
 
+// default maximal length of line received from client.
+pub(crate) const DEFAULT_LINELEN: usize = 2000;
+
 // special LinesCodec for IRC - encode with "\r\n".
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub(crate) struct IRCLinesCodec(LinesCodec);
@@ -195,8 +265,16 @@ pub(crate) fn validate_source(s: &str) -> bool {
     }
 }
 
+// channel prefixes (CHANTYPES in ISUPPORT). Any check of channel prefix should use it.
+pub(crate) const CHANTYPES: &str = "&#";
+
+// check whether name starts with channel prefix.
+pub(crate) fn has_channel_prefix(name: &str) -> bool {
+    name.starts_with(|c: char| CHANTYPES.contains(c))
+}
+
 pub(crate) fn validate_username(username: &str) -> Result<(), ValidationError> {
-    if !username.is_empty() && (username.as_bytes()[0] == b'#' || username.as_bytes()[0] == b'&') {
+    if has_channel_prefix(username) {
         Err(ValidationError::new(
             "Username must not have channel prefix.",
         ))
@@ -209,11 +287,93 @@ pub(crate) fn validate_username(username: &str) -> Result<(), ValidationError> {
     }
 }
 
+// validate realname (GECOS). max_len - maximal length in characters,
+// no_control_chars - if true then reject any control character.
+pub(crate) fn validate_realname(
+    realname: &str,
+    max_len: Option<usize>,
+    no_control_chars: bool,
+) -> Result<(), ValidationError> {
+    if realname.contains(|c: char| c == '\r' || c == '\n' || c == '\0') {
+        Err(ValidationError::new(
+            "Realname must not contains CR, LF or NUL.",
+        ))
+    } else if max_len.map_or(false, |max_len| realname.chars().count() > max_len) {
+        Err(ValidationError::new("Realname is too long."))
+    } else if no_control_chars && realname.chars().any(|c| c.is_control()) {
+        Err(ValidationError::new(
+            "Realname must not contains control characters.",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// split targets to targets within limit (TARGMAX) and rest of targets.
+pub(crate) fn split_targets_limited<'a, 'b>(
+    targets: &'b [&'a str],
+    limit: Option<usize>,
+) -> (&'b [&'a str], &'b [&'a str]) {
+    match limit {
+        Some(limit) if targets.len() > limit => targets.split_at(limit),
+        _ => (targets, &[]),
+    }
+}
+
+// check rate limit with sliding window. times - times of previous requests.
+// Returns true if request is allowed and then it will be recorded.
+pub(crate) fn check_rate_limit(
+    times: &mut VecDeque<Instant>,
+    count: usize,
+    period: Duration,
+    now: Instant,
+) -> bool {
+    while let Some(first) = times.front() {
+        if now.saturating_duration_since(*first) >= period {
+            times.pop_front();
+        } else {
+            break;
+        }
+    }
+    if times.len() < count {
+        times.push_back(now);
+        true
+    } else {
+        false
+    }
+}
+
+// get mask of masked PRIVMSG/NOTICE target: '$#' - host mask (returns true),
+// '$$' - server mask (returns false).
+pub(crate) fn get_masked_target(target: &str) -> Option<(bool, &str)> {
+    if let Some(mask) = target.strip_prefix("$#") {
+        Some((true, mask))
+    } else {
+        target.strip_prefix("$$").map(|mask| (false, mask))
+    }
+}
+
+// CTCP message is message text that starts with '\x01'.
+pub(crate) fn is_ctcp(text: &str) -> bool {
+    text.starts_with('\x01')
+}
+
+// check whether text contains color codes (\x03 with optional digits, \x04 with
+// optional hex color) or other formatting codes (bold, italic, underline, etc).
+pub(crate) fn has_formatting_codes(text: &str) -> bool {
+    text.contains(|c: char| {
+        matches!(
+            c,
+            '\x02' | '\x03' | '\x04' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f'
+        )
+    })
+}
+
 pub(crate) fn validate_channel(channel: &str) -> Result<(), ValidationError> {
     if !channel.is_empty()
         && !channel.contains(':')
         && !channel.contains(',')
-        && (channel.as_bytes()[0] == b'#' || channel.as_bytes()[0] == b'&')
+        && has_channel_prefix(channel)
     {
         Ok(())
     } else {
@@ -224,6 +384,37 @@ pub(crate) fn validate_channel(channel: &str) -> Result<(), ValidationError> {
     }
 }
 
+pub(crate) fn validate_key(key: &str) -> Result<(), ValidationError> {
+    if !key.is_empty()
+        && !key.contains(',')
+        && !key.starts_with(':')
+        && !key.chars().any(|c| c == ' ' || c.is_control())
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "Channel key must not be empty and \
+                must not contains ',', spaces or control characters.",
+        ))
+    }
+}
+
+// validate server name from configuration - it must be DNS-like name with at least one dot.
+pub(crate) fn validate_server_name(name: &str) -> Result<(), ValidationError> {
+    if name.contains('.')
+        && name.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "Server name must contain '.' and must have only \
+                letters, digits and '-' in its parts.",
+        ))
+    }
+}
+
 pub(crate) fn validate_server<E: Error>(s: &str, e: E) -> Result<(), E> {
     if s.contains('.') {
         Ok(())
@@ -276,12 +467,7 @@ pub(crate) fn validate_usermodes<'a>(
     let mut param_idx = 1;
     modes.iter().try_for_each(|(ms, margs)| {
         if !ms.is_empty() {
-            if ms
-                .find(|c| {
-                    c != '+' && c != '-' && c != 'i' && c != 'o' && c != 'O' && c != 'r' && c != 'w'
-                })
-                .is_some()
-            {
+            if ms.find(|c| !"+-ioOrwBHg".contains(c)).is_some() {
                 Err(UnknownUModeFlag(param_idx))
             } else if !margs.is_empty() {
                 Err(WrongParameter(MODEId, param_idx))
@@ -296,6 +482,10 @@ pub(crate) fn validate_usermodes<'a>(
     })
 }
 
+// channel mode letter for free invite - any channel member can invite even if
+// channel is invite only. Change it here (and in CHANMODES) if it clashes.
+pub(crate) const CHANMODE_FREE_INVITE: char = 'g';
+
 pub(crate) fn validate_channelmodes<'a>(
     target: &'a str,
     modes: &[(&'a str, Vec<&'a str>)],
@@ -390,7 +580,43 @@ pub(crate) fn validate_channelmodes<'a>(
                             });
                         }
                     }
-                    'i' | 'm' | 't' | 'n' | 's' => {}
+                    'f' => {
+                        if mode_set {
+                            if let Some(arg) = margs_it.next() {
+                                validate_channel(arg).map_err(|e| InvalidModeParam {
+                                    target: target.to_string(),
+                                    modechar: c,
+                                    param: arg.to_string(),
+                                    description: e.to_string(),
+                                })?;
+                                if *arg == target {
+                                    return Err(InvalidModeParam {
+                                        target: target.to_string(),
+                                        modechar: c,
+                                        param: arg.to_string(),
+                                        description: "Channel can't forward to itself"
+                                            .to_string(),
+                                    });
+                                }
+                                arg_param_idx += 1;
+                            } else {
+                                return Err(InvalidModeParam {
+                                    target: target.to_string(),
+                                    modechar: c,
+                                    param: "".to_string(),
+                                    description: "No argument".to_string(),
+                                });
+                            }
+                        } else if let Some(arg) = margs_it.next() {
+                            return Err(InvalidModeParam {
+                                target: target.to_string(),
+                                modechar: c,
+                                param: arg.to_string(),
+                                description: "Unexpected argument".to_string(),
+                            });
+                        }
+                    }
+                    'i' | 'm' | 't' | 'n' | 's' | 'P' | 'D' | 'R' | 'c' | CHANMODE_FREE_INVITE => {}
                     c => {
                         return Err(UnknownMode(param_idx, c, target.to_string()));
                     }
@@ -467,10 +693,29 @@ pub(crate) fn match_wildcard<'a>(pattern: &'a str, text: &'a str) -> bool {
     (!pattern.is_empty() && pattern.as_bytes()[pattern.len() - 1] == b'*') || t.is_empty()
 }
 
+// match user to mask. Extended masks (extbans) match account of user:
+// '$a' - any logged in user, '$a:mask' - user logged in as account that matches mask,
+// '$~a' - user that is not logged in. Other masks match source of user.
+pub(crate) fn match_user_mask(mask: &str, source: &str, account: Option<&str>) -> bool {
+    if let Some(amask) = mask.strip_prefix("$a") {
+        if let Some(amask) = amask.strip_prefix(':') {
+            account.map_or(false, |a| match_wildcard(amask, a))
+        } else {
+            amask.is_empty() && account.is_some()
+        }
+    } else if mask == "$~a" {
+        account.is_none()
+    } else {
+        match_wildcard(mask, source)
+    }
+}
+
 // normalize source mask - for example '*' to '*!*@*'
 pub(crate) fn normalize_sourcemask(mask: &str) -> String {
     let mut out = String::new();
-    if let Some(p) = mask.find('!') {
+    if mask.starts_with('$') {
+        out += mask; // extended mask is not normalized
+    } else if let Some(p) = mask.find('!') {
         out += mask; // normalized
         if mask[p + 1..].find('@').is_none() {
             out += "@*";
@@ -558,6 +803,65 @@ pub(crate) fn validate_password_hash(hash_str: &str) -> Result<(), ValidationErr
     }
 }
 
+// generate random token (used to resume session). Token has 128 bits from
+// random generator of operating system, so it can not be guessed.
+pub(crate) fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("No random generator");
+    hex_string(&bytes)
+}
+
+// make cloaked host from IP address. Cloak is HMAC-SHA256 of IP address with key, so
+// it is constant for IP address and it does not reveal IP address without key.
+pub(crate) fn cloak_host(ip: IpAddr, key: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes any key");
+    mac.update(ip.to_string().as_bytes());
+    format!("{}.cloak", hex_string(&mac.finalize().into_bytes()[..8]))
+}
+
+// decode standard base64 string (with padding) - used by SASL authentication.
+pub(crate) fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if s.len() % 4 != 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, chunk) in s.chunks(4).enumerate() {
+        let last = i + 1 == s.len() / 4;
+        let mut value = 0u32;
+        let mut padding = 0;
+        for &c in chunk {
+            let v = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                // padding only at end of last chunk
+                b'=' if last => {
+                    padding += 1;
+                    0
+                }
+                _ => return None,
+            };
+            if padding != 0 && c != b'=' {
+                return None;
+            }
+            value = (value << 6) | u32::from(v);
+        }
+        if padding > 2 {
+            return None;
+        }
+        out.extend_from_slice(&value.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+// format bytes as lowercase hexadecimal string (tokens, cloaks and fingerprints).
+pub(crate) fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -601,6 +905,119 @@ mod test {
         assert_eq!(false, validate_username("aL:a").is_ok());
     }
 
+    #[test]
+    fn test_validate_realname() {
+        assert_eq!(true, validate_realname("Ala Kowalska", None, false).is_ok());
+        assert_eq!(true, validate_realname("Ala Kowalska", Some(12), true).is_ok());
+        assert_eq!(false, validate_realname("Ala\nKowalska", None, false).is_ok());
+        assert_eq!(false, validate_realname("Ala\rKowalska", None, false).is_ok());
+        assert_eq!(false, validate_realname("Ala\0Kowalska", None, false).is_ok());
+        assert_eq!(false, validate_realname("Ala Kowalska", Some(11), false).is_ok());
+        assert_eq!(true, validate_realname("Ala\x03Kowalska", None, false).is_ok());
+        assert_eq!(false, validate_realname("Ala\x03Kowalska", None, true).is_ok());
+    }
+
+    #[test]
+    fn test_split_targets_limited() {
+        let targets = ["alice", "bob", "carol"];
+        assert_eq!(
+            (&targets[..], &[][..]),
+            split_targets_limited(&targets, None)
+        );
+        assert_eq!(
+            (&targets[..], &[][..]),
+            split_targets_limited(&targets, Some(3))
+        );
+        assert_eq!(
+            (&["alice", "bob"][..], &["carol"][..]),
+            split_targets_limited(&targets, Some(2))
+        );
+        assert_eq!(
+            (&[][..], &targets[..]),
+            split_targets_limited(&targets, Some(0))
+        );
+    }
+
+    #[test]
+    fn test_get_masked_target() {
+        assert_eq!(Some((true, "*.org")), get_masked_target("$#*.org"));
+        assert_eq!(Some((false, "irc.*")), get_masked_target("$$irc.*"));
+        assert_eq!(None, get_masked_target("$irc.*"));
+        assert_eq!(None, get_masked_target("bobby"));
+    }
+
+    #[test]
+    fn test_is_ctcp() {
+        assert!(is_ctcp("\x01VERSION\x01"));
+        assert!(is_ctcp("\x01PING 1234"));
+        assert!(!is_ctcp("VERSION"));
+        assert!(!is_ctcp(""));
+    }
+
+    #[test]
+    fn test_has_formatting_codes() {
+        assert!(has_formatting_codes("\x0304,12red on blue\x03"));
+        assert!(has_formatting_codes("color \x03 without digits"));
+        assert!(has_formatting_codes("\x03"));
+        assert!(has_formatting_codes("\x04FF0000hex color"));
+        assert!(has_formatting_codes("\x02bold\x02"));
+        assert!(has_formatting_codes("\x1funderline"));
+        assert!(has_formatting_codes("\x16reverse"));
+        assert!(has_formatting_codes("\x1ditalic\x0f"));
+        assert!(!has_formatting_codes("plain text 03"));
+        assert!(!has_formatting_codes("\x01ACTION waves\x01"));
+        assert!(!has_formatting_codes(""));
+    }
+
+    #[test]
+    fn test_check_rate_limit() {
+        let mut times = VecDeque::new();
+        let period = Duration::from_secs(10);
+        let start = Instant::now();
+        assert!(check_rate_limit(&mut times, 2, period, start));
+        assert!(check_rate_limit(
+            &mut times,
+            2,
+            period,
+            start + Duration::from_secs(3)
+        ));
+        assert!(!check_rate_limit(
+            &mut times,
+            2,
+            period,
+            start + Duration::from_secs(5)
+        ));
+        // first request is out of window
+        assert!(check_rate_limit(
+            &mut times,
+            2,
+            period,
+            start + Duration::from_secs(10)
+        ));
+        assert!(!check_rate_limit(
+            &mut times,
+            2,
+            period,
+            start + Duration::from_secs(12)
+        ));
+        assert!(check_rate_limit(
+            &mut times,
+            2,
+            period,
+            start + Duration::from_secs(13)
+        ));
+        assert!(!check_rate_limit(&mut times, 0, period, start));
+    }
+
+    #[test]
+    fn test_has_channel_prefix() {
+        assert!(has_channel_prefix("#ala"));
+        assert!(has_channel_prefix("&ala"));
+        assert!(!has_channel_prefix("+ala"));
+        assert!(!has_channel_prefix("ala"));
+        assert!(!has_channel_prefix(""));
+    }
+
     #[test]
     fn test_validate_channel() {
         assert_eq!(true, validate_channel("#ala").is_ok());
@@ -612,6 +1029,17 @@ mod test {
         assert_eq!(false, validate_channel("ala").is_ok());
     }
 
+    #[test]
+    fn test_validate_key() {
+        assert_eq!(true, validate_key("secret").is_ok());
+        assert_eq!(true, validate_key("se:cret").is_ok());
+        assert_eq!(false, validate_key("").is_ok());
+        assert_eq!(false, validate_key("sec,ret").is_ok());
+        assert_eq!(false, validate_key("sec ret").is_ok());
+        assert_eq!(false, validate_key(":secret").is_ok());
+        assert_eq!(false, validate_key("sec\x01ret").is_ok());
+    }
+
     #[test]
     fn test_validate_server() {
         assert_eq!(
@@ -624,6 +1052,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_validate_server_name() {
+        assert_eq!(true, validate_server_name("irc.example.com").is_ok());
+        assert_eq!(true, validate_server_name("irc-1.local").is_ok());
+        assert_eq!(false, validate_server_name("").is_ok());
+        assert_eq!(false, validate_server_name("ircexample").is_ok());
+        assert_eq!(false, validate_server_name("irc example.com").is_ok());
+        assert_eq!(false, validate_server_name("irc..com").is_ok());
+        assert_eq!(false, validate_server_name(".irc.com").is_ok());
+        assert_eq!(false, validate_server_name("irc.com.").is_ok());
+        assert_eq!(false, validate_server_name("irc:x.com").is_ok());
+    }
+
     #[test]
     fn test_validate_server_mask() {
         assert_eq!(
@@ -733,6 +1174,10 @@ mod test {
             validate_usermodes(&vec![("+io", vec![]), ("-rO", vec![]), ("-w", vec![])])
                 .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(()),
+            validate_usermodes(&vec![("+B-H", vec![]), ("+g", vec![])]).map_err(|e| e.to_string())
+        );
         assert_eq!(
             Err("Wrong parameter 1 in command 'MODE'".to_string()),
             validate_usermodes(&vec![("+io-rw", vec!["xx"]), ("-O", vec![])])
@@ -752,6 +1197,21 @@ mod test {
             validate_channelmodes("#xchan", &vec![("+nt", vec![]), ("-sm", vec![])])
                 .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+Dnt", vec![])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+Rn", vec![]), ("-R", vec![])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+cn", vec![]), ("-c", vec![])])
+                .map_err(|e| e.to_string())
+        );
         assert_eq!(
             Ok(()),
             validate_channelmodes(
@@ -833,6 +1293,40 @@ mod test {
             )
             .map_err(|e| e.to_string())
         );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+ig", vec![]), ("-g", vec![])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+nP", vec![]), ("-P", vec![])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Ok(()),
+            validate_channelmodes("#xchan", &vec![("+nf", vec!["#ychan"]), ("-f", vec![])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err(
+                "Invalid mode parameter: #xchan f ychan Validation error: Channel name \
+                must have '#' or '&' at start and must not contains ',' or ':'. [{}]"
+                    .to_string()
+            ),
+            validate_channelmodes("#xchan", &vec![("+nf", vec!["ychan"])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Invalid mode parameter: #xchan f #xchan Channel can't forward to itself"
+                .to_string()),
+            validate_channelmodes("#xchan", &vec![("+f", vec!["#xchan"])])
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            Err("Invalid mode parameter: #xchan f  No argument".to_string()),
+            validate_channelmodes("#xchan", &vec![("+f", vec![])]).map_err(|e| e.to_string())
+        );
         assert_eq!(
             Err("Unknown mode u in parameter 2 for #xchan".to_string()),
             validate_channelmodes("#xchan", &vec![("+nt", vec![]), ("-sum", vec![])])
@@ -962,6 +1456,21 @@ mod test {
         assert_eq!("u*xn!b*o@*", &normalize_sourcemask("u*xn!b*o"));
         assert_eq!("*!*@*", &normalize_sourcemask("*"));
         assert_eq!("bob.com!*@*", &normalize_sourcemask("bob.com"));
+        assert_eq!("$a:bob*", &normalize_sourcemask("$a:bob*"));
+    }
+
+    #[test]
+    fn test_match_user_mask() {
+        assert!(match_user_mask("bob!*@*", "bob!bobby@bob.com", None));
+        assert!(!match_user_mask("bob!*@*", "bobo!bobby@bob.com", Some("bob")));
+        assert!(match_user_mask("$a", "bob!bobby@bob.com", Some("bobacc")));
+        assert!(!match_user_mask("$a", "bob!bobby@bob.com", None));
+        assert!(match_user_mask("$a:bob*", "zed!zed@zed.com", Some("bobacc")));
+        assert!(!match_user_mask("$a:bob*", "bob!bobby@bob.com", Some("robacc")));
+        assert!(!match_user_mask("$a:bob*", "bob!bobby@bob.com", None));
+        assert!(match_user_mask("$~a", "bob!bobby@bob.com", None));
+        assert!(!match_user_mask("$~a", "bob!bobby@bob.com", Some("bobacc")));
+        assert!(!match_user_mask("$ab", "bob!bobby@bob.com", Some("bobacc")));
     }
 
     #[test]
@@ -1004,4 +1513,45 @@ mod test {
             validate_password_hash("xxxxxxxxx").map_err(|e| e.to_string())
         );
     }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert_eq!(32, token.len());
+        assert!(token.bytes().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn test_cloak_host() {
+        let ip: IpAddr = "192.168.1.7".parse().unwrap();
+        let cloak = cloak_host(ip, "secret");
+        assert_eq!(22, cloak.len());
+        assert!(cloak.ends_with(".cloak"));
+        assert_eq!(cloak, cloak_host(ip, "secret"));
+        assert_ne!(cloak, cloak_host("192.168.1.8".parse().unwrap(), "secret"));
+        assert_ne!(cloak, cloak_host(ip, "other"));
+        // cloak must not change between builds - bans can be set on cloaks.
+        assert_eq!(
+            "6c8b3a2f646fed39.cloak",
+            cloak_host("10.0.0.1".parse().unwrap(), "changeme")
+        );
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(Some(vec![]), decode_base64(""));
+        assert_eq!(Some(b"f".to_vec()), decode_base64("Zg=="));
+        assert_eq!(Some(b"fo".to_vec()), decode_base64("Zm8="));
+        assert_eq!(Some(b"foo".to_vec()), decode_base64("Zm9v"));
+        assert_eq!(Some(b"foobar".to_vec()), decode_base64("Zm9vYmFy"));
+        assert_eq!(
+            Some(b"\0alice\0secret".to_vec()),
+            decode_base64("AGFsaWNlAHNlY3JldA==")
+        );
+        assert_eq!(None, decode_base64("Zm9"));
+        assert_eq!(None, decode_base64("Zm=v"));
+        assert_eq!(None, decode_base64("Zg==Zm9v"));
+        assert_eq!(None, decode_base64("Zm9*"));
+    }
 }